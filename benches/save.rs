@@ -0,0 +1,48 @@
+//! What `save` costs on a large database when nothing changed, compared to writing it again.
+//! Run with `cargo bench --bench save`.
+
+use splitter::api::Ledger;
+use std::time::{Duration, Instant};
+
+/// entries of the database, with long hex names that hardly compress so the file gets large
+const ENTRIES: usize = 12_000;
+
+fn timed(run: impl FnOnce() -> anyhow::Result<()>) -> Duration {
+    let start = Instant::now();
+    run().unwrap();
+    start.elapsed()
+}
+
+fn main() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db = dir.path().join("large.db");
+    let mut ledger = Ledger::open(&db)?;
+    ledger.create_group("flat", &["alice", "bob"])?;
+    for i in 0..ENTRIES {
+        let name: String = (0..16)
+            .map(|j| {
+                format!(
+                    "{:016x}",
+                    ((i * 16 + j) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                )
+            })
+            .collect();
+        ledger.split("flat", &name, 1_00 + i as i64, &["alice"], &[])?;
+    }
+    ledger.save()?;
+    println!(
+        "{} entries, {} bytes",
+        ENTRIES,
+        std::fs::metadata(&db)?.len()
+    );
+
+    let unchanged = timed(|| Ledger::open(&db)?.save());
+    let changed = timed(|| {
+        let mut ledger = Ledger::open(&db)?;
+        ledger.pay("flat", 1_00, "bob", "alice")?;
+        ledger.save()
+    });
+    println!("open and save unchanged: {:?}", unchanged);
+    println!("open and save one payment: {:?}", changed);
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! The skeleton of a chat bot keeping the expenses of a room with splitter's engine.
+//! Reads one message per line from stdin instead of a chat server, e.g.
+//!
+//! ```text
+//! cargo run --example bot_skeleton -- room.db
+//! !split 42.50 pizza alice
+//! !pay 10 bob alice
+//! !balance
+//! ```
+
+use std::io::BufRead;
+use anyhow::{anyhow, Result};
+use splitter::api::{parse_amount, Ledger};
+
+/// the group of the room the bot answers in
+const GROUP: &str = "room";
+
+/// Handles one message, returns the answer
+fn handle(ledger: &mut Ledger, message: &str) -> Result<String> {
+    let words: Vec<&str> = message.split_whitespace().collect();
+    match words.as_slice() {
+        ["!split", amount, description, payer] => {
+            let amount = parse_amount(amount).map_err(|problem| anyhow!(problem))?;
+            ledger.split(GROUP, description, amount, &[payer], &[])?;
+            ledger.save()?;
+            Ok(format!("{} paid {} for {}", payer, cents(amount), description))
+        }
+        ["!pay", amount, from, to] => {
+            let amount = parse_amount(amount).map_err(|problem| anyhow!(problem))?;
+            ledger.pay(GROUP, amount, from, to)?;
+            ledger.save()?;
+            Ok(format!("{} paid {} back to {}", from, cents(amount), to))
+        }
+        ["!balance"] => {
+            let transfers = ledger.settle(GROUP)?;
+            if transfers.is_empty() {
+                return Ok("Everyone is settled".to_string());
+            }
+            Ok(transfers.iter()
+                .map(|transfer| format!("{} owes {} {}", transfer.from, transfer.to, cents(transfer.amount)))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        _ => Ok("Try !split <amount> <what> <who paid>, !pay <amount> <from> <to> or !balance".to_string()),
+    }
+}
+
+fn cents(amount: i64) -> String {
+    format!("{}.{:02}", amount / 100, amount % 100)
+}
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).unwrap_or_else(|| "bot.db".to_string());
+    let mut ledger = Ledger::open(path)?.author("bot");
+    if ledger.balances(GROUP).is_err() {
+        ledger.create_group(GROUP, &["alice", "bob", "carl"])?;
+        ledger.save()?;
+    }
+    for message in std::io::stdin().lock().lines() {
+        match handle(&mut ledger, &message?) {
+            Ok(answer) => println!("{}", answer),
+            Err(e) => println!("Sorry: {:#}", e),
+        }
+    }
+    Ok(())
+}
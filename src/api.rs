@@ -0,0 +1,135 @@
+//! The engine for other programs, e.g. a chat bot keeping the expenses of its rooms. Amounts are
+//! in minor units of the group currency, e.g. cents, like everywhere in splitter.
+//!
+//! ```
+//! use splitter::api::{Ledger, Transfer};
+//!
+//! let path = std::env::temp_dir().join(format!("splitter-api-{}.db", std::process::id()));
+//! let mut ledger = Ledger::open(&path)?.author("bot");
+//! ledger.create_group("flat", &["alice", "bob", "carl"])?;
+//! ledger.split("flat", "groceries", 30_00, &["alice"], &[])?;
+//! ledger.pay("flat", 5_00, "bob", "alice")?;
+//! assert_eq!(ledger.balances("flat")?, vec![
+//!     ("alice".to_string(), 15_00), ("bob".to_string(), -5_00), ("carl".to_string(), -10_00)]);
+//! assert_eq!(ledger.settle("flat")?, vec![
+//!     Transfer { from: "bob".to_string(), to: "alice".to_string(), amount: 5_00 },
+//!     Transfer { from: "carl".to_string(), to: "alice".to_string(), amount: 10_00 }]);
+//! ledger.save()?;
+//!
+//! assert_eq!(Ledger::open(&path)?.balances("flat")?[0].1, 15_00);
+//! std::fs::remove_file(path)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::path::PathBuf;
+use crate::error::*;
+use crate::group::SplitOptions;
+use crate::logic::Splitter;
+use crate::money::{check_ceiling, Money};
+
+/// Parses an amount like `12.50`, `12,5` or `3*4.20` into minor units, like the command line does
+///
+/// ```
+/// assert_eq!(splitter::api::parse_amount("3*4.20"), Ok(12_60));
+/// assert!(splitter::api::parse_amount("-1").is_err());
+/// ```
+pub fn parse_amount(input: &str) -> std::result::Result<i64, String> {
+    crate::money::parse_amount(input).map(|amount| amount.0)
+}
+
+/// A splitter database, changed in memory until `save` writes it
+pub struct Ledger {
+    splitter: Splitter,
+    author: Option<String>,
+}
+
+/// A payment settling a group: `from` pays `amount` to `to`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: i64,
+}
+
+impl Ledger {
+    /// Opens the database at `path`, compressed or plain like the command line does. A missing file
+    /// is an empty database, created by the first `save`.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Ok(Self { splitter: Splitter::new(path.into())?.pager(false), author: None })
+    }
+
+    /// records `author` as the author of the entries added, like `--author`
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Starts group `name` with `members`, in euros. The name must not be taken, ignoring case.
+    pub fn create_group(&mut self, name: &str, members: &[&str]) -> Result<()> {
+        self.splitter.create_group(name.to_string(), members.iter().map(|member| member.to_string()).collect())
+    }
+
+    /// Records an expense of `amount` like `splitter split`: `from` and `to` take the same targets as
+    /// `--from` and `--to`, e.g. `alice:10` or `bob:25%`. Without `to` everyone shares it equally.
+    ///
+    /// ```
+    /// # let path = std::env::temp_dir().join(format!("splitter-api-split-{}.db", std::process::id()));
+    /// let mut ledger = splitter::api::Ledger::open(&path)?;
+    /// ledger.create_group("trip", &["alice", "bob"])?;
+    /// ledger.split("trip", "fuel", 60_00, &["alice"], &["bob:40"])?;
+    /// assert_eq!(ledger.balances("trip")?[1], ("bob".to_string(), -40_00));
+    /// assert!(ledger.split("trip", "fuel", 60_00, &["dora"], &[]).is_err());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn split(&mut self, group: &str, description: &str, amount: i64, from: &[&str], to: &[&str]) -> Result<()> {
+        check_ceiling(Money(amount)).map_err(|problem| anyhow!(problem))?;
+        let author = self.author.clone();
+        let group = self.splitter.group_mut(group)?;
+        group.split(Money(amount), from.iter().map(|target| target.to_string()).collect(),
+                    to.iter().map(|target| target.to_string()).collect(), description.to_string(),
+                    SplitOptions::default())?;
+        group.set_last_author(author);
+        Ok(())
+    }
+
+    /// Records that `from` paid `amount` to `to`, like `splitter pay`
+    pub fn pay(&mut self, group: &str, amount: i64, from: &str, to: &str) -> Result<()> {
+        check_ceiling(Money(amount)).map_err(|problem| anyhow!(problem))?;
+        let author = self.author.clone();
+        let group = self.splitter.group_mut(group)?;
+        group.log_pay_transaction(Money(amount), from.to_string(), to.to_string())?;
+        group.set_last_author(author);
+        Ok(())
+    }
+
+    /// the members of `group` with their balances, sorted by name. Positive means they are owed money.
+    pub fn balances(&self, group: &str) -> Result<Vec<(String, i64)>> {
+        Ok(self.splitter.group(group)?.member_overview().into_iter()
+            .map(|member| (member.name, member.balance.0))
+            .collect())
+    }
+
+    /// The transfers settling `group`, as `splitter balance` suggests them with the algorithm set for
+    /// the group. Nothing is recorded.
+    ///
+    /// ```
+    /// # let path = std::env::temp_dir().join(format!("splitter-api-settle-{}.db", std::process::id()));
+    /// let mut ledger = splitter::api::Ledger::open(&path)?;
+    /// ledger.create_group("flat", &["alice", "bob"])?;
+    /// assert!(ledger.settle("flat")?.is_empty());
+    /// ledger.split("flat", "rent", 900_00, &["bob"], &[])?;
+    /// assert_eq!(ledger.settle("flat")?[0].amount, 450_00);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn settle(&self, group: &str) -> Result<Vec<Transfer>> {
+        Ok(self.splitter.group(group)?.settle(None, None)?.1.into_iter()
+            .map(|transaction| Transfer { from: transaction.from, to: transaction.to, amount: transaction.amount.0 })
+            .collect())
+    }
+
+    /// Writes the changes to the database. Fails without writing if another program changed it
+    /// since it was opened.
+    pub fn save(&mut self) -> Result<()> {
+        self.splitter.save()
+    }
+}
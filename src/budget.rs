@@ -0,0 +1,118 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Local};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::logic::Money;
+use crate::money::Currency;
+use crate::period::Month;
+
+/// share of a budget from which on every split warns
+const WARN_PERCENT: i128 = 80;
+/// width of the progress bar of `budget status`
+const BAR_WIDTH: usize = 20;
+
+/// what spend a budget limits
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BudgetPeriod {
+    /// the current calendar month
+    Month,
+    /// the open period, after the last month closed with `close-month`
+    Period,
+}
+
+/// An advisory limit for the splits of a group, or of one category of it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Budget {
+    pub(crate) amount: Money,
+    pub(crate) per: BudgetPeriod,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) category: Option<String>,
+}
+
+impl Budget {
+    /// where the period the budget limits starts, `open_since` being that of the group
+    pub(crate) fn since(&self, now: DateTime<Local>, open_since: Option<DateTime<Local>>) -> Option<DateTime<Local>> {
+        match self.per {
+            BudgetPeriod::Month => Some(Month::of(now).start()),
+            BudgetPeriod::Period => open_since,
+        }
+    }
+    fn label(&self) -> String {
+        let per = match self.per {
+            BudgetPeriod::Month => "this month",
+            BudgetPeriod::Period => "in the open period",
+        };
+        match &self.category {
+            Some(category) => format!("budget for {} {}", category, per),
+            None => format!("budget {}", per),
+        }
+    }
+}
+
+/// a budget together with what was spent in its period
+#[derive(Debug)]
+pub(crate) struct BudgetStatus {
+    pub(crate) budget: Budget,
+    pub(crate) spent: Money,
+    pub(crate) currency: Currency,
+}
+
+impl BudgetStatus {
+    /// spent share of the budget in percent, rounded down
+    pub(crate) fn percent(&self) -> i128 {
+        self.spent.0 as i128 * 100 / (self.budget.amount.0 as i128).max(1)
+    }
+    /// the warning line to print after a split, if the spend reached 80% of the budget
+    pub(crate) fn warning(&self, group: &str) -> Option<String> {
+        let percent = self.percent();
+        let state = if percent >= 100 {
+            "exceeded"
+        } else if percent >= WARN_PERCENT {
+            "almost used up"
+        } else {
+            return None;
+        };
+        Some(format!("Warning: {} of group {} {}: {} of {} spent ({}%)", self.budget.label(), group, state,
+                     self.currency.format(self.spent), self.currency.format(self.budget.amount), percent))
+    }
+}
+
+impl Display for BudgetStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let filled = (self.percent().clamp(0, 100) as usize * BAR_WIDTH) / 100;
+        write!(f, "[{}{}] {:>3}% {} of {}, {}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), self.percent(),
+               self.currency.format(self.spent), self.currency.format(self.budget.amount), self.budget.label())
+    }
+}
+
+#[cfg(test)]
+mod budget_tests {
+    use super::*;
+
+    fn status(spent: Money, category: Option<&str>) -> BudgetStatus {
+        BudgetStatus {
+            budget: Budget { amount: Money(200_00), per: BudgetPeriod::Month, category: category.map(String::from) },
+            spent,
+            currency: Currency::EUR,
+        }
+    }
+
+    #[test]
+    fn test_warning() {
+        assert_eq!(status(Money(159_99), None).warning("flat"), None);
+        assert_eq!(status(Money(160_00), Some("food")).warning("flat").unwrap(),
+                   "Warning: budget for food this month of group flat almost used up: 160.00€ of 200.00€ spent (80%)");
+        assert_eq!(status(Money(210_00), None).warning("flat").unwrap(),
+                   "Warning: budget this month of group flat exceeded: 210.00€ of 200.00€ spent (105%)");
+    }
+
+    #[test]
+    fn test_status_bar() {
+        assert_eq!(status(Money(50_00), Some("food")).to_string(),
+                   "[#####---------------]  25% 50.00€ of 200.00€, budget for food this month");
+        assert!(status(Money(300_00), None).to_string().starts_with("[####################] 150%"));
+    }
+}
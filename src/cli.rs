@@ -0,0 +1,408 @@
+//! The `splitter` command line program, built with the `cli` feature
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use chrono::Local;
+use clap::Parser;
+use crate::audit::AuditEntry;
+use crate::config::{Cli, CompleteTarget, MemberList, SubCommand};
+use crate::group::{preview_members, preview_split};
+use crate::info::Info;
+use crate::logic::Splitter;
+use crate::remote::Mirror;
+use crate::money::{Currency, Money};
+use crate::report::preview_table;
+use crate::render::DateFormat;
+use crate::settings::Settings;
+use crate::watch::Watch;
+use crate::error::*;
+use crate::{completion, doctor, inspect, money, pager, remote, repl};
+
+/// name of the marker file next to the executable that switches on portable mode
+const PORTABLE_MARKER: &str = "portable";
+
+/// the settings file and the default database, see `locations`
+type Locations = (Option<PathBuf>, Option<PathBuf>);
+
+/// Where the settings file and the default database are: `./splitter.yaml` and `./splitter.db`
+/// in portable mode, in `~/.config/splitter` otherwise, `None` without a home directory
+fn locations(cli: &Cli) -> Locations {
+    let marker = std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(PORTABLE_MARKER)))
+        .is_some_and(|marker| marker.exists());
+    if cli.portable || marker {
+        if cli.verbose {
+            println!("Portable mode ({}): using the current working directory",
+                     if cli.portable { "--portable" } else { "marker file" });
+        }
+        (Some(PathBuf::from("splitter.yaml")), Some(PathBuf::from("splitter.db")))
+    } else {
+        let splitter_home = dirs::home_dir().map(|home| home.join(".config/splitter"));
+        if cli.verbose {
+            println!("Home mode: using {:?}", splitter_home);
+        }
+        (splitter_home.as_ref().map(|dir| dir.join("config.yaml")),
+         splitter_home.map(|dir| dir.join("default.db")))
+    }
+}
+
+/// the settings file at `path`, the defaults without one
+fn load_settings(path: Option<PathBuf>) -> Result<Settings> {
+    match path {
+        Some(path) => Settings::load(path.as_path()),
+        None => Ok(Settings::default()),
+    }
+}
+
+/// Installs the ceiling for amounts of this run and checks the amounts given on the command line against it
+fn check_amounts<'a>(allow_huge: bool, settings: &Settings, commands: impl IntoIterator<Item=&'a SubCommand>)
+                     -> Result<()> {
+    let ceiling = settings.amount_ceiling.unwrap_or(money::MAX_AMOUNT);
+    money::install_ceiling(if allow_huge { None } else { Some(ceiling) });
+    for amount in commands.into_iter().flat_map(SubCommand::amounts) {
+        money::check_ceiling(amount).map_err(|problem| anyhow!(problem))?;
+    }
+    Ok(())
+}
+
+/// Figures out which database to open.
+/// Precedence: `--database`, then the `database` key of the settings file, then the default
+/// location, which is `./splitter.db` in portable mode and `~/.config/splitter/default.db` otherwise.
+/// Also returns the settings read on the way. A remote database is fetched into its local copy,
+/// which is returned as the database together with the mirror uploading it again.
+fn resolve_database(cli: &Cli, (settings_path, default_db): Locations) -> Result<(PathBuf, Settings, Option<Mirror>)> {
+    let settings = load_settings(settings_path)?;
+
+    let remote = cli.database.clone()
+        .or_else(|| settings.database.as_ref().map(|database| database.to_string_lossy().into_owned()))
+        .filter(|database| remote::is_remote(database));
+    if let Some(database) = remote {
+        let dir = default_db.as_ref().and_then(|db| db.parent()).map(|dir| dir.join("remote")).ok_or(anyhow!(
+            "Could not find a home directory to keep the copy of {} in. Please use --portable", database))?;
+        let push_command = cli.push_command.clone().or(settings.push_command.clone());
+        let mut mirror = Mirror::new(&database, remote::open(&database, push_command)?, &dir);
+        mirror.fetch()?;
+        if cli.verbose {
+            println!("Database: {} (local copy {:?})", database, mirror.local());
+        }
+        return Ok((mirror.local().to_path_buf(), settings, Some(mirror)));
+    }
+
+    let dbpath = if let Some(database) = &cli.database {
+        database.into()
+    } else if let Some(database) = &settings.database {
+        database.clone()
+    } else {
+        let default_db = default_db.ok_or(anyhow!(
+            "Could not find a home directory. Please explicitly specify a database or use --portable"))?;
+        if let Some(parent) = default_db.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent).context(format!("Could not create {:?}", parent))?;
+            }
+        }
+        default_db
+    };
+    if cli.verbose {
+        println!("Database: {:?}", dbpath);
+    }
+    Ok((dbpath, settings, None))
+}
+
+/// The database completion reads, found like `resolve_database` does but without writing or network access:
+/// of a remote database only an existing local copy is read, and no directory is created.
+fn completion_database(cli: &Cli, (settings_path, default_db): Locations) -> Option<PathBuf> {
+    let settings = load_settings(settings_path).ok()?;
+    let database = cli.database.clone()
+        .or_else(|| settings.database.as_ref().map(|database| database.to_string_lossy().into_owned()));
+    let dbpath = match database {
+        Some(database) if remote::is_remote(&database) => {
+            remote::local_copy(&database, &default_db?.parent()?.join("remote"))
+        }
+        Some(database) => database.into(),
+        None => default_db?,
+    };
+    dbpath.is_file().then_some(dbpath)
+}
+
+/// Checks that the database at `dbpath` can be loaded and, if the commands `write`, saved, before
+/// anything runs: it must not be a directory and its directory, which also holds its journal and
+/// conflict file, must exist and be writable. With `create_dirs` a missing directory is created.
+fn check_database(dbpath: &Path, write: bool, create_dirs: bool) -> Result<()> {
+    if dbpath.is_dir() {
+        return Err(anyhow!("The database {:?} is a directory, point --database to a file in it, e.g. {:?}",
+                           dbpath, dbpath.join("splitter.db")));
+    }
+    let dir = match dbpath.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.exists() {
+        if !create_dirs {
+            return Err(anyhow!("The directory {:?} of the database does not exist, create it or pass --create-dirs",
+                               dir));
+        }
+        fs::create_dir_all(dir).context(format!("Could not create the directory {:?} of the database", dir))?;
+    }
+    if !dir.is_dir() {
+        return Err(anyhow!("{:?} in the path of the database {:?} is no directory", dir, dbpath));
+    }
+    if !write {
+        return Ok(());
+    }
+    // the database is rewritten as a whole and its journal and conflict file are created next to it
+    let probe = dir.join(format!(".splitter-probe-{}", std::process::id()));
+    OpenOptions::new().write(true).create_new(true).open(&probe)
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| anyhow!("The directory {:?} of the database is not writable, changes could not be saved: {}",
+                             dir, e))?;
+    if dbpath.exists() {
+        OpenOptions::new().write(true).open(dbpath)
+            .map_err(|e| anyhow!("The database {:?} is not writable, changes could not be saved: {}", dbpath, e))?;
+    }
+    Ok(())
+}
+
+/// answers `splitter __complete ...`. Runs inside the user's shell, so any problem
+/// with the database results in no suggestions instead of an error, and nothing is saved.
+fn complete(cli: &Cli, what: &CompleteTarget) {
+    let output = match what {
+        CompleteTarget::Script { shell } => completion::script(*shell),
+        _ => completion_database(cli, locations(cli))
+            .and_then(|dbpath| Splitter::new(dbpath).ok())
+            .map(|logic| logic.complete(what).iter().map(|name| format!("{name}\n")).collect())
+            .unwrap_or_default(),
+    };
+    print!("{}", output);
+}
+
+/// `splitter doctor`: checks the config file and the database found like `resolve_database` does,
+/// without creating anything. Fails if one of the checks failed.
+fn doctor(cli: &Cli, (settings_path, default_db): Locations) -> Result<()> {
+    let (mut checks, settings) = doctor::config(settings_path.as_deref());
+    match cli.database.as_ref().map(PathBuf::from).or(settings.database).or(default_db) {
+        Some(dbpath) if remote::is_remote(&dbpath.to_string_lossy()) =>
+            checks.push(doctor::Check::ok("database", format!("{} is remote, not checked", dbpath.to_string_lossy()))),
+        Some(dbpath) => checks.extend(doctor::database(&dbpath)),
+        None => checks.push(doctor::Check::fail("database", "no home directory to keep the default database in",
+                                                "give one with --database or use --portable")),
+    }
+    let report = doctor::Report(checks);
+    print!("{}", report);
+    report.result()
+}
+
+/// `splitter info`: the versions and capabilities of this binary, and the storage version of the
+/// database found like `resolve_database` does if it exists. Creates nothing.
+fn info(cli: &Cli, json: bool, (settings_path, default_db): Locations) -> Result<()> {
+    let settings = load_settings(settings_path)?;
+    let database = match cli.database.as_ref().map(PathBuf::from).or(settings.database).or(default_db) {
+        Some(dbpath) if dbpath.exists() => Some(Splitter::new(dbpath)?.info()),
+        _ => None,
+    };
+    let info = Info::new(database);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        print!("{}", info);
+    }
+    Ok(())
+}
+
+/// `splitter compute`: what a split would change, computed among made up members without
+/// opening a database
+fn compute(cli: Cli, (settings_path, _): Locations) -> Result<()> {
+    let settings = load_settings(settings_path)?;
+    check_amounts(cli.allow_huge, &settings, &cli.command)?;
+    let Some(SubCommand::Compute { amount, members, from, to, balance_rest, currency, json }) = cli.command else {
+        unreachable!("compute is only called for the compute subcommand")
+    };
+    let (names, count) = match members {
+        MemberList::Count(count) => (vec![], Some(count)),
+        MemberList::Names(names) => (names, None),
+    };
+    let currency = currency.unwrap_or(Currency::EUR);
+    let amount = currency.amount(amount).map_err(|problem| anyhow!(problem))?;
+    let members = preview_members(names, count, amount, &from, &to, currency)?;
+    let preview = preview_split(amount, members, from, to, balance_rest, currency)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&preview)?);
+    } else {
+        pager::page(&preview_table(&preview), false);
+    }
+    Ok(())
+}
+
+/// token separating chained subcommands on the command line
+const AND_THEN: &str = "--and-then";
+
+/// Splits the command line at `--and-then`. The first part is parsed as a regular invocation,
+/// every further part as a bare subcommand sharing the global options of the first one.
+/// Everything is parsed before anything runs, so a typo in a later step changes nothing.
+fn parse_chain() -> (Cli, Vec<SubCommand>) {
+    let args: Vec<String> = std::env::args().collect();
+    let mut parts = args.split(|arg| arg == AND_THEN);
+    let cli = Cli::parse_from(parts.next().unwrap_or_default());
+    let chained = parts.enumerate()
+        .map(|(idx, part)| {
+            let argv = args.iter().take(1).chain(part.iter());
+            match Cli::try_parse_from(argv) {
+                Ok(Cli { command: Some(command), .. }) => command,
+                Ok(_) => {
+                    eprintln!("Step {}: '{}' must be followed by a subcommand", idx + 2, AND_THEN);
+                    std::process::exit(2);
+                }
+                Err(e) => {
+                    eprintln!("Step {}:", idx + 2);
+                    e.exit();
+                }
+            }
+        })
+        .collect();
+    (cli, chained)
+}
+
+/// `splitter inspect`: what `file` is, without opening it as the database. Fails if it is no
+/// splitter database or a hash chain in it is broken.
+fn inspect(file: &Path, json: bool) -> Result<()> {
+    let inspection = inspect::inspect(file)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&inspection)?);
+    } else {
+        print!("{}", inspection);
+    }
+    inspection.result()
+}
+
+/// Runs the commands of one invocation on the database and saves it. Without any command it
+/// starts the interactive mode instead. The local copy of a `remote` database keeps no journal,
+/// only the database itself is uploaded.
+fn run(cli: Cli, chained: Vec<SubCommand>, dbpath: PathBuf, settings: Settings, remote: bool) -> Result<()> {
+    let date_format = match (cli.date_format.clone(), &settings.date_format) {
+        (Some(format), _) => Some(format),
+        (None, Some(format)) => Some(DateFormat::parse(format).map_err(|error| anyhow!(error))
+            .context("Invalid date_format in the config file")?),
+        (None, None) => None,
+    };
+    if let Some(format) = date_format {
+        format.install();
+    }
+    let commands: Vec<SubCommand> = cli.command.into_iter().chain(chained).collect();
+    check_amounts(cli.allow_huge, &settings, &commands)?;
+    let write = commands.is_empty() || !commands.iter().all(SubCommand::read_only);
+    check_database(&dbpath, write, cli.create_dirs)?;
+    let nag_days = settings.nag_days.filter(|_| !cli.no_nag && !commands.iter().any(SubCommand::machine_output));
+    let nag_threshold = settings.nag_threshold.unwrap_or(Money::ZERO);
+    if let [SubCommand::Watch { group }] = commands.as_slice() {
+        return Watch::new(dbpath, group.clone()).run(&mut std::io::stdout());
+    }
+    let mut logic = Splitter::new(dbpath)?
+        .verbose(cli.verbose)
+        .force_save(cli.force_save)
+        .compression_level(cli.compression_level.or(settings.compression_level))
+        .journal((cli.journal || settings.journal) && !remote)
+        .summary_file(cli.summary_file.clone().or(settings.summary_file))
+        .author(cli.author.clone().or(settings.me))
+        .case_sensitive_names(settings.case_sensitive_group_names)
+        .max_name_width(cli.max_name_width)
+        .pager(!cli.no_pager)
+        .no_switch(cli.no_switch);
+    for renamed in logic.take_renamed() {
+        eprintln!("Warning: {}", renamed);
+    }
+    if let Some(days) = nag_days {
+        for stale in logic.stale_groups(Local::now(), days, nag_threshold) {
+            eprintln!("{}", stale);
+        }
+    }
+    if let [SubCommand::Sync { remote }] = commands.as_slice() {
+        return logic.sync(remote.clone());
+    }
+    if commands.is_empty() {
+        return repl::run(&mut logic, &mut std::io::stdin().lock());
+    }
+    logic.run_all(commands)?;
+    logic.save()?;
+    logic.take_failure()
+}
+
+/// Runs one invocation, apart from `__complete`. `database` is set to the database once it is
+/// resolved, for the audit log.
+fn dispatch(mut cli: Cli, mut chained: Vec<SubCommand>, located: Locations, database: &mut Option<PathBuf>)
+            -> Result<()> {
+    if cli.json {
+        if cli.command.is_none() {
+            return Err(anyhow!("--json needs a command, the interactive mode prints no JSON"));
+        }
+        cli.command = cli.command.map(SubCommand::into_json).transpose().map_err(|problem| anyhow!(problem))?;
+        chained = chained.into_iter().map(SubCommand::into_json).collect::<Result<_, _>>()
+            .map_err(|problem| anyhow!(problem))?;
+    }
+    if let (Some(SubCommand::Completions { shell }), true) = (&cli.command, chained.is_empty()) {
+        print!("{}", completion::generated(*shell));
+        return Ok(());
+    }
+    if let (Some(SubCommand::Doctor), true) = (&cli.command, chained.is_empty()) {
+        return doctor(&cli, located);
+    }
+    if let (Some(SubCommand::Info { json }), true) = (&cli.command, chained.is_empty()) {
+        return info(&cli, *json, located);
+    }
+    if let (Some(SubCommand::Inspect { file, json }), true) = (&cli.command, chained.is_empty()) {
+        return inspect(file, *json);
+    }
+    if let (Some(SubCommand::Compute { .. }), true) = (&cli.command, chained.is_empty()) {
+        return compute(cli, located);
+    }
+    let (dbpath, settings, mirror) = resolve_database(&cli, located)?;
+    *database = Some(dbpath.clone());
+    let verbose = cli.verbose;
+    let result = run(cli, chained, dbpath, settings, mirror.is_some());
+    let Some(mut mirror) = mirror else { return result };
+    // a failing batch may still have saved changes, they are uploaded all the same
+    match (result, mirror.push()) {
+        (Err(e), Err(pushed)) => {
+            eprintln!("Error: {:#}", pushed);
+            Err(e)
+        }
+        (result, pushed) => {
+            if verbose && pushed.as_ref().is_ok_and(|pushed| *pushed) {
+                println!("Uploaded the changes");
+            }
+            result.and(pushed.map(|_| ()))
+        }
+    }
+}
+
+/// Parses the command line, runs it and writes the audit log, for every invocation but
+/// `__complete`, which the shell runs on every press of tab
+pub fn main() -> Result<()> {
+    let started = (Local::now(), Instant::now());
+    let (cli, chained) = parse_chain();
+    if let Some(SubCommand::Complete { what }) = &cli.command {
+        complete(&cli, what);
+        return Ok(());
+    }
+    let located = locations(&cli);
+    // a config file that cannot be read fails the invocation itself, which is audited if given with --audit-log
+    let audit_log = cli.audit_log.clone().or_else(|| {
+        located.0.as_deref().and_then(|path| Settings::load(path).ok()).and_then(|settings| settings.audit_log)
+    });
+    let mut database = None;
+    let result = dispatch(cli, chained, located, &mut database);
+    if let Some(path) = audit_log {
+        let entry = AuditEntry {
+            time: started.0,
+            database,
+            args: std::env::args().skip(1).collect(),
+            // what returning the error from main exits with
+            exit_code: if result.is_ok() { 0 } else { 1 },
+            duration: started.1.elapsed(),
+        };
+        if let Err(e) = entry.append(&path) {
+            eprintln!("Warning: could not write audit log {:?}: {}", path, e);
+        }
+    }
+    result
+}
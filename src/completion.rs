@@ -0,0 +1,134 @@
+use clap::CommandFactory;
+use crate::config::{Cli, Shell};
+
+/// the completion script clap_complete generates for the whole command line, every subcommand and flag
+/// included but without group or member names
+pub(crate) fn generated(shell: Shell) -> String {
+    let shell = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+    };
+    let mut script = vec![];
+    clap_complete::generate(shell, &mut Cli::command(), "splitter", &mut script);
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+/// visible subcommand names and the subset of them taking a positional group argument,
+/// taken from the clap definitions so the scripts cannot drift from the actual cli
+fn subcommands() -> (Vec<String>, Vec<String>) {
+    let cli = Cli::command();
+    let visible: Vec<_> = cli.get_subcommands().filter(|sc| !sc.is_hide_set()).collect();
+    let names = visible.iter().map(|sc| sc.get_name().to_string()).collect();
+    let with_group = visible.iter()
+        .filter(|sc| sc.get_positionals().any(|arg| arg.get_id() == "group"))
+        .map(|sc| sc.get_name().to_string())
+        .collect();
+    (names, with_group)
+}
+
+const BASH: &str = r#"_splitter() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local db_args=() group_args=() subcommand="" subcommand_idx=0 i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -d|--database) db_args=(-d "${COMP_WORDS[i+1]}"); ((i++)) ;;
+            -g|--group) group_args=("${COMP_WORDS[i+1]}") ;;
+            -*) ;;
+            *) [[ -z "$subcommand" ]] && subcommand="${COMP_WORDS[i]}" && subcommand_idx=$i ;;
+        esac
+    done
+    case "$prev" in
+        -g|--group)
+            COMPREPLY=($(compgen -W "$(splitter "${db_args[@]}" __complete groups 2>/dev/null)" -- "$cur"))
+            return ;;
+        -f|--from|-t|--to|--except)
+            COMPREPLY=($(compgen -W "$(splitter "${db_args[@]}" __complete members "${group_args[@]}" 2>/dev/null)" -- "$cur"))
+            return ;;
+    esac
+    if [[ -z "$subcommand" ]]; then
+        COMPREPLY=($(compgen -W "@SUBCOMMANDS@" -- "$cur"))
+        return
+    fi
+    case "$subcommand" in
+        @GROUP_SUBCOMMANDS_BASH@)
+            if ((COMP_CWORD == subcommand_idx + 1)) && [[ "$cur" != -* ]]; then
+                COMPREPLY=($(compgen -W "$(splitter "${db_args[@]}" __complete groups 2>/dev/null)" -- "$cur"))
+            fi ;;
+    esac
+}
+complete -F _splitter splitter
+"#;
+
+const ZSH: &str = r#"#compdef splitter
+_splitter() {
+    local -a db_args group_args
+    local subcommand="" subcommand_idx=0 i
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${words[i]}" in
+            -d|--database) db_args=(-d "${words[i+1]}"); ((i++)) ;;
+            -g|--group) group_args=("${words[i+1]}") ;;
+            -*) ;;
+            *) [[ -z "$subcommand" ]] && subcommand="${words[i]}" && subcommand_idx=$i ;;
+        esac
+    done
+    case "${words[CURRENT-1]}" in
+        -g|--group)
+            compadd -- ${(f)"$(splitter $db_args __complete groups 2>/dev/null)"}
+            return ;;
+        -f|--from|-t|--to|--except)
+            compadd -- ${(f)"$(splitter $db_args __complete members $group_args 2>/dev/null)"}
+            return ;;
+    esac
+    if [[ -z "$subcommand" ]]; then
+        compadd -- @SUBCOMMANDS@
+        return
+    fi
+    case "$subcommand" in
+        @GROUP_SUBCOMMANDS_BASH@)
+            if ((CURRENT == subcommand_idx + 1)) && [[ "${words[CURRENT]}" != -* ]]; then
+                compadd -- ${(f)"$(splitter $db_args __complete groups 2>/dev/null)"}
+            fi ;;
+    esac
+}
+compdef _splitter splitter
+"#;
+
+const FISH: &str = r#"function __splitter_db_args
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] -d --database
+            printf '%s\n' -d $tokens[(math $i + 1)]
+        end
+    end
+end
+function __splitter_group_arg
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] -g --group
+            printf '%s\n' $tokens[(math $i + 1)]
+        end
+    end
+end
+complete -c splitter -f
+complete -c splitter -n __fish_use_subcommand -a "@SUBCOMMANDS@"
+complete -c splitter -s g -l group -x -a "(splitter (__splitter_db_args) __complete groups 2>/dev/null)"
+complete -c splitter -s f -l from -x -a "(splitter (__splitter_db_args) __complete members (__splitter_group_arg) 2>/dev/null)"
+complete -c splitter -s t -l to -x -a "(splitter (__splitter_db_args) __complete members (__splitter_group_arg) 2>/dev/null)"
+complete -c splitter -l except -x -a "(splitter (__splitter_db_args) __complete members (__splitter_group_arg) 2>/dev/null)"
+complete -c splitter -n "__fish_seen_subcommand_from @GROUP_SUBCOMMANDS_FISH@" -a "(splitter (__splitter_db_args) __complete groups 2>/dev/null)"
+"#;
+
+/// shell snippet completing subcommands statically and group/member names via `splitter __complete`
+pub(crate) fn script(shell: Shell) -> String {
+    let template = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    };
+    let (names, with_group) = subcommands();
+    template
+        .replace("@SUBCOMMANDS@", names.join(" ").as_str())
+        .replace("@GROUP_SUBCOMMANDS_BASH@", with_group.join("|").as_str())
+        .replace("@GROUP_SUBCOMMANDS_FISH@", with_group.join(" ").as_str())
+}
@@ -1,89 +1,831 @@
-use clap::{command, Parser, Subcommand};
+// without the `cli` feature nothing parses the commands, the engine only matches on them
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
 
+use std::path::PathBuf;
+use chrono::NaiveDate;
+#[cfg(feature = "cli")]
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::logic::Money;
+use crate::money::{Currency, Rate};
+#[cfg(feature = "cli")]
+use crate::money::{parse_amount, parse_currency};
+use crate::budget::BudgetPeriod;
+use crate::period::Month;
+#[cfg(feature = "cli")]
+use crate::render::DateFormat;
+use crate::report::SpendingBy;
+use crate::settle::Algorithm;
 
+
+#[cfg(feature = "cli")]
 #[derive(Parser, Debug)]
+#[command(after_help = "Several subcommands can be chained with `--and-then`, e.g. \
+`splitter split 30 -n dinner -f alice --and-then pay 20 -f bob -t alice`. \
+Either all of them are applied or, if one fails, none.")]
 pub(crate) struct Cli {
     #[command(subcommand)]
     pub(crate) command: Option<SubCommand>,
 
+    /// the database file, or a remote one given as `https://...` URL or as `cmd:<command>` printing it
     #[arg(long, short)]
     pub(crate) database: Option<String>,
+
+    /// command writing back a database given as `cmd:`, which it reads on stdin
+    #[arg(long)]
+    pub(crate) push_command: Option<String>,
+
+    /// create the directory of the database if it does not exist
+    #[arg(long)]
+    pub(crate) create_dirs: bool,
+
+    /// keep database and config in the current working directory instead of ~/.config/splitter
+    #[arg(long)]
+    pub(crate) portable: bool,
+
+    #[arg(long, short)]
+    pub(crate) verbose: bool,
+
+    /// print JSON instead of text, for scripts; only for commands printing data, like list, stat or balance
+    #[arg(long)]
+    pub(crate) json: bool,
+
+    /// append a one-line summary of every new log entry to this file
+    #[arg(long)]
+    pub(crate) summary_file: Option<PathBuf>,
+
+    /// append a line about this invocation and its outcome to this file, see README
+    #[arg(long)]
+    pub(crate) audit_log: Option<PathBuf>,
+
+    /// save even if another program changed the database in the meantime
+    #[arg(long)]
+    pub(crate) force_save: bool,
+
+    /// who is entering the new log entries, defaults to `me` from the config file
+    #[arg(long)]
+    pub(crate) author: Option<String>,
+
+    /// truncate names in tables to this many columns
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..))]
+    pub(crate) max_name_width: Option<usize>,
+
+    /// print long output directly instead of through `$PAGER`
+    #[arg(long)]
+    pub(crate) no_pager: bool,
+
+    /// keep the default group even when a command changes another group
+    #[arg(long)]
+    pub(crate) no_switch: bool,
+
+    /// how dates are shown: `relative` (like `3 days ago`, within 30 days) or a strftime pattern like `%d.%m.%Y`
+    #[arg(long, value_parser = DateFormat::parse)]
+    pub(crate) date_format: Option<DateFormat>,
+
+    /// append new log entries to `<database>.journal` instead of rewriting a compressed database every time
+    #[arg(long)]
+    pub(crate) journal: bool,
+
+    /// brotli quality (0 to 11) used when writing a compressed database, lower is faster
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=11))]
+    pub(crate) compression_level: Option<u32>,
+
+    /// print no notice about groups with old unsettled debts, see `nag_days` in the README
+    #[arg(long)]
+    pub(crate) no_nag: bool,
+
+    /// accept amounts above the ceiling of 1000000000.00, or the `amount_ceiling` of the config file
+    #[arg(long)]
+    pub(crate) allow_huge: bool,
 }
 
-impl Cli {
-    pub(crate) fn is_empty(&self) -> bool {
-        self.command.is_none() && self.database.is_none()
+impl SubCommand {
+    /// whether the command prints output meant for other programs, which no notice may get in between
+    pub(crate) fn machine_output(&self) -> bool {
+        match self {
+            Self::List { json, porcelain, .. } | Self::Balance { json, porcelain, .. }
+            | Self::Groups { json, porcelain, .. } => *json || *porcelain,
+            Self::Stat { porcelain, format, .. } => *porcelain || !matches!(format, StatFormat::Text),
+            Self::Top { json, .. } | Self::Members { json, .. } | Self::Info { json } | Self::Compute { json, .. }
+            | Self::Inspect { json, .. } | Self::Feed { json, .. } | Self::Quote { json, .. } => *json,
+            Self::Export { .. } | Self::ExportLedger { .. } | Self::ExportYnab { .. } | Self::Complete { .. }
+            | Self::Completions { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// whether the command only looks at the database, so it never has to be written
+    pub(crate) fn read_only(&self) -> bool {
+        matches!(self, Self::List { .. } | Self::Feed { .. } | Self::Stat { .. } | Self::Top { .. } | Self::Chart { .. }
+            | Self::Groups { .. } | Self::Members { .. } | Self::Report { .. } | Self::Export { .. }
+            | Self::ExportLedger { .. } | Self::ExportYnab { .. } | Self::Whoami | Self::Watch { .. } | Self::Quote { .. })
+    }
+
+    /// The command with its JSON output switched on, for `--json`. Commands without JSON output are
+    /// refused, so nothing but JSON reaches stdout. `balance` only applies its transfers with `--yes`.
+    pub(crate) fn into_json(self) -> Result<Self, String> {
+        Ok(match self {
+            Self::List { group, author, all, archived, .. } =>
+                Self::List { group, author, all, archived, json: true, porcelain: false },
+            Self::Balance { group, yes, in_currency, rate, algorithm, banker, .. } =>
+                Self::Balance { group, yes, json: true, porcelain: false, in_currency, rate, algorithm, banker },
+            Self::Groups { all, .. } => Self::Groups { all, json: true, porcelain: false },
+            Self::Stat { group, all, threshold, show_all, in_currency, rate, period, as_of, member, .. } =>
+                Self::Stat { group, all, porcelain: false, format: StatFormat::Json, threshold, show_all, in_currency,
+                             rate, period, as_of, member },
+            Self::Top { group, count, category, member, .. } => Self::Top { group, count, category, member, json: true },
+            Self::Members { group, .. } => Self::Members { group, json: true },
+            Self::Feed { limit, .. } => Self::Feed { limit, json: true },
+            Self::Info { .. } => Self::Info { json: true },
+            Self::Inspect { file, .. } => Self::Inspect { file, json: true },
+            Self::Compute { amount, members, from, to, balance_rest, currency, .. } =>
+                Self::Compute { amount, members, from, to, balance_rest, currency, json: true },
+            Self::Quote { group, amount, among, by_ratio, .. } => Self::Quote { group, amount, among, by_ratio, json: true },
+            command @ (Self::Export { .. } | Self::Complete { .. }) => command,
+            _ => return Err("--json works with list, stat, balance, groups, members, top, feed, compute, info, \
+                inspect and export; other commands print no JSON".to_string()),
+        })
+    }
+
+    /// The `split` or `pay` a shorthand `s` or `p` stands for, with `me` as payer and the default
+    /// group. Other commands are returned as they are.
+    pub(crate) fn expand_shorthand(self, me: Option<&str>) -> Result<Self, String> {
+        let me = || me.map(String::from).ok_or_else(|| "Who paid? The shorthands pay as you: set `me: <name>` \
+            in the config file or pass --author, `splitter whoami` shows who you are".to_string());
+        Ok(match self {
+            Self::S { amount, name, yes } => Self::Split {
+                amount,
+                from: vec![me()?],
+                to: vec![],
+                from_file: None,
+                to_file: None,
+                name: name.join(" "),
+                group: None,
+                balance_rest: false,
+                category: None,
+                by_ratio: None,
+                strict: false,
+                each: false,
+                refund: false,
+                except: vec![],
+                yes,
+            },
+            Self::P { amount, to, yes } => Self::Pay { amount, group: None, from: me()?, to, yes },
+            command => command,
+        })
+    }
+
+    /// the amounts given on the command line, checked against the ceiling before anything runs
+    pub(crate) fn amounts(&self) -> Vec<Money> {
+        match self {
+            Self::Split { amount, .. } | Self::Pay { amount, .. } | Self::Compute { amount, .. } | Self::Quote { amount, .. }
+            | Self::S { amount, .. } | Self::P { amount, .. }
+            | Self::Budget { action: BudgetAction::Set { amount, .. } } => vec![*amount],
+            Self::Stat { threshold: amount, .. } | Self::GroupConfig { confirm_above: amount, .. }
+            | Self::Add { buy_in: amount, .. } => amount.iter().copied().collect(),
+            _ => vec![],
+        }
     }
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
 pub(crate) enum SubCommand {
+    /// record an expense shared by the members of a group
     Split {
-        amount: f32,
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
 
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
         from: Vec<String>,
 
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
         to: Vec<String>,
 
-        #[arg(long, short)]
+        /// file with one --from target per line, `#` starts a comment
+        #[cfg_attr(feature = "cli", arg(long))]
+        from_file: Option<PathBuf>,
+
+        /// file with one --to target per line, `#` starts a comment
+        #[cfg_attr(feature = "cli", arg(long))]
+        to_file: Option<PathBuf>,
+
+        #[cfg_attr(feature = "cli", arg(long, short))]
         name: String,
 
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
         group: Option<String>,
 
-        #[arg(long, short)]
-        balance_rest: Option<bool>,
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        balance_rest: bool,
+
+        /// free-form category like "food", used for filtering reports
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        category: Option<String>,
+
+        /// split the rest by a ratio set of the group instead of equally, see `ratio set`
+        #[cfg_attr(feature = "cli", arg(long))]
+        by_ratio: Option<String>,
+
+        /// fail instead of warning if members are missing from the ratio set
+        #[cfg_attr(feature = "cli", arg(long, requires = "by_ratio"))]
+        strict: bool,
+
+        /// money the group got back, like a returned deposit: the members named with --to received it,
+        /// and everyone's balance improves by their share
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["from", "from_file", "balance_rest"]))]
+        refund: bool,
+
+        /// the amount is per head: multiplied by the members named with --to, or by all members
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "by_ratio"))]
+        each: bool,
+
+        /// a member who takes no part, like someone who wasn't there; may be given more than once
+        #[cfg_attr(feature = "cli", arg(long))]
+        except: Vec<String>,
+
+        /// record an amount above the confirmation threshold of the group without asking
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
     },
+    /// record that one member paid another
     Pay {
-        amount: f32,
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
 
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
         group: Option<String>,
 
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
         from: String,
-        #[arg(long, short)]
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        to: String,
+
+        /// record an amount above the confirmation threshold of the group without asking
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
+    },
+    /// shorthand for `split <amount> -n <name> -f <me>` in the default group, everyone sharing
+    S {
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
+        /// what it was for, several words are joined by spaces
+        #[cfg_attr(feature = "cli", arg(required = true))]
+        name: Vec<String>,
+        /// record an amount above the confirmation threshold of the group without asking
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
+    },
+    /// shorthand for `pay <amount> -f <me> -t <to>` in the default group
+    P {
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
         to: String,
+        /// record an amount above the confirmation threshold of the group without asking
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
     },
+    /// print who new log entries are entered by, which the shorthands `s` and `p` take as payer
+    Whoami,
+    /// undo an entry of the log, the last one by default
     Undo {
         group: Option<String>,
         index: Option<usize>,
+        /// undo without asking even if later entries build on the entry
+        #[cfg_attr(feature = "cli", arg(long))]
+        force: bool,
+    },
+    /// recompute a past split as if more members had taken part in it
+    RebalanceEntry {
+        group: String,
+        /// index of the split as shown by `list`
+        index: usize,
+        /// members sharing the split as well, who joined after it
+        #[cfg_attr(feature = "cli", arg(long, required = true))]
+        include: Vec<String>,
     },
+    /// start a new group
     Create {
         name: String,
 
-        #[arg(short = 'a', long = "add")]
+        #[cfg_attr(feature = "cli", arg(short = 'a', long = "add"))]
         members: Vec<String>,
+        /// keep a tamper-evident hash chain over the log, see `verify`
+        #[cfg_attr(feature = "cli", arg(long))]
+        integrity: bool,
+        /// CSV file of `member,balance` rows to start the group at, the members are added as well
+        #[cfg_attr(feature = "cli", arg(long))]
+        opening_balances: Option<PathBuf>,
+        /// the member absorbing what the opening balances do not add up to zero
+        #[cfg_attr(feature = "cli", arg(long, requires = "opening_balances"))]
+        balance_with: Option<String>,
     },
+    /// delete groups
     DeleteGroup {
+        #[cfg_attr(feature = "cli", arg(required_unless_present = "all", conflicts_with = "all"))]
+        groups: Vec<String>,
+        /// delete every group, archived ones included
+        #[cfg_attr(feature = "cli", arg(long))]
+        all: bool,
+        #[cfg_attr(feature = "cli", arg(short = 'y', long = "yes"))]
+        yes: bool,
+        /// list every member and balance before asking, not just the largest balances
+        #[cfg_attr(feature = "cli", arg(long))]
+        full: bool,
+        /// export the group to this file before deleting it
+        #[cfg_attr(feature = "cli", arg(long))]
+        export_first: Option<PathBuf>,
+    },
+    /// rename a group, keeping its log and settings
+    RenameGroup {
+        old: String,
+        new: String,
+    },
+    /// clear the log and zero the balances of a group, keeping its currency and settings
+    Reset {
         group: String,
-        #[arg(short = 'y', long = "yes")]
-        yes: Option<bool>,
+        /// keep the members, otherwise they are replaced by the ones given with --add
+        #[cfg_attr(feature = "cli", arg(long))]
+        keep_members: bool,
+        /// the members of the fresh group
+        #[cfg_attr(feature = "cli", arg(short = 'a', long = "add", required_unless_present = "keep_members", conflicts_with = "keep_members"))]
+        members: Vec<String>,
+        #[cfg_attr(feature = "cli", arg(short = 'y', long = "yes"))]
+        yes: bool,
+        /// list every member and balance before asking, not just the largest balances
+        #[cfg_attr(feature = "cli", arg(long))]
+        full: bool,
     },
+    /// list the log entries of a group
     List {
         group: Option<String>,
-        #[arg(short = 'a', long = "all")]
-        all: Option<bool>,
+        /// only entries entered by this author
+        #[cfg_attr(feature = "cli", arg(long))]
+        author: Option<String>,
+        #[cfg_attr(feature = "cli", arg(short = 'a', long = "all"))]
+        all: bool,
+        /// with --all, also list archived groups
+        #[cfg_attr(feature = "cli", arg(long))]
+        archived: bool,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+        /// stable tab-separated output for scripts, see README
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "json"))]
+        porcelain: bool,
     },
+    /// list the newest log entries of all groups that are not archived, newest first
+    Feed {
+        #[cfg_attr(feature = "cli", arg(short = 'n', long, default_value_t = 20))]
+        limit: usize,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// show the balances and statistics of a group
     Stat {
         group: Option<String>,
-        all: Option<bool>,
+        /// the statistics of every group
+        #[cfg_attr(feature = "cli", arg(short = 'a', long = "all", conflicts_with = "group"))]
+        all: bool,
+        /// stable tab-separated output for scripts, see README. Short for `--format porcelain`
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "format"))]
+        porcelain: bool,
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = StatFormat::Text))]
+        format: StatFormat,
+        /// collapse members whose balance is closer to zero than this into a single line
+        #[cfg_attr(feature = "cli", arg(long, value_parser = parse_amount))]
+        threshold: Option<Money>,
+        /// list every member, ignoring --threshold
+        #[cfg_attr(feature = "cli", arg(long))]
+        show_all: bool,
+        /// show amounts converted into this currency, using the group's exchange rate
+        #[cfg_attr(feature = "cli", arg(long = "in", value_parser = parse_currency))]
+        in_currency: Option<Currency>,
+        /// exchange rate for --in instead of the stored one
+        #[cfg_attr(feature = "cli", arg(long, requires = "in_currency"))]
+        rate: Option<Rate>,
+        /// `current` only counts the entries after the last month closed with `close-month`
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = Period::All))]
+        period: Period,
+        /// the balances at the end of this day like 2024-03-31, replayed from the entries until then
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "period"))]
+        as_of: Option<NaiveDate>,
+        /// only show the balance of this member, without a group in every group they are in
+        #[cfg_attr(feature = "cli", arg(long, short, conflicts_with = "all"))]
+        member: Option<String>,
+    },
+    /// keep running and show the stat of a group again whenever the database changes, until Ctrl-C
+    Watch {
+        group: Option<String>,
     },
+    /// suggest the transfers settling a group and apply them if confirmed
     Balance {
-        group: String
+        group: String,
+        /// apply the suggested transactions without asking
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
+        /// print the suggested transactions as JSON, only applied together with --yes
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+        /// like --json, but stable tab-separated output, see README
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "json"))]
+        porcelain: bool,
+        /// show amounts converted into this currency, using the group's exchange rate
+        #[cfg_attr(feature = "cli", arg(long = "in", value_parser = parse_currency))]
+        in_currency: Option<Currency>,
+        /// exchange rate for --in instead of the stored one
+        #[cfg_attr(feature = "cli", arg(long, requires = "in_currency"))]
+        rate: Option<Rate>,
+        /// how to work out the transfers, the group default (see group-config) or greedy if not given
+        #[cfg_attr(feature = "cli", arg(long, value_enum))]
+        algorithm: Option<Algorithm>,
+        /// the member everyone settles with for --algorithm pairwise, by default the one owed the most
+        #[cfg_attr(feature = "cli", arg(long))]
+        banker: Option<String>,
+    },
+    /// list the largest expenses of a group
+    Top {
+        group: Option<String>,
+        #[cfg_attr(feature = "cli", arg(short = 'n', long, default_value_t = 5))]
+        count: usize,
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        category: Option<String>,
+        /// only expenses this member paid for or took part in
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        member: Option<String>,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// bar chart of the spending of a group
+    Chart {
+        group: Option<String>,
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = SpendingBy::Payer))]
+        by: SpendingBy,
+    },
+    /// list all groups, most recently used first
+    Groups {
+        /// also list archived groups
+        #[cfg_attr(feature = "cli", arg(short = 'a', long))]
+        all: bool,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+        /// stable tab-separated output for scripts, see README
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "json"))]
+        porcelain: bool,
+    },
+    /// make a group the default for the commands after it, without a group list the groups
+    Use {
+        group: Option<String>,
+    },
+    /// list the members of a group with their balances
+    Members {
+        group: Option<String>,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// write a group with its members, balances and whole log as JSON, tagged with the storage version
+    Export {
+        group: String,
+        /// file to write, `-` for stdout
+        output: PathBuf,
+    },
+    /// add a group written by `export`
+    Import {
+        file: PathBuf,
+        /// the name of the new group, needed if the exported name is taken
+        #[cfg_attr(feature = "cli", arg(long))]
+        rename: Option<String>,
+    },
+    /// export the log entries affecting one member as ledger-cli journal
+    ExportLedger {
+        group: String,
+        #[cfg_attr(feature = "cli", arg(short = 'm', long))]
+        member: String,
+        /// the group's receivable account is <prefix>:<group>
+        #[cfg_attr(feature = "cli", arg(long, default_value = "Assets:Splitter"))]
+        account_prefix: String,
+        /// account for the member's share of expenses, a category is added as sub-account
+        #[cfg_attr(feature = "cli", arg(long, default_value = "Expenses:Splitter"))]
+        expense_account: String,
+        /// account money the member actually paid or received is taken from
+        #[cfg_attr(feature = "cli", arg(long, default_value = "Assets:Cash"))]
+        funds_account: String,
+        /// write to this file instead of stdout
+        #[cfg_attr(feature = "cli", arg(short = 'o', long))]
+        out: Option<PathBuf>,
+    },
+    /// export the log entries affecting one member as CSV for YNAB
+    ExportYnab {
+        group: String,
+        #[cfg_attr(feature = "cli", arg(short = 'm', long))]
+        member: String,
+        /// write to this file instead of stdout
+        #[cfg_attr(feature = "cli", arg(short = 'o', long))]
+        out: Option<PathBuf>,
     },
+    /// bring the database and a shared copy, e.g. in a synced folder, up to date with each other
+    Sync {
+        remote: PathBuf,
+    },
+    /// merge the log entries of another copy of the database into this one
+    Merge {
+        other: PathBuf,
+    },
+    /// write the database as a whole, folding in the journal kept with --journal
+    Checkpoint,
+    /// check the config file and the database, printing a hint for every problem found
+    Doctor,
+    /// print what a split would change without opening a database, see `split` for --from and --to
+    Compute {
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
+        /// how many members take part, or their comma-separated names
+        #[cfg_attr(feature = "cli", arg(long, value_parser = parse_members))]
+        members: MemberList,
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        from: Vec<String>,
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        to: Vec<String>,
+        #[cfg_attr(feature = "cli", arg(long, short))]
+        balance_rest: bool,
+        #[cfg_attr(feature = "cli", arg(long, value_parser = parse_currency))]
+        currency: Option<Currency>,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// what each member's share of a split of this amount in a group would be, recording nothing
+    Quote {
+        group: String,
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
+        /// the comma-separated members taking part, all by default
+        #[cfg_attr(feature = "cli", arg(long, value_delimiter = ','))]
+        among: Vec<String>,
+        /// split by a ratio set of the group instead of equally, see `ratio set`
+        #[cfg_attr(feature = "cli", arg(long))]
+        by_ratio: Option<String>,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// print the version and what this binary supports, e.g. for scripts checking compatibility
+    Info {
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// detect the format of any file and check whether it is an intact splitter database
+    Inspect {
+        file: PathBuf,
+        #[cfg_attr(feature = "cli", arg(long))]
+        json: bool,
+    },
+    /// show or change the settings of a group
+    GroupConfig {
+        group: Option<String>,
+        /// ask before recording a split or payment above this amount
+        #[cfg_attr(feature = "cli", arg(long, value_parser = parse_amount))]
+        confirm_above: Option<Money>,
+        /// record any amount without asking
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "confirm_above"))]
+        no_confirm_above: bool,
+        /// how `balance` settles the group unless given --algorithm
+        #[cfg_attr(feature = "cli", arg(long, value_enum))]
+        algorithm: Option<Algorithm>,
+        /// the member everyone settles with for the pairwise algorithm
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "no_banker"))]
+        banker: Option<String>,
+        /// settle pairwise with the member owed the most again
+        #[cfg_attr(feature = "cli", arg(long))]
+        no_banker: bool,
+    },
+    /// show or set the exchange rate from the group currency used by `--in`
+    ExchangeRate {
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_currency))]
+        currency: Currency,
+        /// units of `currency` per unit of the group currency, e.g. 1.08 for EUR to USD
+        rate: Option<Rate>,
+        #[cfg_attr(feature = "cli", arg(short, long))]
+        group: Option<String>,
+    },
+    /// close a month of a group, printing its report, see README
+    CloseMonth {
+        group: String,
+        /// the month to close like 2024-06, the previous month by default
+        #[cfg_attr(feature = "cli", arg(long))]
+        month: Option<Month>,
+        /// record the suggested transfers as payments
+        #[cfg_attr(feature = "cli", arg(long))]
+        settle: bool,
+    },
+    /// spending and suggested transfers of a month, or of the open period by default
+    Report {
+        group: Option<String>,
+        #[cfg_attr(feature = "cli", arg(long))]
+        month: Option<Month>,
+    },
+    /// start a new group with the balances of another one, which is archived
+    Carry {
+        source: String,
+        name: String,
+        /// take the members of the new group from this group instead of the source
+        #[cfg_attr(feature = "cli", arg(long))]
+        members_from: Option<String>,
+        #[cfg_attr(feature = "cli", arg(short = 'y', long))]
+        yes: bool,
+    },
+    /// advisory spending limits, checked after every split
+    Budget {
+        #[cfg_attr(feature = "cli", command(subcommand))]
+        action: BudgetAction,
+    },
+    /// manage the ratio sets used by `split --by-ratio`
+    Ratio {
+        #[cfg_attr(feature = "cli", command(subcommand))]
+        action: RatioAction,
+    },
+    /// check the hash chain of a group in integrity mode
+    Verify {
+        group: String,
+        /// switch integrity mode on, chaining the existing entries
+        #[cfg_attr(feature = "cli", arg(long))]
+        enable: bool,
+    },
+    /// add members to a group
     Add {
-        #[arg(short = 'g', long = "group")]
+        #[cfg_attr(feature = "cli", arg(short = 'g', long = "group", conflicts_with_all = ["groups", "all"]))]
         group: Option<String>,
         members: Vec<String>,
+        /// a member to add, the same as listing them
+        #[cfg_attr(feature = "cli", arg(long = "member"))]
+        named: Vec<String>,
+        /// the new members start owing this much, credited to who paid for the group's splits so far
+        #[cfg_attr(feature = "cli", arg(long, value_parser = parse_amount, conflicts_with = "buy_in_share"))]
+        buy_in: Option<Money>,
+        /// like --buy-in with an equal share of everything spent in the group so far
+        #[cfg_attr(feature = "cli", arg(long))]
+        buy_in_share: bool,
+        /// add to each of these groups, e.g. `--groups trip,flat`, reporting how it went per group
+        #[cfg_attr(feature = "cli", arg(long, value_delimiter = ',', conflicts_with = "all"))]
+        groups: Vec<String>,
+        /// add to every group that is not archived, like --groups
+        #[cfg_attr(feature = "cli", arg(long))]
+        all: bool,
+        /// with --groups or --all, stop at the first group failing and change nothing
+        #[cfg_attr(feature = "cli", arg(long))]
+        strict: bool,
     },
+    /// remove members from a group
     Remove {
-        #[arg(short = 'g', long = "group")]
+        #[cfg_attr(feature = "cli", arg(short = 'g', long = "group", conflicts_with_all = ["groups", "all"]))]
         group: Option<String>,
-        #[arg(short = 'f', long = "force")]
-        force: Option<bool>,
+        #[cfg_attr(feature = "cli", arg(short = 'f', long = "force"))]
+        force: bool,
+        /// with --force, the member taking over the balances of the removed ones instead of everyone left
+        #[cfg_attr(feature = "cli", arg(long, requires = "force"))]
+        transfer_to: Option<String>,
         members: Vec<String>,
+        /// a member to remove, the same as listing them
+        #[cfg_attr(feature = "cli", arg(long = "member"))]
+        named: Vec<String>,
+        /// remove from each of these groups, e.g. `--groups trip,flat`, reporting how it went per group
+        #[cfg_attr(feature = "cli", arg(long, value_delimiter = ',', conflicts_with = "all"))]
+        groups: Vec<String>,
+        /// remove from every group that is not archived, like --groups
+        #[cfg_attr(feature = "cli", arg(long))]
+        all: bool,
+        /// with --groups or --all, stop at the first group failing and change nothing
+        #[cfg_attr(feature = "cli", arg(long))]
+        strict: bool,
+    },
+    /// rename a member of a group, in its balances, log entries and settings
+    RenameMember {
+        #[cfg_attr(feature = "cli", arg(short = 'g', long = "group"))]
+        group: Option<String>,
+        old: String,
+        new: String,
+    },
+    /// book a correction bringing balances that do not add up to zero back to zero
+    FixResidual {
+        group: String,
+        /// the member the whole residual is booked to
+        #[cfg_attr(feature = "cli", arg(long, required_unless_present = "spread", conflicts_with = "spread"))]
+        assign: Option<String>,
+        /// spread the residual over all members, a cent each
+        #[cfg_attr(feature = "cli", arg(long))]
+        spread: bool,
+    },
+    /// print a completion script for every subcommand and flag, e.g. `source <(splitter completions bash)`
+    Completions {
+        shell: Shell,
+    },
+    /// runtime helpers for shell completion, not meant to be called by hand
+    #[cfg_attr(feature = "cli", command(name = "__complete", hide = true))]
+    Complete {
+        #[cfg_attr(feature = "cli", command(subcommand))]
+        what: CompleteTarget,
+    },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub(crate) enum CompleteTarget {
+    /// print all group names, one per line
+    Groups,
+    /// print the member names of a group (or the current group), one per line
+    Members {
+        group: Option<String>,
     },
+    /// print a completion script calling the helpers above
+    Script {
+        shell: Shell,
+    },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub(crate) enum BudgetAction {
+    /// limit the spend of a group, or of one category with --category
+    Set {
+        group: String,
+        #[cfg_attr(feature = "cli", arg(value_parser = parse_amount))]
+        amount: Money,
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = BudgetPeriod::Month))]
+        per: BudgetPeriod,
+        #[cfg_attr(feature = "cli", arg(short, long))]
+        category: Option<String>,
+    },
+    Remove {
+        group: String,
+        #[cfg_attr(feature = "cli", arg(short, long))]
+        category: Option<String>,
+    },
+    /// show the budgets of a group with their progress
+    Status {
+        group: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub(crate) enum RatioAction {
+    /// store weights for the members of a group, e.g. `ratio set flat income alice=3 bob=2`
+    Set {
+        group: String,
+        name: String,
+        /// `member=weight` with whole numbers as weights
+        #[cfg_attr(feature = "cli", arg(required = true))]
+        shares: Vec<String>,
+    },
+    /// show the ratio sets of a group
+    Show {
+        group: String,
+    },
+    /// delete a ratio set
+    Remove {
+        group: String,
+        name: String,
+    },
+}
+
+/// which entries `stat` counts
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub(crate) enum Period {
+    All,
+    /// the entries after the last closed month
+    Current,
+}
+
+/// how `stat` prints its result
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub(crate) enum StatFormat {
+    Text,
+    Json,
+    Porcelain,
+    /// `KEY=value` lines for `eval` in shell scripts
+    Env,
+}
+
+/// the members of `compute --members`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum MemberList {
+    /// that many members, the ones not named by a target get placeholder names
+    Count(usize),
+    Names(Vec<String>),
+}
+
+/// Parses `--members 5` or `--members alice,bob,carl`
+fn parse_members(input: &str) -> Result<MemberList, String> {
+    match input.trim().parse::<usize>() {
+        Ok(0) => Err("at least one member must take part".to_string()),
+        Ok(count) => Ok(MemberList::Count(count)),
+        Err(_) => Ok(MemberList::Names(input.split(',').map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()).collect())),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
 }
\ No newline at end of file
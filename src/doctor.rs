@@ -0,0 +1,200 @@
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::path::Path;
+use crate::error::*;
+use crate::logic::Splitter;
+use crate::render::DateFormat;
+use crate::settings::Settings;
+use crate::storage::{conflict_path, MAX_LEVEL};
+
+/// outcome of a check of `splitter doctor`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One line of `splitter doctor`: what was checked, what was found and, unless everything is fine,
+/// what to do about it
+#[derive(Debug)]
+pub(crate) struct Check {
+    pub(crate) status: Status,
+    pub(crate) name: String,
+    pub(crate) found: String,
+    pub(crate) hint: Option<String>,
+}
+
+impl Check {
+    pub(crate) fn ok(name: impl Into<String>, found: impl Into<String>) -> Self {
+        Self { status: Status::Ok, name: name.into(), found: found.into(), hint: None }
+    }
+    pub(crate) fn warn(name: impl Into<String>, found: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { status: Status::Warn, name: name.into(), found: found.into(), hint: Some(hint.into()) }
+    }
+    pub(crate) fn fail(name: impl Into<String>, found: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { status: Status::Fail, name: name.into(), found: found.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// The checks of `splitter doctor` in the order they ran
+pub(crate) struct Report(pub(crate) Vec<Check>);
+
+impl Report {
+    /// an error naming the number of failed checks, if there are any
+    pub(crate) fn result(&self) -> Result<()> {
+        match self.0.iter().filter(|check| check.status == Status::Fail).count() {
+            0 => Ok(()),
+            1 => Err(anyhow!("1 check failed")),
+            failed => Err(anyhow!("{} checks failed", failed)),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.0.iter().map(|check| check.name.len()).max().unwrap_or(0);
+        for check in &self.0 {
+            let status = match check.status {
+                Status::Ok => "ok",
+                Status::Warn => "warn",
+                Status::Fail => "fail",
+            };
+            writeln!(f, "{:<4}  {:<width$}  {}", status, check.name, check.found)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "      {:<width$}  hint: {}", "", hint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the settings file at `path`. Returns the settings it holds, the defaults if it cannot be used.
+pub(crate) fn config(path: Option<&Path>) -> (Vec<Check>, Settings) {
+    let Some(path) = path else {
+        return (vec![Check::warn("config", "no home directory to look for config.yaml in",
+                                 "use --portable to keep the config file in the working directory")],
+                Settings::default());
+    };
+    if !path.exists() {
+        return (vec![Check::ok("config", format!("{:?} does not exist, using the defaults", path))],
+                Settings::default());
+    }
+    let settings = match Settings::load(path) {
+        Ok(settings) => settings,
+        Err(e) => return (vec![Check::fail("config", format!("{:#}", e),
+                                           "fix the file or move it away to use the defaults")],
+                          Settings::default()),
+    };
+    let mut checks = vec![Check::ok("config", format!("{:?}", path))];
+    let unknown = unknown_keys(&std::fs::read_to_string(path).unwrap_or_default());
+    if !unknown.is_empty() {
+        checks.push(Check::warn("config keys", format!("unknown keys {}", unknown.join(", ")),
+                                "they are ignored, check them for typos"));
+    }
+    if let Some(Err(e)) = settings.date_format.as_deref().map(DateFormat::parse) {
+        checks.push(Check::fail("config date_format", e, "every command fails until it is fixed"));
+    }
+    if settings.compression_level.is_some_and(|level| level > MAX_LEVEL) {
+        checks.push(Check::warn("config compression_level",
+                                format!("{} is more than the highest level", settings.compression_level.unwrap()),
+                                format!("{} is used instead", MAX_LEVEL)));
+    }
+    (checks, settings)
+}
+
+/// the top level keys of the settings file `raw` that are no setting
+fn unknown_keys(raw: &str) -> Vec<String> {
+    let known = serde_yaml::to_value(Settings::default()).unwrap_or_default();
+    let Ok(serde_yaml::Value::Mapping(keys)) = serde_yaml::from_str(raw) else { return vec![] };
+    keys.keys()
+        .filter(|key| known.get(*key).is_none())
+        .map(|key| key.as_str().map_or_else(|| format!("{:?}", key), String::from))
+        .collect()
+}
+
+/// Checks the database at `path`: whether it can be read and written, then what `Splitter::doctor` finds
+/// in it. A database that cannot be loaded is reported, not returned as error.
+pub(crate) fn database(path: &Path) -> Vec<Check> {
+    if !path.exists() {
+        return vec![Check::warn("database", format!("{:?} does not exist yet", path),
+                                "it is created by the first command changing something, e.g. `splitter create`")];
+    }
+    if !path.is_file() {
+        return vec![Check::fail("database", format!("{:?} is no file", path),
+                                "point --database or the database key of the config file to a file")];
+    }
+    let mut checks = vec![];
+    let readable = std::fs::File::open(path).map_err(|e| e.to_string());
+    let writable = OpenOptions::new().write(true).open(path).map_err(|e| e.to_string());
+    match (&readable, &writable) {
+        (Ok(_), Ok(_)) => checks.push(Check::ok("database", format!("{:?} is readable and writable", path))),
+        (Ok(_), Err(e)) => checks.push(Check::fail("database", format!("{:?} is not writable: {}", path, e),
+                                                   "commands changing something fail, check the permissions")),
+        (Err(e), _) => return vec![Check::fail("database", format!("{:?} is not readable: {}", path, e),
+                                               "check the permissions of the file")],
+    }
+    let conflict = conflict_path(path);
+    if conflict.exists() {
+        checks.push(Check::warn("conflict", format!("{:?} exists", conflict),
+                                "a save ran into changes of another program, merge it with `splitter merge` and remove it"));
+    }
+    match Splitter::new(path.to_path_buf()) {
+        Ok(splitter) => checks.extend(splitter.doctor()),
+        Err(e) => checks.push(Check::fail("load", format!("{:#}", e),
+                                          "restore a backup or check that this is the right file")),
+    }
+    checks
+}
+
+#[cfg(test)]
+mod doctor_tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = Report(vec![
+            Check::ok("config", "none"),
+            Check::warn("database", "missing", "create a group"),
+        ]);
+        assert_eq!(report.to_string(), "ok    config    none\nwarn  database  missing\n                hint: create a group\n");
+        assert!(report.result().is_ok());
+        let report = Report(vec![Check::fail("load", "broken", "restore"), Check::fail("group a", "off", "undo")]);
+        assert_eq!(report.result().unwrap_err().to_string(), "2 checks failed");
+    }
+
+    #[test]
+    fn test_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let (checks, _) = config(Some(&path));
+        assert_eq!(checks[0].status, Status::Ok);
+
+        std::fs::write(&path, "me: alice\njornal: true\ndate_format: '%Q'\n").unwrap();
+        let (checks, settings) = config(Some(&path));
+        assert_eq!(settings.me.as_deref(), Some("alice"));
+        let statuses: Vec<Status> = checks.iter().map(|check| check.status).collect();
+        assert_eq!(statuses, vec![Status::Ok, Status::Warn, Status::Fail]);
+        assert_eq!(checks[1].found, "unknown keys jornal");
+
+        std::fs::write(&path, "me: [alice\n").unwrap();
+        let (checks, settings) = config(Some(&path));
+        assert_eq!(checks[0].status, Status::Fail);
+        assert!(checks[0].found.starts_with("Could not parse config file"), "{}", checks[0].found);
+        assert_eq!(settings.me, None);
+    }
+
+    #[test]
+    fn test_database() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(database(&dir.path().join("missing.db"))[0].status, Status::Warn);
+        assert_eq!(database(dir.path())[0].status, Status::Fail);
+
+        let path = dir.path().join("garbage.db");
+        std::fs::write(&path, b"no brotli at all").unwrap();
+        std::fs::write(conflict_path(&path), b"").unwrap();
+        let checks = database(&path);
+        let found: Vec<(&str, Status)> = checks.iter().map(|check| (check.name.as_str(), check.status)).collect();
+        assert_eq!(found, vec![("database", Status::Ok), ("conflict", Status::Warn), ("load", Status::Fail)]);
+    }
+}
@@ -25,4 +25,35 @@ impl Display for SplitterError {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+/// how many valid names an error message lists before cutting off
+const MAX_LISTED_OPTIONS: usize = 10;
+
+/// Formats the valid choices for a failed lookup, e.g. `valid members: alice, bob`,
+/// cut off after a few names so large groups do not flood the terminal
+pub(crate) fn valid_options<S: AsRef<str>>(kind: &str, options: &[S]) -> String {
+    if options.is_empty() {
+        return format!("there are no {kind}");
+    }
+    let listed: Vec<&str> = options.iter().take(MAX_LISTED_OPTIONS).map(|o| o.as_ref()).collect();
+    let more = options.len().saturating_sub(MAX_LISTED_OPTIONS);
+    if more > 0 {
+        format!("valid {kind}: {} and {more} more", listed.join(", "))
+    } else {
+        format!("valid {kind}: {}", listed.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_options() {
+        assert_eq!(valid_options::<&str>("groups", &[]), "there are no groups");
+        assert_eq!(valid_options("members", &["alice", "bob"]), "valid members: alice, bob");
+        let many: Vec<String> = (0..12).map(|i| format!("m{i}")).collect();
+        assert_eq!(valid_options("members", &many),
+                   "valid members: m0, m1, m2, m3, m4, m5, m6, m7, m8, m9 and 2 more");
+    }
+}
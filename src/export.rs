@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use crate::error::*;
+use crate::group::Group;
+use crate::logging::LoggedCommand;
+use crate::logic::{Money, Splitter};
+use crate::money::Currency;
+
+/// A single group serialized on its own, tagged with the state version that wrote it
+#[derive(Serialize)]
+pub(crate) struct GroupExport<'a> {
+    pub(crate) version: &'a str,
+    pub(crate) group: &'a Group,
+}
+
+impl<'a> GroupExport<'a> {
+    pub(crate) fn new(group: &'a Group) -> Self {
+        Self {
+            version: Splitter::CURRENT_VERSION,
+            group,
+        }
+    }
+
+    /// the export as pretty printed JSON
+    pub(crate) fn json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.json()?)
+            .context(format!("Could not export group {} to {:?}", self.group.name, path))
+    }
+}
+
+/// A group file written by `GroupExport`
+#[derive(Deserialize)]
+pub(crate) struct GroupImport {
+    pub(crate) version: String,
+    pub(crate) group: Group,
+}
+
+impl GroupImport {
+    pub(crate) fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path).context(format!("Could not read {:?}", path))?;
+        serde_json::from_str(&json).context(format!("{:?} is no group exported by splitter", path))
+    }
+}
+
+/// writes an export to `out` or, if not given, to stdout
+pub(crate) fn write_or_print(out: Option<PathBuf>, content: &str) -> Result<()> {
+    match out {
+        Some(path) => std::fs::write(&path, content)
+            .context(format!("Could not write export to {:?}", path)),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+/// Accounts used by the ledger export
+pub(crate) struct LedgerAccounts {
+    /// what the group owes the member, one sub-account per group
+    pub(crate) receivable: String,
+    /// the member's own share of expenses, one sub-account per category if there is one
+    pub(crate) expense: String,
+    /// where money the member actually paid or received comes from
+    pub(crate) funds: String,
+}
+
+/// amount in ledger syntax, e.g. `-12.50 EUR`
+fn ledger_amount(amount: Money, currency: Currency) -> String {
+    format!("{} {:?}", amount.to_decimal_string(currency), currency)
+}
+
+/// How a single log entry affected one member
+struct MemberEffect<'a> {
+    time: Option<DateTime<Local>>,
+    /// the split name, or who paid whom
+    payee: String,
+    category: Option<&'a str>,
+    /// money the member actually paid (negative if received)
+    paid: Money,
+    /// the member's share of an expense
+    share: Money,
+    author: Option<&'a str>,
+}
+
+impl MemberEffect<'_> {
+    /// how the member's balance changed
+    fn balance_change(&self) -> Money {
+        self.paid - self.share
+    }
+}
+
+/// The effects of all log entries of `group` on `member`, leaving out entries not involving them
+fn member_effects<'a>(group: &'a Group, member: &str) -> Result<Vec<MemberEffect<'a>>> {
+    if !group.member_names().iter().any(|name| *name == member) {
+        return Err(anyhow!(SplitterError::MemberNotFound)).context(format!(
+            "{} is not within group {} ({})", member, group.name,
+            valid_options("members", &group.member_names())));
+    }
+    Ok(group.log().iter()
+        .map(|entry| match &entry.command {
+            LoggedCommand::Split { name, category, .. } => {
+                let share = entry.shares().into_iter()
+                    .find(|(name, _)| name == member)
+                    .map_or(Money::ZERO, |(_, share)| share);
+                MemberEffect {
+                    time: entry.time,
+                    payee: name.clone(),
+                    category: category.as_deref(),
+                    paid: share + entry.change_of(member),
+                    share,
+                    author: entry.author.as_deref(),
+                }
+            }
+            LoggedCommand::Pay { amount, from, to, .. } => MemberEffect {
+                time: entry.time,
+                payee: format!("payment {} → {}", from, to),
+                category: None,
+                paid: if from == member { *amount } else if to == member { -amount } else { Money::ZERO },
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+            LoggedCommand::Opening { source, .. } => MemberEffect {
+                time: entry.time,
+                payee: format!("opening balance from {}", source),
+                category: None,
+                paid: entry.change_of(member),
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+            LoggedCommand::WriteOff { member: removed, .. } => MemberEffect {
+                time: entry.time,
+                payee: format!("write-off of {}", removed),
+                category: None,
+                paid: entry.change_of(member),
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+            LoggedCommand::BuyIn { member: newcomer, .. } => MemberEffect {
+                time: entry.time,
+                payee: format!("buy-in of {}", newcomer),
+                category: None,
+                paid: entry.change_of(member),
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+            LoggedCommand::Correction { .. } => MemberEffect {
+                time: entry.time,
+                payee: "residual correction".to_string(),
+                category: None,
+                paid: entry.change_of(member),
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+            LoggedCommand::Reset { .. } => MemberEffect {
+                time: entry.time,
+                payee: "reset".to_string(),
+                category: None,
+                paid: Money::ZERO,
+                share: Money::ZERO,
+                author: entry.author.as_deref(),
+            },
+        })
+        .filter(|effect| !effect.paid.is_zero() || !effect.share.is_zero())
+        .collect())
+}
+
+/// Renders every log entry of `group` that affects `member` as a ledger-cli transaction.
+/// A split posts the member's share as expense and what they paid against the funds account,
+/// the difference ends up in the receivable account, which thereby mirrors the member's balance.
+/// Entries without a date (written by older versions) are dated `today`.
+pub(crate) fn ledger(group: &Group, member: &str, accounts: &LedgerAccounts, today: NaiveDate) -> Result<String> {
+    let receivable = format!("{}:{}", accounts.receivable, group.name);
+    let mut journal = String::new();
+    for effect in member_effects(group, member)? {
+        let expense = match effect.category {
+            Some(category) => format!("{}:{}", accounts.expense, category),
+            None => accounts.expense.clone(),
+        };
+        let postings: Vec<_> = [(expense, effect.share),
+            (receivable.clone(), effect.balance_change()),
+            (accounts.funds.clone(), -effect.paid)]
+            .into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .collect();
+        let width = postings.iter().map(|(account, _)| account.chars().count()).max().unwrap_or(0);
+        let date = effect.time.map_or(today, |time| time.date_naive());
+        journal += &format!("{} {}\n", date.format("%Y/%m/%d"), effect.payee);
+        if effect.time.is_none() {
+            journal += "    ; original date unknown\n";
+        }
+        if let Some(author) = effect.author {
+            journal += &format!("    ; author: {}\n", author);
+        }
+        for (account, amount) in &postings {
+            journal += &format!("    {:<width$}  {:>14}\n", account, ledger_amount(*amount, group.currency));
+        }
+        journal += "\n";
+    }
+    Ok(journal)
+}
+
+/// amount for YNAB's Outflow/Inflow columns: no currency symbol, always positive, `.` as decimal point
+fn ynab_amount(amount: Money, currency: Currency) -> String {
+    amount.abs().to_decimal_string(currency)
+}
+
+/// Renders every log entry of `group` that affects `member` as a row of a YNAB import file.
+/// The rows mirror the member's balance: what they fronted for others is an inflow, their share
+/// of an expense an outflow, so the imported account ends up with the member's balance.
+/// Entries without a date (written by older versions) are dated `today`.
+pub(crate) fn ynab(group: &Group, member: &str, today: NaiveDate) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(["Date", "Payee", "Memo", "Outflow", "Inflow"])?;
+    for effect in member_effects(group, member)? {
+        let change = effect.balance_change();
+        if change.is_zero() {
+            continue;
+        }
+        let mut memo = match effect.category {
+            Some(category) => format!("{} / {}", group.name, category),
+            None => group.name.clone(),
+        };
+        if let Some(author) = effect.author {
+            memo += &format!(", by {}", author);
+        }
+        let amount = ynab_amount(change, group.currency);
+        let (outflow, inflow) = if change.is_negative() { (amount, String::new()) } else { (String::new(), amount) };
+        let date = effect.time.map_or(today, |time| time.date_naive());
+        writer.write_record([date.format("%Y-%m-%d").to_string(), effect.payee, memo, outflow, inflow])?;
+    }
+    String::from_utf8(writer.into_inner()?).context("YNAB export is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"
+name: trip
+currency: EUR
+members:
+  alice: 1100
+  bob: 0
+  carol: -1100
+log:
+- command: !Split
+    name: dinner
+    amount: 3000
+    from:
+    - member: alice
+      amount: null
+    to: []
+    group: trip
+    balance_rest: false
+    category: food
+  change:
+    alice: 2000
+    bob: -1000
+    carol: -1000
+  time: 2024-06-01T12:00:00Z
+- command: !Split
+    name: taxi
+    amount: 1200
+    from:
+    - member: bob
+      amount: null
+    to: []
+    group: trip
+    balance_rest: false
+  change:
+    alice: -400
+    bob: 800
+    carol: -400
+  time: 2024-06-02T12:00:00Z
+- command: !Pay
+    amount: 500
+    from: bob
+    to: alice
+    group: trip
+  change:
+    bob: 500
+    alice: -500
+- command: !Pay
+    amount: 300
+    from: carol
+    to: bob
+    group: trip
+  change:
+    carol: 300
+    bob: -300
+  time: 2024-06-04T12:00:00Z
+  author: carol
+"#;
+
+    fn accounts() -> LedgerAccounts {
+        LedgerAccounts {
+            receivable: "Assets:Splitter".to_string(),
+            expense: "Expenses:Splitter".to_string(),
+            funds: "Assets:Cash".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ledger() {
+        let group: Group = serde_yaml::from_str(FIXTURE).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        assert_eq!(ledger(&group, "alice", &accounts(), today).unwrap(),
+                   "2024/06/01 dinner\n\
+                   \x20   Expenses:Splitter:food       10.00 EUR\n\
+                   \x20   Assets:Splitter:trip         20.00 EUR\n\
+                   \x20   Assets:Cash                 -30.00 EUR\n\
+                   \n\
+                   2024/06/02 taxi\n\
+                   \x20   Expenses:Splitter           4.00 EUR\n\
+                   \x20   Assets:Splitter:trip       -4.00 EUR\n\
+                   \n\
+                   2024/06/10 payment bob → alice\n\
+                   \x20   ; original date unknown\n\
+                   \x20   Assets:Splitter:trip       -5.00 EUR\n\
+                   \x20   Assets:Cash                 5.00 EUR\n\
+                   \n");
+        // carol is only affected by the splits and her own payment
+        let carol = ledger(&group, "carol", &accounts(), today).unwrap();
+        assert_eq!(carol.matches("\n\n").count(), 3);
+        assert!(carol.contains("2024/06/04 payment carol → bob\n\
+                               \x20   ; author: carol\n\
+                               \x20   Assets:Splitter:trip        3.00 EUR\n\
+                               \x20   Assets:Cash                -3.00 EUR\n"));
+        assert!(ledger(&group, "dave", &accounts(), today).is_err());
+        assert_eq!(ledger_amount(Money(-1050), Currency::JPY), "-1050 JPY");
+    }
+
+    #[test]
+    fn test_ledger_balances() {
+        let group: Group = serde_yaml::from_str(FIXTURE).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        for member in ["alice", "bob", "carol"] {
+            let journal = ledger(&group, member, &accounts(), today).unwrap();
+            let mut receivable = Money::ZERO;
+            for transaction in journal.split("\n\n").filter(|t| !t.is_empty()) {
+                let amounts: Vec<(String, Money)> = transaction.lines()
+                    .filter(|line| line.ends_with(" EUR"))
+                    .map(|line| {
+                        let mut parts = line.split_whitespace();
+                        let account = parts.next().unwrap().to_string();
+                        let amount = Money(parts.next().unwrap().replace('.', "").parse().unwrap());
+                        (account, amount)
+                    })
+                    .collect();
+                assert_eq!(amounts.iter().map(|(_, amount)| amount).sum::<Money>(), Money::ZERO, "{transaction}");
+                receivable += amounts.iter()
+                    .filter(|(account, _)| account == "Assets:Splitter:trip")
+                    .map(|(_, amount)| amount)
+                    .sum::<Money>();
+            }
+            assert_eq!(receivable, group.member_overview().iter()
+                .find(|m| m.name == member).unwrap().balance, "{member}");
+        }
+    }
+
+    #[test]
+    fn test_ynab() {
+        let group: Group = serde_yaml::from_str(&FIXTURE.replace("name: taxi", "name: 'taxi, \"late\"'")).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        let csv = ynab(&group, "alice", today).unwrap();
+        assert_eq!(csv, "Date,Payee,Memo,Outflow,Inflow\n\
+                         2024-06-01,dinner,trip / food,,20.00\n\
+                         2024-06-02,\"taxi, \"\"late\"\"\",trip,4.00,\n\
+                         2024-06-10,payment bob → alice,trip,5.00,\n");
+
+        // parsing it again gives back the same values and adds up to the member's balance
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        assert_eq!(reader.headers().unwrap(), vec!["Date", "Payee", "Memo", "Outflow", "Inflow"]);
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(&rows[1][1], "taxi, \"late\"");
+        let cents = |field: &str| Money(if field.is_empty() { 0 } else { field.replace('.', "").parse().unwrap() });
+        let total: Money = rows.iter().map(|row| cents(&row[4]) - cents(&row[3])).sum();
+        assert_eq!(total, Money(11_00));
+        assert!(ynab(&group, "carol", today).unwrap().ends_with("2024-06-04,payment carol → bob,\"trip, by carol\",,3.00\n"));
+        assert_eq!(ynab_amount(Money(-1050), Currency::JPY), "1050");
+    }
+}
@@ -0,0 +1,165 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Local};
+use serde::{Serialize, Serializer};
+use crate::group::Group;
+use crate::logging::LogEntry;
+
+/// The newest entry of one log not yet taken by the feed
+struct Head<'a> {
+    time: Option<DateTime<Local>>,
+    /// which of the logs, earlier logs come first among entries of the same time
+    log: usize,
+    index: usize,
+    entry: &'a LogEntry,
+}
+
+impl Head<'_> {
+    /// newer first, undated entries last, then in the order of the logs
+    fn key(&self) -> (Option<DateTime<Local>>, Reverse<usize>) {
+        (self.time, Reverse(self.log))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Head<'_> {}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Merges logs, each oldest first like a group keeps it, into one sequence newest first. Yields
+/// the position of the log, the index of the entry in it and the entry, without copying any log.
+pub(crate) struct Merge<'a> {
+    logs: Vec<&'a [LogEntry]>,
+    heads: BinaryHeap<Head<'a>>,
+}
+
+impl<'a> Merge<'a> {
+    pub(crate) fn new(logs: Vec<&'a [LogEntry]>) -> Self {
+        let mut merge = Self { logs, heads: BinaryHeap::new() };
+        for log in 0..merge.logs.len() {
+            merge.push(log, merge.logs[log].len());
+        }
+        merge
+    }
+
+    /// queues the entry before `end` of log `log`, if there is one
+    fn push(&mut self, log: usize, end: usize) {
+        if let Some(index) = end.checked_sub(1) {
+            let entry = &self.logs[log][index];
+            self.heads.push(Head { time: entry.time, log, index, entry });
+        }
+    }
+}
+
+impl<'a> Iterator for Merge<'a> {
+    type Item = (usize, usize, &'a LogEntry);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.heads.pop()?;
+        self.push(head.log, head.index);
+        Some((head.log, head.index, head.entry))
+    }
+}
+
+/// An entry of `splitter feed`
+#[derive(Serialize)]
+pub(crate) struct FeedItem<'a> {
+    #[serde(serialize_with = "group_name")]
+    pub(crate) group: &'a Group,
+    /// the index of the entry in the log of the group
+    #[serde(skip)]
+    pub(crate) index: usize,
+    pub(crate) entry: &'a LogEntry,
+}
+
+/// groups appear in the JSON of the feed by name
+fn group_name<S: Serializer>(group: &&Group, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&group.name)
+}
+
+impl Display for FeedItem<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.group.name)?;
+        self.entry.write(f, self.index, self.group.currency)
+    }
+}
+
+/// the entries of all groups that are not archived, newest first
+pub(crate) fn feed(groups: &[Group]) -> impl Iterator<Item=FeedItem<'_>> {
+    let active: Vec<&Group> = groups.iter().filter(|group| !group.archived).collect();
+    Merge::new(active.iter().map(|group| group.log()).collect())
+        .map(move |(log, index, entry)| FeedItem { group: active[log], index, entry })
+}
+
+#[cfg(test)]
+mod feed_tests {
+    use std::collections::HashMap;
+    use chrono::{Duration, TimeZone};
+    use crate::logging::LoggedCommand;
+    use crate::money::Money;
+    use super::*;
+
+    fn pay(group: &str, amount: Money, time: Option<DateTime<Local>>) -> LogEntry {
+        let command = LoggedCommand::Pay { amount, from: "alice".to_string(), to: "bob".to_string(),
+                                           group: group.to_string(), settled_by: None };
+        let change = HashMap::from([("alice".to_string(), amount), ("bob".to_string(), -amount)]);
+        let mut entry = LogEntry::new(command, change);
+        entry.time = time;
+        entry
+    }
+
+    #[test]
+    fn test_merge() {
+        let day = |day: u32| Some(Local.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap());
+        let flat = vec![pay("flat", Money(1), None), pay("flat", Money(2), day(1)), pay("flat", Money(3), day(4)),
+                        pay("flat", Money(4), day(9))];
+        let trip = vec![pay("trip", Money(5), day(2)), pay("trip", Money(6), day(4)), pay("trip", Money(7), day(8))];
+        let club = vec![pay("club", Money(8), None), pay("club", Money(9), day(3))];
+        let merged: Vec<(usize, usize)> = Merge::new(vec![&flat, &trip, &club, &[]])
+            .map(|(log, index, _)| (log, index))
+            .collect();
+        // flat wins the tie on day 4 by coming first, the undated entries come last
+        assert_eq!(merged, vec![(0, 3), (1, 2), (0, 2), (1, 1), (2, 1), (1, 0), (0, 1), (0, 0), (2, 0)]);
+        assert_eq!(Merge::new(vec![&trip, &club]).take(2).map(|(_, _, entry)| entry.time).collect::<Vec<_>>(),
+                   vec![day(8), day(4)]);
+        assert_eq!(Merge::new(vec![]).count(), 0);
+    }
+
+    #[test]
+    fn test_feed() {
+        let group = |name: &str| Group::new(name.to_string(), vec!["alice".to_string(), "bob".to_string()], None).unwrap();
+        let mut flat = group("flat");
+        let mut trip = group("trip");
+        let mut archived = group("old");
+        archived.log_pay_transaction(Money(1_00), "alice".to_string(), "bob".to_string()).unwrap();
+        archived.archived = true;
+        flat.log_pay_transaction(Money(10_00), "alice".to_string(), "bob".to_string()).unwrap();
+        std::thread::sleep(Duration::milliseconds(5).to_std().unwrap());
+        trip.log_pay_transaction(Money(20_00), "bob".to_string(), "alice".to_string()).unwrap();
+        let groups = vec![flat, archived, trip];
+
+        let items: Vec<FeedItem> = feed(&groups).collect();
+        assert_eq!(items.iter().map(|item| item.group.name.as_str()).collect::<Vec<_>>(), vec!["trip", "flat"]);
+        assert!(items[0].to_string().starts_with("trip: [0] "), "{}", items[0]);
+        assert!(items[0].to_string().ends_with("bob → alice 20.00€"), "{}", items[0]);
+        let json = serde_json::to_value(&items[1]).unwrap();
+        assert_eq!(json["group"], "flat");
+        assert_eq!(json["entry"]["command"]["Pay"]["amount"], 10_00);
+    }
+}
@@ -1,17 +1,120 @@
-use std::collections::HashMap;
-use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use chrono::{Local, NaiveDate};
 use serde::{Deserialize, Serialize};
+use crate::budget::{Budget, BudgetStatus};
 use crate::error::*;
+use crate::integrity::{first_broken_link, rechain, Rechain};
 use crate::logging::{LogEntry, LoggedCommand};
-use crate::logic::{Money, Splitter, Target, Transaction, TransactionChange};
-use crate::money::Currency;
+use crate::logic::{normalize_name, valid_name, Money, Target, Transaction, TransactionChange};
+use crate::money::{eval_decimal, parse_balance, Currency, Rate};
+use crate::period::{day_end, ClosedMonth, Month};
+use crate::render::date;
+use crate::settle::{greedy, settle, Algorithm};
+use crate::storage::{by_name, sorted};
+use crate::report::{top_expenses, AsOf, GroupListing, PreviewRow, SplitPreview, ListedEntry, GroupOverview, GroupStat, LogSummary, MemberOverview,
+                    MemberPeriod, PeriodReport, TopEntry};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct Group {
     pub(crate) name: String,
     pub(crate) currency: Currency,
+    #[serde(serialize_with = "sorted")]
     members: HashMap<String, Money>,
     log: Vec<LogEntry>,
+    /// archived groups are hidden from overviews unless explicitly requested
+    #[serde(default)]
+    pub(crate) archived: bool,
+    /// every log entry carries a hash over its content and the previous entry's hash
+    #[serde(default)]
+    pub(crate) integrity: bool,
+    /// where the history was rewritten in integrity mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rechains: Vec<Rechain>,
+    /// exchange rates from the group currency, used to show amounts in other currencies
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) rates: BTreeMap<Currency, Rate>,
+    /// named weights like incomes, used by `split --by-ratio` instead of equal shares
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) ratios: BTreeMap<String, BTreeMap<String, u64>>,
+    /// months closed with `close-month`, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) closed_months: Vec<ClosedMonth>,
+    /// advisory spending limits, at most one per category and one for the whole group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) budgets: Vec<Budget>,
+    /// splits and payments above this amount are only recorded after confirmation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) confirm_above: Option<Money>,
+    /// how `balance` settles the group unless told otherwise, greedy if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) algorithm: Option<Algorithm>,
+    /// the member everyone settles with for the pairwise algorithm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) banker: Option<String>,
+    /// what everyone who appears in the log did over all of it, kept up to date with the log.
+    /// Files written by older versions have none, they are counted on load.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    totals: BTreeMap<String, Totals>,
+    /// minor units per major unit the amounts are stored in, only written if not 100. Older versions
+    /// stored yen in hundredths too, `migrate` converts them to the subdivision of the currency.
+    #[serde(default = "hundredths", skip_serializing_if = "is_hundredths")]
+    subdivision: i64,
+}
+
+fn hundredths() -> i64 {
+    100
+}
+
+fn is_hundredths(subdivision: &i64) -> bool {
+    *subdivision == hundredths()
+}
+
+/// Running totals of a member over the whole log
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Totals {
+    /// what they paid for splits
+    pub(crate) paid: Money,
+    /// their shares of splits
+    pub(crate) consumed: Money,
+    /// entries that changed their balance or gave them a share
+    pub(crate) entries: usize,
+}
+
+/// Adds what `entry` did to `totals`, or takes it away again with `undo`
+fn count(totals: &mut BTreeMap<String, Totals>, entry: &LogEntry, undo: bool) {
+    let sign = if undo { -1 } else { 1 };
+    let shares: HashMap<String, Money> = entry.shares().into_iter().collect();
+    let members: HashSet<&String> = entry.change().keys().chain(shares.keys()).collect();
+    for member in members {
+        let share = shares.get(member).copied().unwrap_or(Money::ZERO);
+        let change = entry.change_of(member);
+        if share.is_zero() && change.is_zero() {
+            continue;
+        }
+        let member_totals = totals.entry(member.clone()).or_default();
+        if matches!(entry.command, LoggedCommand::Split { .. }) {
+            member_totals.paid += sign * (share + change);
+            member_totals.consumed += sign * share;
+        }
+        if undo {
+            member_totals.entries -= 1;
+        } else {
+            member_totals.entries += 1;
+        }
+        if *member_totals == Totals::default() {
+            totals.remove(member);
+        }
+    }
+}
+
+/// the totals of everyone in `entries`
+fn totals_of<'a>(entries: impl IntoIterator<Item=&'a LogEntry>) -> BTreeMap<String, Totals> {
+    let mut totals = BTreeMap::new();
+    for entry in entries {
+        count(&mut totals, entry, false);
+    }
+    totals
 }
 
 impl Group {
@@ -20,32 +123,181 @@ impl Group {
             return Err(anyhow!(SplitterError::LogicError))
                 .context("Group must have at least one member");
         }
-        let membrs = {
-            let mut map = HashMap::with_capacity(members.len());
-            let regex = Regex::new(Splitter::NAME_REGEX).unwrap();
-            for m in members {
-                if regex.is_match(m.as_str()) {
-                    map.insert(m, 0);
-                } else {
-                    return Err(anyhow!(SplitterError::InvalidName))
-                        .context(format!("Name {} is not allowed for members", m));
-                }
+        let mut membrs = HashMap::with_capacity(members.len());
+        let mut duplicates = vec![];
+        let mut errors = vec![];
+        for member in members {
+            let normalized = normalize_name(&member);
+            #[allow(clippy::map_entry)]
+            if !valid_name(&normalized) {
+                errors.push(shown_name(&member, normalized));
+            } else if membrs.contains_key(&normalized) {
+                duplicates.push(shown_name(&member, normalized));
+            } else {
+                membrs.insert(normalized, Money::ZERO);
             }
-            map
-        };
+        }
+        if !duplicates.is_empty() || !errors.is_empty() {
+            duplicates.sort();
+            duplicates.dedup();
+            errors.sort();
+            errors.dedup();
+            let problems: Vec<String> = [("names not allowed for members", errors), ("given more than once", duplicates)]
+                .into_iter()
+                .filter(|(_, names)| !names.is_empty())
+                .map(|(problem, names)| format!("{}: {}", problem, names.join(", ")))
+                .collect();
+            return Err(anyhow!(SplitterError::InvalidName)).context(problems.join("\n"));
+        }
+        let currency = currency.unwrap_or(Currency::EUR);
         Ok(Self {
             name,
-            currency: currency.unwrap_or(Currency::EUR),
+            currency,
             members: membrs,
             log: vec![],
+            archived: false,
+            integrity: false,
+            rechains: vec![],
+            rates: BTreeMap::new(),
+            ratios: BTreeMap::new(),
+            closed_months: vec![],
+            budgets: vec![],
+            confirm_above: None,
+            algorithm: None,
+            banker: None,
+            totals: BTreeMap::new(),
+            subdivision: currency.subdivision(),
         })
     }
-    pub(crate) fn get_log(&self, index: Option<usize>) -> Result<&LogEntry> {
+    /// the log entry at `index` or the last one, together with its resolved index
+    pub(crate) fn get_log(&self, index: Option<usize>) -> Result<(usize, &LogEntry)> {
         if self.log.is_empty() {
             return Err(anyhow!(SplitterError::LogEntryNotFound));
         }
         let index = index.unwrap_or(self.log.len() - 1);
-        self.log.get(index).ok_or(anyhow!(SplitterError::LogEntryNotFound))
+        self.log.get(index).map(|entry| (index, entry)).ok_or(anyhow!(SplitterError::LogEntryNotFound))
+    }
+    /// fills in fields missing from entries written by older versions
+    pub(crate) fn migrate(&mut self) {
+        for entry in self.log.iter_mut() {
+            if let LoggedCommand::Pay { group, .. } = &mut entry.command {
+                if group.is_empty() {
+                    group.clone_from(&self.name);
+                }
+            }
+            entry.fix_legacy_pay_change();
+            if entry.id.is_empty() {
+                entry.id = entry.content_hash();
+            }
+        }
+        if self.totals.is_empty() && !self.log.is_empty() {
+            self.rebuild_totals();
+        }
+        let (from, to) = (self.subdivision, self.currency.subdivision());
+        if from != to {
+            let convert = |amount: Money| amount.rescale(from, to);
+            for balance in self.members.values_mut() {
+                *balance = convert(*balance);
+            }
+            for entry in self.log.iter_mut() {
+                entry.convert_amounts(convert);
+            }
+            for budget in self.budgets.iter_mut() {
+                budget.amount = convert(budget.amount);
+            }
+            self.confirm_above = self.confirm_above.map(convert);
+            self.subdivision = to;
+            self.rebuild_totals();
+            self.rewrite_chain(0, &format!("amounts converted to the subdivision of {:?}", self.currency));
+        }
+    }
+    /// an amount given on the command line in minor units of the group currency, see `Currency::amount`
+    pub(crate) fn in_currency(&self, given: Money) -> Result<Money> {
+        self.currency.amount(given).map_err(|problem| anyhow!(problem))
+    }
+    /// Renames the members whose names `normalize_name` changes, as written by versions that took
+    /// names as given, and returns what was renamed. Names whose normalized form is taken or still
+    /// invalid stay as they are, see `unnormalized_members`.
+    pub(crate) fn normalize_member_names(&mut self) -> Vec<String> {
+        let mut renamed = vec![];
+        let names: Vec<String> = self.member_names().into_iter().cloned().collect();
+        for name in names {
+            let normalized = normalize_name(&name);
+            if normalized != name && self.rename_member(&name, normalized.clone()).is_ok() {
+                renamed.push(format!("renamed member {:?} of group {} to {:?}", name, self.name, normalized));
+            }
+        }
+        renamed
+    }
+    /// the members `normalize_member_names` could not rename, with the reason
+    #[cfg(any(feature = "cli", test))]
+    pub(crate) fn unnormalized_members(&self) -> Vec<String> {
+        self.member_names().into_iter()
+            .filter(|name| normalize_name(name) != **name)
+            .map(|name| {
+                let normalized = normalize_name(name);
+                let reason = self.check_new_member_name(&normalized).err().map_or_else(String::new, |e| e.to_string());
+                format!("{:?} is {:?} once normalized, but {}", name, normalized, reason)
+            })
+            .collect()
+    }
+    /// counts the running totals from the log again
+    pub(crate) fn rebuild_totals(&mut self) {
+        self.totals = totals_of(&self.log);
+    }
+    /// the running totals of `member`, zero if they appear nowhere in the log
+    pub(crate) fn totals(&self, member: &str) -> Totals {
+        self.totals.get(member).copied().unwrap_or_default()
+    }
+    /// ids of entries that exist in both groups with different content
+    pub(crate) fn conflicting_entries(&self, theirs: &Group) -> Vec<String> {
+        let ours: HashMap<&str, &LogEntry> = self.log.iter().map(|e| (e.id.as_str(), e)).collect();
+        theirs.log.iter()
+            .filter(|e| ours.get(e.id.as_str()).is_some_and(|o| o.content_hash() != e.content_hash()))
+            .map(|e| e.id.clone())
+            .collect()
+    }
+    /// Merges another copy of this group: the log becomes the union of both logs ordered by time,
+    /// members missing here are added and the entries missing here are replayed onto the balances.
+    /// Returns how many entries were new to us and how many were new to them.
+    /// Entries removed by `undo` on one side come back if the other side still has them.
+    /// Integrity mode carries over, and entries sorted in before existing ones re-chain the log.
+    pub(crate) fn merge(&mut self, theirs: &Group) -> Result<(usize, usize)> {
+        let conflicts = self.conflicting_entries(theirs);
+        if !conflicts.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "Group {} has entries with the same id but different content: {}",
+                self.name, conflicts.join(", ")));
+        }
+        let theirs_ids: HashSet<&str> = theirs.log.iter().map(|e| e.id.as_str()).collect();
+        let pushed = self.log.iter().filter(|e| !theirs_ids.contains(e.id.as_str())).count();
+        let ours_ids: HashSet<&str> = self.log.iter().map(|e| e.id.as_str()).collect();
+        let new_entries: Vec<LogEntry> = theirs.log.iter()
+            .filter(|e| !ours_ids.contains(e.id.as_str()))
+            .cloned()
+            .collect();
+        if theirs.integrity {
+            self.enable_integrity();
+        }
+        for member in theirs.members.keys() {
+            self.members.entry(member.clone()).or_insert(Money::ZERO);
+        }
+        for entry in &new_entries {
+            for (member, change) in entry.change() {
+                *self.members.entry(member.clone()).or_insert(Money::ZERO) += change;
+            }
+            count(&mut self.totals, entry, false);
+        }
+        let pulled = new_entries.len();
+        let before: Vec<String> = self.log.iter().map(|e| e.id.clone()).collect();
+        self.log.extend(new_entries);
+        self.log.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.id.cmp(&b.id)));
+        if let Some(from) = self.log.iter().zip(&before).position(|(entry, id)| &entry.id != id) {
+            self.rewrite_chain(from, "merge");
+        } else if self.integrity {
+            rechain(&mut self.log, before.len());
+        }
+        Ok((pulled, pushed))
     }
     pub(crate) fn remove_log(&mut self, index: Option<usize>) -> Result<LogEntry> {
         if self.log.is_empty() {
@@ -55,139 +307,780 @@ impl Group {
         if index >= self.log.len() {
             return Err(anyhow!(SplitterError::LogEntryNotFound));
         }
-        Ok(self.log.remove(index))
+        let removed = self.log.remove(index);
+        count(&mut self.totals, &removed, true);
+        self.rewrite_chain(index, "undo");
+        Ok(removed)
     }
-    pub(crate) fn stat(&self) -> String {
-        let mut string =
-            format!("Group Statistics for group {} ({}):\n\
-        Members:\n\
-        ", self.name, self.currency);
-
-        for (name, balance) in &self.members {
-            string = format!("{}\n{}: {:.02}{}", string, name, *balance as f32 / self.currency.subdivision(), self.currency);
-        }
-        string
-    }
-    pub(crate) fn list(&self) -> String {
-        let accu = format!("Log Listing for Group {} ({})\n", self.name, self.currency);
-        self.log.iter()
-            .fold(accu, |a, e| format!("{a}{}\n", e.to_string(self.currency)))
-    }
-    pub(crate) fn balance(&self) -> Vec<Transaction> {
-        let members = &self.members;
-        struct Member {
-            name: String,
-            balance: Money,
-        }
-        let mut creditors: Vec<Member> =
-            members.iter().filter(|&(_, balance)| *balance > 0)
-                .map(|(name, balance)| Member { name: name.clone(), balance: *balance })
-                .collect();
-        let mut debtors: Vec<Member> =
-            members.iter().filter(|&(_, balance)| *balance < 0)
-                .map(|(name, balance)| Member { name: name.clone(), balance: *balance })
-                .collect();
-        creditors.sort_unstable_by(|el1, el2| el1.balance.partial_cmp(&el2.balance).unwrap());
-        debtors.sort_unstable_by(
-            |el1, el2| el1.balance.abs().partial_cmp(&el2.balance.abs())
-                .unwrap());
-        let mut transactions = vec![];
-        // find matching c and d & match them up
-        for d in debtors.iter_mut() {
-            for c in creditors.iter_mut() {
-                if -d.balance < c.balance {
-                    break; // break the loop
-                }
-                if d.balance == -c.balance {
-                    transactions.push(Transaction::new(&d.name, &c.name, c.balance));
-                    d.balance = 0;
-                    c.balance = 0;
-                }
+    /// appends an entry, linking it into the hash chain in integrity mode
+    fn push_log(&mut self, entry: LogEntry) {
+        let index = self.log.len();
+        count(&mut self.totals, &entry, false);
+        self.log.push(entry);
+        if self.integrity {
+            rechain(&mut self.log, index);
+        }
+    }
+    /// Re-chains the log from `from` on after the history was changed there, recording where
+    /// and why so `verify` can report it. Does nothing outside of integrity mode.
+    fn rewrite_chain(&mut self, from: usize, reason: &str) {
+        if !self.integrity {
+            return;
+        }
+        rechain(&mut self.log, from);
+        if from < self.log.len() {
+            self.rechains.push(Rechain { from, time: Local::now(), reason: reason.to_string() });
+        }
+    }
+    /// Renames the group along with the group recorded in its log entries, re-chaining them in
+    /// integrity mode
+    pub(crate) fn rename(&mut self, name: String) {
+        for entry in self.log.iter_mut() {
+            entry.command.set_group(&name);
+        }
+        self.name = name;
+        self.rewrite_chain(0, "rename-group");
+    }
+    /// `new` can be given to a member: it is valid and appears neither in the group nor in its log
+    fn check_new_member_name(&self, new: &str) -> Result<()> {
+        if !valid_name(new) {
+            return Err(anyhow!(SplitterError::InvalidName)).context(format!("Name {} is not allowed for members", new));
+        }
+        if self.members.contains_key(new) {
+            return Err(anyhow!(SplitterError::InvalidName)).context(format!("{} already is a member of {}", new, self.name));
+        }
+        if self.totals.contains_key(new) {
+            return Err(anyhow!(SplitterError::InvalidName)).context(format!(
+                "{} took part in earlier entries of {}, pick a name nobody had in the group", new, self.name));
+        }
+        Ok(())
+    }
+    /// Renames member `old` to `new` in the balances, the log, the ratio sets and as banker,
+    /// re-chaining the log in integrity mode. `new` must be valid and must not appear in the group
+    /// or its log yet.
+    pub(crate) fn rename_member(&mut self, old: &str, new: String) -> Result<()> {
+        let new = normalize_name(&new);
+        let Some(balance) = self.members.get(old).copied() else {
+            return Err(anyhow!(SplitterError::MemberNotFound)).context(format!(
+                "{} is not a member of {} ({})", old, self.name, valid_options("members", &self.member_names())));
+        };
+        self.check_new_member_name(&new)?;
+        self.members.remove(old);
+        self.members.insert(new.clone(), balance);
+        for entry in self.log.iter_mut() {
+            entry.rename_member(old, &new);
+        }
+        if let Some(totals) = self.totals.remove(old) {
+            self.totals.insert(new.clone(), totals);
+        }
+        for weights in self.ratios.values_mut() {
+            if let Some(weight) = weights.remove(old) {
+                weights.insert(new.clone(), weight);
             }
         }
-
-        let mut c_idx = 0;
-        // non-matching loop
-        for d in debtors.iter_mut() {
-            if d.balance == 0 {
-                continue;
+        if self.banker.as_deref() == Some(old) {
+            self.banker = Some(new.clone());
+        }
+        self.rewrite_chain(0, &format!("renamed {} to {}", old, new));
+        Ok(())
+    }
+    /// Records who entered the entry just added. Called right after `split` or `pay`,
+    /// the entry is re-hashed in integrity mode.
+    pub(crate) fn set_last_author(&mut self, author: Option<String>) {
+        let Some(last) = self.log.len().checked_sub(1) else { return };
+        self.log[last].author = author;
+        if self.integrity {
+            rechain(&mut self.log, last);
+        }
+    }
+    /// switches integrity mode on and chains the existing entries, returns false if it already was
+    pub(crate) fn enable_integrity(&mut self) -> bool {
+        if self.integrity {
+            return false;
+        }
+        self.integrity = true;
+        rechain(&mut self.log, 0);
+        true
+    }
+    /// Walks the hash chain. Fails at the first entry that does not match it, otherwise
+    /// describes the chain including the places where the history was rewritten.
+    pub(crate) fn verify(&self) -> Result<String> {
+        if !self.integrity {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "Group {} is not in integrity mode, enable it with `splitter verify {} --enable`",
+                self.name, self.name));
+        }
+        if let Some(index) = first_broken_link(&self.log) {
+            let problem = if self.log[index].hash.is_none() { "has no hash" } else { "does not match its hash" };
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "The hash chain of group {} is broken at entry [{}], which {}:\n{}",
+                self.name, index, problem, self.log[index].to_string(index, self.currency)));
+        }
+        let mut report = format!("The hash chain of group {} is intact ({} entries)", self.name, self.log.len());
+        for rechain in &self.rechains {
+            report.push_str(&format!("\nHistory rewritten from entry [{}] on {} ({})",
+                                     rechain.from, date(rechain.time, "%Y-%m-%d %H:%M"), rechain.reason));
+        }
+        Ok(report)
+    }
+    /// What does not add up in the group: balances not summing to zero, balances differing from what
+    /// the log adds up to and, in integrity mode, a broken hash chain. Empty if it is consistent.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let total: Money = self.members.values().sum();
+        if !total.is_zero() {
+            problems.push(format!("the balances add up to {} instead of zero", self.currency.format(total)));
+        }
+        let mut logged = replay(&self.log);
+        for member in self.members.keys() {
+            logged.entry(member).or_insert(Money::ZERO);
+        }
+        for (member, logged) in logged {
+            match self.members.get(member) {
+                Some(balance) if *balance != logged => problems.push(format!(
+                    "{} has {} but the log adds up to {}", member, self.currency.format(*balance),
+                    self.currency.format(logged))),
+                None if !logged.is_zero() => problems.push(format!(
+                    "the log leaves {} with {} but they are no member", member, self.currency.format(logged))),
+                _ => {}
+            }
+        }
+        let counted = totals_of(&self.log);
+        let differing: BTreeSet<&str> = counted.keys().chain(self.totals.keys())
+            .filter(|member| self.totals.get(*member) != counted.get(*member))
+            .map(String::as_str)
+            .collect();
+        if !differing.is_empty() {
+            problems.push(format!("the running totals of {} differ from what the log adds up to",
+                                  differing.into_iter().collect::<Vec<_>>().join(", ")));
+        }
+        if let Some(index) = self.integrity.then(|| first_broken_link(&self.log)).flatten() {
+            problems.push(format!("the hash chain is broken at entry [{}]", index));
+        }
+        problems
+    }
+    /// member names in alphabetical order
+    pub(crate) fn member_names(&self) -> Vec<&String> {
+        by_name(&self.members).into_iter().map(|(name, _)| name).collect()
+    }
+    pub(crate) fn log(&self) -> &[LogEntry] {
+        &self.log
+    }
+    /// Takes the entries from `from` on off the log, undoing what they changed in the balances
+    pub(crate) fn split_off_log(&mut self, from: usize) -> Vec<LogEntry> {
+        let entries = self.log.split_off(from);
+        for entry in &entries {
+            self.apply_tachange(entry.change().iter().map(|(name, change)| (name.clone(), -change)).collect());
+            count(&mut self.totals, entry, true);
+        }
+        entries
+    }
+    /// Appends an entry read back from the journal as it was recorded, applying its change
+    pub(crate) fn replay(&mut self, entry: LogEntry) {
+        self.apply_tachange(entry.change().clone());
+        count(&mut self.totals, &entry, false);
+        self.log.push(entry);
+    }
+    /// the rows of `members`, sorted by name
+    pub(crate) fn member_overview(&self) -> Vec<MemberOverview> {
+        self.member_names().into_iter()
+            .map(|name| MemberOverview { name: name.clone(), balance: self.members[name] })
+            .collect()
+    }
+    pub(crate) fn summary(&self) -> LogSummary {
+        LogSummary::of(&self.log)
+    }
+    /// the `count` largest splits, optionally only of one category or involving one member
+    pub(crate) fn top(&self, count: usize, category: Option<&str>, member: Option<&str>) -> Vec<TopEntry> {
+        top_expenses(&self.log, count, category, member)
+    }
+    pub(crate) fn stat_data(&self) -> GroupStat {
+        GroupStat {
+            name: self.name.clone(),
+            currency: self.currency,
+            members: self.member_names().into_iter()
+                .map(|name| (name.clone(), self.members[name]))
+                .collect(),
+            below_threshold: vec![],
+            threshold: Money::ZERO,
+            summary: self.summary(),
+            converted: None,
+            as_of: None,
+            totals: self.member_names().into_iter()
+                .map(|name| (name.clone(), self.totals(name)))
+                .collect(),
+        }
+    }
+    /// like `stat_data`, but only what the entries of the open period changed
+    fn open_period_stat(&self) -> GroupStat {
+        let entries: Vec<LogEntry> = self.log_between(self.open_since(), None).cloned().collect();
+        GroupStat {
+            members: self.member_names().into_iter()
+                .map(|name| (name.clone(), entries.iter().map(|entry| entry.change_of(name)).sum()))
+                .collect(),
+            summary: LogSummary::of(&entries),
+            totals: vec![],
+            ..self.stat_data()
+        }
+    }
+    /// Like `stat_data`, but the balances at the end of `date`, replayed from the entries recorded
+    /// until then. Members who left since are shown if they had a balance then.
+    fn as_of_stat(&self, date: NaiveDate) -> GroupStat {
+        let entries: Vec<LogEntry> = self.log_between(None, Some(day_end(date))).cloned().collect();
+        let mut members: BTreeMap<String, Money> = replay(&entries).into_iter()
+            .filter(|(_, balance)| !balance.is_zero())
+            .map(|(member, balance)| (member.clone(), balance))
+            .collect();
+        for name in self.member_names() {
+            members.entry(name.clone()).or_insert(Money::ZERO);
+        }
+        GroupStat {
+            members: members.into_iter().collect(),
+            summary: LogSummary::of(&entries),
+            as_of: Some(AsOf { date, undated: entries.iter().filter(|entry| entry.time.is_none()).count() }),
+            totals: vec![],
+            ..self.stat_data()
+        }
+    }
+    /// where the open period starts, the end of the last closed month
+    pub(crate) fn open_since(&self) -> Option<chrono::DateTime<Local>> {
+        self.closed_months.last().map(|closed| closed.month.end())
+    }
+    /// Entries recorded in `[from, to)`, unbounded where `None`. Entries without a time (written
+    /// by older versions) only belong to a period without a start.
+    fn log_between(&self, from: Option<chrono::DateTime<Local>>, to: Option<chrono::DateTime<Local>>)
+                   -> impl Iterator<Item=&LogEntry> {
+        self.log.iter().filter(move |entry| match entry.time {
+            Some(time) => from.is_none_or(|from| time >= from) && to.is_none_or(|to| time < to),
+            None => from.is_none(),
+        })
+    }
+    /// Spending in `[from, to)` and the transfers settling what the entries changed, see `log_between`
+    fn period_report(&self, period: String, from: Option<chrono::DateTime<Local>>,
+                     to: Option<chrono::DateTime<Local>>) -> PeriodReport {
+        let mut expenses = 0;
+        let mut spend = Money::ZERO;
+        let mut members: BTreeMap<&String, MemberPeriod> = self.member_names().into_iter()
+            .map(|name| (name, MemberPeriod { name: name.clone(), paid: Money::ZERO, consumed: Money::ZERO }))
+            .collect();
+        let mut net: HashMap<String, Money> = HashMap::new();
+        for entry in self.log_between(from, to) {
+            for (member, change) in entry.change() {
+                *net.entry(member.clone()).or_default() += change;
             }
-            while creditors.get(c_idx).unwrap().balance == 0 {
-                c_idx += 1;
+            if let LoggedCommand::Split { amount, .. } = &entry.command {
+                expenses += 1;
+                spend += amount;
+                let shares = entry.shares();
+                for (name, member) in members.iter_mut() {
+                    let consumed = shares.iter().find(|(member, _)| member == *name).map_or(Money::ZERO, |(_, share)| *share);
+                    member.consumed += consumed;
+                    member.paid += consumed + entry.change_of(name);
+                }
             }
-            let mut c = creditors.get_mut(c_idx).unwrap();
-            if c.balance == -d.balance {
-                transactions.push(Transaction::new(&d.name, &c.name, c.balance));
-                d.balance = 0;
-                c.balance = 0;
-                c_idx += 1;
-                continue;
+        }
+        PeriodReport {
+            group: self.name.clone(),
+            currency: self.currency,
+            period,
+            expenses,
+            spend,
+            members: members.into_values().collect(),
+            transfers: greedy(&net),
+        }
+    }
+    /// the report of `month`, or of the open period
+    pub(crate) fn report(&self, month: Option<Month>) -> PeriodReport {
+        match month {
+            Some(month) => self.period_report(month.to_string(), Some(month.start()), Some(month.end())),
+            None => {
+                let since = self.open_since();
+                let period = match since {
+                    Some(since) => format!("the open period since {}", date(since, "%Y-%m-%d")),
+                    None => "all entries, no month was closed yet".to_string(),
+                };
+                self.period_report(period, since, None)
             }
-            while c.balance < -d.balance {
-                d.balance += c.balance;
-                transactions.push(Transaction::new(&d.name, &c.name, c.balance));
-                c.balance = 0;
-                c_idx += 1;
-                c = creditors.get_mut(c_idx).unwrap();
+        }
+    }
+    /// Closes `month`, which must have ended and come after the last closed month. `settled` records
+    /// whether the caller pays the suggested transfers. Returns the report of the month.
+    pub(crate) fn close_month(&mut self, month: Month, settled: bool) -> Result<PeriodReport> {
+        if let Some(last) = self.closed_months.last() {
+            if month <= last.month {
+                return Err(anyhow!(SplitterError::LogicError)).context(if month == last.month {
+                    format!("Month {} of group {} is already closed", month, self.name)
+                } else {
+                    format!("Months are closed in order, the last closed month of group {} is {}", self.name, last.month)
+                });
             }
-            if c.balance > -d.balance {
-                c.balance += d.balance;
-                transactions.push(Transaction::new(&d.name, &c.name, d.balance));
-                d.balance = 0;
+        }
+        if month.end() > Local::now() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Month {} is not over yet", month));
+        }
+        let report = self.report(Some(month));
+        self.closed_months.push(ClosedMonth { month, time: Local::now(), settled });
+        Ok(report)
+    }
+    /// stores the ratio set `name` from `member=weight` arguments, replacing an existing one
+    pub(crate) fn set_ratio(&mut self, name: &str, shares: &[String]) -> Result<()> {
+        let mut ratio = BTreeMap::new();
+        for share in shares {
+            let (member, weight) = share.split_once('=')
+                .ok_or(anyhow!(SplitterError::InvalidTargetFormat))
+                .context(format!("Please use the format <member>=<weight> (got '{}')", share))?;
+            let weight: u64 = weight.trim().parse()
+                .map_err(|_| anyhow!(SplitterError::InvalidTargetFormat))
+                .context(format!("The weight of {} must be a whole number (got '{}')", member, weight))?;
+            if !self.members.contains_key(member) {
+                return Err(anyhow!(SplitterError::MemberNotFound))
+                    .context(format!("{} not within group {} ({})", member, self.name,
+                                     valid_options("members", &self.member_names())));
             }
+            ratio.insert(member.to_string(), weight);
+        }
+        if ratio.values().all(|weight| *weight == 0) {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Ratio {} needs at least one member with a weight above zero", name));
         }
-        transactions
+        self.ratios.insert(name.to_string(), ratio);
+        Ok(())
+    }
+    /// the ratio set `name`
+    pub(crate) fn ratio(&self, name: &str) -> Result<&BTreeMap<String, u64>> {
+        let names: Vec<&String> = self.ratios.keys().collect();
+        self.ratios.get(name).ok_or(anyhow!(SplitterError::LogicError))
+            .context(format!("No ratio {} in group {} ({})", name, self.name, valid_options("ratios", &names)))
+    }
+    /// members without a weight in the ratio set `name`, sorted
+    pub(crate) fn missing_from_ratio(&self, name: &str) -> Result<Vec<&String>> {
+        let ratio = self.ratio(name)?;
+        Ok(self.member_names().into_iter().filter(|member| !ratio.contains_key(*member)).collect())
+    }
+    /// sets a budget, replacing the one for the same category
+    pub(crate) fn set_budget(&mut self, budget: Budget) {
+        self.budgets.retain(|existing| existing.category != budget.category);
+        self.budgets.push(budget);
+        self.budgets.sort_by(|a, b| a.category.cmp(&b.category));
+    }
+    pub(crate) fn remove_budget(&mut self, category: Option<&str>) -> Result<()> {
+        let count = self.budgets.len();
+        self.budgets.retain(|budget| budget.category.as_deref() != category);
+        if self.budgets.len() == count {
+            return Err(anyhow!(SplitterError::LogicError)).context(match category {
+                Some(category) => format!("Group {} has no budget for {}", self.name, category),
+                None => format!("Group {} has no budget for all splits", self.name),
+            });
+        }
+        Ok(())
+    }
+    /// the budgets and what was spent in their current periods, only those limiting splits of
+    /// `category` if given
+    pub(crate) fn budget_status(&self, now: chrono::DateTime<Local>, category: Option<Option<&str>>) -> Vec<BudgetStatus> {
+        self.budgets.iter()
+            .filter(|budget| match category {
+                Some(category) => budget.category.is_none() || budget.category.as_deref() == category,
+                None => true,
+            })
+            .map(|budget| BudgetStatus {
+                budget: budget.clone(),
+                spent: self.log_between(budget.since(now, self.open_since()), None)
+                    .filter_map(|entry| match &entry.command {
+                        LoggedCommand::Split { amount, category, .. }
+                            if budget.category.is_none() || budget.category == *category => Some(*amount),
+                        _ => None,
+                    })
+                    .sum(),
+                currency: self.currency,
+            })
+            .collect()
+    }
+    /// The rate for showing amounts in `currency`: `rate` if given, otherwise the stored one
+    pub(crate) fn rate_to(&self, currency: Currency, rate: Option<Rate>) -> Result<Rate> {
+        if let Some(rate) = rate {
+            return Ok(rate);
+        }
+        self.rates.get(&currency).copied().ok_or(anyhow!(SplitterError::LogicError)).context(format!(
+            "No exchange rate from {:?} to {:?} set for group {}, set one with \
+            `splitter exchange-rate {:?} <rate> -g {}` or pass --rate",
+            self.currency, currency, self.name, currency, self.name))
+    }
+    /// What `stat` shows: balances closer to zero than `threshold` collapsed and, if `currency`
+    /// differs from the group's, all amounts converted. With `open_period` only the entries after
+    /// the last closed month count.
+    pub(crate) fn stat_view(&self, threshold: Money, currency: Option<Currency>, rate: Option<Rate>,
+                            open_period: bool, as_of: Option<NaiveDate>) -> Result<GroupStat> {
+        let stat = match as_of {
+            Some(date) => self.as_of_stat(date),
+            None if open_period => self.open_period_stat(),
+            None => self.stat_data(),
+        };
+        let stat = stat.hide_below(threshold);
+        match currency {
+            Some(currency) if currency != self.currency || rate.is_some() =>
+                Ok(stat.convert(currency, self.rate_to(currency, rate)?)),
+            _ => Ok(stat),
+        }
+    }
+    pub(crate) fn stat(&self) -> String {
+        self.stat_data().to_string()
+    }
+    /// the group's log for `list`, only the entries by `author` if given
+    pub(crate) fn listing(&self, author: Option<&str>) -> GroupListing<'_> {
+        GroupListing {
+            group: self.name.as_str(),
+            currency: self.currency,
+            members: self.members.len(),
+            entries: self.log.iter().enumerate()
+                .filter(|(_, entry)| author.is_none() || entry.author.as_deref() == author)
+                .map(|(index, entry)| ListedEntry { index, entry })
+                .collect(),
+            totals: self.summary(),
+        }
+    }
+    pub(crate) fn overview(&self, current: bool) -> GroupOverview {
+        let summary = self.summary();
+        GroupOverview {
+            name: self.name.clone(),
+            currency: self.currency,
+            members: self.members.len(),
+            entries: summary.entries,
+            net_zero: self.members.values().sum::<Money>().is_zero(),
+            last_activity: summary.last,
+            archived: self.archived,
+            current,
+        }
+    }
+    /// The transfers settling the group with `algorithm`, else the default algorithm of the group,
+    /// together with the algorithm used. `banker` is for `pairwise`, see `settle::settle`.
+    pub(crate) fn settle(&self, algorithm: Option<Algorithm>, banker: Option<&str>) -> Result<(Algorithm, Vec<Transaction>)> {
+        let algorithm = algorithm.or(self.algorithm).unwrap_or_default();
+        let transfers = settle(&self.members, algorithm, banker.or(self.banker.as_deref()))
+            .context(format!("Could not settle group {}", self.name))?;
+        Ok((algorithm, transfers))
     }
     pub(crate) fn add(&mut self, mut members: Vec<String>) -> Result<()> {
         let mut duplicates = vec![];
         let mut errors = vec![];
         for member in members.drain(..) {
+            let normalized = normalize_name(&member);
             #[allow(clippy::map_entry)]
-            if self.members.contains_key(&member) {
-                duplicates.push(member);
-            } else if !Regex::new(Splitter::NAME_REGEX)
-                .unwrap().is_match(member.as_str()) {
-                errors.push(member);
+            if self.members.contains_key(&normalized) {
+                duplicates.push(shown_name(&member, normalized));
+            } else if !valid_name(&normalized) {
+                errors.push(shown_name(&member, normalized));
             } else {
-                self.members.insert(member, 0);
+                self.members.insert(normalized, Money::ZERO);
             }
         }
         if duplicates.is_empty() && errors.is_empty() {
             Ok(())
         } else {
+            duplicates.sort();
+            errors.sort();
             Err(anyhow!(SplitterError::InvalidName))
                 .context(format!("duplicates: {:#?}\ninvalid names: {:#?}", duplicates, errors))
         }
     }
-    pub(crate) fn remove(&mut self, mut members: Vec<String>, force: bool) -> Result<()> {
-        let mut errors = vec![];
-        for member in members.drain(..) {
-            if !self.members.contains_key(&member) ||
-                (*self.members.get(&member).unwrap() != 0 && !force) {
-                errors.push(member);
-            } else {
-                self.members.remove(&member);
+    /// Recomputes the split at `index` as if `include` had taken part in it, from the parameters it
+    /// was recorded with. The difference to the recorded change is applied to the balances and returned,
+    /// and the entry is amended. Fails for splits the recorded change cannot be reproduced for.
+    pub(crate) fn include_in_split(&mut self, index: usize, include: &[String]) -> Result<TransactionChange> {
+        let (_, entry) = self.get_log(Some(index))?;
+        let LoggedCommand::Split { amount, from, to, balance_rest, ratio, excluded, .. } = &entry.command else {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Entry [{}] is no split, only splits can include more members", index));
+        };
+        for member in include {
+            if !self.members.contains_key(member) {
+                return Err(anyhow!(SplitterError::MemberNotFound)).context(format!(
+                    "{} is not a member of {} ({})", member, self.name, valid_options("members", &self.member_names())));
+            }
+            if excluded.contains(member) {
+                return Err(anyhow!(SplitterError::LogicError))
+                    .context(format!("{} was left out of entry [{}] with --except", member, index));
+            }
+            if entry.change().contains_key(member) {
+                return Err(anyhow!(SplitterError::LogicError))
+                    .context(format!("{} already was a member of {} when entry [{}] was recorded", member, self.name, index));
             }
         }
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(anyhow!(SplitterError::InvalidName)).context(
+        let weights = ratio.as_deref().map(|ratio| self.ratio(ratio)).transpose()?;
+        let raw = |targets: &[Target]| targets.iter().map(|target| target.raw(self.currency)).collect::<Vec<_>>();
+        let recompute = |mut participants: Vec<&String>| {
+            participants.sort();
+            let view = Roster { name: &self.name, currency: self.currency, members: participants };
+            split_into_transaction(*amount, &view, raw(from), raw(to), excluded, *balance_rest, weights)
+                .map(|(change, _, _)| change)
+        };
+        let recorded = recompute(entry.change().keys().collect());
+        if recorded.as_ref().ok() != Some(entry.change()) {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "Entry [{}] cannot be reproduced from its recorded parameters, \
+                 probably because it was written by an older version", index));
+        }
+        let amended = recompute(entry.change().keys().chain(include).collect())?;
+        let delta: TransactionChange = amended.iter()
+            .map(|(member, change)| (member.clone(), *change - entry.change_of(member)))
+            .filter(|(_, delta)| !delta.is_zero())
+            .collect();
+        if let Some(gone) = delta.keys().find(|member| !self.members.contains_key(*member)) {
+            return Err(anyhow!(SplitterError::MemberNotFound))
+                .context(format!("{} took part in entry [{}] but has left {} since", gone, index, self.name));
+        }
+        self.apply_tachange(delta.clone());
+        count(&mut self.totals, &self.log[index], true);
+        self.log[index].amend(amended, include);
+        count(&mut self.totals, &self.log[index], false);
+        self.rewrite_chain(index, &format!("included {} in entry [{}]", include.join(", "), index));
+        Ok(delta)
+    }
+    /// an equal share of everything spent in the group so far, as if `newcomers` more members had been in it
+    pub(crate) fn spend_share(&self, newcomers: usize) -> Money {
+        let spend: Money = self.log.iter()
+            .filter_map(|entry| match entry.command {
+                LoggedCommand::Split { amount, .. } => Some(amount),
+                _ => None,
+            })
+            .sum();
+        spend / (self.members.len() + newcomers).max(1) as i64
+    }
+    /// what each member paid for the splits in the log, in alphabetical order
+    fn fronted(&self) -> Vec<(String, Money)> {
+        self.member_names().into_iter()
+            .map(|name| (name.clone(), self.totals(name).paid))
+            .collect()
+    }
+    /// Lets `member` take over `amount` of the costs so far: they owe it, and the members who paid
+    /// for the splits are credited in proportion to what they paid, in a logged entry
+    pub(crate) fn buy_in(&mut self, member: String, amount: Money) -> Result<()> {
+        if !self.members.contains_key(&member) {
+            return Err(anyhow!(SplitterError::MemberNotFound))
+                .context(format!("{} is not a member of {}", member, self.name));
+        }
+        if amount.is_zero() {
+            return Ok(());
+        }
+        let (fronters, weights): (Vec<String>, Vec<u64>) = self.fronted().into_iter()
+            .filter(|(name, paid)| *name != member && paid.is_positive())
+            .map(|(name, paid)| (name, paid.0 as u64))
+            .unzip();
+        if fronters.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Nobody paid for a split in {} yet, so there is nothing to buy into", self.name));
+        }
+        let mut change: TransactionChange = fronters.into_iter()
+            .zip(split_by_weights(amount, &weights))
+            .collect();
+        change.insert(member.clone(), -amount);
+        self.apply_tachange(change.clone());
+        self.push_log(LogEntry::new(LoggedCommand::BuyIn { member, amount, group: self.name.clone() }, change));
+        Ok(())
+    }
+    /// what the balances add up to, zero unless an old bug or a hand edit left them off
+    pub(crate) fn residual(&self) -> Money {
+        self.members.values().sum()
+    }
+    /// Brings the balances back to adding up to zero in a logged entry: the whole residual is booked
+    /// to `assign`, or else spread over all members in alphabetical order, a cent each.
+    /// Returns the residual corrected.
+    pub(crate) fn fix_residual(&mut self, assign: Option<String>) -> Result<Money> {
+        let residual = self.residual();
+        if residual.is_zero() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("The balances of {} already add up to zero", self.name));
+        }
+        let members: Vec<String> = match assign {
+            Some(member) if !self.members.contains_key(&member) => return Err(anyhow!(SplitterError::MemberNotFound))
+                .context(format!("{} is not a member of {}", member, self.name)),
+            Some(member) => vec![member],
+            None => self.member_names().into_iter().cloned().collect(),
+        };
+        if members.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("{} has no members to book the residual to", self.name));
+        }
+        let change: TransactionChange = members.iter().cloned()
+            .zip(split_equal_among(-residual, members.len()))
+            .filter(|(_, amount)| !amount.is_zero())
+            .collect();
+        let members = members.into_iter().filter(|member| change.contains_key(member)).collect();
+        self.apply_tachange(change.clone());
+        self.push_log(LogEntry::new(LoggedCommand::Correction { group: self.name.clone(), residual, members }, change));
+        Ok(residual)
+    }
+    /// Removes `members`. With `force` members with a balance are removed as well, their balance
+    /// is written off to `transfer_to` or else shared equally by the members left, in a logged entry each.
+    pub(crate) fn remove(&mut self, members: Vec<String>, force: bool, transfer_to: Option<String>) -> Result<()> {
+        let mut errors: Vec<&String> = members.iter()
+            .filter(|member| self.members.get(*member).is_none_or(|balance| !balance.is_zero() && !force))
+            .collect();
+        errors.sort();
+        if !errors.is_empty() {
+            return Err(anyhow!(SplitterError::InvalidName)).context(
                 format!("Could not remove some members: Probably they either have to pay money, get money,\
                  or they do not appear in the list:\n{:?}", errors)
-            )
+            );
+        }
+        let takers: Vec<String> = match transfer_to {
+            Some(taker) if !self.members.contains_key(&taker) || members.contains(&taker) =>
+                return Err(anyhow!(SplitterError::MemberNotFound)).context(format!(
+                    "{} cannot take over the balances, only members staying in {} can", taker, self.name)),
+            Some(taker) => vec![taker],
+            None => self.member_names().into_iter()
+                .filter(|member| !members.contains(member))
+                .cloned()
+                .collect(),
+        };
+        for member in &members {
+            let amount = self.members[member];
+            if amount.is_zero() {
+                continue;
+            }
+            if takers.is_empty() {
+                return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                    "Nobody would be left in {} to take over the balance of {}", self.name, member));
+            }
+            let mut change: TransactionChange = takers.iter().cloned()
+                .zip(split_equal_among(amount, takers.len()))
+                .collect();
+            change.insert(member.clone(), -amount);
+            self.apply_tachange(change.clone());
+            self.push_log(LogEntry::new(
+                LoggedCommand::WriteOff { member: member.clone(), amount, group: self.name.clone(), to: takers.clone() },
+                change));
+        }
+        for member in &members {
+            self.members.remove(member);
+        }
+        Ok(())
+    }
+    /// A new group `name` continuing this one: the members in `members` (all members with a balance
+    /// must be among them), the same currency and settings, and one opening entry per member with
+    /// a balance reproducing it. This group is left as it is.
+    pub(crate) fn carried(&self, name: String, members: Vec<String>) -> Result<Group> {
+        let total: Money = self.members.values().sum();
+        if !total.is_zero() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("The balances of group {} add up to {} instead of zero", self.name,
+                                 self.currency.format(total)));
+        }
+        let left_out: Vec<&str> = self.member_names().into_iter()
+            .filter(|member| !self.members[*member].is_zero() && !members.contains(member))
+            .map(|member| member.as_str())
+            .collect();
+        if !left_out.is_empty() {
+            return Err(anyhow!(SplitterError::MemberNotFound))
+                .context(format!("{} still have a balance in {} but would not be members of {}",
+                                 left_out.join(", "), self.name, name));
         }
+        let mut group = Group::new(name, members, Some(self.currency))?;
+        group.integrity = self.integrity;
+        group.rates = self.rates.clone();
+        group.ratios = self.ratios.clone();
+        group.algorithm = self.algorithm;
+        group.banker = self.banker.clone().filter(|banker| group.members.contains_key(banker));
+        for member in self.member_names() {
+            let amount = self.members[member];
+            if !amount.is_zero() {
+                group.push_opening(member.clone(), amount, self.name.clone());
+            }
+        }
+        Ok(group)
+    }
+    /// logs and applies the opening balance `amount` of `member`, taken over from `source`
+    fn push_opening(&mut self, member: String, amount: Money, source: String) {
+        let change = HashMap::from([(member.clone(), amount)]);
+        self.push_log(LogEntry::new(
+            LoggedCommand::Opening { member, amount, group: self.name.clone(), source },
+            change.clone()));
+        self.apply_tachange(change);
+    }
+    /// Starts a group without entries at `balances`, as `stat` shows them, with one opening entry
+    /// per member with a balance, `source` naming where they come from. The balances must add up to
+    /// zero, unless `balance_with` names the member absorbing the difference.
+    pub(crate) fn open(&mut self, mut balances: Vec<(String, Money)>, source: &str, balance_with: Option<&str>)
+                       -> Result<()> {
+        if !self.log.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Group {} already has entries, opening balances need a new group", self.name));
+        }
+        for member in balances.iter().map(|(member, _)| member.as_str()).chain(balance_with) {
+            if !self.members.contains_key(member) {
+                return Err(anyhow!(SplitterError::MemberNotFound)).context(member.to_string());
+            }
+        }
+        let mut seen = HashSet::new();
+        for (member, _) in &balances {
+            if !seen.insert(member) {
+                return Err(anyhow!(SplitterError::LogicError))
+                    .context(format!("{} has more than one opening balance", member));
+            }
+        }
+        let total: Money = balances.iter().map(|(_, balance)| balance).sum();
+        if !total.is_zero() {
+            let Some(absorber) = balance_with else {
+                return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                    "The opening balances add up to {} instead of zero, \
+                     name the member absorbing the difference with --balance-with", self.currency.format(total)));
+            };
+            match balances.iter_mut().find(|(member, _)| member == absorber) {
+                Some((_, balance)) => *balance -= total,
+                None => balances.push((absorber.to_string(), -total)),
+            }
+        }
+        balances.sort();
+        for (member, amount) in balances {
+            if !amount.is_zero() {
+                self.push_opening(member, amount, source.to_string());
+            }
+        }
+        Ok(())
+    }
+    /// Starts the group over: the log and the closed months are cleared and the balances zeroed, the
+    /// members replaced by `members` if given. Currency, rates, ratios and budgets stay.
+    /// The reset is recorded as the first entry of the fresh log.
+    pub(crate) fn reset(&mut self, members: Option<Vec<String>>) -> Result<()> {
+        if let Some(members) = members {
+            self.members = Group::new(self.name.clone(), members, None)?.members;
+        }
+        for balance in self.members.values_mut() {
+            *balance = Money::ZERO;
+        }
+        let cleared = self.log.len();
+        self.log.clear();
+        self.totals.clear();
+        self.closed_months.clear();
+        self.rechains.clear();
+        let members = self.member_names().into_iter().cloned().collect();
+        self.log.push(LogEntry::new(LoggedCommand::Reset { group: self.name.clone(), members, cleared },
+                                    TransactionChange::new()));
+        self.rewrite_chain(0, "reset");
+        Ok(())
     }
     pub(crate) fn apply_tachange(&mut self, tac: TransactionChange) {
         for (name, balance) in self.members.iter_mut() {
-            *balance += tac.get(name.as_str()).unwrap();
+            *balance += tac.get(name.as_str()).unwrap_or(&Money::ZERO);
+        }
+    }
+    pub(crate) fn log_pay_transaction(&mut self, amount: Money, from: String, to: String) -> Result<()> {
+        self.log_pay(amount, normalize_name(&from), normalize_name(&to), None)
+    }
+    /// Records `transfers` suggested by `algorithm` as payments entered by `author`
+    pub(crate) fn log_settlement(&mut self, transfers: &[Transaction], algorithm: Algorithm, author: Option<String>)
+                                 -> Result<()> {
+        for transfer in transfers {
+            self.log_pay(transfer.amount, transfer.from.clone(), transfer.to.clone(), Some(algorithm))?;
+            self.set_last_author(author.clone());
         }
+        Ok(())
     }
-    pub(crate) fn log_pay_transaction(&mut self, amount: i64, from: String, to: String) -> Result<()> {
+    fn log_pay(&mut self, amount: Money, from: String, to: String, settled_by: Option<Algorithm>) -> Result<()> {
         // calculate transaction
         let mut transaction = HashMap::with_capacity(2);
-        transaction.insert(from.clone(), -amount);
-        transaction.insert(to.clone(), amount);
+        transaction.insert(from.clone(), amount);
+        transaction.insert(to.clone(), -amount);
 
         // apply transaction
         let mut found_both = 0;
@@ -205,26 +1098,73 @@ impl Group {
         }
         if found_both != 2 {
             return Err(anyhow!(SplitterError::MemberNotFound))
-                .context(format!("Either {from} or {to} do not exists within this group"));
+                .context(format!("Either {from} or {to} do not exists within group {} ({})",
+                                 self.name, valid_options("members", &self.member_names())));
         }
 
         // log transaction
-        self.log.push(
-            LogEntry::new(LoggedCommand::Pay { amount, from, to },
+        self.push_log(
+            LogEntry::new(LoggedCommand::Pay { amount, from, to, group: self.name.clone(), settled_by },
                           transaction)
         );
         Ok(())
     }
+    /// What `split` with these arguments would change, the parsed targets along with it. Changes nothing.
+    pub(crate) fn compute_split(&self, amount: Money, from: Vec<String>, to: Vec<String>, except: &[String],
+                                balance_rest: bool, ratio: Option<&str>)
+                                -> Result<(TransactionChange, Vec<Target>, Vec<Target>)> {
+        let weights = ratio.map(|ratio| self.ratio(ratio)).transpose()?;
+        split_into_transaction(amount, &self.roster(), from, to, except, balance_rest, weights)
+    }
+    /// What each member would take of a split of `amount`, computed like `split` does but recording
+    /// nothing, see `quote`. Only the members in `among` take part if any are given.
+    pub(crate) fn quote(&self, amount: Money, among: &[String], ratio: Option<&str>) -> Result<SplitPreview> {
+        let roster = self.roster();
+        let unknown: Vec<&str> = among.iter().filter(|name| !roster.contains(name)).map(|name| name.as_str()).collect();
+        if !unknown.is_empty() {
+            return Err(anyhow!(SplitterError::MemberNotFound))
+                .context(format!("{} not within group {} ({})", unknown.join(", "), self.name,
+                                 valid_options("members", &roster.members)));
+        }
+        let mut weights = ratio.map(|ratio| self.ratio(ratio)).transpose()?.cloned();
+        if !among.is_empty() {
+            // those left out weigh nothing, the others as much as the ratio says or all the same
+            weights = Some(roster.members.iter()
+                .map(|name| (name.to_string(), match &weights {
+                    _ if !among.contains(*name) => 0,
+                    Some(weights) => weights.get(*name).copied().unwrap_or(0),
+                    None => 1,
+                }))
+                .collect());
+        }
+        // paid by someone outside, the change of every member is what they take
+        let Some(payer) = roster.members.first().map(|name| name.to_string()) else {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!("Group {} has no members", self.name));
+        };
+        let (change, _, _) = split_into_transaction(amount, &roster, vec![payer.clone()], vec![], &[], false,
+                                                    weights.as_ref())?;
+        let members = roster.members.iter()
+            .map(|name| {
+                let paid = if **name == payer { amount } else { Money::ZERO };
+                let share = paid - change[*name];
+                PreviewRow { name: name.to_string(), paid: Money::ZERO, share, change: -share }
+            })
+            .collect();
+        Ok(SplitPreview { amount, currency: self.currency, members })
+    }
+    /// the members a split is computed among
+    pub(crate) fn roster(&self) -> Roster<'_> {
+        Roster { name: &self.name, currency: self.currency, members: self.member_names() }
+    }
     /// split endpoint calling the calculation function, logging the result and applying the result to
     /// the current member's balances
-    pub(crate) fn split(&mut self, amount: Money,
-                        from: Vec<String>, to: Vec<String>, name: String, balance_rest: bool)
-                        -> Result<()>
-    {
-        let (transaction, from, to) =
-            split_into_transaction(amount, self, from.clone(), to.clone(), balance_rest)?;
+    /// A negative `amount` records a refund, the `from` members having got the money back.
+    pub(crate) fn split(&mut self, amount: Money, from: Vec<String>, to: Vec<String>, name: String,
+                        options: SplitOptions) -> Result<()> {
+        let SplitOptions { balance_rest, category, ratio, except } = options;
+        let (transaction, from, to) = self.compute_split(amount, from, to, &except, balance_rest, ratio.as_deref())?;
         // log the transaction about to take place
-        self.log.push(LogEntry::new(
+        self.push_log(LogEntry::new(
             LoggedCommand::Split {
                 amount,
                 from,
@@ -232,6 +1172,10 @@ impl Group {
                 name,
                 group: self.name.clone(),
                 balance_rest,
+                category,
+                ratio,
+                included: vec![],
+                excluded: except,
             },
             transaction.clone(),
         ));
@@ -244,23 +1188,79 @@ impl Group {
 }
 
 
+/// How `Group::split` shares the amount besides who paid and who took part
+#[derive(Default)]
+pub(crate) struct SplitOptions {
+    /// the payers take part in the rest as well, see `split --balance-rest`
+    pub(crate) balance_rest: bool,
+    pub(crate) category: Option<String>,
+    /// a ratio set the consumption is split by, members missing from it take nothing
+    pub(crate) ratio: Option<String>,
+    /// members taking no part, recorded with a change of zero, see `split --except`
+    pub(crate) except: Vec<String>,
+}
+
+/// Transfers settling `members`: from debtors to creditors, exact matches first.
+/// Members with the same balance are taken in alphabetical order.
+/// Reads the `member,balance` rows of `create --opening-balances`. A first row `member,balance` is
+/// taken as header, lines starting with `#` are ignored.
+pub(crate) fn read_balances(path: &Path) -> Result<Vec<(String, Money)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| format!("Could not read opening balances from {}", path.display()))?;
+    let mut balances = vec![];
+    for (number, record) in reader.records().enumerate() {
+        let record = record.with_context(|| format!("Could not read opening balances from {}", path.display()))?;
+        let line = format!("{}:{}", path.display(), record.position().map_or(number as u64 + 1, |p| p.line()));
+        if record.len() != 2 {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!("{}: expected `member,balance`", line));
+        }
+        if number == 0 && record[0].eq_ignore_ascii_case("member") && record[1].eq_ignore_ascii_case("balance") {
+            continue;
+        }
+        let balance = parse_balance(&record[1]).map_err(|error| anyhow!(error)).context(line)?;
+        balances.push((normalize_name(&record[0]), balance));
+    }
+    Ok(balances)
+}
+
+/// A rejected member name for an error: its normalized form, and the name as given if that differs
+fn shown_name(given: &str, normalized: String) -> String {
+    match normalized.as_str() {
+        "" => format!("{:?} (empty once normalized)", given),
+        name if name == given => normalized,
+        _ => format!("{} (given as {:?})", normalized, given),
+    }
+}
+
+/// what the changes of `entries` add up to per member, everyone they ever touched included
+fn replay<'a>(entries: impl IntoIterator<Item=&'a LogEntry>) -> BTreeMap<&'a String, Money> {
+    let mut balances = BTreeMap::new();
+    for entry in entries {
+        for (member, change) in entry.change() {
+            *balances.entry(member).or_default() += change;
+        }
+    }
+    balances
+}
+
 /// Helper function to split `cents` Cents among `among` many people as just a possible. among > 0.
 /// This means splitting as equal as possible, distributing leftover cents from the top equally
-fn split_equal_among(cents: Money, among: usize) -> Vec<Money> {
+pub(crate) fn split_equal_among(cents: Money, among: usize) -> Vec<Money> {
     let mut result = Vec::with_capacity(among);
-    let everyone_split = cents / among as Money;
+    let everyone_split = cents / among as i64;
 
-    let neg = cents < 0;
-    let cents = cents.unsigned_abs() as usize;
-
-    let remainder = cents % among;
-    let mut remainder = if neg { -(remainder as Money) } else { remainder as Money };
+    // the cents left over, handed out one by one
+    let mut remainder = (cents % among as i64).0;
 
     result.resize_with(result.capacity(), || everyone_split);
     for res in result.iter_mut() {
-        *res += remainder.signum();
+        *res += Money(remainder.signum());
         remainder -= remainder.signum();
-        if remainder.abs() == 0 {
+        if remainder == 0 {
             break;
         }
     }
@@ -268,34 +1268,255 @@ fn split_equal_among(cents: Money, among: usize) -> Vec<Money> {
 }
 
 
+/// Splits `cents` proportionally to `weights` using the largest remainder method: everybody gets
+/// the rounded down share, the cents left over go to the largest remainders, ties to the first.
+/// The weights must not all be zero.
+pub(crate) fn split_by_weights(cents: Money, weights: &[u64]) -> Vec<Money> {
+    let total: i128 = weights.iter().map(|w| *w as i128).sum();
+    let exact: Vec<(i128, i128)> = weights.iter()
+        .map(|w| {
+            let product = cents.unsigned_abs() as i128 * *w as i128;
+            (product / total, product % total)
+        })
+        .collect();
+    let mut result: Vec<i128> = exact.iter().map(|(share, _)| *share).collect();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by(|a, b| exact[*b].1.cmp(&exact[*a].1));
+    let left = cents.unsigned_abs() as i128 - result.iter().sum::<i128>();
+    for index in order.into_iter().take(left as usize) {
+        result[index] += 1;
+    }
+    result.into_iter().map(|share| Money(share as i64) * cents.signum()).collect()
+}
+
+/// What a split is computed against: the members of a group in name order, without their balances,
+/// and the name and currency to use in messages. Needs no stored group, see `preview_split`.
+pub(crate) struct Roster<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) currency: Currency,
+    pub(crate) members: Vec<&'a String>,
+}
+
+impl Roster<'_> {
+    fn contains(&self, member: &String) -> bool {
+        self.members.binary_search(&member).is_ok()
+    }
+}
+
+/// The members of a `compute` preview: `names`, or with `count` that many, made up of the members
+/// the targets name and placeholders `p1`, `p2`, ... for the others
+#[cfg(any(feature = "cli", test))]
+pub(crate) fn preview_members(names: Vec<String>, count: Option<usize>, amount: Money, from: &[String], to: &[String],
+                              currency: Currency) -> Result<Vec<String>> {
+    let Some(count) = count else { return Ok(names) };
+    let mut members: Vec<String> = vec![];
+    for targets in [from, to] {
+        for target in Target::parse_multiple(targets.to_vec(), amount, currency)?.0 {
+            if !members.contains(&target.member) {
+                members.push(target.member);
+            }
+        }
+    }
+    if members.len() > count {
+        return Err(anyhow!(SplitterError::LogicError))
+            .context(format!("The targets name {} members, more than the {} given with --members", members.len(), count));
+    }
+    let width = count.to_string().len();
+    let placeholders: Vec<String> = (1..).map(|n| format!("p{:0width$}", n))
+        .filter(|name| !members.contains(name))
+        .take(count - members.len())
+        .collect();
+    members.extend(placeholders);
+    Ok(members)
+}
+
+/// What a split among `members` would change, computed like `split` does but without a group,
+/// see `compute`. Without `from` only the shares are computed, as if someone else paid.
+#[cfg(any(feature = "cli", test))]
+pub(crate) fn preview_split(amount: Money, mut members: Vec<String>, from: Vec<String>, to: Vec<String>,
+                            balance_rest: bool, currency: Currency) -> Result<SplitPreview> {
+    members.sort();
+    members.dedup();
+    if members.is_empty() {
+        return Err(anyhow!(SplitterError::LogicError)).context("A split needs at least one member");
+    }
+    let outside_payer = from.is_empty();
+    let from = if outside_payer { vec![members[0].clone()] } else { from };
+    let roster = Roster { name: "compute", currency, members: members.iter().collect() };
+    let (change, from, to) = split_into_transaction(amount, &roster, from, to, &[], balance_rest, None)?;
+    let entry = LogEntry::new(LoggedCommand::Split {
+        name: String::new(), amount, from, to, group: String::new(), balance_rest, category: None, ratio: None,
+        included: vec![], excluded: vec![],
+    }, change);
+    let shares = entry.shares();
+    let rows = members.iter()
+        .map(|name| {
+            let share = shares.iter().find(|(member, _)| member == name).map_or(Money::ZERO, |(_, share)| *share);
+            let change = if outside_payer { -share } else { entry.change_of(name) };
+            PreviewRow { name: name.clone(), paid: change + share, share, change }
+        })
+        .collect();
+    Ok(SplitPreview { amount, currency, members: rows })
+}
+
+/// which list of a split targets were given with
+#[derive(Clone, Copy)]
+enum Side {
+    From,
+    To,
+}
+
+/// Explains a `--from` or `--to` list whose amounts add up to more than `total`. The rest left
+/// to `sharing`, the members without an amount, or to a `rest` target would be negative: payers
+/// paying less than nothing, or members being paid for taking part.
+fn check_overdrawn(side: Side, targets: &[Target], total: Money, sharing: &[&String], currency: Currency)
+                   -> Result<()> {
+    let named: Money = targets.iter().filter(|el| !el.rest).filter_map(|el| el.amount).sum();
+    if named <= total {
+        return Ok(());
+    }
+    let over = currency.format(named - total);
+    let taker = match targets.iter().find(|el| el.rest) {
+        Some(rest) => format!("{}:rest", rest.member),
+        // lists without anyone taking the rest have to add up to the total exactly, checked elsewhere
+        None if sharing.is_empty() => return Ok(()),
+        None => sharing.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(", "),
+    };
+    let between = if sharing.len() > 1 && targets.iter().all(|el| !el.rest) { " between them" } else { "" };
+    let (option, consequence) = match side {
+        Side::From => ("--from", format!("{} would pay -{}{}", taker, over, between)),
+        Side::To => ("--to", format!("{} would take -{}{}, being paid for taking part", taker, over, between)),
+    };
+    Err(anyhow!(SplitterError::LogicError)).context(format!(
+        "The amounts of {} add up to {}, {} more than the total of {}, so {}. Lower the amounts or raise the \
+         total; money given back is a refund, record it with `split --refund`",
+        option, currency.format(named), over, currency.format(total), consequence))
+}
+
 /// receives vectors of --from and --to arguments, a amount to be split, a group name this
 /// should be assigned to and a flag indicating whether members named in a --to directive
-/// should share the rest of the bill with them
-fn split_into_transaction(total_amount: Money, group: &Group,
-                          from: Vec<String>, to: Vec<String>, balance_rest: bool)
+/// should share the rest of the bill with them. With `weights` the rest is split by these weights
+/// instead of equally.
+/// A `--to` target with `%share` takes that percentage of the share it would have as full participant:
+/// the rest is split once with it taking part, its amount fixed, and then split again without it.
+/// A negative total is a refund, the mirror of a split: the `from` members got the money back, the
+/// change and the amounts of the targets are those of the positive split with the sign flipped.
+fn split_into_transaction(total_amount: Money, group: &Roster,
+                          from: Vec<String>, to: Vec<String>, except: &[String], balance_rest: bool,
+                          weights: Option<&BTreeMap<String, u64>>)
                           -> Result<(TransactionChange, Vec<Target>, Vec<Target>)> {
-    let givers = Target::parse_multiple(from, total_amount)?;
-    let recvrs = Target::parse_multiple(to, total_amount)?;
-    if recvrs.0.iter().any(|el| el.amount.is_none()) {
-        return Err(anyhow!(SplitterError::InvalidTargetFormat)).context("Amounts for --to must be specified explicitly");
-    } else if givers.0.iter().fold(0i64, |a, b| a.saturating_add(b.amount.unwrap_or(i64::MAX))) <=
-        recvrs.0.iter().fold(0, |a, b| b.amount.unwrap() + a) {
+    if total_amount.is_negative() {
+        let (change, from, to) = split_into_transaction(-total_amount, group, from, to, except, balance_rest, weights)?;
+        let flip = |targets: Vec<Target>| -> Vec<Target> {
+            targets.into_iter().map(|target| Target { amount: target.amount.map(|amount| -amount), ..target }).collect()
+        };
+        return Ok((change.into_iter().map(|(member, change)| (member, -change)).collect(), flip(from), flip(to)));
+    }
+    let givers = Target::parse_unchecked(from, total_amount, group.currency)?;
+    let mut recvrs = Target::parse_unchecked(to, total_amount, group.currency)?;
+    if group.members.iter().all(|name| except.contains(name)) {
+        return Err(anyhow!(SplitterError::LogicError))
+            .context("Everybody is left out with --except, nobody is left to share the split");
+    }
+    if givers.0.iter().any(|el| el.share.is_some()) {
+        return Err(anyhow!(SplitterError::InvalidTargetFormat))
+            .context("A percentage of a share with %share only works for --to");
+    } else if givers.0.iter().any(|el| el.weight.is_some()) {
+        return Err(anyhow!(SplitterError::InvalidTargetFormat))
+            .context("Shares of the rest like <name>:2x only work for --to");
+    } else if recvrs.0.iter().any(|el| el.amount.is_none() && el.share.is_none() && el.weight.is_none()) {
+        return Err(anyhow!(SplitterError::InvalidTargetFormat))
+            .context("Amounts for --to must be specified explicitly, as <name>:rest or as shares like <name>:2x");
+    } else if givers.2 == 0 && givers.1 != total_amount {
+        return Err(anyhow!(SplitterError::LogicError)).context(format!(
+            "The amounts of --from add up to {} instead of {}, add a member without amount or <name>:rest",
+            group.currency.format(givers.1), group.currency.format(total_amount)));
+    }
+    let unknown: Vec<&str> = givers.0.iter().chain(recvrs.0.iter())
+        .map(|el| &el.member)
+        .chain(except)
+        .filter(|name| !group.contains(name))
+        .map(|name| name.as_str())
+        .collect();
+    if !unknown.is_empty() {
+        return Err(anyhow!(SplitterError::MemberNotFound))
+            .context(format!("{} not within group {} ({})", unknown.join(", "), group.name,
+                             valid_options("members", &group.members)));
+    }
+    if let Some(named) = givers.0.iter().chain(recvrs.0.iter()).find(|el| except.contains(&el.member)) {
         return Err(anyhow!(SplitterError::LogicError))
-            .context("Amounts of --from directives must either contain a catch-all or be >= amounts specified by --to");
-    } else if
-    recvrs.0.iter().any(|el| !group.members.contains_key(&el.member)) ||
-        givers.0.iter().any(|el| !group.members.contains_key(&el.member)) {
-        return Err(anyhow!(SplitterError::InvalidName))
-            .context(format!("One or more of the Receivers or Givers are not within group {}", group.name));
+            .context(format!("{} is left out with --except, but named with --from or --to", named.member));
+    }
+    let payers_without_amount: Vec<&String> = givers.0.iter()
+        .filter(|el| el.amount.is_none() && !el.rest)
+        .map(|el| &el.member)
+        .collect();
+    check_overdrawn(Side::From, &givers.0, total_amount, &payers_without_amount, group.currency)?;
+    // members named with an amount of their own only share the rest with --balance-rest, those
+    // left out with --except never do
+    let takes_rest = |recvrs: &[Target], name: &String| {
+        !except.contains(name) && (balance_rest || !recvrs.iter().any(|el| &el.member == name && el.weight.is_none()))
+    };
+    let sharing_rest = |recvrs: &[Target]| -> Vec<&String> {
+        group.members.iter().copied().filter(|name| takes_rest(recvrs, name)).collect()
+    };
+    check_overdrawn(Side::To, &recvrs.0, total_amount, &sharing_rest(&recvrs.0), group.currency)?;
+    // the shares of the rest given like `2x`, everyone else has one
+    let counts: BTreeMap<String, u64> = recvrs.0.iter()
+        .filter_map(|el| Some((el.member.clone(), el.weight?)))
+        .collect();
+    // the rest split among `sharing`, equally or by the weights times the shares, in name order
+    let share_out = |rest: Money, sharing: &[&String]| -> Result<Vec<Money>> {
+        if sharing.is_empty() {
+            return Ok(vec![]);
+        } else if weights.is_none() && counts.is_empty() {
+            return Ok(split_equal_among(rest, sharing.len()));
+        }
+        let weighted = sharing.iter()
+            .map(|name| {
+                let weight = weights.map_or(1, |weights| weights.get(*name).copied().unwrap_or(0));
+                weight.checked_mul(counts.get(*name).copied().unwrap_or(1))
+            })
+            .collect::<Option<Vec<u64>>>()
+            .ok_or_else(|| anyhow!(SplitterError::InvalidTargetFormat))
+            .context("The shares given like <name>:2x are too large")?;
+        if weighted.iter().all(|weight| *weight == 0) {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context("None of the members sharing the rest has a weight in the ratio");
+        }
+        Ok(split_by_weights(rest, &weighted))
+    };
+    let names = &group.members;
+    if recvrs.0.iter().any(|el| el.share.is_some()) {
+        let fixed = |name: &String| recvrs.0.iter()
+            .any(|el| &el.member == name && el.share.is_none() && el.weight.is_none());
+        let sharing: Vec<&String> = names.iter().copied()
+            .filter(|name| !except.contains(name) && (balance_rest || !fixed(name)))
+            .collect();
+        let provisional = share_out(total_amount - recvrs.1, &sharing)?;
+        for target in recvrs.0.iter_mut().filter(|el| el.share.is_some()) {
+            let share = target.share.as_deref().unwrap();
+            let full = provisional[sharing.iter().position(|name| **name == target.member).unwrap()];
+            let amount = eval_decimal(share).ok().and_then(|percent| percent.percent_of(full))
+                .ok_or_else(|| anyhow!(SplitterError::InvalidTargetFormat))
+                .context(format!("{}%share of {} is too large", share, group.currency.format(full)))?;
+            target.amount = Some(amount);
+            recvrs.1 += amount;
+        }
+        check_overdrawn(Side::To, &recvrs.0, total_amount, &sharing_rest(&recvrs.0), group.currency)?;
+    }
+    if sharing_rest(&recvrs.0).is_empty() && recvrs.1 != total_amount {
+        return Err(anyhow!(SplitterError::LogicError)).context(format!(
+            "Everybody has an amount given with --to, but they only add up to {} instead of {}",
+            group.currency.format(recvrs.1), group.currency.format(total_amount)));
     }
     // normalize givers to contain entries for all members of the group
-    let moneysplit =
-        split_equal_among(total_amount - givers.1, givers.2);
+    let moneysplit = if givers.2 == 0 { vec![] } else { split_equal_among(total_amount - givers.1, givers.2) };
     let mut wcg_index = 0;
     let mut transaction_map = HashMap::with_capacity(group.members.len());
 
-    // positively add all the froms
-    for name in group.members.keys() {
+    // positively add all the froms, in name order like the rest below
+    for name in names.iter().copied() {
         if let Some(giver) = givers.0.iter().find(|&target| &target.member == name)
         {
             if let Some(amount) = giver.amount {
@@ -305,7 +1526,7 @@ fn split_into_transaction(total_amount: Money, group: &Group,
                 wcg_index += 1;
             }
         } else {
-            transaction_map.insert(name.clone(), 0);
+            transaction_map.insert(name.clone(), Money::ZERO);
         }
     }
 
@@ -313,22 +1534,22 @@ fn split_into_transaction(total_amount: Money, group: &Group,
     // peter started with 0, but takes 5€ of the pot, reaching a balance of -5€
     // if balance_rest is true, everything gets split onto the --to takers as well, if not, they
     // are excluded from the calculation and pay exactly as much as specified
-    let moneysplit = split_equal_among(
-        total_amount - recvrs.1,
-        group.members.len() - if balance_rest { 0 } else { recvrs.0.len() },
-    );
+    // in name order, so that leftover cents always go to the same members
+    let sharing = sharing_rest(&recvrs.0);
+    let moneysplit = share_out(total_amount - recvrs.1, &sharing)?;
     let mut ms_idx = 0;
-    for name in group.members.keys() {
-        if let Some(recv) = recvrs.0.iter().find(|&el| &el.member == name) {
-            let x = transaction_map.get_mut(name).unwrap();
-            *x -= recv.amount.unwrap();
-            if balance_rest {
-                *x -= moneysplit[ms_idx];
-                ms_idx += 1;
-            }
-        } else {
-            let x = transaction_map.get_mut(name).unwrap();
+    for name in names.iter().copied() {
+        let x = transaction_map.get_mut(name).unwrap();
+        let recv = recvrs.0.iter_mut().find(|el| &el.member == name);
+        if let Some(amount) = recv.as_ref().and_then(|el| el.amount) {
+            *x -= amount;
+        }
+        if sharing.get(ms_idx) == Some(&name) {
             *x -= moneysplit[ms_idx];
+            // members with shares of the rest are logged with what they came to
+            if let Some(recv) = recv.filter(|el| el.weight.is_some()) {
+                recv.amount = Some(moneysplit[ms_idx]);
+            }
             ms_idx += 1;
         }
     }
@@ -342,24 +1563,393 @@ mod group_tests {
     use crate::logic::Transaction;
     use super::*;
 
+    #[test]
+    fn test_preview_members() {
+        let members = preview_members(vec![], Some(12), Money(100_00), &["p03:20".to_string(), "alice".to_string()],
+                                      &["bob:10%".to_string()], Currency::EUR).unwrap();
+        assert_eq!(members.len(), 12);
+        assert_eq!(&members[..4], ["p03", "alice", "bob", "p01"]);
+        assert!(!members[3..].contains(&"p03".to_string()));
+        let names = vec!["x".to_string(), "y".to_string()];
+        assert_eq!(preview_members(names.clone(), None, Money(100_00), &[], &[], Currency::EUR).unwrap(), names);
+        assert!(preview_members(vec![], Some(1), Money(100_00), &["a".to_string()], &["b".to_string()], Currency::EUR).is_err());
+    }
+
+    #[test]
+    fn test_preview_split() {
+        let members = vec!["alice".to_string(), "bob".to_string(), "carl".to_string()];
+        let preview = preview_split(Money(100_00), members.clone(), vec!["alice".to_string()], vec![], false, Currency::EUR)
+            .unwrap();
+        let rows: Vec<(&str, Money, Money, Money)> = preview.members.iter()
+            .map(|row| (row.name.as_str(), row.paid, row.share, row.change)).collect();
+        assert_eq!(rows, vec![("alice", Money(100_00), Money(33_34), Money(66_66)),
+                              ("bob", Money::ZERO, Money(33_33), Money(-33_33)),
+                              ("carl", Money::ZERO, Money(33_33), Money(-33_33))]);
+        assert_eq!(preview.members.iter().map(|row| row.share).sum::<Money>(), Money(100_00));
+
+        // nobody of the members paid
+        let preview = preview_split(Money(90_00), members.clone(), vec![], vec!["bob:50%".to_string()], false, Currency::EUR)
+            .unwrap();
+        assert!(preview.members.iter().all(|row| row.paid.is_zero() && row.change == -row.share));
+        assert_eq!(preview.members[1].share, Money(45_00));
+        assert!(preview_split(Money(90_00), members, vec!["dave".to_string()], vec![], false, Currency::EUR).is_err());
+    }
+
     #[test]
     fn test_remove_member() {
         let mut group = setup_group();
         assert_eq!(group.members.len(), 4);
-        let r = group.remove(vec!["Alice".to_string()], false);
+        let r = group.remove(vec!["Alice".to_string()], false, None);
         assert!(r.is_ok());
         assert_eq!(group.members.len(), 3);
         let mut group = setup_group();
-        let r = group.remove(vec!["Theseus".to_string()], false);
+        let r = group.remove(vec!["Theseus".to_string()], false, None);
         assert!(r.is_err());
 
         let mut group = setup_group();
-        *group.members.get_mut("Alice").unwrap() = 100;
-        let r = group.remove(vec!["Alice".to_string()], true);
+        *group.members.get_mut("Alice").unwrap() = Money(100);
+        let r = group.remove(vec!["Alice".to_string()], true, None);
         assert!(r.is_ok());
         assert_eq!(group.members.len(), 3);
     }
 
+    #[test]
+    fn test_add_many_members() {
+        let mut group = setup_group();
+        group.add((0..5_000).map(|i| format!("member{}", i)).collect()).unwrap();
+        assert_eq!(group.members.len(), 5_004);
+        assert!(group.add(vec!["-dash".to_string(), "".to_string(), "a b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_problems() {
+        let mut group = setup_group();
+        group.enable_integrity();
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.remove(vec!["Bob".to_string()], true, None).unwrap();
+        assert!(group.problems().is_empty(), "{:?}", group.problems());
+
+        *group.members.get_mut("Alice").unwrap() += Money(1_00);
+        group.members.remove("Charly");
+        group.log[0].time = None;
+        assert_eq!(group.problems(), vec![
+            "the balances add up to 34.33€ instead of zero",
+            "Alice has 67.66€ but the log adds up to 66.66€",
+            "the log leaves Charly with -33.33€ but they are no member",
+            "the hash chain is broken at entry [0]",
+        ]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut group = setup_group();
+        group.enable_integrity();
+        group.set_ratio("income", &["Alice=2".to_string(), "Bob=1".to_string()]).unwrap();
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(10_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        assert!(group.reset(Some(vec!["no name".to_string()])).is_err());
+        assert_eq!(group.log.len(), 2);
+
+        group.reset(None).unwrap();
+        assert_eq!(group.members.len(), 4);
+        assert!(group.members.values().all(|balance| balance.is_zero()));
+        assert_eq!(group.log.len(), 1);
+        assert_eq!(group.log[0].to_string(0, Currency::EUR).split_once(": ").unwrap().1,
+                   "reset: in group testgroup 2 entries cleared, members Alice, Bob, Charly, Django");
+        assert!(group.ratios.contains_key("income"));
+        assert!(group.verify().unwrap().contains("History rewritten from entry [0]"));
+        assert_eq!(group.summary().volume, Money::ZERO);
+
+        group.reset(Some(vec!["Emil".to_string()])).unwrap();
+        assert_eq!(group.member_names(), vec!["Emil"]);
+        assert!(matches!(&group.log[0].command, LoggedCommand::Reset { cleared: 1, .. }));
+    }
+
+    #[test]
+    fn test_remove_write_off() {
+        let mut group = setup_group();
+        group.enable_integrity();
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        assert!(group.remove(vec!["Bob".to_string()], true, Some("Theseus".to_string())).is_err());
+        assert!(group.remove(vec!["Bob".to_string(), "Charly".to_string()], true, Some("Charly".to_string())).is_err());
+        assert_eq!(group.log.len(), 1);
+
+        group.remove(vec!["Bob".to_string()], true, Some("Charly".to_string())).unwrap();
+        assert_eq!(group.members["Charly"], Money(-50_00));
+        assert!(matches!(&group.log[1].command,
+            LoggedCommand::WriteOff { member, amount: Money(-25_00), to, .. } if member == "Bob" && to == &["Charly"]));
+
+        // without a target everyone left takes a share
+        group.remove(vec!["Alice".to_string()], true, None).unwrap();
+        assert_eq!(group.members, HashMap::from([("Charly".to_string(), Money(-12_50)), ("Django".to_string(), Money(12_50))]));
+        assert_eq!(group.log[2].to_string(2, Currency::EUR).split_once(": ").unwrap().1,
+                   "write-off: in group testgroup Alice removed with 75.00€, taken over by Charly, Django");
+        assert!(group.verify().is_ok());
+
+        // the last members cannot pass their balances on to anyone
+        assert!(group.remove(vec!["Charly".to_string(), "Django".to_string()], true, None).is_err());
+    }
+
+    #[test]
+    fn test_fix_residual() {
+        let mut group = setup_group();
+        group.enable_integrity();
+        group.split(Money(40_00), vec!["Alice".to_string()], vec![], "rent".to_string(), SplitOptions::default()).unwrap();
+        assert!(group.fix_residual(None).is_err(), "nothing to correct");
+        // what an old bug left behind
+        *group.members.get_mut("Alice").unwrap() += Money(3);
+        assert_eq!(group.residual(), Money(3));
+        assert!(group.fix_residual(Some("Theseus".to_string())).is_err());
+
+        assert_eq!(group.fix_residual(None).unwrap(), Money(3));
+        assert_eq!(group.residual(), Money::ZERO);
+        assert_eq!(group.members["Alice"], Money(30_02));
+        assert_eq!(group.members["Bob"], Money(-10_01));
+        assert_eq!(group.members["Charly"], Money(-10_01));
+        assert_eq!(group.members["Django"], Money(-10_00));
+        assert!(matches!(&group.log[1].command,
+            LoggedCommand::Correction { residual: Money(3), members, .. } if members == &["Alice", "Bob", "Charly"]));
+
+        *group.members.get_mut("Bob").unwrap() -= Money(2);
+        assert_eq!(group.fix_residual(Some("Django".to_string())).unwrap(), Money(-2));
+        assert_eq!(group.members["Django"], Money(-9_98));
+        assert_eq!(group.log[2].to_string(2, Currency::EUR).split_once(": ").unwrap().1,
+                   "correction: in group testgroup residual of -0.02€ booked to Django");
+        assert_eq!(group.summary().volume, Money(40_00));
+        assert!(group.verify().is_ok());
+    }
+
+    #[test]
+    fn test_buy_in() {
+        let mut group = setup_group();
+        assert!(group.buy_in("Alice".to_string(), Money(10_00)).is_err(), "nobody paid anything yet");
+        group.split(Money(90_00), vec!["Alice".to_string()], vec![], "hotel".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.split(Money(30_00), vec!["Bob".to_string()], vec![], "dinner".to_string(), SplitOptions::default()).unwrap();
+        // 120.00 spent among five
+        assert_eq!(group.spend_share(1), Money(24_00));
+
+        group.add(vec!["Egon".to_string()]).unwrap();
+        group.buy_in("Egon".to_string(), Money(24_00)).unwrap();
+        assert_eq!(group.members["Egon"], Money(-24_00));
+        // Alice fronted three times as much as Bob
+        assert_eq!(group.log[2].change_of("Alice"), Money(18_00));
+        assert_eq!(group.log[2].change_of("Bob"), Money(6_00));
+        assert_eq!(group.members.values().sum::<Money>(), Money::ZERO);
+        assert_eq!(group.log[2].to_string(2, Currency::EUR).split_once(": ").unwrap().1,
+                   "buy-in: in group testgroup Egon joins owing 24.00€");
+        assert!(group.buy_in("Theseus".to_string(), Money(1_00)).is_err());
+    }
+
+    #[test]
+    fn test_include_in_split() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Alice".to_string(), "Bob".to_string(), "Charly".to_string()], None).unwrap();
+        group.enable_integrity();
+        group.split(Money(30_00), vec!["Alice".to_string()], vec![], "dinner".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(5_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.add(vec!["Egon".to_string()]).unwrap();
+        assert!(group.include_in_split(1, &["Egon".to_string()]).is_err(), "no split");
+        assert!(group.include_in_split(0, &["Theseus".to_string()]).is_err());
+        assert!(group.include_in_split(0, &["Bob".to_string()]).is_err(), "already part of it");
+
+        let delta = group.include_in_split(0, &["Egon".to_string()]).unwrap();
+        assert_eq!(delta, HashMap::from([("Alice".to_string(), Money(2_50)), ("Bob".to_string(), Money(2_50)),
+                                         ("Charly".to_string(), Money(2_50)), ("Egon".to_string(), Money(-7_50))]));
+        // everyone's share went from 10.00 down to 7.50
+        for member in ["Bob", "Charly", "Egon"] {
+            assert_eq!(group.log[0].change_of(member), Money(-7_50), "{}", member);
+        }
+        assert_eq!(group.members["Alice"], Money(17_50));
+        assert_eq!(group.members.values().sum::<Money>(), Money::ZERO);
+        assert!(group.log[0].to_string(0, Currency::EUR).ends_with(", amended to include Egon"));
+        assert!(group.verify().unwrap().contains("included Egon in entry [0]"));
+
+        // a change that does not match the parameters cannot be amended
+        group.add(vec!["Fred".to_string()]).unwrap();
+        group.log[0].amend(HashMap::from([("Alice".to_string(), Money(1_00)), ("Bob".to_string(), Money(-1_00))]), &[]);
+        assert!(group.include_in_split(0, &["Fred".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_yen_migrated() {
+        // 3000 yen split among three, written when yen were stored in hundredths
+        let fixture = r#"
+name: trip
+currency: JPY
+members:
+  alice: 200000
+  bob: -100000
+  charly: -100000
+confirm_above: 500000
+log:
+- command: !Split
+    name: dinner
+    amount: 300000
+    from:
+    - member: alice
+      amount: null
+    to:
+    - member: bob
+      amount: 100000
+    group: trip
+    balance_rest: false
+  change:
+    alice: 200000
+    bob: -100000
+    charly: -100000
+"#;
+        let mut group: Group = serde_yaml::from_str(fixture).unwrap();
+        group.migrate();
+        assert_eq!(group.members["alice"], Money(2000));
+        assert_eq!(group.confirm_above, Some(Money(5000)));
+        assert_eq!(group.log[0].command.amount(), Money(3000));
+        assert!(group.log[0].to_string(0, group.currency).contains("\nbob: 1000¥\n"));
+        assert_eq!(group.totals("alice"), Totals { paid: Money(3000), consumed: Money(1000), entries: 1 });
+        assert!(serde_yaml::to_string(&group).unwrap().contains("subdivision: 1\n"));
+        // converted once, the subdivision is stored along
+        let mut loaded: Group = serde_yaml::from_str(&serde_yaml::to_string(&group).unwrap()).unwrap();
+        loaded.migrate();
+        assert_eq!(loaded.members["alice"], Money(2000));
+        assert_eq!(loaded.in_currency(Money(1050_00)).unwrap(), Money(1050));
+        assert!(loaded.in_currency(Money(10_50)).is_err());
+        // new groups start in the subdivision of their currency
+        let fresh = Group::new("trip".to_string(), vec!["alice".to_string()], Some(Currency::JPY)).unwrap();
+        assert_eq!(fresh.subdivision, 1);
+    }
+
+    #[test]
+    fn test_target_expressions() {
+        // the same split of 30.00 with bob paying 10%, recorded without and with the expression
+        let split = |expression: &str| format!(r#"
+- command: !Split
+    name: dinner
+    amount: 3000
+    from:
+    - member: alice
+      amount: null
+    to:
+    - member: bob
+      amount: 300{}
+    group: trip
+    balance_rest: false
+  change:
+    alice: 1650
+    bob: -300
+    charly: -1350"#, expression);
+        let fixture = format!("name: trip\ncurrency: EUR\nmembers:\n  alice: 3300\n  bob: -600\n  charly: -2700\nlog:{}{}\n",
+                              split(""), split("\n      expression: 10%"));
+        let mut group: Group = serde_yaml::from_str(&fixture).unwrap();
+        group.migrate();
+        assert!(group.log[0].to_string(0, Currency::EUR).contains("\nbob: 3.00€\n"));
+        assert!(group.log[1].to_string(1, Currency::EUR).contains("\nbob: 10% (=3.00€)\n"));
+        assert!(serde_yaml::to_string(&group).unwrap().matches("expression").count() == 1);
+
+        group.add(vec!["dora".to_string()]).unwrap();
+        for index in [1, 0] {
+            let delta = group.include_in_split(index, &["dora".to_string()]).unwrap();
+            assert_eq!(delta["dora"], Money(-9_00));
+            assert_eq!(group.log[index].change_of("bob"), Money(-3_00));
+        }
+        // undo, as `splitter undo` does it
+        for index in [1, 0] {
+            let entry = group.remove_log(Some(index)).unwrap();
+            group.apply_tachange(entry.reversed_change());
+        }
+        assert!(group.members.values().all(|balance| balance.is_zero()));
+    }
+
+    #[test]
+    fn test_rename_member() {
+        let mut group = setup_group();
+        group.enable_integrity();
+        group.set_ratio("income", &["Alice=3".to_string(), "Bob=2".to_string()]).unwrap();
+        group.banker = Some("Bob".to_string());
+        group.split(Money(40_00), vec!["Bob".to_string()], vec!["Alice:10%".to_string()], "dinner".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        group.remove(vec!["Django".to_string()], true, Some("Bob".to_string())).unwrap();
+        let balance = group.members["Bob"];
+
+        assert!(group.rename_member("Bobby", "Rob".to_string()).is_err());
+        assert!(group.rename_member("Bob", "Rob ert".to_string()).is_err());
+        assert!(group.rename_member("Bob", "Alice".to_string()).is_err());
+        // a former member would get their history
+        assert!(group.rename_member("Bob", "Django".to_string()).is_err());
+
+        group.rename_member("Bob", "Robert".to_string()).unwrap();
+        assert!(!group.members.contains_key("Bob"));
+        assert_eq!(group.members["Robert"], balance);
+        assert_eq!(group.ratio("income").unwrap()["Robert"], 2);
+        assert_eq!(group.banker.as_deref(), Some("Robert"));
+        assert_eq!(group.totals("Robert").paid, Money(40_00));
+        let listed = group.log[0].to_string(0, Currency::EUR);
+        assert!(listed.contains("\nRobert: *\n") && !listed.contains("Bob"), "{}", listed);
+        assert!(matches!(&group.log[1].command, LoggedCommand::Pay { to, .. } if to == "Robert"));
+        assert!(matches!(&group.log[2].command, LoggedCommand::WriteOff { to, .. } if to == &["Robert"]));
+        assert!(group.verify().unwrap().ends_with("(renamed Bob to Robert)"));
+
+        // undoing an entry from before the rename applies to the renamed member
+        let entry = group.remove_log(Some(1)).unwrap();
+        group.apply_tachange(entry.reversed_change());
+        assert_eq!(group.members["Robert"], balance + Money(5_00));
+        assert_eq!(group.members.values().sum::<Money>(), Money::ZERO);
+        assert!(group.include_in_split(0, &["Charly".to_string()]).is_err(), "already part of it");
+    }
+
+    #[test]
+    fn test_totals() {
+        let mut group = setup_group();
+        group.split(Money(40_00), vec!["Alice".to_string()], vec![], "dinner".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.split(Money(10_00), vec!["Bob".to_string()], vec!["Alice:10".to_string()], "taxi".to_string(),
+                    SplitOptions::default())
+            .unwrap();
+        group.log_pay_transaction(Money(5_00), "Charly".to_string(), "Alice".to_string()).unwrap();
+        assert_eq!(group.totals("Alice"), Totals { paid: Money(40_00), consumed: Money(20_00), entries: 3 });
+        assert_eq!(group.totals("Bob"), Totals { paid: Money(10_00), consumed: Money(10_00), entries: 2 });
+        assert_eq!(group.totals("Charly"), Totals { paid: Money::ZERO, consumed: Money(10_00), entries: 2 });
+
+        group.add(vec!["Egon".to_string()]).unwrap();
+        group.include_in_split(0, &["Egon".to_string()]).unwrap();
+        group.buy_in("Egon".to_string(), Money(4_00)).unwrap();
+        let undone = group.remove_log(Some(2)).unwrap();
+        group.apply_tachange(undone.reversed_change());
+        assert_eq!(group.totals("Egon"), Totals { paid: Money::ZERO, consumed: Money(8_00), entries: 2 });
+        assert_eq!(group.totals("Charly"), Totals { paid: Money::ZERO, consumed: Money(8_00), entries: 1 });
+        assert_eq!(group.totals, totals_of(&group.log));
+        let stat = group.stat_data();
+        assert_eq!(stat.totals.len(), 5);
+        assert_eq!(stat.totals[0], ("Alice".to_string(), Totals { paid: Money(40_00), consumed: Money(18_00), entries: 3 }));
+
+        let entries = group.split_off_log(1);
+        assert_eq!(group.totals, totals_of(&group.log));
+        for entry in entries {
+            group.replay(entry);
+        }
+        assert!(group.problems().is_empty(), "{:?}", group.problems());
+
+        group.totals.get_mut("Bob").unwrap().paid += Money(1);
+        group.totals.remove("Egon");
+        assert_eq!(group.problems(), vec!["the running totals of Bob, Egon differ from what the log adds up to"]);
+        group.rebuild_totals();
+        assert!(group.problems().is_empty());
+
+        // files written before the totals existed count them on load
+        let mut yaml: serde_yaml::Value = serde_yaml::to_value(&group).unwrap();
+        yaml.as_mapping_mut().unwrap().remove("totals");
+        let mut loaded: Group = serde_yaml::from_value(yaml).unwrap();
+        loaded.migrate();
+        assert_eq!(loaded.totals, group.totals);
+
+        group.reset(None).unwrap();
+        assert!(group.totals.is_empty() && group.problems().is_empty());
+    }
+
     #[test]
     fn test_add_member() {
         let mut group = setup_group();
@@ -368,7 +1958,7 @@ mod group_tests {
         assert!(r.is_ok(), "{:#?}", r.unwrap_err());
         assert_eq!(group.members.len(), 5);
         assert!(group.members.contains_key("Egbert"));
-        assert_eq!(group.members["Egbert"], 0);
+        assert_eq!(group.members["Egbert"], Money::ZERO);
 
         let mut group = setup_group();
         assert_eq!(group.members.len(), 4);
@@ -377,6 +1967,19 @@ mod group_tests {
         assert_eq!(group.members.len(), 4);
     }
 
+    #[test]
+    fn test_new_member_names() {
+        let names = ["Alice", "-bob", "Carl", "Alice", "dan ny", "Carl", "Eve", "-bob"].map(String::from).to_vec();
+        let err = Group::new("testgroup".to_string(), names, None).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::InvalidName).as_ref());
+        assert_eq!(err.to_string(), "names not allowed for members: -bob, dan ny\ngiven more than once: Alice, Carl");
+
+        let err = Group::new("testgroup".to_string(), vec!["Alice".to_string(), "Alice".to_string()], None).err().unwrap();
+        assert_eq!(err.to_string(), "given more than once: Alice");
+        let group = Group::new("testgroup".to_string(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        assert_eq!(group.members.len(), 2);
+    }
+
     #[test]
     fn test_apply_tachange() {
         let mut group =
@@ -384,11 +1987,11 @@ mod group_tests {
                        vec!["Alice".to_string(), "Bob".to_string()],
                        None).unwrap();
         let tac = TransactionChange::from(
-            [("Alice".into(), -10),
-                ("Bob".into(), 10)]);
+            [("Alice".into(), Money(-10)),
+                ("Bob".into(), Money(10))]);
         group.apply_tachange(tac);
-        assert_eq!(group.members["Alice"], -10);
-        assert_eq!(group.members["Bob"], 10);
+        assert_eq!(group.members["Alice"], Money(-10));
+        assert_eq!(group.members["Bob"], Money(10));
     }
 
     #[test]
@@ -397,12 +2000,73 @@ mod group_tests {
             Group::new("testgroup".to_string(),
                        vec!["Alice".to_string(), "Bob".to_string()],
                        None).unwrap();
-        *(group.members.get_mut("Alice").unwrap()) = -10_00;
-        *(group.members.get_mut("Bob").unwrap()) = 10_00;
+        *(group.members.get_mut("Alice").unwrap()) = Money(-10_00);
+        *(group.members.get_mut("Bob").unwrap()) = Money(10_00);
 
-        let tas = group.balance();
+        let tas = group.settle(None, None).unwrap().1;
         assert_eq!(tas.len(), 1);
-        assert_eq!(tas[0], Transaction::new("Alice", "Bob", 10_00));
+        assert_eq!(tas[0], Transaction::new("Alice", "Bob", Money(10_00)));
+    }
+
+    #[test]
+    fn test_balance_ties() {
+        // every map gets its own iteration order, the suggestions must not follow it
+        for _ in 0..20 {
+            let mut group = setup_group();
+            group.apply_tachange(HashMap::from([("Alice".to_string(), Money(5_00)), ("Bob".to_string(), Money(-5_00)),
+                                                ("Charly".to_string(), Money(5_00)), ("Django".to_string(), Money(-5_00))]));
+            assert_eq!(group.settle(None, None).unwrap().1, vec![Transaction::new("Bob", "Alice", Money(5_00)),
+                                             Transaction::new("Django", "Charly", Money(5_00))]);
+        }
+    }
+
+    #[test]
+    fn test_balance_exact_rest() {
+        let mut group =
+            Group::new("testgroup".to_string(),
+                       vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()],
+                       None).unwrap();
+        *(group.members.get_mut("Alice").unwrap()) = Money(11_00);
+        *(group.members.get_mut("Bob").unwrap()) = Money(3_00);
+        *(group.members.get_mut("Carol").unwrap()) = Money(-14_00);
+        let tas = group.settle(None, None).unwrap().1;
+        assert_eq!(tas, vec![Transaction::new("Carol", "Bob", Money(3_00)), Transaction::new("Carol", "Alice", Money(11_00))]);
+    }
+
+    #[test]
+    fn test_balance_output_currencies() {
+        for (currency, expected) in [(Currency::EUR, "10.50€"), (Currency::USD, "10.50$"),
+            (Currency::GBP, "10.50£"), (Currency::JPY, "1050¥")] {
+            let mut group =
+                Group::new("testgroup".to_string(),
+                           vec!["Alice".to_string(), "Bob".to_string()],
+                           Some(currency)).unwrap();
+            *(group.members.get_mut("Alice").unwrap()) = Money(-10_50);
+            *(group.members.get_mut("Bob").unwrap()) = Money(10_50);
+            let tas = group.settle(None, None).unwrap().1;
+            let table = Transaction::table(&tas, group.currency).to_string();
+            assert!(table.lines().last().unwrap().split_whitespace().eq(["Alice", "Bob", expected]), "{}", table);
+        }
+    }
+
+    #[test]
+    fn test_balance_output_aligned() {
+        let mut group =
+            Group::new("testgroup".to_owned(),
+                       vec!["Alice".to_string(), "Bob".to_string(),
+                            "Charly".to_string(), "Django".to_string()],
+                       Some(Currency::USD)).unwrap();
+        *(group.members.get_mut("Alice").unwrap()) = Money(-1685);
+        *(group.members.get_mut("Bob").unwrap()) = Money(316);
+        *(group.members.get_mut("Charly").unwrap()) = Money(2117);
+        *(group.members.get_mut("Django").unwrap()) = Money(-748);
+        let table = Transaction::table(&group.settle(None, None).unwrap().1, group.currency).to_string();
+        assert_eq!(table,
+                   "From    To      Amount\n\
+                    ----------------------\n\
+                    Django  Bob      3.16$\n\
+                    Django  Charly   4.32$\n\
+                    Alice   Charly  16.85$\n");
     }
 
     #[test]
@@ -412,31 +2076,31 @@ mod group_tests {
                        vec!["Alice".to_string(), "Bob".to_string(),
                             "Charly".to_string(), "Django".to_string()],
                        None).unwrap();
-        *(group.members.get_mut("Alice").unwrap()) = -1685;
-        *(group.members.get_mut("Bob").unwrap()) = 316;
-        *(group.members.get_mut("Charly").unwrap()) = 2117;
-        *(group.members.get_mut("Django").unwrap()) = -748;
-        let tas = group.balance();
-        assert_eq!(tas[0], Transaction::new(&"Django".to_string(), &"Bob".to_string(), 316));
-        assert_eq!(tas[1], Transaction::new(&"Django".to_string(), &"Charly".to_string(), 432));
-        assert_eq!(tas[2], Transaction::new(&"Alice".to_string(), &"Charly".to_string(), 1685));
+        *(group.members.get_mut("Alice").unwrap()) = Money(-1685);
+        *(group.members.get_mut("Bob").unwrap()) = Money(316);
+        *(group.members.get_mut("Charly").unwrap()) = Money(2117);
+        *(group.members.get_mut("Django").unwrap()) = Money(-748);
+        let tas = group.settle(None, None).unwrap().1;
+        assert_eq!(tas[0], Transaction::new("Django", "Bob", Money(316)));
+        assert_eq!(tas[1], Transaction::new("Django", "Charly", Money(432)));
+        assert_eq!(tas[2], Transaction::new("Alice", "Charly", Money(1685)));
     }
 
     #[test]
     fn remove_log_test() {
         let mut group = setup_group();
         {
-            let r = group.log_pay_transaction(12, "Alice".into(), "Bob".into());
+            let r = group.log_pay_transaction(Money(12), "Alice".into(), "Bob".into());
             assert!(r.is_ok());
-            let r = group.log_pay_transaction(13, "Alice".into(), "Bob".into());
+            let r = group.log_pay_transaction(Money(13), "Alice".into(), "Bob".into());
             assert!(r.is_ok());
             assert_eq!(group.log.len(), 2);
             let r = group.remove_log(Some(1));
             assert!(r.is_ok());
             assert_eq!(group.log.len(), 1);
             match &group.log[0].command {
-                LoggedCommand::Pay { amount, from, to } => {
-                    assert_eq!(*amount, 12);
+                LoggedCommand::Pay { amount, from, to, .. } => {
+                    assert_eq!(*amount, Money(12));
                     assert_eq!(from, "Alice");
                     assert_eq!(to, "Bob");
                 }
@@ -447,9 +2111,9 @@ mod group_tests {
             assert_eq!(group.log.len(), 0);
         }
         {
-            let r = group.log_pay_transaction(12, "Alice".into(), "Bob".into());
+            let r = group.log_pay_transaction(Money(12), "Alice".into(), "Bob".into());
             assert!(r.is_ok());
-            let r = group.log_pay_transaction(13, "Alice".into(), "Bob".into());
+            let r = group.log_pay_transaction(Money(13), "Alice".into(), "Bob".into());
             assert!(r.is_ok());
             let r = group.remove_log(None);
             assert!(r.is_ok());
@@ -461,70 +2125,557 @@ mod group_tests {
     fn test_split_equal_among() {
         // tests positive values
         // tests "perfect" split
-        let result = split_equal_among(100, 10);
+        let result = split_equal_among(Money(100), 10);
         for x in result {
-            assert_eq!(x, 10);
+            assert_eq!(x, Money(10));
         }
         // tests "imperfect" split
-        let result = split_equal_among(100, 9);
-        let expected_vec = vec![12, 11, 11, 11, 11, 11, 11, 11, 11];
+        let result = split_equal_among(Money(100), 9);
+        let expected_vec = [12, 11, 11, 11, 11, 11, 11, 11, 11].map(Money);
         for i in 0..expected_vec.len() {
             assert_eq!(result[i], expected_vec[i]);
         }
         // tests negative values
         // tests "perfect" split
-        let result = split_equal_among(-100, 10);
+        let result = split_equal_among(Money(-100), 10);
         for x in result {
-            assert_eq!(x, -10);
+            assert_eq!(x, Money(-10));
         }
         // tests "imperfect" split
-        let result = split_equal_among(-100, 9);
-        let expected_vec = vec![-12, -11, -11, -11, -11, -11, -11, -11, -11];
+        let result = split_equal_among(Money(-100), 9);
+        let expected_vec = [-12, -11, -11, -11, -11, -11, -11, -11, -11].map(Money);
         for i in 0..expected_vec.len() {
             assert_eq!(result[i], expected_vec[i]);
         }
     }
 
+    #[test]
+    fn test_close_month() {
+        let mut group = setup_group();
+        let june: Month = "2024-06".parse().unwrap();
+        let at = |month: Month, day: i64| month.start() + chrono::Duration::days(day);
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log[0].time = Some(at(june.previous(), 20));
+        group.split(Money(40_00), vec!["Alice".to_string()], vec![], "dinner".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log[1].time = Some(at(june, 2));
+        group.split(Money(20_00), vec!["Bob:15".to_string(), "Charly".to_string()], vec!["Django:8".to_string()],
+                    "drinks".to_string(), SplitOptions::default()).unwrap();
+        group.log[2].time = Some(at(june, 29));
+        group.log_pay_transaction(Money(5_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.log[3].time = Some(at(june.next(), 0));
+
+        let report = group.close_month(june, false).unwrap();
+        assert_eq!((report.expenses, report.spend), (2, Money(60_00)));
+        assert_eq!(report.members, vec![
+            MemberPeriod { name: "Alice".to_string(), paid: Money(40_00), consumed: Money(14_00) },
+            MemberPeriod { name: "Bob".to_string(), paid: Money(15_00), consumed: Money(14_00) },
+            MemberPeriod { name: "Charly".to_string(), paid: Money(5_00), consumed: Money(14_00) },
+            MemberPeriod { name: "Django".to_string(), paid: Money::ZERO, consumed: Money(18_00) },
+        ]);
+        // the transfers settle June only, the payment in July does not count
+        assert_eq!(report.transfers, vec![Transaction::new("Charly", "Bob", Money(1_00)), Transaction::new("Charly", "Alice", Money(8_00)),
+                                          Transaction::new("Django", "Alice", Money(18_00))]);
+        let net: Money = report.members.iter().map(|m| m.paid - m.consumed).sum();
+        assert_eq!(net, Money::ZERO);
+
+        assert!(group.close_month(june, false).is_err(), "closed twice");
+        assert!(group.close_month(june.previous(), false).is_err(), "before the last closed month");
+        assert!(group.close_month(Month::of(Local::now()), false).is_err(), "not over yet");
+
+        // the open period starts in July
+        assert_eq!(group.open_since(), Some(june.end()));
+        let open = group.report(None);
+        assert_eq!((open.expenses, open.transfers.len()), (0, 1));
+        let stat = group.stat_view(Money::ZERO, None, None, true, None).unwrap();
+        assert_eq!(stat.members, vec![("Alice".to_string(), Money(-5_00)), ("Bob".to_string(), Money(5_00)),
+                                      ("Charly".to_string(), Money::ZERO), ("Django".to_string(), Money::ZERO)]);
+        assert_eq!(stat.summary.entries, 1);
+    }
+
+    #[test]
+    fn test_as_of() {
+        let mut group = setup_group();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        group.split(Money(40_00), vec!["Alice".to_string()], vec![], "rent".to_string(), SplitOptions::default()).unwrap();
+        group.log[0].time = Some(day_end(day(9)) - chrono::Duration::minutes(1));
+        group.log_pay_transaction(Money(10_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.log[1].time = Some(day_end(day(10)));
+        group.log_pay_transaction(Money(10_00), "Charly".to_string(), "Alice".to_string()).unwrap();
+        group.log[2].time = None;
+        let balances = |stat: GroupStat| stat.members.iter().map(|(_, balance)| *balance).collect::<Vec<_>>();
+
+        // the payment on the 11th is left out, the undated one counted in
+        let stat = group.stat_view(Money::ZERO, None, None, false, Some(day(10))).unwrap();
+        assert_eq!(stat.as_of.map(|as_of| as_of.undated), Some(1));
+        assert_eq!(stat.summary.entries, 2);
+        assert_eq!(balances(stat), vec![Money(20_00), Money(-10_00), Money::ZERO, Money(-10_00)]);
+        // as of now the replay matches the stored balances, which are left alone
+        let now = group.stat_view(Money::ZERO, None, None, false, Some(Local::now().date_naive())).unwrap();
+        let current = group.stat_view(Money::ZERO, None, None, false, None).unwrap();
+        assert_eq!(now.members, current.members);
+        assert_eq!(now.summary, current.summary);
+        assert_eq!(group.members["Bob"], Money::ZERO);
+
+        // a member who left since still shows up with what they had
+        group.remove(vec!["Django".to_string()], true, None).unwrap();
+        let stat = group.stat_view(Money::ZERO, None, None, false, Some(day(10))).unwrap();
+        assert_eq!(stat.members.last().unwrap(), &("Django".to_string(), Money(-10_00)));
+    }
+
+    #[test]
+    fn test_budget_status() {
+        use crate::budget::BudgetPeriod;
+        let mut group = setup_group();
+        group.set_budget(Budget { amount: Money(500_00), per: BudgetPeriod::Month, category: None });
+        group.set_budget(Budget { amount: Money(200_00), per: BudgetPeriod::Month, category: Some("food".to_string()) });
+        group.set_budget(Budget { amount: Money(100_00), per: BudgetPeriod::Month, category: Some("food".to_string()) });
+        assert_eq!(group.budgets.len(), 2);
+        let split = |group: &mut Group, amount: Money, category: Option<&str>| group.split(
+            amount, vec!["Alice".to_string()], vec![], "x".to_string(),
+            SplitOptions { category: category.map(String::from), ..SplitOptions::default() }).unwrap();
+        let warnings = |group: &Group, category: Option<&str>| group.budget_status(Local::now(), Some(category))
+            .iter().filter_map(|status| status.warning(&group.name)).collect::<Vec<_>>();
+
+        split(&mut group, Money(70_00), Some("food"));
+        split(&mut group, Money(300_00), None);
+        // last month does not count
+        split(&mut group, Money(400_00), Some("food"));
+        group.log[2].time = Some(Month::of(Local::now()).previous().start());
+        assert!(warnings(&group, Some("food")).is_empty());
+
+        split(&mut group, Money(10_00), Some("food"));
+        // 380.00€ of all splits are only 76%
+        assert_eq!(warnings(&group, Some("food")), vec![
+            "Warning: budget for food this month of group testgroup almost used up: 80.00€ of 100.00€ spent (80%)"]);
+        split(&mut group, Money(150_00), None);
+        // the food budget is not affected by other splits
+        assert_eq!(warnings(&group, None), vec![
+            "Warning: budget this month of group testgroup exceeded: 530.00€ of 500.00€ spent (106%)".to_string()]);
+        assert_eq!(warnings(&group, Some("food")).len(), 2);
+
+        assert!(group.remove_budget(Some("drinks")).is_err());
+        group.remove_budget(None).unwrap();
+        assert_eq!(group.budget_status(Local::now(), None).len(), 1);
+    }
+
+    #[test]
+    fn test_carried() {
+        let mut group = setup_group();
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(10_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+
+        let members: Vec<String> = ["Alice", "Bob", "Django", "Erik"].map(String::from).to_vec();
+        assert!(group.carried("next".to_string(), members.clone()).is_err(), "Charly still has a balance");
+        // Charly is settled now
+        group.log_pay_transaction(Money(25_00), "Charly".to_string(), "Alice".to_string()).unwrap();
+        let next = group.carried("next".to_string(), members).unwrap();
+        assert_eq!(next.currency, group.currency);
+        for member in ["Alice", "Bob", "Django"] {
+            assert_eq!(next.members[member], group.members[member], "{}", member);
+        }
+        assert_eq!((next.members["Alice"], next.members["Bob"], next.members["Django"]), (Money(40_00), Money(-15_00), Money(-25_00)));
+        assert!(!next.members.contains_key("Charly"));
+        assert_eq!(next.members["Erik"], Money::ZERO);
+        assert_eq!(next.members.values().sum::<Money>(), Money::ZERO);
+        // one opening entry per member with a balance
+        assert_eq!(next.log.len(), 3);
+        assert!(next.log.iter().all(|entry| matches!(&entry.command,
+            LoggedCommand::Opening { source, group, .. } if source == "testgroup" && group == "next")));
+        assert_eq!(group.log.len(), 3);
+    }
+
+    #[test]
+    fn test_open() {
+        let members: Vec<String> = ["Alice", "Bob", "Charly"].map(String::from).to_vec();
+        let balances = || vec![("Bob".to_string(), Money(-20_00)), ("Alice".to_string(), Money(30_00))];
+        let mut group = Group::new("flat".to_string(), members.clone(), None).unwrap();
+        let error = group.open(balances(), "sheet.csv", None).unwrap_err();
+        assert_eq!(format!("{:#}", error).split(": ").next().unwrap(),
+                   "The opening balances add up to 10.00€ instead of zero, name the member absorbing the difference with --balance-with");
+        assert!(group.log.is_empty());
+        assert!(group.open(balances(), "sheet.csv", Some("Django")).is_err());
+
+        group.open(balances(), "sheet.csv", Some("Charly")).unwrap();
+        assert_eq!((group.members["Alice"], group.members["Bob"], group.members["Charly"]), (Money(30_00), Money(-20_00), Money(-10_00)));
+        assert_eq!(group.log.len(), 3);
+        assert!(matches!(&group.log[0].command, LoggedCommand::Opening { member, source, .. }
+            if member == "Alice" && source == "sheet.csv"));
+        // the opening entries are the history the balances are checked against
+        assert!(group.problems().is_empty());
+        assert!(group.open(vec![], "again.csv", None).is_err());
+
+        // the absorbing member may be in the file already
+        let mut group = Group::new("flat".to_string(), members, None).unwrap();
+        group.open(balances(), "sheet.csv", Some("Bob")).unwrap();
+        assert_eq!((group.members["Alice"], group.members["Bob"]), (Money(30_00), Money(-30_00)));
+        assert_eq!(group.log.len(), 2);
+    }
+
+    #[test]
+    fn test_read_balances() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("balances.csv");
+        std::fs::write(&path, "member,balance\n# from the sheet\nalice, 12.50\nbob,-12.50\n").unwrap();
+        assert_eq!(read_balances(&path).unwrap(), vec![("alice".to_string(), Money(12_50)), ("bob".to_string(), Money(-12_50))]);
+        std::fs::write(&path, "alice,12.50\nbob,twelve\n").unwrap();
+        let error = format!("{:#}", read_balances(&path).unwrap_err());
+        assert!(error.contains("balances.csv:2: balance must be a number"), "{}", error);
+    }
+
+    #[test]
+    fn test_split_by_weights() {
+        assert_eq!(split_by_weights(Money(900_00), &[3, 2]), vec![Money(540_00), Money(360_00)]);
+        // 100 / 3 each, the leftover cent goes to the first of the equal remainders
+        assert_eq!(split_by_weights(Money(100), &[1, 1, 1]), [34, 33, 33].map(Money));
+        // exact shares 14.2857.., 28.5714.., 57.1428..: the largest remainder is the second
+        assert_eq!(split_by_weights(Money(100), &[1, 2, 4]), [14, 29, 57].map(Money));
+        assert_eq!(split_by_weights(Money(1), &[1, 1_000_000, 0]), [0, 1, 0].map(Money));
+        assert_eq!(split_by_weights(Money(-100), &[1, 2]), [-33, -67].map(Money));
+        assert_eq!(split_by_weights(Money(1234_57), &[3187, 2411, 1]).iter().sum::<Money>(), Money(1234_57));
+    }
+
+    #[test]
+    fn test_split_weighted_shares() {
+        let mut group = setup_group();
+        // what Alice, Bob, Charly and Django take of a split Django paid
+        let taken = |group: &Group, amount: Money, to: &[&str], balance_rest: bool, ratio: Option<&str>| {
+            let to = to.iter().map(|target| target.to_string()).collect();
+            let weights = ratio.map(|ratio| &group.ratios[ratio]);
+            let (change, _, to) = split_into_transaction(amount, &group.roster(), vec!["Django".to_string()], to, &[],
+                                                         balance_rest, weights).unwrap();
+            let taken = ["Alice", "Bob", "Charly", "Django"].map(|name| {
+                if name == "Django" { amount - change[name] } else { -change[name] }
+            });
+            (taken.to_vec(), to)
+        };
+        let money = |amounts: [i64; 4]| amounts.map(Money).to_vec();
+        // shares only, the leftover cents going to the largest remainders
+        assert_eq!(taken(&group, Money(100_00), &["Bob:2x"], false, None).0, money([20_00, 40_00, 20_00, 20_00]));
+        let (shares, to) = taken(&group, Money(10_00), &["Bob:2x", "Charly:3x"], false, None);
+        assert_eq!(shares, money([1_43, 2_86, 4_28, 1_43]));
+        assert_eq!((to[0].amount, to[0].weight, to[0].raw(Currency::EUR)), (Some(Money(2_86)), Some(2), "Bob:2x".to_string()));
+        // with an absolute amount, a percentage, or both
+        let expected = money([10_00, 45_00, 22_50, 22_50]);
+        assert_eq!(taken(&group, Money(100_00), &["Alice:10", "Bob:2x"], false, None).0, expected);
+        assert_eq!(taken(&group, Money(100_00), &["Alice:10%", "Bob:2x"], false, None).0, expected);
+        assert_eq!(taken(&group, Money(100_00), &["Alice:10", "Charly:20%", "Bob:3x"], false, None).0,
+                   money([10_00, 52_50, 20_00, 17_50]));
+        // a percentage of a share counts the shares of the others in
+        assert_eq!(taken(&group, Money(100_00), &["Alice:50%share", "Bob:2x"], false, None).0, expected);
+        // with --balance-rest the members with an amount take a single share on top
+        assert_eq!(taken(&group, Money(100_00), &["Alice:10", "Bob:2x"], true, None).0,
+                   money([28_00, 36_00, 18_00, 18_00]));
+        // by a ratio the shares multiply the weights
+        group.set_ratio("income", &["Alice=3".to_string(), "Bob=2".to_string(), "Charly=2".to_string()]).unwrap();
+        assert_eq!(taken(&group, Money(90_00), &["Charly:2x"], false, Some("income")).0, money([30_00, 20_00, 40_00, 0]));
+
+        let fails = |from: &[&str], to: &[&str]| {
+            let from = from.iter().map(|target| target.to_string()).collect();
+            let to = to.iter().map(|target| target.to_string()).collect();
+            format!("{:#}", split_into_transaction(Money(10_00), &group.roster(), from, to, &[], false, None).err().unwrap())
+        };
+        assert!(fails(&["Alice:2x"], &[]).starts_with("Shares of the rest like <name>:2x only work for --to"));
+        assert!(fails(&["Alice"], &["Bob:2x", "Charly:rest"]).starts_with("A rest target cannot be combined"));
+        let err = split_into_transaction(Money(10_00), &group.roster(), vec!["Alice".to_string()],
+                                         vec![format!("Bob:{}x", u64::MAX)], &[], false, Some(&group.ratios["income"]));
+        assert!(format!("{:#}", err.err().unwrap()).starts_with("The shares given like <name>:2x are too large"));
+
+        group.split(Money(30_00), vec!["Alice".to_string()], vec!["Bob:2x".to_string()], "pizza".to_string(),
+                    SplitOptions::default()).unwrap();
+        assert_eq!(group.members["Bob"], Money(-12_00));
+        assert!(group.log[0].to_string(0, group.currency).contains("\nBob: 2x (=12.00€)"), "{}",
+                group.log[0].to_string(0, group.currency));
+    }
+
+    #[test]
+    fn test_split_except() {
+        let mut group = setup_group();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        // Django wasn't there
+        group.split(Money(90_00), names(&["Alice"]), vec![], "dinner".to_string(),
+                    SplitOptions { except: names(&["Django"]), ..SplitOptions::default() }).unwrap();
+        assert_eq!([group.members["Alice"], group.members["Bob"], group.members["Charly"], group.members["Django"]],
+                   [Money(60_00), Money(-30_00), Money(-30_00), Money::ZERO]);
+        assert_eq!(group.log[0].change().get("Django"), Some(&Money::ZERO));
+        assert!(group.log[0].to_string(0, group.currency).ends_with(", without Django"), "{}",
+                group.log[0].to_string(0, group.currency));
+        // they stay left out when someone is included afterwards
+        group.add(names(&["Egon"])).unwrap();
+        group.include_in_split(0, &names(&["Egon"])).unwrap();
+        assert_eq!([group.members["Bob"], group.members["Django"], group.members["Egon"]],
+                   [Money(-22_50), Money::ZERO, Money(-22_50)]);
+        let err = group.include_in_split(0, &names(&["Django"])).err().unwrap();
+        assert_eq!(err.to_string(), "Django was left out of entry [0] with --except");
+
+        let fails = |from: &[&str], to: &[&str], except: &[&str]| {
+            split_into_transaction(Money(10_00), &group.roster(), names(from), names(to), &names(except), false, None)
+                .err().unwrap()
+        };
+        let err = fails(&["Alice"], &["Bob:4"], &["Bob"]);
+        assert_eq!(err.downcast_ref(), Some(SplitterError::LogicError).as_ref());
+        assert_eq!(err.to_string(), "Bob is left out with --except, but named with --from or --to");
+        let err = fails(&["Alice"], &[], &["Alice"]);
+        assert_eq!(err.to_string(), "Alice is left out with --except, but named with --from or --to");
+        assert_eq!(fails(&["Alice"], &[], &["Theseus"]).downcast_ref(), Some(SplitterError::MemberNotFound).as_ref());
+        let err = fails(&["Alice"], &[], &["Alice", "Bob", "Charly", "Django", "Egon"]);
+        assert_eq!(err.to_string(), "Everybody is left out with --except, nobody is left to share the split");
+        // the payer alone may be left to share
+        let (change, _, _) = split_into_transaction(Money(10_00), &group.roster(), names(&["Alice"]), vec![],
+                                                    &names(&["Bob", "Charly", "Django", "Egon"]), false, None).unwrap();
+        assert!(change.values().all(|change| change.is_zero()));
+    }
+
+    #[test]
+    fn test_normalize_member_names() {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let group = Group::new("trip".to_string(), names(&["alice ", "\u{a0}bob", "car\u{200B}ol"]), None).unwrap();
+        assert_eq!(group.member_names(), vec!["alice", "bob", "carol"]);
+        let err = Group::new("trip".to_string(), names(&["bob", "bob\u{a0}"]), None).err().unwrap();
+        assert_eq!(err.to_string(), "given more than once: bob (given as \"bob\\u{a0}\")");
+        let err = Group::new("trip".to_string(), names(&["alice", "\u{a0} "]), None).err().unwrap();
+        assert!(format!("{:#}", err).starts_with("names not allowed for members: \"\\u{a0} \" (empty once normalized)"));
+
+        let mut group = group;
+        group.add(names(&["dave\u{a0}"])).unwrap();
+        assert!(group.members.contains_key("dave"));
+        let err = group.add(names(&["alice\u{feff}"])).err().unwrap();
+        assert!(format!("{:#}", err).contains("alice (given as "), "{:#}", err);
+        group.log_pay_transaction(Money(5_00), "alice\u{a0}".to_string(), " bob ".to_string()).unwrap();
+        assert_eq!((group.members["alice"], group.members["bob"]), (Money(5_00), Money(-5_00)));
+        group.rename_member("dave", "David ".to_string()).unwrap();
+        assert!(group.members.contains_key("David"));
+
+        // names stored by older versions, one of them taken once normalized
+        let yaml = serde_yaml::to_string(&group).unwrap().replace("bob", "bob\u{a0}").replace("David", "'carol '");
+        let mut stored: Group = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(stored.member_names(), vec!["alice", "bob\u{a0}", "carol", "carol "]);
+        assert_eq!(stored.normalize_member_names(), vec!["renamed member \"bob\\u{a0}\" of group trip to \"bob\""]);
+        assert_eq!(stored.member_names(), vec!["alice", "bob", "carol", "carol "]);
+        assert_eq!(stored.unnormalized_members(),
+                   vec!["\"carol \" is \"carol\" once normalized, but carol already is a member of trip"]);
+        assert!(stored.problems().is_empty(), "{:?}", stored.problems());
+        let LoggedCommand::Pay { to, .. } = &stored.log[0].command else { unreachable!() };
+        assert_eq!(to, "bob");
+        assert!(stored.normalize_member_names().is_empty(), "only the collision is left");
+    }
+
+    #[test]
+    fn test_quote() {
+        let mut group = setup_group();
+        let shares = |quote: SplitPreview| quote.members.iter().map(|row| row.share).collect::<Vec<_>>();
+        assert_eq!(shares(group.quote(Money(10_00), &[], None).unwrap()), vec![Money(2_50); 4]);
+        let among = ["Bob".to_string(), "Django".to_string()];
+        let quote = group.quote(Money(10_01), &among, None).unwrap();
+        assert_eq!(quote.members[1], PreviewRow { name: "Bob".to_string(), paid: Money::ZERO, share: Money(5_01),
+                                                  change: Money(-5_01) });
+        assert_eq!(shares(quote), vec![Money::ZERO, Money(5_01), Money::ZERO, Money(5_00)]);
+        group.set_ratio("income", &["Alice=3".to_string(), "Bob=2".to_string(), "Charly=1".to_string()]).unwrap();
+        assert_eq!(shares(group.quote(Money(60_00), &[], Some("income")).unwrap()),
+                   vec![Money(30_00), Money(20_00), Money(10_00), Money::ZERO]);
+        assert_eq!(shares(group.quote(Money(60_00), &among[..1], Some("income")).unwrap()),
+                   vec![Money::ZERO, Money(60_00), Money::ZERO, Money::ZERO]);
+
+        let err = group.quote(Money(10_00), &["Erik".to_string()], None).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::MemberNotFound).as_ref());
+        assert!(group.quote(Money(10_00), &among[1..], Some("income")).is_err(), "Django has no weight");
+        assert!(group.quote(Money(10_00), &[], Some("rent")).is_err());
+        assert!(group.log.is_empty());
+    }
+
+    #[test]
+    fn test_split_by_ratio() {
+        let mut group = setup_group();
+        assert!(group.set_ratio("income", &["Alice=3".to_string(), "Bob=2".to_string(), "Erik=1".to_string()]).is_err());
+        assert!(group.set_ratio("income", &["Alice=3.5".to_string()]).is_err());
+        group.set_ratio("income", &["Alice=3".to_string(), "Bob=2".to_string(), "Charly=2".to_string()]).unwrap();
+        assert_eq!(group.missing_from_ratio("income").unwrap(), vec!["Django"]);
+        assert!(group.missing_from_ratio("rent").is_err());
+
+        group.split(Money(100_00), vec!["Alice".to_string()], vec![], "rent".to_string(),
+                    SplitOptions { ratio: Some("income".to_string()), ..SplitOptions::default() }).unwrap();
+        // 42.857.. 28.571.. 28.571..: the leftover cent goes to Bob, the first of the equal remainders
+        assert_eq!(group.members["Alice"], Money(100_00) - Money(42_86));
+        assert_eq!(group.members["Bob"], Money(-28_57));
+        assert_eq!(group.members["Charly"], Money(-28_57));
+        assert_eq!(group.members["Django"], Money::ZERO);
+        let LoggedCommand::Split { ratio, .. } = &group.log[0].command else { unreachable!() };
+        assert_eq!(ratio.as_deref(), Some("income"));
+
+        // --to amounts are taken first, the rest is split by the ratio among the others
+        let (change, _, _) = split_into_transaction(
+            Money(10_00), &group.roster(), vec!["Django".to_string()], vec!["Alice:4".to_string()], &[], false,
+            Some(&group.ratios["income"])).unwrap();
+        assert_eq!(change["Alice"], Money(-4_00));
+        assert_eq!(change["Bob"], Money(-3_00));
+        assert_eq!(change["Charly"], Money(-3_00));
+        assert_eq!(change["Django"], Money(10_00));
+        // nobody left with a weight
+        assert!(split_into_transaction(
+            Money(10_00), &group.roster(), vec!["Django".to_string()], vec!["Bob:1".to_string(), "Charly:1".to_string(),
+                                                                  "Alice:1".to_string()], &[], false,
+            Some(&group.ratios["income"])).is_err());
+    }
+
     #[test]
     fn test_parse_targets() {
         let from_entries = vec!["alice:12".to_string(), "bob:13".to_string(), "charly:10%".to_string()];
 
-        let parsed = Target::parse_multiple(from_entries, 100_00);
+        let parsed = Target::parse_multiple(from_entries, Money(100_00), Currency::EUR);
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
         assert_eq!(parsed.0.len(), 3);
-        assert!(parsed.0.contains(&Target { member: "alice".to_string(), amount: Some(12_00) }), "Alice missing");
-        assert!(parsed.0.contains(&Target { member: "bob".to_string(), amount: Some(13_00) }), "Bob missing");
-        assert!(parsed.0.contains(&Target { member: "charly".to_string(), amount: Some(10_00) }), "Charly missing");
-        assert_eq!(parsed.1, 35_00, "Summed amount is not correct");
+        assert!(parsed.0.contains(&Target { member: "alice".to_string(), amount: Some(Money(12_00)), rest: false, share: None, expression: None, weight: None }), "Alice missing");
+        assert!(parsed.0.contains(&Target { member: "bob".to_string(), amount: Some(Money(13_00)), rest: false, share: None, expression: None, weight: None }), "Bob missing");
+        assert!(parsed.0.contains(&Target { member: "charly".to_string(), amount: Some(Money(10_00)), rest: false, share: None,
+                                          expression: Some("10%".to_string()), weight: None }), "Charly missing");
+        assert_eq!(parsed.1, Money(35_00), "Summed amount is not correct");
         assert_eq!(parsed.2, 0, "No Members had unspecified amounts");
 
         // two wildcard givers
         let from_entries = vec!["alice:12".to_string(), "bob".to_string(), "charly".to_string()];
 
-        let parsed = Target::parse_multiple(from_entries, 100_00);
+        let parsed = Target::parse_multiple(from_entries, Money(100_00), Currency::EUR);
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
         assert_eq!(parsed.0.len(), 3);
-        assert!(parsed.0.contains(&Target { member: "alice".to_string(), amount: Some(12_00) }), "Alice missing");
-        assert!(parsed.0.contains(&Target { member: "bob".to_string(), amount: None }), "Bob missing");
-        assert!(parsed.0.contains(&Target { member: "charly".to_string(), amount: None }), "Charly missing");
-        assert_eq!(parsed.1, 12_00, "Summed amount is not correct");
+        assert!(parsed.0.contains(&Target { member: "alice".to_string(), amount: Some(Money(12_00)), rest: false, share: None, expression: None, weight: None }), "Alice missing");
+        assert!(parsed.0.contains(&Target { member: "bob".to_string(), amount: None, rest: false, share: None, expression: None, weight: None }), "Bob missing");
+        assert!(parsed.0.contains(&Target { member: "charly".to_string(), amount: None, rest: false, share: None, expression: None, weight: None }), "Charly missing");
+        assert_eq!(parsed.1, Money(12_00), "Summed amount is not correct");
         assert_eq!(parsed.2, 2, "No Members had unspecified amounts");
 
         // froms > 100%
         let from_entries = vec!["alice:90".to_string(), "bob:20".to_string(), "charly:10%".to_string()];
 
-        let parsed = Target::parse_multiple(from_entries, 100_00);
+        let parsed = Target::parse_multiple(from_entries, Money(100_00), Currency::EUR);
         assert!(parsed.is_err(), "Expected error showing incorrect amounts");
     }
 
+    #[test]
+    fn test_parse_rest_target() {
+        let entries = vec!["alice:12".to_string(), "bob:rest".to_string(), "charly:10%".to_string()];
+        let (targets, summed, wildcards) = Target::parse_multiple(entries, Money(100_00), Currency::EUR).unwrap();
+        assert!(targets.contains(&Target { member: "bob".to_string(), amount: Some(Money(78_00)), rest: true, share: None, expression: None, weight: None }));
+        assert_eq!(summed, Money(100_00));
+        assert_eq!(wildcards, 0);
+
+        let entries = vec!["alice:100".to_string(), "bob:rest".to_string()];
+        let (targets, _, _) = Target::parse_multiple(entries, Money(100_00), Currency::EUR).unwrap();
+        assert_eq!(targets[1].amount, Some(Money::ZERO));
+
+        let two_rests = vec!["alice:rest".to_string(), "bob:rest".to_string()];
+        assert!(Target::parse_multiple(two_rests, Money(100_00), Currency::EUR).is_err());
+        let rest_and_wildcard = vec!["alice:rest".to_string(), "bob".to_string()];
+        assert!(Target::parse_multiple(rest_and_wildcard, Money(100_00), Currency::EUR).is_err());
+    }
+
+    #[test]
+    fn test_rest_from() {
+        let group = setup_group();
+        let (change, from, _) = split_into_transaction(
+            Money(100), &group.roster(), vec!["Alice:0.3".to_string(), "Bob:rest".to_string()], vec![], &[], false, None).unwrap();
+        assert_eq!(from[1], Target { member: "Bob".to_string(), amount: Some(Money(70)), rest: true, share: None, expression: None, weight: None });
+        assert_eq!(change["Alice"], Money(5));
+        assert_eq!(change["Bob"], Money(45));
+        assert_eq!(change["Charly"], Money(-25));
+        assert_eq!(change["Django"], Money(-25));
+
+        // explicit amounts without a catch-all must add up to the total
+        assert!(split_into_transaction(Money(100), &group.roster(), vec!["Alice:0.3".to_string()], vec![], &[], false, None).is_err());
+    }
+
+    #[test]
+    fn test_rest_to() {
+        let group = setup_group();
+        let (change, _, to) = split_into_transaction(
+            Money(100), &group.roster(), vec!["Alice".to_string()],
+            vec!["Bob:25%".to_string(), "Charly:rest".to_string()], &[], false, None).unwrap();
+        assert_eq!(to[1], Target { member: "Charly".to_string(), amount: Some(Money(75)), rest: true, share: None, expression: None, weight: None });
+        assert_eq!(change["Alice"], Money(100));
+        assert_eq!(change["Bob"], Money(-25));
+        assert_eq!(change["Charly"], Money(-75));
+        assert_eq!(change.get("Django").copied().unwrap_or(Money::ZERO), Money::ZERO);
+
+        // nobody is left to take what the explicit --to amounts leave over
+        let everybody = ["Alice:10", "Bob:10", "Charly:10", "Django:10"].map(String::from).to_vec();
+        assert!(split_into_transaction(Money(100), &group.roster(), vec!["Alice".to_string()], everybody, &[], false, None).is_err());
+    }
+
+    #[test]
+    fn test_negative_rest() {
+        let group = setup_group();
+        let error = |from: &[&str], to: &[&str]| split_into_transaction(
+            Money(50_00), &group.roster(), from.iter().map(|el| el.to_string()).collect(),
+            to.iter().map(|el| el.to_string()).collect(), &[], false, None).unwrap_err();
+        let message = |from: &[&str], to: &[&str]| error(from, to).to_string();
+
+        // the payers without an amount would pay less than nothing
+        assert_eq!(message(&["Alice:60", "Bob"], &[]),
+                   "The amounts of --from add up to 60.00€, 10.00€ more than the total of 50.00€, so Bob would pay \
+                    -10.00€. Lower the amounts or raise the total; money given back is a refund, record it with `split --refund`");
+        assert!(message(&["Alice:30", "Alice:30", "Bob", "Charly"], &[])
+            .contains("so Bob, Charly would pay -10.00€ between them."));
+        assert!(message(&["Alice:60", "Bob:rest"], &[]).contains("so Bob:rest would pay -10.00€."));
+        assert!(error(&["Alice:60", "Bob"], &[]).downcast_ref::<SplitterError>()
+            .is_some_and(|error| matches!(error, SplitterError::LogicError)));
+
+        // the members sharing the rest would be paid for taking part
+        assert!(message(&["Alice"], &["Bob:55"])
+            .contains("The amounts of --to add up to 55.00€, 5.00€ more than the total of 50.00€, so Alice, Charly, \
+                       Django would take -5.00€ between them, being paid for taking part."));
+        assert!(message(&["Alice"], &["Bob:40", "Charly:20", "Django:rest"]).contains("so Django:rest would take -10.00€,"));
+        // a %share taking part in the rest does not make it any larger
+        assert!(message(&["Alice"], &["Bob:55", "Charly:50%share"]).contains("5.00€ more than the total"));
+    }
+
+    #[test]
+    fn test_share_to() {
+        let mut group = setup_group();
+        let (change, _, to) = split_into_transaction(
+            Money(40_00), &group.roster(), vec!["Alice".to_string()], vec!["Bob:50%share".to_string()], &[], false, None).unwrap();
+        assert_eq!(to[0], Target { member: "Bob".to_string(), amount: Some(Money(5_00)), rest: false,
+                                   share: Some("50".to_string()), expression: None, weight: None });
+        assert_eq!(change["Alice"], Money(40_00) - Money(11_67));
+        assert_eq!(change["Bob"], Money(-5_00));
+        assert_eq!(change["Charly"], Money(-11_67));
+        assert_eq!(change["Django"], Money(-11_66));
+
+        // next to a fixed amount the share is that of the rest it leaves
+        let (change, _, _) = split_into_transaction(
+            Money(40_00), &group.roster(), vec!["Alice".to_string()], vec!["Bob:25%share".to_string(), "Charly:4".to_string()], &[],
+            false, None).unwrap();
+        assert_eq!((change["Bob"], change["Charly"], change["Django"]), (Money(-3_00), Money(-4_00), Money(-16_50)));
+
+        for invalid in [vec!["Bob:150%share".to_string(), "Charly:200%share".to_string(), "Django:200%share".to_string()],
+                        vec!["Bob:50%share".to_string(), "Charly:rest".to_string()]] {
+            assert!(split_into_transaction(Money(40_00), &group.roster(), vec!["Alice".to_string()], invalid, &[], false, None).is_err());
+        }
+        assert!(split_into_transaction(Money(40_00), &group.roster(), vec!["Alice:50%share".to_string()], vec![], &[], false, None).is_err());
+
+        // the entry keeps the expression, so including someone later recomputes the share
+        group.enable_integrity();
+        group.split(Money(40_00), vec!["Alice".to_string()], vec!["Bob:50%share".to_string()], "dinner".to_string(),
+                    SplitOptions::default()).unwrap();
+        assert!(group.log[0].to_string(0, Currency::EUR).contains("Bob: 50%share (=5.00€)"));
+        group.members.insert("Emil".to_string(), Money::ZERO);
+        group.include_in_split(0, &["Emil".to_string()]).unwrap();
+        assert_eq!(group.members["Bob"], Money(-4_00));
+        assert_eq!(group.members["Emil"], Money(-9_00));
+        assert!(group.verify().is_ok());
+        let LoggedCommand::Split { to, .. } = &mut group.log[0].command else { unreachable!() };
+        to[0].share = Some("60".to_string());
+        assert_eq!(first_broken_link(&group.log), Some(0));
+    }
+
     #[test]
     fn test_simple_split_one_giver() {
         let group = setup_group();
 
         let transaction_bins = split_into_transaction(
-            120, &group, vec!["Alice".to_string()], vec![], false);
+            Money(120), &group.roster(), vec!["Alice".to_string()], vec![], &[], false, None);
         // alle - 120/4 = -30, Alice +120 | A90, B-30,c-30, D-30
         assert!(transaction_bins.is_ok());
         let (transaction_bins, _, _) = transaction_bins.unwrap();
@@ -533,10 +2684,10 @@ mod group_tests {
         assert!(transaction_bins.contains_key("Bob"));
         assert!(transaction_bins.contains_key("Charly"));
         assert!(transaction_bins.contains_key("Django"));
-        assert_eq!(transaction_bins["Alice"], 90);
-        assert_eq!(transaction_bins["Bob"], -30);
-        assert_eq!(transaction_bins["Charly"], -30);
-        assert_eq!(transaction_bins["Charly"], -30);
+        assert_eq!(transaction_bins["Alice"], Money(90));
+        assert_eq!(transaction_bins["Bob"], Money(-30));
+        assert_eq!(transaction_bins["Charly"], Money(-30));
+        assert_eq!(transaction_bins["Charly"], Money(-30));
     }
 
     #[test]
@@ -544,8 +2695,8 @@ mod group_tests {
         let group = setup_group();
 
         let transaction_bins = split_into_transaction(
-            120, &group,
-            vec!["Alice".to_string(), "Bob".to_string()], vec![], false);
+            Money(120), &group.roster(),
+            vec!["Alice".to_string(), "Bob".to_string()], vec![], &[], false, None);
         // alle - 120/4 = -30, Alice +60, Bob +60 | A30, B30, C-30, D-30
         assert!(transaction_bins.is_ok());
         let (transaction_bins, _, _) = transaction_bins.unwrap();
@@ -554,10 +2705,71 @@ mod group_tests {
         assert!(transaction_bins.contains_key("Bob"));
         assert!(transaction_bins.contains_key("Charly"));
         assert!(transaction_bins.contains_key("Django"));
-        assert_eq!(transaction_bins["Alice"], 30);
-        assert_eq!(transaction_bins["Bob"], 30);
-        assert_eq!(transaction_bins["Charly"], -30);
-        assert_eq!(transaction_bins["Django"], -30);
+        assert_eq!(transaction_bins["Alice"], Money(30));
+        assert_eq!(transaction_bins["Bob"], Money(30));
+        assert_eq!(transaction_bins["Charly"], Money(-30));
+        assert_eq!(transaction_bins["Django"], Money(-30));
+    }
+
+    #[test]
+    fn test_refund_one_receiver() {
+        let group = setup_group();
+        let (transaction_bins, from, _) = split_into_transaction(
+            Money(-120), &group.roster(), vec!["Alice".to_string()], vec![], &[], false, None).unwrap();
+        // the mirror of test_simple_split_one_giver: alle + 120/4 = 30, Alice -120 | A-90, B30, C30, D30
+        assert_eq!(transaction_bins.len(), 4);
+        assert_eq!(transaction_bins["Alice"], Money(-90));
+        assert_eq!(transaction_bins["Bob"], Money(30));
+        assert_eq!(transaction_bins["Charly"], Money(30));
+        assert_eq!(transaction_bins["Django"], Money(30));
+        assert_eq!(from[0].amount, None);
+    }
+
+    #[test]
+    fn test_refund_multiple_receivers() {
+        let group = setup_group();
+        let (transaction_bins, from, _) = split_into_transaction(
+            Money(-120), &group.roster(), vec!["Alice:0.8".to_string(), "Bob:rest".to_string()], vec![], &[], false, None).unwrap();
+        // alle + 30, Alice -80, Bob -40 | A-50, B-10, C30, D30
+        assert_eq!(transaction_bins["Alice"], Money(-50));
+        assert_eq!(transaction_bins["Bob"], Money(-10));
+        assert_eq!(transaction_bins["Charly"], Money(30));
+        assert_eq!(transaction_bins["Django"], Money(30));
+        assert_eq!(from[0].amount, Some(Money(-80)));
+        assert_eq!(from[1].amount, Some(Money(-40)));
+        assert_eq!(from[1].raw(Currency::EUR), "Bob:rest");
+        assert_eq!(from[0].raw(Currency::EUR), "Alice:0.80");
+
+        // the amounts are checked like those of a split
+        assert!(split_into_transaction(Money(-120), &group.roster(), vec!["Alice:1.3".to_string(), "Bob".to_string()],
+                                       vec![], &[], false, None).is_err());
+    }
+
+    #[test]
+    fn test_refund_entry() {
+        let mut group = setup_group();
+        group.split(Money(120_00), vec!["Alice".to_string()], vec![], "deposit".to_string(),
+                    SplitOptions::default()).unwrap();
+        group.split(Money(-90_00), vec!["Alice".to_string()], vec![], "deposit back".to_string(),
+                    SplitOptions::default()).unwrap();
+        assert_eq!(group.members["Alice"], Money(22_50));
+        assert_eq!(group.members["Bob"], Money(-7_50));
+        assert_eq!(group.totals("Alice").paid, Money(30_00));
+        assert_eq!(group.totals("Alice").consumed, Money(7_50));
+        assert_eq!(group.totals("Bob").consumed, Money(7_50));
+        assert_eq!(group.spend_share(0), Money(7_50));
+        assert_eq!(group.log[1].shares()[0], ("Alice".to_string(), Money(-22_50)));
+        assert!(group.log[1].to_string(1, group.currency).ends_with("refund: in group testgroup `deposit back \
+                                                                  90.00€ returned to\nAlice: *\n\n"),
+                "{}", group.log[1].to_string(1, group.currency));
+        assert_eq!(group.log[1].command.amount(), Money(90_00));
+
+        // rebalancing mirrors the split as well: Egbert gets his share of the refund
+        group.add(vec!["Egbert".to_string()]).unwrap();
+        group.include_in_split(1, &["Egbert".to_string()]).unwrap();
+        assert_eq!(group.members["Egbert"], Money(18_00));
+        assert_eq!(group.members["Alice"], Money(18_00));
+        assert_eq!(group.members["Bob"], Money(-12_00));
     }
 
     #[test]
@@ -565,9 +2777,9 @@ mod group_tests {
         let group = setup_group();
 
         let transaction_bins = split_into_transaction(
-            130, &group,
+            Money(130), &group.roster(),
             vec!["Bob".to_string()],
-            vec!["Alice:0,1".to_string()], false);
+            vec!["Alice:0,1".to_string()], &[], false, None);
         // alice - 10 -> A-10
         // total-10 = 120
         // BCD - 120/3 = -40
@@ -580,10 +2792,10 @@ mod group_tests {
         assert!(transaction_bins.contains_key("Bob"));
         assert!(transaction_bins.contains_key("Charly"));
         assert!(transaction_bins.contains_key("Django"));
-        assert_eq!(transaction_bins["Alice"], -10);
-        assert_eq!(transaction_bins["Bob"], 90);
-        assert_eq!(transaction_bins["Charly"], -40);
-        assert_eq!(transaction_bins["Django"], -40);
+        assert_eq!(transaction_bins["Alice"], Money(-10));
+        assert_eq!(transaction_bins["Bob"], Money(90));
+        assert_eq!(transaction_bins["Charly"], Money(-40));
+        assert_eq!(transaction_bins["Django"], Money(-40));
     }
 
     #[test]
@@ -591,9 +2803,9 @@ mod group_tests {
         let group = setup_group();
 
         let transaction_bins = split_into_transaction(
-            140, &group,
+            Money(140), &group.roster(),
             vec!["Bob".to_string()],
-            vec!["Alice:0,1".to_string(), "Charly:0.1".to_string()], false);
+            vec!["Alice:0,1".to_string(), "Charly:0.1".to_string()], &[], false, None);
         // alice - 10 -> A-10
         // charly -10 -> C-10
         // total-10-10 = 120
@@ -607,19 +2819,19 @@ mod group_tests {
         assert!(transaction_bins.contains_key("Bob"));
         assert!(transaction_bins.contains_key("Charly"));
         assert!(transaction_bins.contains_key("Django"));
-        assert_eq!(transaction_bins["Alice"], -10);
-        assert_eq!(transaction_bins["Bob"], 80);
-        assert_eq!(transaction_bins["Charly"], -10);
-        assert_eq!(transaction_bins["Django"], -60);
+        assert_eq!(transaction_bins["Alice"], Money(-10));
+        assert_eq!(transaction_bins["Bob"], Money(80));
+        assert_eq!(transaction_bins["Charly"], Money(-10));
+        assert_eq!(transaction_bins["Django"], Money(-60));
     }
 
     #[test]
     fn test_balance_rest() {
         let group = setup_group();
         let transaction_bins = split_into_transaction(
-            140, &group,
+            Money(140), &group.roster(),
             vec!["Bob".to_string()],
-            vec!["Alice:0,1".to_string(), "Charly:0.1".to_string()], true);
+            vec!["Alice:0,1".to_string(), "Charly:0.1".to_string()], &[], true, None);
         // alice - 10 -> A-10
         // charly -10 -> C-10
         // total-10-10 = 120
@@ -633,10 +2845,96 @@ mod group_tests {
         assert!(transaction_bins.contains_key("Bob"));
         assert!(transaction_bins.contains_key("Charly"));
         assert!(transaction_bins.contains_key("Django"));
-        assert_eq!(transaction_bins["Alice"], -40);
-        assert_eq!(transaction_bins["Bob"], 110);
-        assert_eq!(transaction_bins["Charly"], -40);
-        assert_eq!(transaction_bins["Django"], -30);
+        assert_eq!(transaction_bins["Alice"], Money(-40));
+        assert_eq!(transaction_bins["Bob"], Money(110));
+        assert_eq!(transaction_bins["Charly"], Money(-40));
+        assert_eq!(transaction_bins["Django"], Money(-30));
+    }
+
+    /// applies `count` pseudo-random splits and payments, deterministic for a given seed
+    fn random_entries(group: &mut Group, seed: u64, count: usize) {
+        let members = ["Alice", "Bob", "Charly", "Django"];
+        let mut state = seed;
+        let mut next = |bound: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+        for i in 0..count {
+            let amount = Money(1 + next(100_00) as i64);
+            let from = members[next(4) as usize].to_string();
+            if next(3) == 0 {
+                let to = members.iter().find(|m| **m != from).unwrap().to_string();
+                group.log_pay_transaction(amount, from, to).unwrap();
+            } else {
+                group.split(amount, vec![from], vec![], format!("split {seed} {i}"), SplitOptions::default()).unwrap();
+            }
+        }
+    }
+
+    fn sorted_members(group: &Group) -> Vec<(String, Money)> {
+        group.member_overview().into_iter().map(|m| (m.name, m.balance)).collect()
+    }
+
+    #[test]
+    fn test_merge_both_orders() {
+        for seed in 0..20 {
+            let mut base = setup_group();
+            random_entries(&mut base, seed, 3);
+            let (mut ours, mut theirs) = (base.clone(), base.clone());
+            random_entries(&mut ours, seed + 100, 1 + seed as usize % 4);
+            random_entries(&mut theirs, seed + 200, 1 + seed as usize % 3);
+            theirs.add(vec!["Emil".to_string()]).unwrap();
+
+            let mut ours_theirs = ours.clone();
+            assert_eq!(ours_theirs.merge(&theirs).unwrap(),
+                       (1 + seed as usize % 3, 1 + seed as usize % 4));
+            let mut theirs_ours = theirs.clone();
+            theirs_ours.merge(&ours).unwrap();
+
+            assert_eq!(sorted_members(&ours_theirs), sorted_members(&theirs_ours), "seed {seed}");
+            assert_eq!(ours_theirs.log.iter().map(|e| &e.id).collect::<Vec<_>>(),
+                       theirs_ours.log.iter().map(|e| &e.id).collect::<Vec<_>>(), "seed {seed}");
+            assert_eq!(ours_theirs.members.values().sum::<Money>(), Money::ZERO);
+            // merging again changes nothing
+            let before = sorted_members(&ours_theirs);
+            assert_eq!(ours_theirs.merge(&theirs_ours).unwrap(), (0, 0));
+            assert_eq!(sorted_members(&ours_theirs), before);
+        }
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let mut ours = setup_group();
+        ours.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        let mut theirs = ours.clone();
+        if let LoggedCommand::Pay { amount, .. } = &mut theirs.log[0].command {
+            *amount = Money(50_00);
+        }
+        let id = ours.log[0].id.clone();
+        assert_eq!(ours.conflicting_entries(&theirs), vec![id.clone()]);
+        let err = ours.merge(&theirs).unwrap_err();
+        assert!(format!("{err:#}").contains(id.as_str()));
+        assert_eq!(ours.log.len(), 1);
+    }
+
+    #[test]
+    fn test_integrity() {
+        let mut group = setup_group();
+        group.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        assert!(group.verify().is_err());
+        assert!(group.enable_integrity());
+        for amount in [1_00, 2_00, 3_00].map(Money) {
+            group.log_pay_transaction(amount, "Charly".to_string(), "Django".to_string()).unwrap();
+        }
+        assert!(group.verify().unwrap().contains("intact (4 entries)"));
+
+        group.remove_log(Some(1)).unwrap();
+        let report = group.verify().unwrap();
+        assert!(report.contains("History rewritten from entry [1]") && report.contains("(undo)"), "{report}");
+
+        group.log[2].time = None;
+        let err = format!("{:#}", group.verify().unwrap_err());
+        assert!(err.contains("broken at entry [2]"), "{err}");
     }
 
     fn setup_group() -> Group {
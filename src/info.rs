@@ -0,0 +1,104 @@
+#[cfg(feature = "cli")]
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+#[cfg(feature = "cli")]
+use clap::CommandFactory;
+use serde::Serialize;
+#[cfg(feature = "cli")]
+use crate::config::Cli;
+#[cfg(feature = "cli")]
+use crate::logic::Splitter;
+#[cfg(feature = "cli")]
+use crate::money::Currency;
+use crate::storage::StorageFormat;
+
+/// The database `splitter info` looked at, if it exists
+#[derive(Debug, Serialize)]
+pub(crate) struct DatabaseInfo {
+    pub(crate) path: PathBuf,
+    pub(crate) format: StorageFormat,
+    /// the version of splitter that wrote it
+    pub(crate) version: String,
+    pub(crate) revision: u64,
+}
+
+/// What `splitter info` prints: the versions of this binary and what it supports,
+/// taken from the command line definitions and the currency enum
+#[cfg(feature = "cli")]
+#[derive(Debug, Serialize)]
+pub(crate) struct Info {
+    pub(crate) version: &'static str,
+    /// the version written into databases
+    pub(crate) storage_version: &'static str,
+    /// the version written into groups exported as JSON
+    pub(crate) export_version: &'static str,
+    pub(crate) currencies: Vec<String>,
+    pub(crate) subcommands: Vec<String>,
+    /// `None` if the database does not exist yet
+    pub(crate) database: Option<DatabaseInfo>,
+}
+
+#[cfg(feature = "cli")]
+impl Info {
+    pub(crate) fn new(database: Option<DatabaseInfo>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            storage_version: Splitter::CURRENT_VERSION,
+            export_version: Splitter::CURRENT_VERSION,
+            currencies: Currency::codes(),
+            subcommands: subcommands(),
+            database,
+        }
+    }
+}
+
+/// the names of all subcommands, including the hidden ones
+#[cfg(feature = "cli")]
+fn subcommands() -> Vec<String> {
+    Cli::command().get_subcommands().map(|command| command.get_name().to_string()).collect()
+}
+
+#[cfg(feature = "cli")]
+impl Display for Info {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "splitter {}", self.version)?;
+        writeln!(f, "storage version: {}", self.storage_version)?;
+        writeln!(f, "export version: {}", self.export_version)?;
+        writeln!(f, "currencies: {}", self.currencies.join(", "))?;
+        writeln!(f, "subcommands: {}", self.subcommands.join(", "))?;
+        match &self.database {
+            Some(db) => writeln!(f, "database: {:?}, {}, version {}, revision {}", db.path, db.format.describe(),
+                                 db.version, db.revision),
+            None => writeln!(f, "database: none"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod info_tests {
+    use clap::{Command, Subcommand};
+    use crate::config::SubCommand;
+    use crate::money::parse_currency;
+    use super::*;
+
+    #[test]
+    fn test_subcommands() {
+        let info = Info::new(None);
+        let expected: Vec<String> = SubCommand::augment_subcommands(Command::new("splitter")).get_subcommands()
+            .map(|command| command.get_name().to_string())
+            .collect();
+        assert_eq!(info.subcommands, expected);
+        for name in ["split", "pay", "doctor", "info", "__complete"] {
+            assert!(SubCommand::has_subcommand(name) && info.subcommands.iter().any(|listed| listed == name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_currencies() {
+        let info = Info::new(None);
+        assert_eq!(info.currencies, vec!["EUR", "USD", "JPY", "GBP"]);
+        for code in &info.currencies {
+            assert_eq!(parse_currency(&code.to_lowercase()), Ok(Currency::from(code.as_str())));
+        }
+    }
+}
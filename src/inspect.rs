@@ -0,0 +1,308 @@
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::error::*;
+use crate::integrity::first_broken_link;
+use crate::journal::{self, Journal};
+use crate::logging::LogEntry;
+use crate::storage::{StorageFormat, MAX_DATABASE_SIZE};
+
+/// bytes of the start of the file shown for a file that is no splitter database
+const HEAD_LEN: usize = 64;
+/// bytes looked at to tell text from binary
+const SNIFF_LEN: usize = 1024;
+
+/// What `splitter inspect` finds in a file
+#[derive(Debug, Serialize)]
+pub(crate) struct Inspection {
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    #[serde(flatten)]
+    pub(crate) found: Found,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Found {
+    Database {
+        /// the format detected from the content
+        format: StorageFormat,
+        /// the format splitter reads the file as, given by its extension
+        read_as: StorageFormat,
+        version: String,
+        revision: u64,
+        /// size of the yaml
+        uncompressed_size: u64,
+        groups: Vec<GroupOutline>,
+        /// the journal next to the file, `None` if there is none
+        journal: Option<JournalOutline>,
+    },
+    /// no splitter database: why not and the start of the file as hex dump
+    Unknown {
+        reason: String,
+        head: String,
+    },
+}
+
+/// a group as far as `inspect` looks at it
+#[derive(Debug, Serialize)]
+pub(crate) struct GroupOutline {
+    pub(crate) name: String,
+    pub(crate) entries: usize,
+    pub(crate) integrity: bool,
+    /// the first entry not matching the hash chain, in integrity mode
+    pub(crate) broken_at: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct JournalOutline {
+    pub(crate) records: usize,
+    /// bytes at the end failing their checksum, e.g. cut off by a crash
+    pub(crate) torn: u64,
+}
+
+/// The parts of the state `inspect` reads: the log is kept for the hash chain, everything else
+/// the groups hold is skipped while parsing
+#[derive(Deserialize)]
+struct StateOutline {
+    version: String,
+    #[serde(default)]
+    revision: u64,
+    groups: Vec<GroupFields>,
+}
+
+#[derive(Deserialize)]
+struct GroupFields {
+    name: String,
+    log: Vec<LogEntry>,
+    #[serde(default)]
+    integrity: bool,
+}
+
+/// Counts what is read through it, for the uncompressed size
+struct CountingReader<R> {
+    inner: R,
+    len: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.len += read as u64;
+        Ok(read)
+    }
+}
+
+/// `bytes` as lines of offset, 16 bytes in hex and their printable characters
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes.chunks(16).enumerate().map(|(line, chunk)| {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let text: String = chunk.iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+        format!("{:08x}  {:<47}  |{}|\n", line * 16, hex.join(" "), text)
+    }).collect()
+}
+
+/// whether `head`, the start of a file, is text. A character cut off at the end does not count.
+fn is_text(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(text) => !text.contains('\0'),
+        Err(e) => e.error_len().is_none() && !head.contains(&0),
+    }
+}
+
+/// what other kinds of files `head` could be the start of, to name them in the report
+fn known_other(head: &[u8]) -> Option<&'static str> {
+    [(&b"SQLite format 3\0"[..], "an SQLite database"),
+     (b"PK\x03\x04", "a zip archive"),
+     (b"\x1f\x8b", "a gzip file"),
+     (b"%PDF", "a PDF document"),
+     (b"-----BEGIN PGP", "a PGP message"),
+     (b"age-encryption.org", "an age encrypted file")]
+        .into_iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|(_, name)| name)
+}
+
+/// Parses the file at `path` as a database in `format`, returning the outline with the size of the yaml
+fn outline(path: &Path, format: StorageFormat) -> Result<(StateOutline, u64)> {
+    let file = std::fs::File::open(path).context(format!("Could not read {:?}", path))?;
+    let mut counting = CountingReader { inner: format.decoder(BufReader::new(file)), len: 0 };
+    let outline: StateOutline = serde_yaml::from_reader(&mut counting)?;
+    std::io::copy(&mut counting, &mut std::io::sink())?;
+    Ok((outline, counting.len))
+}
+
+/// Looks at the file at `path` without taking it for a database of any format: detects the
+/// format from the content, then reads the version, the groups and their hash chains and the
+/// journal next to it. A file that is no splitter database is reported as such, only a file that
+/// cannot be read at all is an error.
+pub(crate) fn inspect(path: &Path) -> Result<Inspection> {
+    let meta = std::fs::metadata(path).context(format!("Could not read {:?}", path))?;
+    if !meta.is_file() {
+        return Err(anyhow!("{:?} is no file", path));
+    }
+    let mut head = vec![];
+    std::fs::File::open(path).and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut head))
+        .context(format!("Could not read {:?}", path))?;
+    let unknown = |reason: String| Inspection {
+        path: path.to_path_buf(),
+        size: meta.len(),
+        found: Found::Unknown { reason, head: hex_dump(&head[..head.len().min(HEAD_LEN)]) },
+    };
+    if head.is_empty() {
+        return Ok(unknown("the file is empty".to_string()));
+    }
+    if meta.len() > MAX_DATABASE_SIZE {
+        return Ok(unknown(format!("{} MiB is more than the {} MiB a splitter database can have",
+                                  meta.len() / (1024 * 1024), MAX_DATABASE_SIZE / (1024 * 1024))));
+    }
+    if let Some(other) = known_other(&head) {
+        return Ok(unknown(format!("this is {}", other)));
+    }
+    // text is read as yaml, anything else as brotli stream
+    let format = if is_text(&head) { StorageFormat::Plain } else { StorageFormat::Compressed };
+    let (state, uncompressed_size) = match outline(path, format) {
+        Ok(outline) => outline,
+        Err(e) => return Ok(unknown(format!("not readable as {}: {:#}", format.describe(), e))),
+    };
+    let groups = state.groups.into_iter().map(|group| GroupOutline {
+        entries: group.log.len(),
+        broken_at: group.integrity.then(|| first_broken_link(&group.log)).flatten(),
+        integrity: group.integrity,
+        name: group.name,
+    }).collect();
+    let journal_path = journal::path_for(path);
+    let journal = match journal_path.exists() {
+        true => {
+            let journal = Journal::read(journal_path)?;
+            Some(JournalOutline { records: journal.records().len(), torn: journal.torn() })
+        }
+        false => None,
+    };
+    Ok(Inspection {
+        path: path.to_path_buf(),
+        size: meta.len(),
+        found: Found::Database {
+            format,
+            read_as: StorageFormat::for_path(path),
+            version: state.version,
+            revision: state.revision,
+            uncompressed_size,
+            groups,
+            journal,
+        },
+    })
+}
+
+impl Inspection {
+    /// an error if the file is no splitter database or a hash chain is broken
+    pub(crate) fn result(&self) -> Result<()> {
+        match &self.found {
+            Found::Unknown { .. } => Err(anyhow!("{:?} is not a splitter database", self.path)),
+            Found::Database { groups, .. } => match groups.iter().filter(|group| group.broken_at.is_some()).count() {
+                0 => Ok(()),
+                1 => Err(anyhow!("1 hash chain is broken")),
+                broken => Err(anyhow!("{} hash chains are broken", broken)),
+            },
+        }
+    }
+}
+
+impl Display for Inspection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "file: {:?}, {} bytes", self.path, self.size)?;
+        match &self.found {
+            Found::Unknown { reason, head } => {
+                writeln!(f, "not a splitter database: {}", reason)?;
+                write!(f, "{}", head)
+            }
+            Found::Database { format, read_as, version, revision, uncompressed_size, groups, journal } => {
+                writeln!(f, "format: {}", format.describe())?;
+                if read_as != format {
+                    writeln!(f, "warning: splitter reads it as {} because of its name, rename it to end in {}",
+                             read_as.describe(), if *format == StorageFormat::Plain { ".yaml" } else { ".db" })?;
+                }
+                writeln!(f, "version: {}, revision {}", version, revision)?;
+                writeln!(f, "uncompressed: {} bytes", uncompressed_size)?;
+                let entries: usize = groups.iter().map(|group| group.entries).sum();
+                writeln!(f, "groups: {}, entries: {}", groups.len(), entries)?;
+                for group in groups {
+                    let chain = match (group.integrity, group.broken_at) {
+                        (false, _) => "no hash chain".to_string(),
+                        (true, None) => "hash chain intact".to_string(),
+                        (true, Some(index)) => format!("hash chain broken at entry [{}]", index),
+                    };
+                    writeln!(f, "group {}: {} entries, {}", group.name, group.entries, chain)?;
+                }
+                match journal {
+                    None => writeln!(f, "journal: none"),
+                    Some(JournalOutline { records, torn: 0 }) => writeln!(f, "journal: {} records, checksums verify", records),
+                    Some(JournalOutline { records, torn }) =>
+                        writeln!(f, "journal: {} records, the last {} bytes fail their checksum", records, torn),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod inspect_tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(hex_dump(b"SQLite format 3\0\x10\x00"),
+                   "00000000  53 51 4c 69 74 65 20 66 6f 72 6d 61 74 20 33 00  |SQLite format 3.|\n\
+                    00000010  10 00                                            |..|\n");
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert!(is_text(b"version: 0.1.0\n"));
+        assert!(is_text("name: Jürgen".as_bytes().split_at(11).0), "cut in the middle of ü");
+        assert!(!is_text(b"\x8b\x03\x80version"));
+        assert!(!is_text(b"ver\0sion"));
+        assert_eq!(known_other(b"SQLite format 3\0\x10"), Some("an SQLite database"));
+        assert_eq!(known_other(b"version: 0.1.0"), None);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = "version: 0.1.0\nrevision: 3\ncurrent_group: 0\ngroups:\n\
+                    - name: trip\n  currency: EUR\n  members: {alice: 0}\n  log: []\n";
+        let plain = dir.path().join("trip.yaml");
+        std::fs::write(&plain, yaml).unwrap();
+        let inspection = inspect(&plain).unwrap();
+        assert!(inspection.result().is_ok());
+        let Found::Database { format, read_as, revision, uncompressed_size, groups, journal, .. } = &inspection.found
+            else { panic!("{}", inspection) };
+        assert_eq!((*format, *read_as, *revision), (StorageFormat::Plain, StorageFormat::Plain, 3));
+        assert_eq!(*uncompressed_size, yaml.len() as u64);
+        assert_eq!((groups[0].name.as_str(), groups[0].entries), ("trip", 0));
+        assert!(journal.is_none());
+
+        // compressed, but named like plain yaml
+        let misnamed = dir.path().join("backup.yml");
+        std::fs::write(&misnamed, StorageFormat::Compressed.encode(yaml.to_string(), None)).unwrap();
+        let text = inspect(&misnamed).unwrap().to_string();
+        assert!(text.contains("format: brotli compressed yaml\nwarning: splitter reads it as plain yaml"), "{}", text);
+        assert!(text.contains(&format!("uncompressed: {} bytes", yaml.len())), "{}", text);
+
+        let garbage = dir.path().join("garbage.db");
+        std::fs::write(&garbage, b"SQLite format 3\0rest").unwrap();
+        let inspection = inspect(&garbage).unwrap();
+        assert_eq!(inspection.result().unwrap_err().to_string(), format!("{:?} is not a splitter database", garbage));
+        let json = serde_json::to_value(&inspection).unwrap();
+        assert_eq!(json["kind"], "unknown");
+        assert_eq!(json["reason"], "this is an SQLite database");
+
+        std::fs::write(&garbage, b"groups: [1, 2]\n").unwrap();
+        let text = inspect(&garbage).unwrap().to_string();
+        assert!(text.contains("not a splitter database: not readable as plain yaml: "), "{}", text);
+        assert!(inspect(dir.path()).is_err());
+    }
+}
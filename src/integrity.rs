@@ -0,0 +1,272 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::logging::{LogEntry, LoggedCommand};
+use crate::logic::Target;
+use crate::money::Money;
+use crate::storage::by_name;
+
+/// prefix of every encoded entry, bumped if the encoding ever has to change
+const ENCODING_TAG: &[u8] = b"splitter-log-v1";
+
+/// A place where the history of an integrity checked group was rewritten and re-chained
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Rechain {
+    /// index of the first entry whose hash was recomputed
+    pub(crate) from: usize,
+    pub(crate) time: DateTime<Local>,
+    /// the command that rewrote the history, e.g. "undo"
+    pub(crate) reason: String,
+}
+
+/// Byte-level encoding of log entries for hashing. Independent of serde so that the hashes
+/// stay the same when field names, defaults or the storage format change.
+/// Integers are big-endian, strings and lists are prefixed with their length as u64,
+/// optional values with a 0 or 1 byte.
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+    fn i64(&mut self, value: i64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+    fn money(&mut self, value: Money) {
+        self.i64(value.0);
+    }
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+    fn str(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+    fn option<T>(&mut self, value: Option<T>, mut encode: impl FnMut(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                encode(self, value);
+            }
+            None => self.u8(0),
+        }
+    }
+    fn targets(&mut self, targets: &[Target]) {
+        self.u64(targets.len() as u64);
+        for target in targets {
+            self.str(&target.member);
+            self.option(target.amount, Self::money);
+        }
+    }
+}
+
+/// the stable encoding of an entry, everything but its chain hash
+pub(crate) fn canonical_bytes(entry: &LogEntry) -> Vec<u8> {
+    let mut enc = Encoder(ENCODING_TAG.to_vec());
+    enc.str(&entry.id);
+    enc.option(entry.time, |enc, time| {
+        enc.i64(time.timestamp());
+        enc.u64(time.timestamp_subsec_nanos() as u64);
+    });
+    match &entry.command {
+        LoggedCommand::Split { name, amount, from, to, group, balance_rest, category, .. } => {
+            enc.u8(1);
+            enc.str(name);
+            enc.money(*amount);
+            enc.targets(from);
+            enc.targets(to);
+            enc.str(group);
+            enc.bool(*balance_rest);
+            enc.option(category.as_deref(), Encoder::str);
+        }
+        LoggedCommand::Pay { amount, from, to, group, .. } => {
+            enc.u8(2);
+            enc.money(*amount);
+            enc.str(from);
+            enc.str(to);
+            enc.str(group);
+        }
+        LoggedCommand::Opening { member, amount, group, source } => {
+            enc.u8(3);
+            enc.str(member);
+            enc.money(*amount);
+            enc.str(group);
+            enc.str(source);
+        }
+        LoggedCommand::WriteOff { member, amount, group, to } => {
+            enc.u8(4);
+            enc.str(member);
+            enc.money(*amount);
+            enc.str(group);
+            enc.u64(to.len() as u64);
+            for member in to {
+                enc.str(member);
+            }
+        }
+        LoggedCommand::BuyIn { member, amount, group } => {
+            enc.u8(5);
+            enc.str(member);
+            enc.money(*amount);
+            enc.str(group);
+        }
+        LoggedCommand::Reset { group, members, cleared } => {
+            enc.u8(6);
+            enc.str(group);
+            enc.u64(members.len() as u64);
+            for member in members {
+                enc.str(member);
+            }
+            enc.u64(*cleared as u64);
+        }
+        LoggedCommand::Correction { group, residual, members } => {
+            enc.u8(7);
+            enc.str(group);
+            enc.money(*residual);
+            enc.u64(members.len() as u64);
+            for member in members {
+                enc.str(member);
+            }
+        }
+    }
+    let change = by_name(entry.change());
+    enc.u64(change.len() as u64);
+    for (member, amount) in change {
+        enc.str(member);
+        enc.money(*amount);
+    }
+    // fields added later are only encoded when present, so older entries keep their hash
+    if let Some(author) = &entry.author {
+        enc.u8(b'a');
+        enc.str(author);
+    }
+    if let LoggedCommand::Split { from, to, .. } = &entry.command {
+        for (side, targets) in [(b'f', from), (b't', to)] {
+            if let Some(index) = targets.iter().position(|t| t.rest) {
+                enc.u8(b'r');
+                enc.u8(side);
+                enc.u64(index as u64);
+            }
+        }
+    }
+    if let LoggedCommand::Split { to, .. } = &entry.command {
+        for (index, share) in to.iter().enumerate().filter_map(|(index, t)| Some((index, t.share.as_ref()?))) {
+            enc.u8(b's');
+            enc.u64(index as u64);
+            enc.str(share);
+        }
+        for (index, weight) in to.iter().enumerate().filter_map(|(index, t)| Some((index, t.weight?))) {
+            enc.u8(b'w');
+            enc.u64(index as u64);
+            enc.u64(weight);
+        }
+    }
+    if let LoggedCommand::Split { ratio: Some(ratio), .. } = &entry.command {
+        enc.u8(b'p');
+        enc.str(ratio);
+    }
+    if let LoggedCommand::Split { included, .. } = &entry.command {
+        if !included.is_empty() {
+            enc.u8(b'i');
+            enc.u64(included.len() as u64);
+            for member in included {
+                enc.str(member);
+            }
+        }
+    }
+    if let LoggedCommand::Split { excluded, .. } = &entry.command {
+        if !excluded.is_empty() {
+            enc.u8(b'x');
+            enc.u64(excluded.len() as u64);
+            for member in excluded {
+                enc.str(member);
+            }
+        }
+    }
+    enc.0
+}
+
+/// hash of an entry linked to the hash of the entry before it, empty for the first entry
+pub(crate) fn chain_hash(previous: &str, entry: &LogEntry) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update((previous.len() as u64).to_be_bytes());
+    hasher.update(previous.as_bytes());
+    hasher.update(canonical_bytes(entry));
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// recomputes the hashes of all entries starting at `from`
+pub(crate) fn rechain(log: &mut [LogEntry], from: usize) {
+    let mut previous = match from {
+        0 => String::new(),
+        _ => log[from - 1].hash.clone().unwrap_or_default(),
+    };
+    for entry in log.iter_mut().skip(from) {
+        let hash = chain_hash(&previous, entry);
+        entry.hash = Some(hash.clone());
+        previous = hash;
+    }
+}
+
+/// index of the first entry whose hash is missing or does not match the chain
+pub(crate) fn first_broken_link(log: &[LogEntry]) -> Option<usize> {
+    let mut previous = String::new();
+    for (index, entry) in log.iter().enumerate() {
+        let expected = chain_hash(&previous, entry);
+        if entry.hash.as_deref() != Some(expected.as_str()) {
+            return Some(index);
+        }
+        previous = expected;
+    }
+    None
+}
+
+#[cfg(test)]
+mod integrity_tests {
+    use std::collections::HashMap;
+    use chrono::TimeZone;
+    use super::*;
+
+    fn entry(amount: Money, from: &str, to: &str, seconds: i64) -> LogEntry {
+        let change = HashMap::from([(from.to_string(), amount), (to.to_string(), -amount)]);
+        let mut entry = LogEntry::new(
+            LoggedCommand::Pay { amount, from: from.to_string(), to: to.to_string(), group: "g".to_string(), settled_by: None },
+            change);
+        entry.time = Some(Local.timestamp_opt(seconds, 0).unwrap());
+        entry.id = entry.content_hash();
+        entry
+    }
+
+    #[test]
+    fn test_canonical_bytes_stable() {
+        let mut entry = entry(Money(12_50), "Alice", "Bob", 1_700_000_000);
+        entry.id = "0123456789abcdef".to_string();
+        let bytes = canonical_bytes(&entry);
+        assert!(bytes.starts_with(ENCODING_TAG));
+        // the encoding must never change, or every existing chain breaks
+        assert_eq!(chain_hash("", &entry), "a387c469f5c0b4fae6bef9a2dbd700154c6983fd0efa43cf5049a9fb621e7666");
+        entry.author = Some("alice".to_string());
+        assert_ne!(chain_hash("", &entry), "a387c469f5c0b4fae6bef9a2dbd700154c6983fd0efa43cf5049a9fb621e7666");
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut log: Vec<LogEntry> = (0..5).map(|i| entry(Money(100 + i), "Alice", "Bob", i)).collect();
+        assert_eq!(first_broken_link(&log), Some(0));
+        rechain(&mut log, 0);
+        assert_eq!(first_broken_link(&log), None);
+
+        let LoggedCommand::Pay { amount, .. } = &mut log[2].command else { unreachable!() };
+        *amount = Money(1_000_000);
+        assert_eq!(first_broken_link(&log), Some(2));
+
+        rechain(&mut log, 2);
+        assert_eq!(first_broken_link(&log), None);
+        log.remove(1);
+        assert_eq!(first_broken_link(&log), Some(1));
+        rechain(&mut log, 1);
+        assert_eq!(first_broken_link(&log), None);
+    }
+}
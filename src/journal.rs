@@ -0,0 +1,230 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::error::*;
+use crate::logging::LogEntry;
+
+/// number of records from which on the next save folds the journal into the database
+const CHECKPOINT_RECORDS: usize = 200;
+/// journal size from which on the next save folds it into the database
+const CHECKPOINT_SIZE: u64 = 1024 * 1024;
+
+/// where the journal of the database at `db` is kept: `<db>.journal`
+pub(crate) fn path_for(db: &Path) -> PathBuf {
+    let mut path = db.as_os_str().to_owned();
+    path.push(".journal");
+    PathBuf::from(path)
+}
+
+/// a log entry added to a group
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct JournaledEntry {
+    pub(crate) group: String,
+    pub(crate) entry: LogEntry,
+}
+
+/// What one save added on top of the previous revision
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct JournalRecord {
+    /// the revision of the state after the record is applied
+    pub(crate) revision: u64,
+    pub(crate) current_group: Option<usize>,
+    pub(crate) entries: Vec<JournaledEntry>,
+}
+
+impl JournalRecord {
+    /// one line: the hex SHA-256 of the JSON, a space and the JSON itself
+    fn line(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!("{:x} {}\n", Sha256::digest(json.as_bytes()), json))
+    }
+
+    /// the record of a line as written by `line`, `None` if it is damaged
+    fn parse(line: &str) -> Option<Self> {
+        let (checksum, json) = line.split_once(' ')?;
+        if format!("{:x}", Sha256::digest(json.as_bytes())) != checksum {
+            return None;
+        }
+        serde_json::from_str(json).ok()
+    }
+}
+
+/// Append-only file next to a compressed database holding the log entries saved since the
+/// database was last written as a whole. Loading replays it over the database.
+pub(crate) struct Journal {
+    path: PathBuf,
+    records: Vec<JournalRecord>,
+    /// size of the file as it was read or last written
+    len: u64,
+    /// size of the intact records at the start of the file, anything after it was cut off by a crash
+    valid: u64,
+}
+
+impl Journal {
+    /// Reads the journal at `path`, missing meaning empty. A damaged record, e.g. one whose write
+    /// was interrupted, ends the journal: it and everything after it is ignored and overwritten by
+    /// the next append.
+    pub(crate) fn read(path: PathBuf) -> Result<Self> {
+        let raw = match std::fs::read(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e).context(format!("Could not read the journal {:?}", path)),
+        };
+        let mut records = vec![];
+        let mut valid = 0;
+        for line in raw.split_inclusive(|byte| *byte == b'\n') {
+            let Some(record) = line.strip_suffix(b"\n")
+                .and_then(|line| std::str::from_utf8(line).ok())
+                .and_then(JournalRecord::parse) else { break };
+            records.push(record);
+            valid += line.len() as u64;
+        }
+        let len = raw.len() as u64;
+        if valid < len {
+            eprintln!("Warning: ignoring the last {} bytes of {:?}, they were not completely written", len - valid, path);
+        }
+        Ok(Self { path, records, len, valid })
+    }
+
+    pub(crate) fn records(&self) -> &[JournalRecord] {
+        &self.records
+    }
+
+    /// bytes at the end of the file that were cut off by a crash and are ignored
+    #[cfg(feature = "cli")]
+    pub(crate) fn torn(&self) -> u64 {
+        self.len - self.valid
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// whether the next save should fold the journal into the database
+    pub(crate) fn full(&self) -> bool {
+        self.records.len() >= CHECKPOINT_RECORDS || self.len >= CHECKPOINT_SIZE
+    }
+
+    /// whether another program wrote to the journal since it was read or last written
+    pub(crate) fn changed_on_disk(&self) -> bool {
+        std::fs::metadata(&self.path).map_or(0, |meta| meta.len()) != self.len
+    }
+
+    /// Appends `record` after the intact records and syncs it to disk
+    pub(crate) fn append(&mut self, record: JournalRecord) -> Result<()> {
+        let line = record.line()?;
+        let mut file = OpenOptions::new().create(true).write(true).truncate(false).open(&self.path)
+            .context(format!("Could not open the journal {:?}", self.path))?;
+        file.set_len(self.valid)
+            .and_then(|_| file.sync_data())
+            .and_then(|_| std::io::Seek::seek(&mut file, std::io::SeekFrom::End(0)))
+            .and_then(|_| file.write_all(line.as_bytes()))
+            .and_then(|_| file.sync_data())
+            .context(format!("Could not write the journal {:?}", self.path))?;
+        self.valid += line.len() as u64;
+        self.len = self.valid;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Removes the journal after its records were written to the database
+    pub(crate) fn clear(&mut self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound =>
+                return Err(e).context(format!("Could not remove the journal {:?}", self.path)),
+            _ => {}
+        }
+        self.records.clear();
+        self.len = 0;
+        self.valid = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use std::collections::HashMap;
+    use crate::logging::LoggedCommand;
+    use crate::money::Money;
+    use super::*;
+
+    fn record(revision: u64) -> JournalRecord {
+        let command = LoggedCommand::Pay {
+            amount: Money(revision as i64 * 100),
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            group: "flat".to_string(),
+            settled_by: None,
+        };
+        let change = HashMap::from([("Alice".to_string(), Money(100)), ("Bob".to_string(), Money(-100))]);
+        JournalRecord {
+            revision,
+            current_group: Some(0),
+            entries: vec![JournaledEntry { group: "flat".to_string(), entry: LogEntry::new(command, change) }],
+        }
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = path_for(&dir.path().join("flat.db"));
+        assert!(path.ends_with("flat.db.journal"));
+        let mut journal = Journal::read(path.clone()).unwrap();
+        assert!(journal.is_empty());
+        journal.append(record(1)).unwrap();
+        journal.append(record(2)).unwrap();
+        assert!(!journal.changed_on_disk());
+
+        let journal = Journal::read(path.clone()).unwrap();
+        assert_eq!(journal.records().iter().map(|record| record.revision).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(journal.records()[1].entries[0].entry.change()["Bob"], Money(-100));
+
+        let mut journal = Journal::read(path.clone()).unwrap();
+        journal.clear().unwrap();
+        assert!(!path.exists() && journal.is_empty());
+        journal.clear().unwrap();
+    }
+
+    #[test]
+    fn test_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flat.db.journal");
+        let mut journal = Journal::read(path.clone()).unwrap();
+        journal.append(record(1)).unwrap();
+        journal.append(record(2)).unwrap();
+        let intact = std::fs::read(&path).unwrap();
+
+        // a crash in the middle of writing the third record
+        let third = record(3);
+        let line = third.line().unwrap();
+        let mut torn = intact.clone();
+        torn.extend_from_slice(&line.as_bytes()[..line.len() / 2]);
+        std::fs::write(&path, &torn).unwrap();
+        let mut journal = Journal::read(path.clone()).unwrap();
+        assert_eq!(journal.records().len(), 2);
+        assert!(!journal.changed_on_disk());
+        // the next append replaces the broken end
+        journal.append(third).unwrap();
+        assert_eq!(Journal::read(path.clone()).unwrap().records().len(), 3);
+        assert_eq!(std::fs::read(&path).unwrap(), [intact.as_slice(), line.as_bytes()].concat());
+
+        // a complete line whose content does not match its checksum ends the journal as well
+        let mut damaged = String::from_utf8(intact).unwrap();
+        damaged = damaged.replacen("\"amount\":100", "\"amount\":900", 1);
+        std::fs::write(&path, damaged).unwrap();
+        assert!(Journal::read(path).unwrap().records().is_empty());
+    }
+
+    #[test]
+    fn test_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::read(dir.path().join("flat.db.journal")).unwrap();
+        for revision in 0..CHECKPOINT_RECORDS as u64 {
+            assert!(!journal.full());
+            journal.append(record(revision)).unwrap();
+        }
+        assert!(journal.full());
+    }
+}
@@ -0,0 +1,49 @@
+//! Splitting expenses among groups of people, with the book keeping of the `splitter` program.
+//! Other programs use the engine through [`api`], the command line program is built with the
+//! `cli` feature, which is on by default.
+
+// amounts in minor units are written as `major_minor`, e.g. 25_22 for 25.22€
+#![allow(clippy::inconsistent_digit_grouping)]
+
+pub mod api;
+#[cfg(feature = "cli")]
+pub mod cli;
+
+mod logic;
+mod config;
+mod error;
+mod money;
+mod logging;
+mod group;
+#[cfg(feature = "cli")]
+mod settings;
+#[cfg(feature = "cli")]
+mod completion;
+mod report;
+mod export;
+mod render;
+mod prompt;
+mod storage;
+mod integrity;
+mod porcelain;
+mod shellenv;
+mod period;
+mod budget;
+mod pager;
+#[cfg(feature = "cli")]
+mod audit;
+mod journal;
+#[cfg(feature = "cli")]
+mod doctor;
+mod info;
+mod nag;
+#[cfg(feature = "cli")]
+mod repl;
+mod settle;
+#[cfg(feature = "cli")]
+mod remote;
+#[cfg(feature = "cli")]
+mod inspect;
+mod feed;
+#[cfg(feature = "cli")]
+mod watch;
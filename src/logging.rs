@@ -1,8 +1,16 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Write};
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::group::split_equal_among;
 use crate::logic::{Money, Target, TransactionChange};
 use crate::money::Currency;
+use crate::render::date;
+use crate::settle::Algorithm;
+use crate::storage::{by_name, sorted};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum LoggedCommand {
     Split {
         name: String,
@@ -11,61 +19,272 @@ pub(crate) enum LoggedCommand {
         to: Vec<Target>,
         group: String,
         balance_rest: bool,
+        #[serde(default)]
+        category: Option<String>,
+        /// the ratio set the rest was split by, see `split --by-ratio`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ratio: Option<String>,
+        /// members included afterwards with `rebalance-entry`, marking the entry as amended
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        included: Vec<String>,
+        /// members left out with `split --except`, recorded with a change of zero
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        excluded: Vec<String>,
     },
     Pay {
         amount: Money,
         from: String,
         to: String,
+        /// empty for entries written by older versions until the owning group fills it in on load
+        #[serde(default)]
+        group: String,
+        /// the algorithm that suggested the payment, for payments recorded by `balance`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        settled_by: Option<Algorithm>,
+    },
+    /// the balance `member` had when group `source` was carried into this one, see `carry`
+    Opening {
+        member: String,
+        amount: Money,
+        group: String,
+        source: String,
+    },
+    /// the balance `member` had when removed with `remove --force`, taken over by `to`
+    WriteOff {
+        member: String,
+        amount: Money,
+        group: String,
+        to: Vec<String>,
+    },
+    /// `member` joined owing `amount`, credited to the members who had paid for splits, see `add --buy-in`
+    BuyIn {
+        member: String,
+        amount: Money,
+        group: String,
+    },
+    /// the `cleared` entries before were removed and the balances zeroed, leaving `members`, see `reset`
+    Reset {
+        group: String,
+        members: Vec<String>,
+        cleared: usize,
+    },
+    /// the balances added up to `residual` instead of zero and were corrected at the expense of
+    /// `members`, see `fix-residual`
+    Correction {
+        group: String,
+        residual: Money,
+        members: Vec<String>,
     },
 }
 
 impl LoggedCommand {
-    fn to_string(&self, curr: Currency) -> String {
+    /// the total amount of money moved by this command
+    pub(crate) fn amount(&self) -> Money {
+        match self {
+            Self::Pay { amount, .. } | Self::BuyIn { amount, .. } => *amount,
+            Self::Split { amount, .. } | Self::Opening { amount, .. } | Self::WriteOff { amount, .. } => amount.abs(),
+            Self::Correction { residual, .. } => residual.abs(),
+            Self::Reset { .. } => Money::ZERO,
+        }
+    }
+
+    /// the group the command was entered in
+    pub(crate) fn set_group(&mut self, name: &str) {
+        match self {
+            Self::Split { group, .. } | Self::Pay { group, .. } | Self::Opening { group, .. }
+            | Self::WriteOff { group, .. } | Self::BuyIn { group, .. } | Self::Reset { group, .. }
+            | Self::Correction { group, .. } => group.replace_range(.., name),
+        }
+    }
+
+    /// writes the description of the command, as shown by `list`
+    fn write(&self, out: &mut impl Write, curr: Currency) -> std::fmt::Result {
         match self {
-            Self::Pay { from, to, amount } => {
-                format!("pay: {}\t to {}\t: {}{}", from, to, *amount as f32 / curr.subdivision(), curr)
+            Self::Pay { from, to, amount, group, settled_by } => {
+                write!(out, "pay: in group {} {} → {} {}", group, from, to, curr.format(*amount))?;
+                match settled_by {
+                    Some(algorithm) => write!(out, ", settling {}", algorithm.name()),
+                    None => Ok(()),
+                }
+            }
+            Self::Opening { member, amount, group, source } => {
+                write!(out, "opening: in group {} {} starts at {}, carried over from {}",
+                       group, member, curr.format(*amount), source)
+            }
+            Self::WriteOff { member, amount, group, to } => {
+                write!(out, "write-off: in group {} {} removed with {}, taken over by {}",
+                       group, member, curr.format(*amount), to.join(", "))
+            }
+            Self::BuyIn { member, amount, group } => {
+                write!(out, "buy-in: in group {} {} joins owing {}", group, member, curr.format(*amount))
+            }
+            Self::Reset { group, members, cleared } => {
+                write!(out, "reset: in group {} {} {} cleared, members {}", group, cleared,
+                       if *cleared == 1 { "entry" } else { "entries" }, members.join(", "))
+            }
+            Self::Correction { group, residual, members } => {
+                write!(out, "correction: in group {} residual of {} booked to {}", group, curr.format(*residual),
+                       members.join(", "))
             }
-            Self::Split { name, amount, from, to, group, balance_rest } => {
-                let from = from.iter()
-                    .map(|t|
-                        if t.amount.is_none() {
-                            format!("{}: *", t.member)
-                        } else {
-                            format!("{}: {:.02}{}\n", t.member, t.amount.unwrap() as f32 / curr.subdivision(), curr)
-                        }
-                    )
-                    .fold("".to_string(), |accu, el| format!("{}{}", accu, el));
-                let to = to.iter()
-                    .map(|t|
-                        format!("{}: {:.02}{}\n", t.member, t.amount.unwrap() as f32 / curr.subdivision(), curr)
-                    )
-                    .fold("".to_string(), |accu, el| format!("{}{}", accu, el));
-                if to.is_empty() {
-                    format!("split: in group {} `{} {}{} payed for by\n{}\n{}",
-                            group, name, *amount as f32 / curr.subdivision(), curr, from,
-                            if *balance_rest { ", balancing the rest" } else { "" })
-                } else {
-                    format!("split: in group {} `{} {}{} payed for by\n{}\nto\n{}{}",
-                            group, name, *amount as f32 / curr.subdivision(), curr, from, to,
-                            if *balance_rest { ", balancing the rest" } else { "" })
+            Self::Split { name, amount, from, to, group, balance_rest, category, ratio, included, excluded } => {
+                // a refund, see `split --refund`, logs its amounts negative
+                let sign = amount.signum();
+                write!(out, "{}: in group {} `{}", if sign < 0 { "refund" } else { "split" }, group, name)?;
+                if let Some(category) = category {
+                    write!(out, " [{}]", category)?;
+                }
+                writeln!(out, " {} {}", curr.format(sign * *amount),
+                         if sign < 0 { "returned to" } else { "payed for by" })?;
+                // the amount as it was given, followed by what it came to unless that was a plain amount
+                let given = |t: &Target, amount: Money| match (&t.share, &t.expression) {
+                    (Some(share), _) => format!("{}%share (={})", share, curr.format(amount)),
+                    _ if t.weight.is_some() => format!("{}x (={})", t.weight.unwrap_or_default(), curr.format(amount)),
+                    _ if t.rest => format!("rest (={})", curr.format(amount)),
+                    (None, Some(expression)) => format!("{} (={})", expression, curr.format(amount)),
+                    (None, None) => curr.format(amount),
+                };
+                for t in from {
+                    match t.amount {
+                        Some(amount) => writeln!(out, "{}: {}", t.member, given(t, sign * amount))?,
+                        None => writeln!(out, "{}: *", t.member)?,
+                    }
+                }
+                writeln!(out)?;
+                if !to.is_empty() {
+                    writeln!(out, "to")?;
+                    for t in to {
+                        writeln!(out, "{}: {}", t.member, given(t, sign * t.amount.unwrap()))?;
+                    }
+                }
+                if *balance_rest {
+                    write!(out, ", balancing the rest")?;
+                }
+                if let Some(ratio) = ratio {
+                    write!(out, ", split by ratio {}", ratio)?;
+                }
+                if !excluded.is_empty() {
+                    write!(out, ", without {}", excluded.join(", "))?;
                 }
+                if !included.is_empty() {
+                    write!(out, ", amended to include {}", included.join(", "))?;
+                }
+                Ok(())
             }
         }
     }
 }
 
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct LogEntry {
+    /// stable identity of the entry across copies of the database, derived from its content
+    /// when it is recorded. Empty for entries written by older versions until the group migrates them.
+    #[serde(default)]
+    pub(crate) id: String,
     pub(crate) command: LoggedCommand,
+    #[serde(serialize_with = "sorted")]
     change: TransactionChange,
+    /// when the entry was recorded, missing for entries written by older versions
+    #[serde(default)]
+    pub(crate) time: Option<DateTime<Local>>,
+    /// who entered the entry, missing for entries written by older versions
+    #[serde(default)]
+    pub(crate) author: Option<String>,
+    /// link in the hash chain of groups in integrity mode, see `integrity.rs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hash: Option<String>,
 }
 
 impl LogEntry {
     pub fn new(cmd: LoggedCommand, chg: TransactionChange) -> Self {
-        LogEntry {
+        let mut entry = LogEntry {
+            id: String::new(),
             command: cmd,
             change: chg,
+            time: Some(Local::now()),
+            author: None,
+            hash: None,
+        };
+        entry.id = entry.content_hash();
+        entry
+    }
+
+    /// hash over everything but the id, with the change sorted so equal entries hash equally
+    pub(crate) fn content_hash(&self) -> String {
+        let change: BTreeMap<_, _> = self.change.iter().collect();
+        let content = serde_json::to_vec(&(&self.command, change, self.time))
+            .expect("log entries are always serializable");
+        Sha256::digest(content).iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// applies `convert` to every amount of the entry, see `Group::migrate`
+    pub(crate) fn convert_amounts(&mut self, convert: impl Fn(Money) -> Money) {
+        match &mut self.command {
+            LoggedCommand::Split { amount, from, to, .. } => {
+                *amount = convert(*amount);
+                for target in from.iter_mut().chain(to.iter_mut()) {
+                    target.amount = target.amount.map(&convert);
+                }
+            }
+            LoggedCommand::Pay { amount, .. } | LoggedCommand::Opening { amount, .. }
+            | LoggedCommand::WriteOff { amount, .. } | LoggedCommand::BuyIn { amount, .. } => *amount = convert(*amount),
+            LoggedCommand::Correction { residual, .. } => *residual = convert(*residual),
+            LoggedCommand::Reset { .. } => {}
+        }
+        for change in self.change.values_mut() {
+            *change = convert(*change);
+        }
+    }
+
+    /// the change as applied to the balances
+    pub(crate) fn change(&self) -> &TransactionChange {
+        &self.change
+    }
+
+    /// Replaces the change of a split after `included` were made part of it. The id stays the same.
+    pub(crate) fn amend(&mut self, change: TransactionChange, included: &[String]) {
+        if let LoggedCommand::Split { included: already, .. } = &mut self.command {
+            already.extend_from_slice(included);
+        }
+        self.change = change;
+    }
+
+    /// Replaces member `old` by `new` wherever the entry names them. The id stays the same.
+    pub(crate) fn rename_member(&mut self, old: &str, new: &str) {
+        let rename = |name: &mut String| if name == old { name.replace_range(.., new) };
+        match &mut self.command {
+            LoggedCommand::Split { from, to, included, excluded, .. } => {
+                from.iter_mut().chain(to.iter_mut()).for_each(|target| rename(&mut target.member));
+                included.iter_mut().chain(excluded.iter_mut()).for_each(rename);
+            }
+            LoggedCommand::Pay { from, to, .. } => {
+                rename(from);
+                rename(to);
+            }
+            LoggedCommand::Opening { member, .. } | LoggedCommand::BuyIn { member, .. } => rename(member),
+            LoggedCommand::WriteOff { member, to, .. } => {
+                rename(member);
+                to.iter_mut().for_each(rename);
+            }
+            LoggedCommand::Reset { members, .. } | LoggedCommand::Correction { members, .. } =>
+                members.iter_mut().for_each(rename),
+        }
+        if let Some(change) = self.change.remove(old) {
+            self.change.insert(new.to_string(), change);
+        }
+    }
+
+    /// Entries written by older versions logged payments with the opposite sign of what was
+    /// applied to the balances. Returns whether the change had to be flipped.
+    pub(crate) fn fix_legacy_pay_change(&mut self) -> bool {
+        let LoggedCommand::Pay { from, .. } = &self.command else {
+            return false;
+        };
+        if self.change_of(from).is_negative() {
+            self.change = self.reversed_change();
+            true
+        } else {
+            false
         }
     }
 
@@ -73,7 +292,149 @@ impl LogEntry {
     pub fn reversed_change(&self) -> TransactionChange {
         self.change.iter().map(|(k, &v)| (k.clone(), -v)).collect()
     }
-    pub fn to_string(&self, curr: Currency) -> String {
-        self.command.to_string(curr)
+    /// how the entry changed the balance of `member`
+    pub(crate) fn change_of(&self, member: &str) -> Money {
+        self.change.get(member).copied().unwrap_or(Money::ZERO)
+    }
+    /// the author for display, "(unknown)" for entries written by older versions
+    pub(crate) fn author_name(&self) -> &str {
+        self.author.as_deref().unwrap_or("(unknown)")
+    }
+    /// renders the entry prefixed with its index in the log (as used by `undo`), its timestamp and author
+    pub fn to_string(&self, index: usize, curr: Currency) -> String {
+        let mut rendered = String::new();
+        self.write(&mut rendered, index, curr).expect("writing to a String cannot fail");
+        rendered
+    }
+    /// like `to_string`, written to `out` instead of a new String
+    pub(crate) fn write(&self, out: &mut impl Write, index: usize, curr: Currency) -> std::fmt::Result {
+        write!(out, "[{}] ", index)?;
+        match self.time {
+            Some(time) => write!(out, "{}", date(time, "%Y-%m-%d %H:%M"))?,
+            None => write!(out, "unknown date")?,
+        }
+        write!(out, " by {}: ", self.author_name())?;
+        self.command.write(out, curr)
     }
-}
\ No newline at end of file
+
+    /// What each member consumed in a split, sorted by name: what they paid minus how their
+    /// balance changed. The amount paid by catch-all givers is reconstructed by splitting the
+    /// rest equally among them. Payments between members have no shares.
+    pub(crate) fn shares(&self) -> Vec<(String, Money)> {
+        let LoggedCommand::Split { amount, from, .. } = &self.command else {
+            return vec![];
+        };
+        let explicit: Money = from.iter().filter_map(|t| t.amount).sum();
+        let wildcards = from.iter().filter(|t| t.amount.is_none()).count();
+        let mut rest = if wildcards > 0 {
+            split_equal_among(*amount - explicit, wildcards)
+        } else {
+            vec![]
+        }.into_iter();
+        let paid: TransactionChange = from.iter()
+            .map(|t| (t.member.clone(), t.amount.or_else(|| rest.next()).unwrap_or(Money::ZERO)))
+            .collect();
+        by_name(&self.change).into_iter()
+            .map(|(member, change)| (member.clone(), paid.get(member).copied().unwrap_or_default() - *change))
+            .filter(|(_, share)| !share.is_zero())
+            .collect()
+    }
+}
+
+/// What later entries of a log build on an entry, see `later_dependents`
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Dependents {
+    /// later entries changing the balance of a member the entry changed
+    pub(crate) shared: Vec<usize>,
+    /// later payments, which may have settled balances the entry is part of
+    pub(crate) settlements: Vec<usize>,
+    /// members the entry changed who are no longer in the group
+    pub(crate) removed: Vec<String>,
+}
+
+impl Dependents {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.shared.is_empty() && self.settlements.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Display for Dependents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indices = |indices: &[usize]| indices.iter().map(|index| format!("[{}]", index)).collect::<Vec<_>>().join(", ");
+        if !self.shared.is_empty() {
+            writeln!(f, "- {} later {} the same members: {}", self.shared.len(),
+                     if self.shared.len() == 1 { "entry involves" } else { "entries involve" }, indices(&self.shared))?;
+        }
+        if !self.settlements.is_empty() {
+            writeln!(f, "- {} recorded since: {}",
+                     if self.settlements.len() == 1 { "a payment was" } else { "payments were" }, indices(&self.settlements))?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "- {} left the group since", self.removed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans the entries after `log[index]` for what depends on it. `members` are the current members.
+pub(crate) fn later_dependents(log: &[LogEntry], index: usize, members: &[&String]) -> Dependents {
+    let Some(entry) = log.get(index) else {
+        return Dependents::default();
+    };
+    let involved: Vec<&String> = by_name(&entry.change).into_iter()
+        .filter(|(_, change)| !change.is_zero())
+        .map(|(member, _)| member)
+        .collect();
+    let mut dependents = Dependents::default();
+    for (later, other) in log.iter().enumerate().skip(index + 1) {
+        if matches!(other.command, LoggedCommand::Pay { .. }) {
+            dependents.settlements.push(later);
+        } else if involved.iter().any(|member| !other.change_of(member).is_zero()) {
+            dependents.shared.push(later);
+        }
+    }
+    dependents.removed = involved.into_iter()
+        .filter(|member| !members.contains(member))
+        .cloned()
+        .collect();
+    dependents
+}
+
+#[cfg(test)]
+mod logging_tests {
+    use super::*;
+
+    fn entry(command: LoggedCommand, change: &[(&str, Money)]) -> LogEntry {
+        LogEntry::new(command, change.iter().map(|(member, change)| (member.to_string(), *change)).collect())
+    }
+
+    fn pay(from: &str, to: &str) -> LogEntry {
+        entry(LoggedCommand::Pay { amount: Money(1_00), from: from.to_string(), to: to.to_string(), group: "g".to_string(),
+                                   settled_by: None },
+              &[(from, Money(1_00)), (to, Money(-1_00))])
+    }
+
+    #[test]
+    fn test_later_dependents() {
+        let opening = |member: &str, amount: Money| entry(LoggedCommand::Opening {
+            member: member.to_string(), amount, group: "g".to_string(), source: "f".to_string(),
+        }, &[(member, amount)]);
+        let log = vec![
+            pay("alice", "bob"),
+            opening("carol", Money(5_00)),
+            opening("bob", Money(2_00)),
+            pay("carol", "dora"),
+            opening("dora", Money(1_00)),
+        ];
+        let names: Vec<String> = ["alice", "carol", "dora"].map(String::from).to_vec();
+        let members: Vec<&String> = names.iter().collect();
+        let dependents = later_dependents(&log, 0, &members);
+        assert_eq!(dependents, Dependents { shared: vec![2], settlements: vec![3], removed: vec!["bob".to_string()] });
+        assert_eq!(dependents.to_string(), "- 1 later entry involves the same members: [2]\n\
+                                            - a payment was recorded since: [3]\n\
+                                            - bob left the group since\n");
+        assert_eq!(later_dependents(&log, 3, &members), Dependents { shared: vec![4], ..Default::default() });
+        assert!(later_dependents(&log, 4, &members).is_empty());
+        assert!(later_dependents(&log, 9, &members).is_empty());
+    }
+}
@@ -1,125 +1,443 @@
 use std::collections::HashMap;
-use std::io;
-use std::fmt::{Display, Formatter};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::string::ToString;
-use std::thread::sleep;
-use std::time::Duration;
-use brotli::{CompressorReader, Decompressor};
+use std::sync::OnceLock;
+use chrono::{DateTime, Local};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use crate::config::SubCommand;
+use unicode_normalization::UnicodeNormalization;
+use crate::budget::Budget;
+#[cfg(feature = "cli")]
+use crate::doctor::Check;
+use crate::info::DatabaseInfo;
+use crate::nag::{self, Stale};
+use crate::config::{BudgetAction, CompleteTarget, Period, RatioAction, StatFormat, SubCommand};
 use crate::error::*;
-use crate::group::Group;
+use crate::feed::{feed, FeedItem};
+use crate::export::{ledger, write_or_print, ynab, GroupExport, GroupImport, LedgerAccounts};
+use crate::group::{read_balances, split_by_weights, split_equal_among, Group, SplitOptions};
+use crate::logging::{later_dependents, LoggedCommand};
+use crate::money::{check_ceiling, eval_decimal, Currency, Decimal, Rate};
+use crate::period::Month;
+use crate::pager;
+use crate::prompt;
+use crate::porcelain::Porcelain;
+use crate::settle::Algorithm;
+use crate::shellenv::stat_env;
+use crate::render::{terminal_width, OutputMode, Table};
+use crate::journal::{self, Journal, JournalRecord, JournaledEntry};
+use crate::storage::{conflict_path, content_hash, read_database, FileStamp, StorageFormat};
+use crate::report::{batch_table, change_table, members_table, overview_table, preview_table, sort_overview, spending_by, spending_chart, top_table, Conversion,
+                    GroupOverview, MemberAcross, MergeReport, BRIEF_BALANCES};
 
-pub(crate) type Money = i64;
+pub(crate) use crate::money::Money;
 
 pub(crate) type TransactionChange = HashMap<String, Money>;
 
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct SplitterState {
     version: String,
     groups: Vec<Group>,
     current_group: Option<usize>,
+    /// bumped on every save that changes something, used by `sync`
+    #[serde(default)]
+    revision: u64,
+    /// the revision of the last `sync`
+    #[serde(default)]
+    synced_revision: u64,
+    /// print how abbreviated group names were resolved
+    #[serde(skip)]
+    verbose: bool,
 }
 
 impl SplitterState {
-    fn new(source: PathBuf) -> Self {
-        return if source.exists() {
+    fn new(source: PathBuf) -> Result<Self> {
+        Self::load(source).map(|(state, _, _)| state)
+    }
+
+    /// Loads the state with its journal replayed, together with a stamp of the file it came from
+    /// (`None` for a new database) and the journal
+    fn load(source: PathBuf) -> Result<(Self, Option<FileStamp>, Journal)> {
+        let journal = Journal::read(journal::path_for(&source))?;
+        if source.exists() {
             if source.is_file() {
-                let raw = std::fs::read(source).unwrap();
-                serde_yaml::from_str(
-                    Splitter::decompress(raw.as_slice()).as_str()
-                ).unwrap()
+                let (mut state, stamp) = read_database(&source, |yaml| Self::parse(yaml)
+                    .context(format!("'{:?}' is not a valid splitter database", source)))?;
+                state.replay(&journal).context(format!("Could not replay the journal of {:?}", source))?;
+                state.warn_residuals();
+                Ok((state, Some(stamp), journal))
             } else {
-                panic!("You specified '{:?}', which is not a file", source);
+                Err(anyhow!("You specified '{:?}', which is not a file", source))
             }
         } else {
             if source == PathBuf::new() {
                 println!("The Path to a persistent file is empty. \
                 If you meant to only temporary store the result of this call, ignore the message");
             }
-            Self {
+            Ok((Self {
                 version: Splitter::CURRENT_VERSION.to_string(),
                 groups: vec![],
                 current_group: None,
+                revision: 0,
+                synced_revision: 0,
+                verbose: false,
+            }, None, journal))
+        }
+    }
+
+    /// Applies the records of `journal` not yet contained in the state. Records up to the
+    /// revision of the state were already written to the database before the journal was cleared.
+    fn replay(&mut self, journal: &Journal) -> Result<()> {
+        let written = self.revision;
+        for record in journal.records().iter().filter(|record| record.revision > written) {
+            if record.revision != self.revision + 1 {
+                return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                    "The journal continues with revision {}, but the database is at revision {}",
+                    record.revision, self.revision));
             }
-        };
+            for JournaledEntry { group, entry } in &record.entries {
+                let gidx = self.groups.iter().position(|g| &g.name == group)
+                    .ok_or_else(|| self.group_not_found(Some(group.clone())))?;
+                self.groups[gidx].replay(entry.clone());
+            }
+            self.current_group = record.current_group;
+            self.revision = record.revision;
+        }
+        Ok(())
     }
 
-    /// get a reference to the group or panic
-    fn get_group(&self, group_name: Option<String>) -> Result<&Group> {
-        let group = match &group_name {
-            None => self.groups.get(self.current_group.unwrap_or(0)),
-            Some(name) => self.groups.iter().find(|&gn| gn.name == *name)
+    /// Warns about every group whose balances do not add up to zero, left by bugs of older versions.
+    /// They are not corrected without being asked to, see `fix-residual`.
+    fn warn_residuals(&self) {
+        for group in &self.groups {
+            let residual = group.residual();
+            if !residual.is_zero() {
+                eprintln!("Warning: the balances of group {} add up to {} instead of zero, correct them with \
+                           `splitter fix-residual {} --assign <member>` or `--spread`",
+                          group.name, group.currency.format(residual), group.name);
+            }
+        }
+    }
+
+    /// deserializes a state and brings data written by older versions up to date
+    fn parse(yaml: impl Read) -> Result<Self> {
+        let mut state: Self = serde_yaml::from_reader(yaml)?;
+        for group in state.groups.iter_mut() {
+            group.migrate();
+        }
+        Ok(state)
+    }
+
+    /// Checks that a new group can be called `name`: it has to be valid and differ from every group,
+    /// archived ones included, ignoring case unless `case_sensitive`
+    fn check_new_name(&self, name: &str, case_sensitive: bool) -> Result<()> {
+        self.check_name_besides(name, case_sensitive, None)
+    }
+
+    /// `check_new_name` ignoring the group at `except`, the one being renamed
+    fn check_name_besides(&self, name: &str, case_sensitive: bool, except: Option<usize>) -> Result<()> {
+        if !valid_name(name) {
+            return Err(anyhow!(SplitterError::InvalidName)).context(name.to_string());
+        }
+        let same = |other: &str| if case_sensitive { other == name } else { other.eq_ignore_ascii_case(name) };
+        let Some(other) = self.groups.iter().enumerate()
+            .find(|(idx, group)| Some(*idx) != except && same(&group.name))
+            .map(|(_, group)| group) else { return Ok(()) };
+        let problem = match (other.archived, other.name == name) {
+            (false, true) => format!("Group already exists! {}", name),
+            (false, false) => format!("Group '{}' already exists, group names are compared ignoring case", other.name),
+            (true, true) => format!("A group of this name is archived: '{}', pick another name or delete it first", name),
+            (true, false) => format!("An archived group of this name exists: '{}', group names are compared ignoring case; \
+                                      pick another name or delete it first", other.name),
         };
-        if let Some(g) = group {
-            Ok(g)
+        Err(anyhow!(SplitterError::InvalidName)).context(problem)
+    }
+
+    /// error for a failed group lookup, listing the existing groups and the default one
+    fn group_not_found(&self, group_name: Option<String>) -> anyhow::Error {
+        let names: Vec<&str> = self.groups.iter().map(|g| g.name.as_str()).collect();
+        let default = self.current_group.and_then(|gidx| self.groups.get(gidx))
+            .map_or("none".to_string(), |g| format!("'{}'", g.name));
+        anyhow!(SplitterError::GroupNotFound).context(format!(
+            "Group '{}' not found ({}; default group: {})",
+            group_name.unwrap_or("None".to_string()), valid_options("groups", &names), default))
+    }
+    /// Writes group `name` as JSON to `output`, `-` meaning stdout
+    fn export_group(&self, name: String, output: &Path) -> Result<()> {
+        let group = self.get_group(Some(name))?;
+        let export = GroupExport::new(group);
+        if output == Path::new("-") {
+            print!("{}", export.json()?);
         } else {
-            Err(anyhow!(SplitterError::GroupNotFound))
-                .context(group_name.unwrap_or("None".to_string()))
+            export.write(output)?;
+            println!("Exported group '{}' to {:?}", group.name, output);
+        }
+        Ok(())
+    }
+    /// Adds the group exported to `file`, called `rename` if given. The name must be free, the
+    /// member names valid and the balances must match the log. Returns the name of the new group.
+    fn import_group(&mut self, file: &Path, rename: Option<String>, case_sensitive: bool) -> Result<String> {
+        let GroupImport { version, mut group } = GroupImport::read(file)?;
+        if version != Splitter::CURRENT_VERSION {
+            eprintln!("Warning: {:?} was written by storage version {}, this splitter writes {}",
+                      file, version, Splitter::CURRENT_VERSION);
+        }
+        group.migrate();
+        for renamed in group.normalize_member_names() {
+            eprintln!("Warning: {}", renamed);
+        }
+        let renamed = rename.is_some();
+        if let Some(name) = rename {
+            group.rename(name);
+        }
+        self.check_new_name(&group.name, case_sensitive).map_err(|e| match renamed {
+            true => e,
+            false => e.context("import it under another name with --rename <name>"),
+        })?;
+        if let Some(member) = group.member_names().into_iter().find(|member| !valid_name(member)) {
+            return Err(anyhow!(SplitterError::InvalidName))
+                .context(format!("Member '{}' of the group in {:?} has an invalid name", member, file));
+        }
+        let problems = group.problems();
+        if !problems.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("The group in {:?} does not add up: {}", file, problems.join(", ")));
+        }
+        self.groups.push(group);
+        Ok(self.groups[self.groups.len() - 1].name.clone())
+    }
+    /// Looks up a group by its name or, if no name matches exactly, by an unambiguous prefix
+    fn find_group(&self, name: &str) -> Result<usize> {
+        if let Some(idx) = self.groups.iter().position(|g| g.name == name) {
+            return Ok(idx);
+        }
+        let candidates: Vec<usize> = self.groups.iter().enumerate()
+            .filter(|(_, g)| g.name.starts_with(name))
+            .map(|(idx, _)| idx)
+            .collect();
+        match candidates.as_slice() {
+            [] => Err(self.group_not_found(Some(name.to_string()))),
+            [idx] => {
+                if self.verbose {
+                    println!("Using group '{}' for '{}'", self.groups[*idx].name, name);
+                }
+                Ok(*idx)
+            }
+            _ => {
+                let names: Vec<&str> = candidates.iter().map(|idx| self.groups[*idx].name.as_str()).collect();
+                Err(anyhow!(SplitterError::GroupNotFound)).context(format!(
+                    "'{}' is ambiguous ({})", name, valid_options("candidates", &names)))
+            }
         }
     }
+    /// get a reference to the group or an error listing the valid groups
+    fn get_group(&self, group_name: Option<String>) -> Result<&Group> {
+        let idx = self.get_group_idx(group_name)?;
+        Ok(&self.groups[idx])
+    }
     fn get_group_mut(&mut self, group_name: Option<String>) -> Result<&mut Group> {
-        let group = match &group_name {
-            None => self.groups.get_mut(self.current_group.unwrap_or(0)),
-            Some(name) => self.groups.iter_mut().find(|gn| gn.name == *name)
-        };
-        if let Some(g) = group {
-            Ok(g)
-        } else {
-            Err(anyhow!(SplitterError::GroupNotFound)).context(group_name.unwrap_or("None".to_string()))
+        let idx = self.get_group_idx(group_name)?;
+        Ok(&mut self.groups[idx])
+    }
+    /// Index of the named group, the default group if no name is given. Without a default group,
+    /// e.g. after it was deleted, a single group is used and several ones are an error.
+    fn get_group_idx(&self, group_name: Option<String>) -> Result<usize> {
+        match group_name {
+            None => match self.current_group {
+                Some(gidx) if gidx < self.groups.len() => Ok(gidx),
+                _ if self.groups.len() == 1 => Ok(0),
+                _ if self.groups.is_empty() => Err(self.group_not_found(None)),
+                _ => {
+                    let names: Vec<&str> = self.groups.iter().map(|g| g.name.as_str()).collect();
+                    Err(anyhow!(SplitterError::GroupNotFound)).context(format!(
+                        "No default group is selected, name the group or pick one with `splitter use <group>` ({})",
+                        valid_options("groups", &names)))
+                }
+            },
+            Some(name) => self.find_group(name.as_str()),
         }
     }
-    fn get_group_idx(&mut self, group_name: Option<String>) -> Result<usize> {
-        if self.groups.is_empty() {
-            return Err(anyhow!(SplitterError::GroupNotFound))
-                .context(group_name.unwrap_or("None".to_string()));
+    /// Merges another copy of the database into this one, see `Group::merge`.
+    /// Nothing is changed if any group has conflicting entries.
+    fn merge(&mut self, theirs: SplitterState) -> Result<MergeReport> {
+        let conflicts: Vec<String> = theirs.groups.iter()
+            .filter_map(|group| self.groups.iter().find(|g| g.name == group.name)
+                .map(|ours| ours.conflicting_entries(group)))
+            .flatten()
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "Both copies have entries with the same id but different content, nothing was merged: {}",
+                conflicts.join(", ")));
         }
-        let gidx = match &group_name {
-            None => self.current_group.unwrap_or(0),
-            Some(name) => {
-                let idx = self.groups.iter().enumerate().find(|(_, g)|
-                    g.name == *name).map(|(n, _)| n);
-                if idx.is_none() {
-                    return Err(anyhow!(SplitterError::GroupNotFound))
-                        .context(group_name.unwrap_or("None".to_string()));
+        let mut report = MergeReport::default();
+        for group in theirs.groups {
+            match self.groups.iter_mut().find(|g| g.name == group.name) {
+                Some(ours) => {
+                    let (pulled, pushed) = ours.merge(&group)?;
+                    report.merged.push((group.name, pulled, pushed));
+                }
+                None => {
+                    report.new_groups.push(group.name.clone());
+                    self.groups.push(group);
                 }
-                idx.unwrap()
             }
+        }
+        Ok(report)
+    }
+    /// `name|members|Σowed` of the default group for the prompt of the interactive mode, Σ being what
+    /// its members are owed in total. `None` without groups.
+    fn status(&self) -> Option<String> {
+        let group = self.groups.get(self.get_group_idx(None).ok()?)?;
+        let owed: Money = group.member_overview().iter().map(|member| member.balance.max(Money::ZERO)).sum();
+        let members = group.member_names().len();
+        Some(format!("{}|{} {}|Σ{}", group.name, members, if members == 1 { "member" } else { "members" },
+                     if owed.is_zero() { "0".to_string() } else { group.currency.format(owed) }))
+    }
+    /// the rows of `groups`, archived groups only with `all`
+    fn overview(&self, all: bool) -> Vec<GroupOverview> {
+        let current = self.get_group_idx(None).ok();
+        let mut overview: Vec<GroupOverview> = self.groups.iter().enumerate()
+            .filter(|(_, group)| all || !group.archived)
+            .map(|(idx, group)| group.overview(Some(idx) == current))
+            .collect();
+        sort_overview(&mut overview);
+        overview
+    }
+    /// Renames group `old` to `new`, which is checked like the name of a new group. Returns the
+    /// previous name.
+    fn rename_group(&mut self, old: &str, new: String, case_sensitive: bool) -> Result<String> {
+        let idx = self.find_group(old)?;
+        self.check_name_besides(&new, case_sensitive, Some(idx))?;
+        let previous = self.groups[idx].name.clone();
+        if previous == new {
+            return Err(anyhow!(SplitterError::InvalidName)).context(format!("Group {} is already called that", new));
+        }
+        self.groups[idx].rename(new);
+        Ok(previous)
+    }
+
+    /// Starts group `name` with the members of `members_from` (the source by default) and the
+    /// balances of `source` as opening entries, then archives the source and makes the new
+    /// group the default. Returns whether it was done.
+    fn carry(&mut self, source: String, name: String, members_from: Option<String>, yes: bool, case_sensitive: bool)
+             -> Result<bool> {
+        self.check_new_name(&name, case_sensitive)?;
+        let sidx = self.find_group(&source)?;
+        let members: Vec<String> = match members_from {
+            Some(other) => self.get_group(Some(other))?.member_names().into_iter().cloned().collect(),
+            None => self.groups[sidx].member_names().into_iter().cloned().collect(),
         };
-        Ok(gidx)
+        let carried = self.groups[sidx].carried(name, members)?;
+        if !yes {
+            println!("This will archive group '{}' and start '{}' with these balances:\n",
+                     self.groups[sidx].name, carried.name);
+            println!("{}\n", carried.stat());
+            if !prompt::confirm() {
+                println!("Operation Cancelled");
+                return Ok(false);
+            }
+        }
+        self.groups[sidx].archived = true;
+        self.groups.push(carried);
+        self.current_group = Some(self.groups.len() - 1);
+        Ok(true)
     }
-    fn delete_group(&mut self, group_name: String, yes: bool) -> Result<()> {
-        println!("This will delete the group '{}' forever with no more undo options available.\n",
-                 group_name);
-        {
-            let group = self.get_group_mut(Some(group_name.clone()))?;
-            group.stat();
-        }
-        let really = yes || Splitter::confirm();
-        if really && !yes { // manually confirmed
-            println!("Confirmed. Deleting group");
-            self.groups = self.groups.drain(..)
-                .filter(|grp| grp.name != group_name).collect();
-            self.current_group = Some(0);
-        } else if yes { // silent mode if yes was specified
-            self.groups = self.groups.drain(..)
-                .filter(|grp| grp.name != group_name).collect();
-            self.current_group = Some(0);
-        } else { // !confirm && !yes
+    /// Deletes the named groups, or every group with `all`. Nothing is deleted
+    /// if one of the names does not match a group.
+    fn delete_groups(&mut self, group_names: Vec<String>, all: bool, yes: bool, full: bool,
+                     export_first: Option<PathBuf>) -> Result<()> {
+        let mut indices = if all {
+            (0..self.groups.len()).collect()
+        } else {
+            let (found, missing): (Vec<_>, Vec<_>) = group_names.iter()
+                .map(|name| (name, self.find_group(name)))
+                .partition(|(_, idx)| idx.is_ok());
+            if !missing.is_empty() {
+                let missing: Vec<&str> = missing.iter().map(|(name, _)| name.as_str()).collect();
+                let names: Vec<&str> = self.groups.iter().map(|g| g.name.as_str()).collect();
+                return Err(anyhow!(SplitterError::GroupNotFound)).context(format!(
+                    "Groups not found: {}, nothing was deleted ({})",
+                    missing.join(", "), valid_options("groups", &names)));
+            }
+            found.into_iter().map(|(_, idx)| idx.unwrap()).collect::<Vec<usize>>()
+        };
+        indices.sort();
+        indices.dedup();
+        if indices.is_empty() {
+            println!("There are no groups to delete");
+            return Ok(());
+        }
+        if export_first.is_some() && indices.len() > 1 {
+            return Err(anyhow!(SplitterError::LogicError))
+                .context("--export-first writes a single group, delete the groups one by one to export them");
+        }
+        match indices.as_slice() {
+            _ if all => println!("This will delete all {} {} forever with no more undo options available.\n",
+                                 indices.len(), if indices.len() == 1 { "group" } else { "groups" }),
+            [idx] => println!("This will delete the group '{}' forever with no more undo options available.\n",
+                              self.groups[*idx].name),
+            _ => println!("This will delete {} groups forever with no more undo options available.\n",
+                          indices.len()),
+        }
+        for idx in &indices {
+            print_at_stake(&self.groups[*idx], full);
+        }
+        if !yes && !prompt::confirm() {
+            println!("Operation Cancelled");
+            return Ok(());
+        }
+        if let Some(path) = export_first {
+            let group = &self.groups[indices[0]];
+            GroupExport::new(group).write(path.as_path())?;
+            println!("Exported group '{}' to {:?}", group.name, path);
+        }
+        if !yes { // silent mode if yes was specified
+            println!("Confirmed. Deleting {}", if indices.len() == 1 { "group" } else { "groups" });
+        }
+        for idx in indices.iter().rev() {
+            self.groups.remove(*idx);
+        }
+        // the default group stays selected if it is kept, its index shifting with the groups deleted before it
+        self.current_group = self.current_group.filter(|current| !indices.contains(current))
+            .map(|current| current - indices.iter().filter(|idx| **idx < current).count());
+        Ok(())
+    }
+    /// Clears the history of the named group after showing what will be lost, see `Group::reset`
+    fn reset_group(&mut self, name: &str, members: Option<Vec<String>>, yes: bool, full: bool) -> Result<()> {
+        let group = self.get_group_mut(Some(name.to_string()))?;
+        println!("This will clear the history of group '{}' forever with no more undo options available.\n",
+                 group.name);
+        print_at_stake(group, full);
+        if !yes && !prompt::confirm() {
             println!("Operation Cancelled");
+            return Ok(());
+        }
+        group.reset(members)?;
+        if !yes {
+            println!("Confirmed. Group '{}' starts over with {}", group.name,
+                     group.member_names().iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", "));
         }
-        self.current_group = None;
         Ok(())
     }
 }
 
+/// what is lost with a group deleted or reset, printed before asking; its whole `stat` with `full`
+fn print_at_stake(group: &Group, full: bool) {
+    if full {
+        println!("{}\n", group.stat());
+        println!("{}\n", group.summary().to_string(group.currency));
+    } else {
+        println!("{}\n", group.stat_data().brief(BRIEF_BALANCES));
+    }
+}
+
 #[cfg(test)]
 mod splitterstate_tests {
     use crate::group::Group;
+    use crate::logging::LoggedCommand;
     use super::*;
 
     #[test]
@@ -131,9 +449,12 @@ mod splitterstate_tests {
             version: Splitter::CURRENT_VERSION.to_string(),
             groups: vec![group.unwrap()],
             current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
         };
         assert_eq!(splitterstate.groups.len(), 1);
-        let r = splitterstate.delete_group("testgroup".to_string(), true);
+        let r = splitterstate.delete_groups(vec!["testgroup".to_string()], false, true, false, None);
         assert!(r.is_ok());
         assert_eq!(splitterstate.groups.len(), 0);
     }
@@ -147,79 +468,645 @@ mod splitterstate_tests {
             version: Splitter::CURRENT_VERSION.to_string(),
             groups: vec![group.unwrap()],
             current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
         };
         assert_eq!(splitterstate.groups.len(), 1);
-        let r = splitterstate.delete_group("txt".to_string(), true);
+        let r = splitterstate.delete_groups(vec!["txt".to_string()], false, true, false, None);
         assert!(r.is_err());
         assert_eq!(r.unwrap_err()
                        .downcast_ref(), Some(SplitterError::GroupNotFound).as_ref());
     }
+
+    #[test]
+    fn test_delete_groups() {
+        let state = || SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups: ["old1", "old2", "old3", "flat"].iter()
+                .map(|name| Group::new(name.to_string(), vec!["Alice".to_string()], None).unwrap())
+                .collect(),
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        let mut splitterstate = state();
+        splitterstate.delete_groups(vec!["old1".to_string(), "old3".to_string(), "old1".to_string()],
+                                    false, true, false, None).unwrap();
+        assert_eq!(splitterstate.groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(), vec!["old2", "flat"]);
+
+        // one missing name keeps every group
+        let mut splitterstate = state();
+        let err = splitterstate.delete_groups(vec!["old1".to_string(), "nope".to_string(), "gone".to_string()],
+                                              false, true, false, None).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::GroupNotFound).as_ref());
+        assert!(err.to_string().starts_with("Groups not found: nope, gone, nothing was deleted"), "{}", err);
+        assert_eq!(splitterstate.groups.len(), 4);
+
+        assert!(splitterstate.delete_groups(vec!["old1".to_string(), "old2".to_string()], false, true, false,
+                                            Some(PathBuf::from("export.json"))).is_err());
+        assert_eq!(splitterstate.groups.len(), 4);
+
+        splitterstate.delete_groups(vec![], true, true, false, None).unwrap();
+        assert!(splitterstate.groups.is_empty());
+    }
+
+    #[test]
+    fn test_group_prefix() {
+        let groups = ["skitrip-2024", "skitrip-2025", "flat", "flatmates"].iter()
+            .map(|name| Group::new(name.to_string(), vec!["Alice".to_string()], None).unwrap())
+            .collect();
+        let mut splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups,
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        assert_eq!(splitterstate.get_group_idx(Some("skitrip-2025".to_string())).unwrap(), 1);
+        assert_eq!(splitterstate.get_group_idx(Some("skitrip-2024".to_string())).unwrap(), 0);
+        // exact matches win over prefix matches
+        assert_eq!(splitterstate.get_group_idx(Some("flat".to_string())).unwrap(), 2);
+        assert_eq!(splitterstate.get_group_idx(Some("flatm".to_string())).unwrap(), 3);
+        let err = splitterstate.get_group_idx(Some("ski".to_string())).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::GroupNotFound).as_ref());
+        assert!(err.to_string().contains("valid candidates: skitrip-2024, skitrip-2025"));
+        assert!(splitterstate.get_group_idx(Some("beach".to_string())).is_err());
+
+        splitterstate.delete_groups(vec!["flatm".to_string()], false, true, false, None).unwrap();
+        assert_eq!(splitterstate.groups.len(), 3);
+        assert!(splitterstate.groups.iter().all(|g| g.name != "flatmates"));
+    }
+
+    #[test]
+    fn test_check_new_name() {
+        let mut groups: Vec<Group> = ["Trip", "old"].iter()
+            .map(|name| Group::new(name.to_string(), vec!["Alice".to_string()], None).unwrap())
+            .collect();
+        groups[1].archived = true;
+        let mut splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups,
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        let problem = |name: &str, case_sensitive: bool| splitterstate.check_new_name(name, case_sensitive)
+            .unwrap_err().to_string();
+        assert_eq!(problem("Trip", false), "Group already exists! Trip");
+        assert_eq!(problem("trip", false), "Group 'Trip' already exists, group names are compared ignoring case");
+        assert!(problem("old", true).starts_with("A group of this name is archived: 'old'"));
+        assert!(problem("OLD", false).starts_with("An archived group of this name exists: 'old'"));
+        assert!(problem("-x", false).starts_with("-x"));
+        assert!(splitterstate.check_new_name("trip", true).is_ok());
+        assert!(splitterstate.check_new_name("flat", false).is_ok());
+
+        assert!(splitterstate.carry("Trip".to_string(), "OLD".to_string(), None, true, false).is_err());
+        assert!(splitterstate.carry("Trip".to_string(), "OLD".to_string(), None, true, true).unwrap());
+    }
+
+    #[test]
+    fn test_rename_group() {
+        let mut groups: Vec<Group> = ["Trip", "flat"].iter()
+            .map(|name| Group::new(name.to_string(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap())
+            .collect();
+        groups[0].enable_integrity();
+        groups[0].split(Money(10_00), vec!["Alice".to_string()], vec![], "fuel".to_string(),
+                        SplitOptions::default()).unwrap();
+        groups[0].log_pay_transaction(Money(5_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        let mut state = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups,
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        assert!(state.rename_group("Trip", "FLAT".to_string(), false).is_err());
+        assert!(state.rename_group("Trip", "a b".to_string(), false).is_err());
+        assert!(state.rename_group("Trip", "Trip".to_string(), false).is_err());
+        assert!(state.rename_group("nope", "x".to_string(), false).is_err());
+
+        assert_eq!(state.rename_group("Tr", "trip".to_string(), false).unwrap(), "Trip");
+        assert_eq!(state.current_group, Some(0));
+        let trip = &state.groups[0];
+        assert_eq!(trip.name, "trip");
+        assert!(trip.log().iter().all(|entry| match &entry.command {
+            LoggedCommand::Split { group, .. } | LoggedCommand::Pay { group, .. } => group == "trip",
+            _ => false,
+        }));
+        let report = trip.verify().unwrap();
+        assert!(report.ends_with("(rename-group)"), "{}", report);
+    }
+
+    #[test]
+    fn test_overview() {
+        let mut groups: Vec<Group> = ["flat", "trip", "old"].iter()
+            .map(|name| Group::new(name.to_string(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap())
+            .collect();
+        groups[1].log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        groups[2].archived = true;
+        let splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups,
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        let overview = splitterstate.overview(false);
+        assert_eq!(overview.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(), vec!["trip", "flat"]);
+        assert_eq!(overview[0].entries, 1);
+        assert!(overview[0].net_zero);
+        assert!(overview[1].current && !overview[0].current);
+        assert_eq!(splitterstate.overview(true).len(), 3);
+
+        let table = overview_table(&overview).to_string();
+        assert!(table.lines().nth(3).unwrap().starts_with("→  flat"));
+        assert!(table.contains("✓"));
+    }
+
+    #[test]
+    fn test_status() {
+        let mut groups: Vec<Group> = ["flat", "solo"].iter()
+            .map(|name| Group::new(name.to_string(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap())
+            .collect();
+        groups[0].log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        groups[1] = Group::new("solo".to_string(), vec!["Carl".to_string()], None).unwrap();
+        let mut splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups,
+            current_group: None,
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        // several groups and none selected
+        assert_eq!(splitterstate.status(), None);
+        splitterstate.current_group = Some(0);
+        assert_eq!(splitterstate.status().unwrap(), "flat|2 members|Σ5.00€");
+        splitterstate.current_group = Some(1);
+        assert_eq!(splitterstate.status().unwrap(), "solo|1 member|Σ0");
+        splitterstate.groups.clear();
+        assert_eq!(splitterstate.status(), None);
+    }
+
+    #[test]
+    fn test_parse_legacy_pay() {
+        let yaml = r#"
+version: 0.1.0
+groups:
+- name: flat
+  currency: EUR
+  members:
+    Alice: 500
+    Bob: -500
+  log:
+  - command: !Pay
+      amount: 500
+      from: Alice
+      to: Bob
+    change:
+      Alice: -500
+      Bob: 500
+current_group: 0
+"#;
+        let state = SplitterState::parse(yaml.as_bytes()).unwrap();
+        let (_, entry) = state.groups[0].get_log(None).unwrap();
+        assert!(entry.time.is_none());
+        assert_eq!(entry.id.len(), 16);
+        assert_eq!(entry.change_of("Alice"), Money(500), "the change matches what was applied to the balances");
+        assert_eq!(entry.change_of("Bob"), Money(-500));
+        match &entry.command {
+            LoggedCommand::Pay { group, .. } => assert_eq!(group, "flat"),
+            _ => unreachable!("Command is not expected Variant")
+        }
+        assert_eq!(entry.to_string(0, Currency::EUR), "[0] unknown date by (unknown): pay: in group flat Alice → Bob 5.00€");
+    }
+
+    #[test]
+    fn test_money_format() {
+        // saved before amounts had a type, they still load and save as plain integers of cents
+        let fixture = include_str!("../tests/golden/money-format.yaml");
+        let state = SplitterState::parse(fixture.as_bytes()).unwrap();
+        let trip = &state.groups[0];
+        assert_eq!(trip.member_overview().iter().map(|m| m.balance).collect::<Vec<_>>(),
+                   vec![Money(-3_00), Money(-30_00), Money(33_00)]);
+        let (_, refund) = trip.get_log(None).unwrap();
+        assert!(matches!(refund.command, LoggedCommand::Split { amount: Money(-6_00), .. }));
+        assert_eq!(refund.change_of("alice"), Money(-4_00));
+        assert!(trip.problems().is_empty(), "{:?}", trip.problems());
+        assert_eq!(serde_yaml::to_string(&state).unwrap(), fixture);
+    }
+
+    #[test]
+    fn test_export_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("flat.json");
+        let mut group = Group::new("flat".to_owned(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        group.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        let state = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups: vec![group],
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        state.export_group("fl".to_string(), &export).unwrap();
+        let exported: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&export).unwrap()).unwrap();
+        assert_eq!(exported["version"], Splitter::CURRENT_VERSION);
+        assert_eq!(exported["group"]["members"]["Alice"], 5_00);
+        assert_eq!(exported["group"]["log"].as_array().unwrap().len(), 1);
+
+        let err = state.export_group("trip".to_string(), &export).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::GroupNotFound).as_ref());
+    }
+
+    #[test]
+    fn test_import_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("flat.json");
+        let mut group = Group::new("flat".to_owned(), vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        group.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        GroupExport::new(&group).write(&export).unwrap();
+        let mut state = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups: vec![group],
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+
+        let err = state.import_group(&export, None, false).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::InvalidName).as_ref());
+        assert!(format!("{:#}", err).starts_with("import it under another name with --rename <name>: Group already exists"));
+        assert!(state.import_group(&export, Some("FLAT".to_string()), false).is_err(), "case is ignored");
+        assert_eq!(state.import_group(&export, Some("flat2".to_string()), false).unwrap(), "flat2");
+        let imported = &state.groups[1];
+        assert_eq!(imported.log().len(), 1);
+        assert_eq!(imported.member_overview()[0].balance, Money(5_00));
+        let LoggedCommand::Pay { group, .. } = &imported.log()[0].command else { unreachable!() };
+        assert_eq!(group, "flat2");
+
+        let json = std::fs::read_to_string(&export).unwrap();
+        std::fs::write(&export, json.replace("\"Bob\"", "\"B o b\"")).unwrap();
+        let err = state.import_group(&export, Some("flat3".to_string()), false).unwrap_err();
+        assert!(format!("{:#}", err).contains("Member 'B o b'"), "{:#}", err);
+        std::fs::write(&export, json.replace("\"Bob\": -500", "\"Bob\": -400")).unwrap();
+        let err = state.import_group(&export, Some("flat3".to_string()), false).unwrap_err();
+        assert!(format!("{:#}", err).contains("does not add up"), "{:#}", err);
+        assert_eq!(state.groups.len(), 2);
+    }
+
+    #[test]
+    fn test_delete_group_export_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("testgroup.json");
+        let mut group =
+            Group::new("testgroup".to_owned(),
+                       vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        group.log_pay_transaction(Money(5_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        let mut splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups: vec![group],
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        let r = splitterstate.delete_groups(vec!["testgroup".to_string()], false, true, false, Some(export.clone()));
+        assert!(r.is_ok());
+        assert_eq!(splitterstate.groups.len(), 0);
+        let exported: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(export).unwrap().as_str()).unwrap();
+        assert_eq!(exported["version"], Splitter::CURRENT_VERSION);
+        assert_eq!(exported["group"]["name"], "testgroup");
+        assert_eq!(exported["group"]["log"].as_array().unwrap().len(), 1);
+
+        // an export that cannot be written keeps the group
+        let mut splitterstate = SplitterState {
+            version: Splitter::CURRENT_VERSION.to_string(),
+            groups: vec![Group::new("testgroup".to_owned(), vec!["Alice".to_string()], None).unwrap()],
+            current_group: Some(0),
+            revision: 0,
+            synced_revision: 0,
+            verbose: false,
+        };
+        let r = splitterstate.delete_groups(vec!["testgroup".to_string()], false, true, false,
+                                            Some(dir.path().join("missing/dir/export.json")));
+        assert!(r.is_err());
+        assert_eq!(splitterstate.groups.len(), 1);
+    }
+}
+
+/// the name of a target as written
+struct TargetName {
+    name: String,
+    /// given in quotes, which allows any character
+    quoted: bool,
+}
+
+/// the part of a target after the `:`
+enum TargetAmount {
+    Rest,
+    /// `position` is the byte offset of the amount in the target
+    Value { value: Decimal, percent: bool, position: usize },
+    /// a percentage of an equal share, as written, resolved by `split_into_transaction`
+    Share(String),
+    /// how many shares of the rest the member carries, like `2x`
+    Weight(u64),
+}
+
+/// Hand-written parser for the targets of `--from` and `--to`:
+///
+/// ```text
+/// target := name (':' amount)?
+/// name   := '"' (char | '\' char)* '"' | (char except ':' and '"' | '\' char)+
+/// amount := 'rest' | digits 'x' | expression ('%' | '%share')?
+/// ```
+///
+/// where `expression` is an amount expression, see `eval_decimal`. Errors carry the byte offset
+/// of the problem in the target.
+struct TargetParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+type TargetError = (usize, String);
+
+impl TargetParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn target(&mut self) -> Result<(TargetName, Option<TargetAmount>), TargetError> {
+        let name = self.name()?;
+        match self.bump() {
+            None => Ok((name, None)),
+            Some(':') => Ok((name, Some(self.amount()?))),
+            Some(c) => Err((self.position - c.len_utf8(), format!("unexpected '{}' after the name, expected ':'", c))),
+        }
+    }
+
+    fn name(&mut self) -> Result<TargetName, TargetError> {
+        let start = self.position;
+        let mut name = String::new();
+        let quoted = self.peek() == Some('"');
+        if quoted {
+            self.bump();
+            loop {
+                match self.bump() {
+                    None => return Err((start, "the quote is never closed".to_string())),
+                    Some('"') => break,
+                    Some('\\') => name.push(self.escaped()?),
+                    Some(c) => name.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = self.peek() {
+                match c {
+                    ':' => break,
+                    '"' => return Err((self.position, "unexpected '\"', quote the whole name".to_string())),
+                    _ => {
+                        self.bump();
+                        name.push(if c == '\\' { self.escaped()? } else { c });
+                    }
+                }
+            }
+        }
+        if name.is_empty() {
+            return Err((start, "the name is missing".to_string()));
+        }
+        Ok(TargetName { name, quoted })
+    }
+
+    /// the character after a `\`
+    fn escaped(&mut self) -> Result<char, TargetError> {
+        let backslash = self.position - 1;
+        self.bump().ok_or((backslash, "nothing to escape after '\\'".to_string()))
+    }
+
+    fn amount(&mut self) -> Result<TargetAmount, TargetError> {
+        let position = self.position;
+        let raw = &self.input[position..];
+        self.position = self.input.len();
+        if raw.trim().is_empty() {
+            return Err((position, "the amount after ':' is missing".to_string()));
+        }
+        if raw.trim() == "rest" {
+            return Ok(TargetAmount::Rest);
+        }
+        if let Some(count) = raw.trim().strip_suffix('x') {
+            return match count.trim().parse::<u64>() {
+                Ok(count) if count > 0 => Ok(TargetAmount::Weight(count)),
+                _ => Err((position, "the number of shares before 'x' must be a whole number above 0".to_string())),
+            };
+        }
+        let share = raw.trim_end().strip_suffix("%share");
+        let (expression, percent) = match share.or_else(|| raw.trim_end().strip_suffix('%')) {
+            Some(expression) => (expression, true),
+            None => (raw, false),
+        };
+        if let Some(at) = expression.find('%') {
+            return Err((position + at, "'%' can only come at the end of the amount".to_string()));
+        }
+        if expression.trim().is_empty() {
+            return Err((position, "the amount before '%' is missing".to_string()));
+        }
+        let value = eval_decimal(expression).map_err(|error| (position, format!("invalid amount: {}", error)))?;
+        if share.is_some() {
+            return Ok(TargetAmount::Share(expression.trim().to_string()));
+        }
+        Ok(TargetAmount::Value { value, percent, position })
+    }
 }
 
 /// helper struct containing money and a name. Can be used as a "from" or as a "to"
-/// Can be parsed from --from/to {name}[:amount[%]]
-#[derive(PartialEq, Serialize, Deserialize, Debug)]
+/// Can be parsed from --from/to {name}[:amount[%|%share]], {name}:rest or {name}:{count}x, see `TargetParser`
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub(crate) struct Target {
     pub(crate) member: String,
-    pub(crate) amount: Option<i64>,
+    pub(crate) amount: Option<Money>,
+    /// whether the amount is what remained after the other amounts of the list, see `parse_multiple`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) rest: bool,
+    /// the percentage of an equal share given with `%share`, as written. The amount is filled in
+    /// by `split_into_transaction` once the share is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) share: Option<String>,
+    /// the amount as written if it was more than a plain number, like `10%` or `3*4.20`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expression: Option<String>,
+    /// the number of shares of the rest given with `x`. The amount is filled in by
+    /// `split_into_transaction` once the rest is shared out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) weight: Option<u64>,
 }
 
 impl Target {
-    /// Parses a target directive specified via `--from` or `--to` into a Target Struct
-    fn parse(input: &str, total_money: i64) -> Result<Self> {
-        let in_split: Vec<_> = input.trim_end_matches('%').split(':').collect();
-        if in_split[0].is_empty() {
-            return Err(anyhow!(SplitterError::InvalidTargetFormat))
-                .context("Please use the format <name>:[<number>[%]]. (maybe you forgot ':'?");
+    /// Parses a target directive specified via `--from` or `--to` into a Target Struct, see `TargetParser`.
+    /// Amounts are read in `currency`, with as many decimals as it has.
+    fn parse(input: &str, total_money: Money, currency: Currency) -> Result<Self> {
+        let invalid = |(position, problem): (usize, String)| anyhow!(SplitterError::InvalidTargetFormat)
+            .context(format!("Invalid target '{}' at byte {}: {}", input, position, problem));
+        let mut parser = TargetParser { input, position: 0 };
+        let (name, amount) = parser.target().map_err(invalid)?;
+        let member = normalize_name(&name.name);
+        match amount {
+            None => {
+                if !name.quoted && !valid_name(&member) {
+                    return Err(anyhow!(SplitterError::InvalidName))
+                        .context(format!("Invalid target '{}': '{}' is no valid member name", input, member));
+                }
+                Ok(Self { member, amount: None, rest: false, share: None, expression: None, weight: None })
+            }
+            Some(TargetAmount::Rest) => Ok(Self { member, amount: None, rest: true, share: None, expression: None, weight: None }),
+            Some(TargetAmount::Share(share)) =>
+                Ok(Self { member, amount: None, rest: false, share: Some(share), expression: None, weight: None }),
+            Some(TargetAmount::Weight(weight)) =>
+                Ok(Self { member, amount: None, rest: false, share: None, expression: None, weight: Some(weight) }),
+            Some(TargetAmount::Value { value, percent, position }) => {
+                let invalid = |problem: &str| invalid((position, problem.to_string()));
+                let amount = if percent {
+                    value.percent_of(total_money)
+                        .ok_or_else(|| invalid("the percentage is too large"))?
+                } else {
+                    let amount = value.to_minor(currency.decimals())
+                        .ok_or_else(|| invalid(&format!("{} are allowed in {:?}", currency.allowed_decimals(), currency)))?
+                        .try_into()
+                        .map(Money)
+                        .map_err(|_| invalid("the amount does not fit into 64 bits"))?;
+                    check_ceiling(amount).map_err(|problem| invalid(&problem))?
+                };
+                let written = input[position..].trim();
+                let plain = written.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',');
+                let expression = if plain { None } else { Some(written.to_string()) };
+                Ok(Self { member, amount: Some(amount), rest: false, share: None, expression, weight: None })
+            }
         }
-        if in_split.len() == 2 {
-            let amount = if input.ends_with('%') {
-                let percent: f32 = in_split[1].trim().replace(',', ".").parse::<f32>()?
-                    / 100.;
-                (percent * total_money as f32) as i64
-            } else {
-                let amount: f32 = in_split[1].trim().replace(',', ".").parse::<f32>()?
-                    * 100.;
-                amount.round() as i64
-            };
-            Ok(Self {
-                member: in_split[0].to_owned(),
-                amount: Some(amount),
-            })
-        } else if in_split.len() == 1 {
-            if !Regex::new(Splitter::NAME_REGEX).unwrap().is_match(in_split[0]) {
-                return Err(anyhow!(SplitterError::InvalidName))
-                    .context(in_split[0].to_string())
-                ;
-            }
-            Ok(Self {
-                member: in_split[0].to_owned(),
-                amount: None,
-            })
-        } else {
-            Err(anyhow!(SplitterError::InvalidTargetFormat))
-                .context("Please use the format <name>:[<number>[%]]. (maybe you forgot ':'?")
+    }
+    /// the target in the syntax `parse` reads, resolving to the same amount again among the same members
+    pub(crate) fn raw(&self, currency: Currency) -> String {
+        match (self.amount, &self.share) {
+            (_, Some(share)) => format!("{}:{}%share", self.member, share),
+            _ if self.weight.is_some() => format!("{}:{}x", self.member, self.weight.unwrap_or_default()),
+            _ if self.rest => format!("{}:rest", self.member),
+            (Some(_), None) if self.expression.is_some() =>
+                format!("{}:{}", self.member, self.expression.as_deref().unwrap_or_default()),
+            // refunds log their amounts negative, but are given positive like a split
+            (Some(amount), None) => format!("{}:{}", self.member, amount.abs().to_decimal_string(currency)),
+            (None, None) => self.member.clone(),
+        }
+    }
+    /// Reads the targets of --from-file or --to-file, one per line in the usual syntax.
+    /// Blank lines and everything after a `#` are ignored. Each target is checked here already
+    /// so that errors point to the file and line.
+    pub(crate) fn read_file(path: &Path, total_amount: Money, currency: Currency) -> Result<Vec<String>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read targets from {}", path.display()))?;
+        let mut targets = vec![];
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            for target in Target::split_combined(line) {
+                Target::parse(&target, total_amount, currency)
+                    .with_context(|| format!("{}:{}: invalid target '{}'", path.display(), number + 1, line))?;
+            }
+            targets.push(line.to_string());
+        }
+        Ok(targets)
+    }
+
+    /// Splits a single --from/--to argument holding several targets like `alice:10,50,bob`.
+    /// A comma followed by a letter starts a new target, any other comma is a decimal separator.
+    /// Commas in quoted names or escaped with `\` never split.
+    fn split_combined(raw: &str) -> Vec<String> {
+        let mut targets = vec![String::new()];
+        let mut chars = raw.chars().peekable();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            if c == ',' && !quoted && chars.peek().is_some_and(|next| next.is_alphabetic() || *next == '"') {
+                targets.push(String::new());
+                continue;
+            }
+            let target = targets.last_mut().unwrap();
+            target.push(c);
+            match c {
+                '"' => quoted = !quoted,
+                '\\' => target.extend(chars.next()),
+                _ => {}
+            }
         }
+        targets
     }
+
     /// Parses entries that originate with --from or --to arguments.
     /// it returns the list of the names together with an option denoting their amounts.
     /// None means they did not specify an amount.
     /// The second return value is the total amount that was explicitly given
     /// The third return value is the number of wildcard givers
-    pub(crate) fn parse_multiple(raw_targets: Vec<String>, total_amount: i64) -> Result<(Vec<Target>, i64, usize)> {
+    /// A single `name:rest` target gets whatever the other amounts leave of the total,
+    /// so together they are the total amount.
+    #[cfg(any(feature = "cli", test))]
+    pub(crate) fn parse_multiple(raw_targets: Vec<String>, total_amount: Money, currency: Currency)
+                                 -> Result<(Vec<Target>, Money, usize)> {
+        let parsed = Self::parse_unchecked(raw_targets, total_amount, currency)?;
+        if parsed.1.abs() > total_amount
+            || parsed.0.iter().any(|target| target.amount.is_some_and(|amount| amount.is_negative())) {
+            let summed: Money = parsed.0.iter().filter(|target| !target.rest).filter_map(|target| target.amount).sum();
+            return Err(anyhow!(SplitterError::LogicError))
+                .context(format!("Error: The amounts specified with '--from' or '--to' sum up to more than the total amount: {} vs {}",
+                                 summed.0, total_amount.0));
+        }
+        Ok(parsed)
+    }
+
+    /// Like `parse_multiple`, but amounts adding up to more than `total_amount` are left to the
+    /// caller to explain. The sum is then larger than the total or the rest target negative.
+    pub(crate) fn parse_unchecked(raw_targets: Vec<String>, total_amount: Money, currency: Currency)
+                                  -> Result<(Vec<Target>, Money, usize)> {
+        let raw_targets: Vec<String> = raw_targets.iter().flat_map(|raw| Target::split_combined(raw)).collect();
         let mut targets_parsed = Vec::with_capacity(raw_targets.len());
-        let mut summed = 0i64;
+        let mut summed = Money::ZERO;
         let mut wildcard_givers = 0usize;
         for giver in &raw_targets {
-            targets_parsed.push(Target::parse(giver.as_str(), total_amount)?);
-            let t_amount = targets_parsed.last().unwrap().amount;
-            summed += t_amount.unwrap_or(0);
-            wildcard_givers += if t_amount.is_none() { 1 } else { 0 };
+            targets_parsed.push(Target::parse(giver.as_str(), total_amount, currency)?);
+            let target = targets_parsed.last().unwrap();
+            summed = summed.checked_add(target.amount.unwrap_or(Money::ZERO))
+                .ok_or_else(|| anyhow!(SplitterError::InvalidTargetFormat))
+                .context("The amounts specified with '--from' or '--to' do not fit into 64 bits when summed up")?;
+            wildcard_givers += if target.amount.is_none() && !target.rest && target.share.is_none() && target.weight.is_none() {
+                1
+            } else {
+                0
+            };
         }
-        if summed.abs() > total_amount {
-            return Err(anyhow!(SplitterError::LogicError))
-                .context(format!("Error: The amounts specified with '--from' or '--to' sum up to more than the total amount: {} vs {}",
-                                 summed, total_amount));
+        let rests = targets_parsed.iter().filter(|t| t.rest).count();
+        if rests > 1 {
+            return Err(anyhow!(SplitterError::InvalidTargetFormat))
+                .context("Only one target per --from or --to list can take the rest");
+        }
+        if rests == 1 {
+            if wildcard_givers > 0 || targets_parsed.iter().any(|t| t.share.is_some() || t.weight.is_some()) {
+                return Err(anyhow!(SplitterError::InvalidTargetFormat))
+                    .context("A rest target cannot be combined with targets without an amount, with a share or shares");
+            }
+            let rest = targets_parsed.iter_mut().find(|t| t.rest).unwrap();
+            rest.amount = Some(total_amount - summed);
+            summed = total_amount;
         }
         Ok((targets_parsed, summed, wildcard_givers))
     }
@@ -227,66 +1114,189 @@ impl Target {
 
 #[cfg(test)]
 mod target_tests {
-    use crate::logic::{Target};
+    use crate::logic::{normalize_name, Money, Target};
+    use crate::money::Currency;
 
     #[test]
     fn test_target_parse() {
 
         // valid cases
         let case_absolute_amount_comma = "peter:25,22";
-        let ft = Target::parse(case_absolute_amount_comma, 100_00);
+        let ft = Target::parse(case_absolute_amount_comma, Money(100_00), Currency::EUR);
         assert!(ft.is_ok());
         let ft = ft.unwrap();
         assert_eq!(ft.member, "peter");
-        assert_eq!(ft.amount.unwrap(), 25_22);
+        assert_eq!(ft.amount.unwrap(), Money(25_22));
 
         let case_absolute_amount_dot = "peter:25.22";
-        let ft = Target::parse(case_absolute_amount_dot, 100_00);
+        let ft = Target::parse(case_absolute_amount_dot, Money(100_00), Currency::EUR);
         assert!(ft.is_ok());
         let ft = ft.unwrap();
         assert_eq!(ft.member, "peter");
-        assert_eq!(ft.amount.unwrap(), 25_22);
+        assert_eq!(ft.amount.unwrap(), Money(25_22));
 
 
         let case_percentage = "peter:10%";
-        let ft = Target::parse(case_percentage, 100_00);
+        let ft = Target::parse(case_percentage, Money(100_00), Currency::EUR);
         assert!(ft.is_ok());
         let ft = ft.unwrap();
         assert_eq!(ft.member, "peter");
-        assert_eq!(ft.amount.unwrap(), 10_00);
+        assert_eq!(ft.amount.unwrap(), Money(10_00));
+        assert_eq!(ft.expression.as_deref(), Some("10%"));
+        assert_eq!(ft.raw(Currency::EUR), "peter:10%");
+        assert_eq!(Target::parse("peter:25,22", Money(100_00), Currency::EUR).unwrap().expression, None);
+
+        let case_expression = "peter:2*4.20+1";
+        let ft = Target::parse(case_expression, Money(100_00), Currency::EUR).unwrap();
+        assert_eq!((ft.amount, ft.expression.as_deref()), (Some(Money(9_40)), Some("2*4.20+1")));
+        let case_percentage_expression = "peter:(100-25)/3%";
+        assert!(Target::parse(case_percentage_expression, Money(100_00), Currency::EUR).is_err());
+        let case_percentage_expression = "peter:12.5*2%";
+        assert_eq!(Target::parse(case_percentage_expression, Money(100_00), Currency::EUR).unwrap().amount, Some(Money(25_00)));
+        let case_rest = "peter:rest";
+        assert_eq!(Target::parse(case_rest, Money(100_00), Currency::EUR).unwrap(),
+                   Target { member: "peter".to_string(), amount: None, rest: true, share: None, expression: None, weight: None });
+        assert!(Target::parse("peter:rest%", Money(100_00), Currency::EUR).is_err());
+        let case_err_decimals = "peter:1.234";
+        let ft = Target::parse(case_err_decimals, Money(100_00), Currency::EUR);
+        assert!(format!("{:#}", ft.unwrap_err()).contains("at most 2 decimals"));
 
 
         // invalid cases
         let case_err_nosplit = "peter25,22";
-        let ft = Target::parse(case_err_nosplit, 100_00);
+        let ft = Target::parse(case_err_nosplit, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
         let case_err_noamount = "peter";
-        let ft = Target::parse(case_err_noamount, 100_00);
+        let ft = Target::parse(case_err_noamount, Money(100_00), Currency::EUR);
         assert!(ft.is_ok());
         let case_err_noname = "25,22";
-        let ft = Target::parse(case_err_noname, 100_00);
+        let ft = Target::parse(case_err_noname, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
         let case_err_noamount_percentage = "peter:%";
-        let ft = Target::parse(case_err_noamount_percentage, 100_00);
+        let ft = Target::parse(case_err_noamount_percentage, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
         let case_err_nothing = ":";
-        let ft = Target::parse(case_err_nothing, 100_00);
+        let ft = Target::parse(case_err_nothing, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
         let case_err_noname_double = ":25,22";
-        let ft = Target::parse(case_err_noname_double, 100_00);
+        let ft = Target::parse(case_err_noname_double, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
         let case_err_noamount_double = "peter:";
-        let ft = Target::parse(case_err_noamount_double, 100_00);
+        let ft = Target::parse(case_err_noamount_double, Money(100_00), Currency::EUR);
         assert!(ft.is_err());
     }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("bob "), "bob");
+        assert_eq!(normalize_name("\u{a0}bob\u{a0}"), "bob");
+        assert_eq!(normalize_name("Aunt \u{a0}\t Erna"), "Aunt Erna");
+        assert_eq!(normalize_name("\u{feff}bo\u{200b}b\u{2060}"), "bob");
+        assert_eq!(normalize_name("Ame\u{301}lie"), "Am\u{e9}lie");
+        assert_eq!(normalize_name("\u{a0} \u{200d}"), "");
+        assert_eq!(Target::parse("bob\u{a0}:5", Money(10_00), Currency::EUR).unwrap().member, "bob");
+        assert_eq!(Target::parse("\"Ame\u{301}lie \"", Money(10_00), Currency::EUR).unwrap().member, "Am\u{e9}lie");
+    }
+
+    #[test]
+    fn test_target_grammar() {
+        let parsed = |input: &str| Target::parse(input, Money(100_00), Currency::EUR).map(|t| (t.member, t.amount, t.rest));
+        let ok = |member: &str, amount: Option<Money>, rest: bool| Some((member.to_string(), amount, rest));
+        assert_eq!(parsed(r#""Aunt Erna":12.50"#).ok(), ok("Aunt Erna", Some(Money(12_50)), false));
+        assert_eq!(parsed(r#""a\"b\\c":1"#).ok(), ok(r#"a"b\c"#, Some(Money(1_00)), false));
+        assert_eq!(parsed(r#""50%: off":10%"#).ok(), ok("50%: off", Some(Money(10_00)), false));
+        assert_eq!(parsed(r"bob\:x:2").ok(), ok("bob:x", Some(Money(2_00)), false));
+        assert_eq!(parsed(r#""Aunt Erna""#).ok(), ok("Aunt Erna", None, false));
+        assert_eq!(parsed(r#""Aunt Erna":rest"#).ok(), ok("Aunt Erna", None, true));
+        assert_eq!(parsed("peter: rest ").ok(), ok("peter", None, true));
+        assert_eq!(parsed("peter:10 %").ok(), ok("peter", Some(Money(10_00)), false));
+        assert_eq!(parsed("jürgen:5").ok(), ok("jürgen", Some(Money(5_00)), false));
+        assert_eq!(parsed("élodie:5%").ok(), ok("élodie", Some(Money(5_00)), false));
+        assert_eq!(parsed("bob:50%share").ok(), ok("bob", None, false));
+        assert_eq!(Target::parse("bob: 12.5*2 %share", Money(100_00), Currency::EUR).unwrap().share.as_deref(), Some("12.5*2"));
+        assert_eq!(parsed("bob:2x").ok(), ok("bob", None, false));
+        assert_eq!(Target::parse("bob: 3x ", Money(100_00), Currency::EUR).unwrap().weight, Some(3));
+        assert_eq!(Target::parse("bob:3x", Money(100_00), Currency::EUR).unwrap().raw(Currency::EUR), "bob:3x");
+
+        // the input, and the start of the expected error
+        let errors = [
+            ("", "at byte 0: the name is missing"),
+            (":5", "at byte 0: the name is missing"),
+            (r#""":5"#, "at byte 0: the name is missing"),
+            ("peter::12", "at byte 6: invalid amount: unexpected ':'"),
+            ("peter:12:3", "at byte 6: invalid amount: unexpected ':'"),
+            (":::", "at byte 0: the name is missing"),
+            ("peter:", "at byte 6: the amount after ':' is missing"),
+            ("peter:  ", "at byte 6: the amount after ':' is missing"),
+            ("peter:%", "at byte 6: the amount before '%' is missing"),
+            ("peter:12%extra", "at byte 8: '%' can only come at the end"),
+            ("peter:10%%", "at byte 8: '%' can only come at the end"),
+            ("peter:10%shares", "at byte 8: '%' can only come at the end"),
+            ("peter:%share", "at byte 6: the amount before '%' is missing"),
+            ("peter:rest%", "at byte 6: invalid amount"),
+            ("peter:1e3", "at byte 6: invalid amount"),
+            ("peter:1.234", "at byte 6: at most 2 decimals"),
+            ("peter:0x", "at byte 6: the number of shares before 'x' must be a whole number above 0"),
+            ("peter:1.5x", "at byte 6: the number of shares before 'x'"),
+            ("peter:x", "at byte 6: the number of shares before 'x'"),
+            (r#""bob"#, "at byte 0: the quote is never closed"),
+            (r#""bob"x:1"#, "at byte 5: unexpected 'x' after the name"),
+            (r#"bo"b":1"#, "at byte 2: unexpected '\"', quote the whole name"),
+            (r"bob\", "at byte 3: nothing to escape"),
+            (r#""jürgen"€:1"#, "at byte 9: unexpected '€' after the name"),
+        ];
+        for (input, expected) in errors {
+            let err = format!("{:#}", Target::parse(input, Money(100_00), Currency::EUR).unwrap_err());
+            assert!(err.starts_with(&format!("Invalid target '{}' {}", input, expected)), "{}: {}", input, err);
+        }
+        // names without an amount must be valid member names unless they are quoted
+        for input in ["jürgen", "a b", "25,22", "peter%"] {
+            let err = format!("{:#}", Target::parse(input, Money(100_00), Currency::EUR).unwrap_err());
+            assert!(err.contains("is no valid member name"), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_read_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shares.txt");
+        std::fs::write(&path, "# monthly shares\nalice:10\n\n  bob:25% # half of it\ncharly\n").unwrap();
+        assert_eq!(Target::read_file(&path, Money(100_00), Currency::EUR).unwrap(), vec!["alice:10", "bob:25%", "charly"]);
+
+        std::fs::write(&path, "alice:10\n\nbob:x\n").unwrap();
+        let err = format!("{:#}", Target::read_file(&path, Money(100_00), Currency::EUR).unwrap_err());
+        assert!(err.contains("shares.txt:3: invalid target 'bob:x'"), "{}", err);
+        assert!(Target::read_file(&dir.path().join("missing.txt"), Money(100_00), Currency::EUR).is_err());
+    }
+
+    #[test]
+    fn test_split_combined() {
+        assert_eq!(Target::split_combined("alice:10,50,bob"), vec!["alice:10,50", "bob"]);
+        assert_eq!(Target::split_combined("alice:10,bob,charly:5"), vec!["alice:10", "bob", "charly:5"]);
+        assert_eq!(Target::split_combined("alice,bob:2,5%,charly:rest"), vec!["alice", "bob:2,5%", "charly:rest"]);
+        assert_eq!(Target::split_combined("alice:1,5*2,bob"), vec!["alice:1,5*2", "bob"]);
+        assert_eq!(Target::split_combined("alice:25,22"), vec!["alice:25,22"]);
+        assert_eq!(Target::split_combined("jürgen,élodie"), vec!["jürgen", "élodie"]);
+        assert_eq!(Target::split_combined(r#""Aunt, Erna":5,bob"#), vec![r#""Aunt, Erna":5"#, "bob"]);
+        assert_eq!(Target::split_combined(r#"alice:10,"Aunt Erna""#), vec!["alice:10", r#""Aunt Erna""#]);
+        assert_eq!(Target::split_combined(r"a\,b:1,c"), vec![r"a\,b:1", "c"]);
+
+        let combined = vec!["alice:10,50,bob".to_string(), "charly:5".to_string()];
+        let (targets, summed, wildcards) = Target::parse_multiple(combined, Money(100_00), Currency::EUR).unwrap();
+        assert_eq!(targets.iter().map(|t| (t.member.as_str(), t.amount)).collect::<Vec<_>>(),
+                   vec![("alice", Some(Money(10_50))), ("bob", None), ("charly", Some(Money(5_00)))]);
+        assert_eq!((summed, wildcards), (Money(15_50), 1));
+        // every part still goes through the usual checks
+        assert!(Target::parse_multiple(vec!["alice:10,bob:".to_string()], Money(100_00), Currency::EUR).is_err());
+    }
 }
 
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Transaction {
-    from: String,
-    to: String,
-    amount: Money,
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) amount: Money,
 }
 
 impl Transaction {
@@ -297,177 +1307,1521 @@ impl Transaction {
             amount: amount.abs(),
         }
     }
-}
 
-impl Display for Transaction {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} \tpays {}:\t{:.02}€", self.from, self.to, self.amount as f32 / 100.)
+    pub(crate) fn output(&self, currency: Currency) -> TransactionOutput {
+        TransactionOutput {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            amount_minor: self.amount,
+            amount: currency.format(self.amount),
+            currency,
+        }
     }
-}
 
-pub struct Splitter {
-    state: SplitterState,
-    db_path: PathBuf,
+    /// the suggested transactions with aligned columns
+    pub(crate) fn table(transactions: &[Transaction], currency: Currency) -> Table {
+        let mut table = Table::new(&["From", "To", "Amount"]).align_right(2).name_column(0).name_column(1);
+        for t in transactions {
+            table.row(vec![t.from.clone(), t.to.clone(), currency.format(t.amount)]);
+        }
+        table
+    }
 }
 
+/// a suggested transaction as printed by `balance --json`, with the amount both in
+/// minor units and formatted so consumers need not know about currencies
+#[derive(Serialize)]
+pub(crate) struct TransactionOutput {
+    from: String,
+    to: String,
+    amount_minor: Money,
+    amount: String,
+    currency: Currency,
+}
+
+pub struct Splitter {
+    state: SplitterState,
+    db_path: PathBuf,
+    format: StorageFormat,
+    /// the file as it was loaded or last written, to notice other programs changing it
+    stamp: Option<FileStamp>,
+    /// overwrite the file even if it was changed by another program
+    force_save: bool,
+    /// the state as it is on disk (or an empty one for a new database),
+    /// saving is skipped as long as nothing changed
+    saved: Saved,
+    /// what was saved since the database file was last written
+    journal: Journal,
+    /// save new log entries to the journal instead of rewriting the database
+    journal_mode: bool,
+    /// fold the journal into the database on save, set by `checkpoint`
+    checkpoint: bool,
+    /// brotli quality for compressed databases, chosen by their size if not set
+    compression_level: Option<u32>,
+    /// file receiving a line per new log entry on save
+    summary_file: Option<PathBuf>,
+    summaries: Vec<String>,
+    author: Option<String>,
+    max_name_width: Option<usize>,
+    pager: bool,
+    /// only exactly equal names collide when creating a group
+    case_sensitive_names: bool,
+    /// commands changing a group do not make it the default one
+    no_switch: bool,
+    /// a batch command failed for some groups, see `batch`
+    failure: Option<anyhow::Error>,
+    /// the members renamed by `normalize_member_names` on load, see `take_renamed`
+    renamed: Vec<String>,
+}
+
+/// What was last loaded or saved. Besides the hash of the state, enough to tell whether new log
+/// entries are all that changed since.
+struct Saved {
+    hash: [u8; 32],
+    /// length of the log of each group
+    logs: Vec<usize>,
+    revision: u64,
+    current_group: Option<usize>,
+}
+
+impl Saved {
+    /// `yaml` is the serialized `state`
+    fn of(state: &SplitterState, yaml: &str) -> Self {
+        Self {
+            hash: content_hash(yaml),
+            logs: state.groups.iter().map(|g| g.log().len()).collect(),
+            revision: state.revision,
+            current_group: state.current_group,
+        }
+    }
+}
+
+/// whether `name` may be used for a member or group, compiling `Splitter::NAME_REGEX` only once
+pub(crate) fn valid_name(name: &str) -> bool {
+    static NAME: OnceLock<Regex> = OnceLock::new();
+    NAME.get_or_init(|| Regex::new(Splitter::NAME_REGEX).expect("NAME_REGEX is a valid regex")).is_match(name)
+}
+
+/// A member name as typed or pasted: without zero-width characters and surrounding whitespace,
+/// inner runs of whitespace like non-breaking spaces collapsed into one space, in Unicode NFC
+pub(crate) fn normalize_name(name: &str) -> String {
+    let visible: String = name.chars().filter(|c| !matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}')).collect();
+    visible.split_whitespace().collect::<Vec<_>>().join(" ").nfc().collect()
+}
+
+// the library calls only a few of these, the command line program goes through `run`
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
 impl Splitter {
     pub(crate) const CURRENT_VERSION: &'static str = "0.1.0";
     pub(crate) const NAME_REGEX: &'static str = r"^[a-zA-Z0-9][a-zA-Z0-9_\-()]*$";
 
-    pub fn new(source: PathBuf) -> Self {
-        let state = SplitterState::new(source.clone());
-        Self {
+    /// Loads the database at `source`. Member names stored by older versions are normalized after
+    /// the journal is replayed, and only then, so the next save writes the new names.
+    pub fn new(source: PathBuf) -> Result<Self> {
+        let (mut state, stamp, journal) = SplitterState::load(source.clone())?;
+        let saved = Saved::of(&state, &serde_yaml::to_string(&state)?);
+        let renamed = state.groups.iter_mut().flat_map(|group| group.normalize_member_names()).collect();
+        Ok(Self {
+            saved,
             state,
+            format: StorageFormat::for_path(&source),
             db_path: source,
+            journal,
+            journal_mode: false,
+            checkpoint: false,
+            stamp,
+            force_save: false,
+            compression_level: None,
+            summary_file: None,
+            summaries: vec![],
+            author: None,
+            max_name_width: None,
+            pager: true,
+            case_sensitive_names: false,
+            no_switch: false,
+            failure: None,
+            renamed,
+        })
+    }
+
+    /// What was renamed while loading, to be reported once; the renames are saved with the next save
+    pub(crate) fn take_renamed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.renamed)
+    }
+
+    /// append a summary of every new log entry to this file when saving
+    pub(crate) fn summary_file(mut self, summary_file: Option<PathBuf>) -> Self {
+        self.summary_file = summary_file;
+        self
+    }
+
+    /// recorded as author of the log entries added
+    pub(crate) fn author(mut self, author: Option<String>) -> Self {
+        self.author = author;
+        self
+    }
+
+    /// names in tables are truncated to this many columns
+    pub(crate) fn max_name_width(mut self, width: Option<usize>) -> Self {
+        self.max_name_width = width;
+        self
+    }
+
+    /// keep the default group when commands change another group, see `switch_to`
+    pub(crate) fn no_switch(mut self, no_switch: bool) -> Self {
+        self.no_switch = no_switch;
+        self
+    }
+
+    /// Makes group `gidx` the default one after a command changed it, unless `no_switch`.
+    /// Commands only looking at a group leave the default alone.
+    fn switch_to(&mut self, gidx: usize) {
+        if !self.no_switch {
+            self.state.current_group = Some(gidx);
         }
     }
 
-    fn confirm() -> bool {
-        println!("Confirm? [yY|nN]: ");
-        let mut buffer = String::new();
-        let stdin = io::stdin();
-        stdin.read_line(&mut buffer).expect("stdin Input Error");
-        if buffer.starts_with(['y', 'Y']) {
-            sleep(Duration::from_secs(2));
-            true
+    /// show long output of `list` through `$PAGER` when printing to a terminal
+    pub(crate) fn pager(mut self, pager: bool) -> Self {
+        self.pager = pager;
+        self
+    }
+
+    /// let new group names differ from existing ones only in case, e.g. `Trip` next to `trip`
+    pub(crate) fn case_sensitive_names(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_names = case_sensitive;
+        self
+    }
+
+    /// overwrite the database even if another program changed it since it was loaded
+    pub(crate) fn force_save(mut self, force_save: bool) -> Self {
+        self.force_save = force_save;
+        self
+    }
+
+    /// save new log entries of a compressed database to its journal instead of rewriting it
+    pub(crate) fn journal(mut self, journal_mode: bool) -> Self {
+        self.journal_mode = journal_mode;
+        self
+    }
+
+    /// brotli quality (0 to 11) for compressed databases
+    pub(crate) fn compression_level(mut self, level: Option<u32>) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// report how abbreviated group names are resolved
+    pub(crate) fn verbose(mut self, verbose: bool) -> Self {
+        self.state.verbose = verbose;
+        self
+    }
+
+    /// Runs `action` on each group in `groups`, or every active group with `all`, and prints how it
+    /// went per group. A group the action fails for is left as it was and the others go on; the failure
+    /// is reported once the batch is saved, see `take_failure`. With `strict` the first failure ends
+    /// the command instead.
+    fn batch(&mut self, verb: &str, groups: Vec<String>, all: bool, strict: bool,
+             action: impl Fn(&mut Group) -> Result<()>) -> Result<()> {
+        let targets: Vec<String> = if all {
+            self.state.groups.iter().filter(|group| !group.archived).map(|group| group.name.clone()).collect()
         } else {
-            false
-        }
-    }
-    fn balance(&mut self, group: Option<String>) -> Result<()> {
-        let group = self.state.get_group_mut(group)?;
-        let mut transactions = group.balance();
-        println!("The following transactions are recommended:");
-        for t in &transactions {
-            println!("{}", t);
-        }
-        if Self::confirm() {
-            let tac_len = transactions.len();
-            let tac =
-                transactions.drain(..)
-                    .fold(HashMap::with_capacity(tac_len),
-                          |mut accu, ta| {
-                              accu.insert(ta.from, ta.amount);
-                              accu.insert(ta.to, -ta.amount);
-                              accu
-                          },
-                    );
-            group.apply_tachange(tac);
+            groups
+        };
+        let mut outcomes = vec![];
+        for target in targets {
+            let outcome = self.state.get_group_idx(Some(target.clone())).and_then(|gidx| {
+                let before = self.state.groups[gidx].clone();
+                action(&mut self.state.groups[gidx]).inspect_err(|_| self.state.groups[gidx] = before)
+            });
+            match outcome {
+                Err(e) if strict => return Err(e).context(format!("{} failed in group {}, nothing was changed", verb, target)),
+                outcome => outcomes.push((target, outcome.err().map(|e| format!("{:#}", e)))),
+            }
+        }
+        print!("{}", batch_table(&outcomes).max_name_width(self.max_name_width));
+        let failed = outcomes.iter().filter(|(_, problem)| problem.is_some()).count();
+        if failed > 0 {
+            self.failure = Some(anyhow!(SplitterError::LogicError)
+                .context(format!("{} failed in {} of {} groups, the others were changed", verb, failed, outcomes.len())));
+        }
+        Ok(())
+    }
+
+    /// The failure of a batch that changed what it could, to report after saving
+    pub(crate) fn take_failure(&mut self) -> Result<()> {
+        match self.failure.take() {
+            Some(failure) => Err(failure),
+            None => Ok(()),
+        }
+    }
+
+    /// Prints the transfers settling group `gidx`, worked out by the algorithm given with them, and
+    /// records them as payments if confirmed. Returns whether they were.
+    fn balance(&mut self, gidx: usize, (algorithm, transactions): (Algorithm, Vec<Transaction>), yes: bool,
+               output: OutputMode, shown_in: Option<(Currency, Rate)>) -> Result<bool> {
+        let group = &mut self.state.groups[gidx];
+        let (shown, currency) = match shown_in {
+            Some((currency, rate)) => (transactions.iter()
+                .map(|t| Transaction::new(&t.from, &t.to, rate.convert(t.amount, group.currency, currency)))
+                .collect(), currency),
+            None => (transactions.clone(), group.currency),
+        };
+        let apply = match output {
+            OutputMode::Json => {
+                let output: Vec<_> = shown.iter().map(|t| t.output(currency)).collect();
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                yes
+            }
+            OutputMode::Porcelain => {
+                print!("{}", shown.porcelain());
+                yes
+            }
+            OutputMode::Human => {
+                println!("The following transactions are recommended:");
+                if let Some((currency, rate)) = shown_in {
+                    println!("Amounts in {:?} {}, applied in {:?}", currency,
+                             Conversion { from: group.currency, rate }, group.currency);
+                }
+                print!("{}", Transaction::table(&shown, currency).max_name_width(self.max_name_width));
+                yes || prompt::confirm()
+            }
+        };
+        if apply {
+            group.log_settlement(&transactions, algorithm, self.author.clone())?;
+        }
+        Ok(apply)
+    }
+
+    /// Runs several commands against the in-memory state. Nothing is saved in between, so the
+    /// caller can drop the whole batch by not calling `save` when a step fails.
+    pub(crate) fn run_all(&mut self, commands: Vec<SubCommand>) -> Result<()> {
+        let steps = commands.len();
+        for (idx, command) in commands.into_iter().enumerate() {
+            let before = self.log_lengths();
+            let result = self.run(command);
+            if steps > 1 {
+                result.context(format!("Step {} of {} failed, none of the steps were saved", idx + 1, steps))?;
+            } else {
+                result?;
+            }
+            self.collect_summaries(before);
         }
         Ok(())
     }
 
+    /// Runs `command` like `run_all`, but if it fails the state is left as it was, so the interactive
+    /// mode can go on and save the other commands later
+    pub(crate) fn try_run(&mut self, command: SubCommand) -> Result<()> {
+        let before = self.state.clone();
+        self.run_all(vec![command]).inspect_err(|_| self.state = before)
+    }
+
+    fn log_lengths(&self) -> HashMap<String, usize> {
+        self.state.groups.iter().map(|g| (g.name.clone(), g.log().len())).collect()
+    }
+
+    /// renders every log entry added since `before` as a single line for the summary file
+    fn collect_summaries(&mut self, before: HashMap<String, usize>) {
+        if self.summary_file.is_none() {
+            return;
+        }
+        for group in &self.state.groups {
+            let known = before.get(&group.name).copied().unwrap_or(0);
+            for (index, entry) in group.log().iter().enumerate().skip(known) {
+                let rendered = entry.to_string(index, group.currency);
+                self.summaries.push(rendered.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+        }
+    }
+
+    /// Appends the collected summaries in a single write. The database is already saved at
+    /// this point, so failing to write the summary only warns.
+    fn write_summaries(&mut self) {
+        let summaries = std::mem::take(&mut self.summaries);
+        let Some(path) = &self.summary_file else { return };
+        if summaries.is_empty() {
+            return;
+        }
+        let result = std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(format!("{}\n", summaries.join("\n")).as_bytes()));
+        if let Err(e) = result {
+            eprintln!("Warning: could not write summary to {:?}: {}", path, e);
+        }
+    }
+
+    /// Asks before recording an `amount` above the `confirm_above` threshold of the group, unless `yes`.
+    /// Returns whether to record it. Fails instead of asking if nobody can answer.
+    fn confirm_large(group: &Group, amount: Money, yes: bool) -> Result<bool> {
+        let Some(threshold) = group.confirm_above.filter(|threshold| amount > *threshold && !yes) else {
+            return Ok(true);
+        };
+        if !prompt::interactive() {
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "{} is above the confirmation threshold of {} of group {}, pass --yes to record it",
+                group.currency.format(amount), group.currency.format(threshold), group.name));
+        }
+        println!("\n    You are about to record {}\n", group.currency.format(amount));
+        Ok(prompt::confirm())
+    }
+
+    /// Asks who paid for a split given without `--from` and, with `ask_participants`, who did not take part.
+    /// Returns the `--from` and `--to` targets for the answers, `None` if the user gave none.
+    fn pick_participants(group: &Group, amount: Money, name: &str, ask_participants: bool)
+                         -> Result<Option<(Vec<String>, Vec<String>)>> {
+        let names = group.member_names();
+        println!("Who paid for {} ({})?", name, group.currency.format(amount));
+        let Some(payers) = prompt::pick("Numbers of the payers, e.g. 1 3:", &names, false) else {
+            return Ok(None);
+        };
+        let from = payers.iter().map(|idx| names[*idx].clone()).collect();
+        if !ask_participants {
+            return Ok(Some((from, vec![])));
+        }
+        println!("Who did not take part?");
+        let Some(left_out) = prompt::pick("Numbers of those left out, Enter if everyone took part:", &names, true) else {
+            return Ok(None);
+        };
+        if left_out.is_empty() {
+            return Ok(Some((from, vec![])));
+        }
+        let participants: Vec<&String> = names.iter().enumerate()
+            .filter(|(idx, _)| !left_out.contains(idx))
+            .map(|(_, member)| *member)
+            .collect();
+        if participants.is_empty() {
+            return Err(anyhow!(SplitterError::LogicError)).context("Somebody has to take part in the split");
+        }
+        // those left out are not named and share the nothing that is left
+        let to = participants.iter().zip(split_equal_among(amount, participants.len()))
+            .map(|(member, share)| Target {
+                member: member.to_string(), amount: Some(share), rest: false, share: None, expression: None, weight: None,
+            }.raw(group.currency))
+            .collect();
+        Ok(Some((from, to)))
+    }
+
+    /// The total of `split --each`: `each` times the receivers named with `--to`, one with shares
+    /// like `2x` counting that often, or times all members not left out with `--except` without any.
+    /// Receivers named without an amount get parts of what the other amounts leave of the total, by
+    /// their shares, and are returned with these amounts.
+    fn per_head(group: &Group, each: Money, to: Vec<String>, except: &[String]) -> Result<(Money, Vec<String>)> {
+        let named = Target::parse_unchecked(to.clone(), each, group.currency)?.0.iter()
+            .fold(0u64, |heads, target| heads.saturating_add(target.weight.unwrap_or(1)));
+        let present = group.member_names().iter().filter(|name| !except.contains(name)).count();
+        let heads = if named == 0 { present as u64 } else { named };
+        let total = i64::try_from(heads).ok().and_then(|heads| each.checked_mul(heads))
+            .ok_or_else(|| anyhow!("the amount does not fit into 64 bits"))
+            .and_then(|total| check_ceiling(total).map_err(|problem| anyhow!(problem)))
+            .context(format!("{} each for {} members is too much", group.currency.format(each), heads))?;
+        let (targets, summed, _) = Target::parse_unchecked(to, total, group.currency)?;
+        if summed > total || targets.iter().any(|target| target.amount.is_some_and(|amount| amount.is_negative())) {
+            let given: Money = targets.iter().filter(|target| !target.rest).filter_map(|target| target.amount).sum();
+            return Err(anyhow!(SplitterError::LogicError)).context(format!(
+                "The amounts given with --to add up to {}, more than {} for {} members at {} each",
+                group.currency.format(given), group.currency.format(total), heads, group.currency.format(each)));
+        }
+        let unpriced: Vec<u64> = targets.iter()
+            .filter(|target| target.amount.is_none() && !target.rest && target.share.is_none())
+            .map(|target| target.weight.unwrap_or(1))
+            .collect();
+        let mut parts = if unpriced.is_empty() { vec![] } else { split_by_weights(total - summed, &unpriced) }.into_iter();
+        let to = targets.into_iter()
+            .map(|target| match target.amount {
+                None if !target.rest && target.share.is_none() =>
+                    Target { amount: parts.next(), weight: None, ..target }.raw(group.currency),
+                _ => target.raw(group.currency),
+            })
+            .collect();
+        Ok((total, to))
+    }
+
     pub(crate) fn run(&mut self, command: SubCommand) -> Result<()> {
-        match command {
-            SubCommand::Add { group, members } => {
-                let group = self.state.get_group_mut(group)?;
-                group.add(members)?;
+        if matches!(command, SubCommand::S { .. } | SubCommand::P { .. }) {
+            self.state.get_group_idx(None)
+                .context("The shorthands use the default group, pick one with `splitter use <group>`")?;
+        }
+        match command.expand_shorthand(self.author.as_deref()).map_err(|problem| anyhow!(problem))? {
+            SubCommand::S { .. } | SubCommand::P { .. } => unreachable!("expanded above"),
+            SubCommand::Whoami => match &self.author {
+                Some(me) => println!("{}", me),
+                None => println!("Nobody yet, set `me: <name>` in the config file or pass --author"),
+            },
+            SubCommand::Add { group, mut members, named, buy_in, buy_in_share, groups, all, strict } => {
+                members.extend(named);
+                let add = |group: &mut Group| {
+                    let buy_in = match buy_in {
+                        _ if buy_in_share => Some(group.spend_share(members.len())),
+                        Some(amount) => Some(group.in_currency(amount)?),
+                        None => None,
+                    };
+                    group.add(members.clone())?;
+                    match buy_in {
+                        Some(Money::ZERO) => println!("Nothing was spent in group {} yet, the new members start at zero", group.name),
+                        Some(amount) => for member in &members {
+                            group.buy_in(member.clone(), amount)?;
+                        },
+                        None => {}
+                    }
+                    Ok(())
+                };
+                if groups.is_empty() && !all {
+                    add(self.state.get_group_mut(group)?)?;
+                } else {
+                    self.batch("add", groups, all, strict, add)?;
+                }
             }
-            SubCommand::Remove { group, force, members } => {
-                let group = self.state.get_group_mut(group)?;
-                group.remove(members, force.unwrap_or(false))?;
+            SubCommand::Remove { group, force, transfer_to, mut members, named, groups, all, strict } => {
+                members.extend(named);
+                let remove = |group: &mut Group| group.remove(members.clone(), force, transfer_to.clone());
+                if groups.is_empty() && !all {
+                    remove(self.state.get_group_mut(group)?)?;
+                } else {
+                    self.batch("remove", groups, all, strict, remove)?;
+                }
             }
-            SubCommand::Create { name, members } => {
-                if !Regex::new(Splitter::NAME_REGEX).unwrap().is_match(name.as_str()) {
-                    return Err(anyhow!(SplitterError::InvalidName))
-                        .context(name);
+            SubCommand::RenameMember { group, old, new } => {
+                let gidx = self.state.get_group_idx(group)?;
+                let group = &mut self.state.groups[gidx];
+                group.rename_member(&old, new.clone())?;
+                println!("Renamed {} to {} in group {}", old, new, group.name);
+                self.switch_to(gidx);
+            }
+            SubCommand::FixResidual { group, assign, spread: _ } => {
+                let group = self.state.get_group_mut(Some(group))?;
+                let residual = group.fix_residual(assign)?;
+                println!("Booked a correction of {} in group {}, the balances add up to zero again",
+                         group.currency.format(-residual), group.name);
+            }
+            SubCommand::Create { name, mut members, integrity, opening_balances, balance_with } => {
+                self.state.check_new_name(&name, self.case_sensitive_names)?;
+                let balances = opening_balances.as_deref().map(read_balances).transpose()?;
+                for (member, _) in balances.iter().flatten() {
+                    if !members.contains(member) {
+                        members.push(member.clone());
+                    }
                 }
-                if self.state.groups.iter().any(|thing| thing.name == name) {
-                    return Err(anyhow!(SplitterError::InvalidName))
-                        .context(format!("Group already exists! {}", name));
+                let mut group = Group::new(name, members, None)?;
+                group.integrity = integrity;
+                if let (Some(balances), Some(path)) = (balances, &opening_balances) {
+                    let source = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                    group.open(balances, &source, balance_with.as_deref())?;
                 }
-                self.state.groups.push(Group::new(name, members, None)?);
-                self.state.current_group = Some(self.state.groups.len() - 1);
+                self.state.groups.push(group);
+                self.switch_to(self.state.groups.len() - 1);
             }
-            SubCommand::Undo { group, index } => {
+            SubCommand::Undo { group, index, force } => {
                 let group = self.state.get_group_mut(group)?;
-                let (lentry, currency) = (group.get_log(index)?, group.currency);
-                println!("You are about to undo\n`{}`", lentry.to_string(currency));
+                let ((index, lentry), currency) = (group.get_log(index)?, group.currency);
+                match &lentry.command {
+                    LoggedCommand::Opening { source, .. } => return Err(anyhow!(SplitterError::LogicError))
+                        .context(format!("Opening balances carried over from {} cannot be undone one by one", source)),
+                    LoggedCommand::WriteOff { member, .. } => return Err(anyhow!(SplitterError::LogicError))
+                        .context(format!("The write-off of {} cannot be undone, add them again instead", member)),
+                    LoggedCommand::Reset { .. } => return Err(anyhow!(SplitterError::LogicError))
+                        .context("A reset cannot be undone"),
+                    _ => {}
+                }
+                println!("You are about to undo\n`{}`", lentry.to_string(index, currency));
                 println!("This cannot be reversed");
+                let dependents = later_dependents(group.log(), index, &group.member_names());
+                if !dependents.is_empty() {
+                    eprint!("Warning: later history builds on entry [{}]:\n{}", index, dependents);
+                    if !force && !prompt::confirm() {
+                        println!("Operation Cancelled");
+                        return Ok(());
+                    }
+                }
                 group.apply_tachange(lentry.reversed_change());
-                group.remove_log(index)?;
+                group.remove_log(Some(index))?;
+                if group.integrity {
+                    println!("The hash chain was re-chained from entry [{}]", index);
+                }
                 println!("Success");
             }
-            SubCommand::DeleteGroup { group, yes } =>
-                self.state.delete_group(group, yes.unwrap_or(false))?,
-            SubCommand::List { group, all } => {
-                if all.unwrap_or(false) {
-                    for g in &self.state.groups {
-                        println!("{}\n", g.list());
+            SubCommand::RebalanceEntry { group, index, include } => {
+                let group = self.state.get_group_mut(Some(group))?;
+                let delta = group.include_in_split(index, &include)?;
+                println!("Entry [{}] of group {} now includes {}, balances changed by:", index, group.name,
+                         include.join(", "));
+                let mut delta: Vec<_> = delta.into_iter().collect();
+                delta.sort();
+                for (member, change) in delta {
+                    println!("{}: {}{}", member, if change.is_positive() { "+" } else { "" }, group.currency.format(change));
+                }
+            }
+            SubCommand::DeleteGroup { groups, all, yes, full, export_first } =>
+                self.state.delete_groups(groups, all, yes, full, export_first)?,
+            SubCommand::RenameGroup { old, new } => {
+                let previous = self.state.rename_group(&old, new.clone(), self.case_sensitive_names)?;
+                println!("Renamed group {} to {}", previous, new);
+            }
+            SubCommand::Reset { group, keep_members, members, yes, full } =>
+                self.state.reset_group(&group, if keep_members { None } else { Some(members) }, yes, full)?,
+            SubCommand::List { group, author, all, archived, json, porcelain } => {
+                let output = OutputMode::from_flags(json, porcelain);
+                if all {
+                    let listings: Vec<_> = self.state.groups.iter()
+                        .filter(|g| archived || !g.archived)
+                        .map(|g| g.listing(author.as_deref()))
+                        .collect();
+                    match output {
+                        OutputMode::Json => println!("{}", serde_json::to_string_pretty(&listings)?),
+                        OutputMode::Porcelain => for listing in listings {
+                            print!("{}", listing.porcelain());
+                        },
+                        OutputMode::Human => pager::page(&std::fmt::from_fn(|f| {
+                            listings.iter().try_for_each(|listing| write!(f, "{}\n\n", listing))
+                        }), self.pager),
                     }
                 } else {
-                    let gidx = self.state.get_group_idx(group.clone())?;
-                    let group = self.state.get_group(group)?;
-                    println!("\n{}\n", group.list());
-                    self.state.current_group = Some(gidx);
+                    let gidx = self.state.get_group_idx(group)?;
+                    let listing = self.state.groups[gidx].listing(author.as_deref());
+                    match output {
+                        OutputMode::Json => println!("{}", serde_json::to_string_pretty(&listing)?),
+                        OutputMode::Porcelain => print!("{}", listing.porcelain()),
+                        OutputMode::Human => pager::page(&std::fmt::from_fn(|f| write!(f, "\n{}\n\n", listing)), self.pager),
+                    }
+                }
+            }
+            SubCommand::Feed { limit, json } => {
+                let items: Vec<FeedItem> = feed(&self.state.groups).take(limit).collect();
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&items)?);
+                } else if items.is_empty() {
+                    println!("No log entries");
+                } else {
+                    pager::page(&std::fmt::from_fn(|f| items.iter().try_for_each(|item| writeln!(f, "{}", item))),
+                                self.pager);
                 }
             }
-            SubCommand::Stat { group, all } => {
-                if all.unwrap_or(false) {
-                    for g in &self.state.groups {
-                        println!("{}\n", g.stat());
+            SubCommand::Quote { group, amount, among, by_ratio, json } => {
+                let group = self.state.get_group(Some(group))?;
+                let quote = group.quote(group.in_currency(amount)?, &among, by_ratio.as_deref())?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&quote)?);
+                } else {
+                    pager::page(&preview_table(&quote), self.pager);
+                }
+            }
+            SubCommand::Stat { group, all, porcelain, format, threshold, show_all, in_currency, rate, period, as_of,
+                member } => {
+                let format = if porcelain { StatFormat::Porcelain } else { format };
+                // output for scripts and of a single member always lists every member
+                let threshold = match format {
+                    StatFormat::Text | StatFormat::Json if !show_all && member.is_none() => threshold.unwrap_or(Money::ZERO),
+                    _ => Money::ZERO,
+                };
+                let full_view = |g: &Group| {
+                    let stat = g.stat_view(g.in_currency(threshold)?, in_currency, rate, period == Period::Current, as_of)?;
+                    if let Some(undated) = stat.as_of.map(|as_of| as_of.undated).filter(|undated| *undated > 0) {
+                        eprintln!("Warning: {} {} of group {} without a date counted in as of {}", undated,
+                                  if undated == 1 { "entry" } else { "entries" }, g.name, as_of.unwrap());
+                    }
+                    Ok::<_, anyhow::Error>(stat)
+                };
+                let view = |g: &Group| {
+                    let stat = full_view(g)?;
+                    match &member {
+                        Some(member) => stat.only(member).ok_or(anyhow!(SplitterError::MemberNotFound))
+                            .context(format!("{} has no balance in group {}", member, g.name)),
+                        None => Ok(stat),
+                    }
+                };
+                if let (None, Some(member)) = (&group, &member) {
+                    // one member across all groups, skipping those they are not in; archived groups
+                    // are left out as their balances live on in the groups carried from them
+                    let mut stats = vec![];
+                    for g in self.state.groups.iter().filter(|g| !g.archived && g.member_names().contains(&member)) {
+                        stats.extend(full_view(g)?.only(member));
+                    }
+                    if stats.is_empty() {
+                        return Err(anyhow!(SplitterError::MemberNotFound))
+                            .context(format!("{} is not a member of any group", member));
+                    }
+                    match format {
+                        StatFormat::Text => println!("{}", MemberAcross::new(member.clone(), &stats)),
+                        StatFormat::Json =>
+                            println!("{}", serde_json::to_string_pretty(&MemberAcross::new(member.clone(), &stats))?),
+                        StatFormat::Porcelain => for stat in stats {
+                            print!("{}", stat.porcelain());
+                        },
+                        StatFormat::Env => return Err(anyhow!(SplitterError::LogicError))
+                            .context("--format env describes a single group, name one to show a member's balance in"),
+                    }
+                } else if all {
+                    let stats = self.state.groups.iter().map(view).collect::<Result<Vec<_>>>()?;
+                    match format {
+                        StatFormat::Text => for stat in stats {
+                            println!("{}\n", stat);
+                        },
+                        StatFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+                        StatFormat::Porcelain => for stat in stats {
+                            print!("{}", stat.porcelain());
+                        },
+                        StatFormat::Env => return Err(anyhow!(SplitterError::LogicError))
+                            .context("--format env describes a single group and cannot be used with all groups"),
                     }
                 } else {
-                    let gidx = self.state.get_group_idx(group.clone())?;
-                    let group = self.state.get_group(group)?;
-                    println!("{}", group.stat());
-                    self.state.current_group = Some(gidx);
+                    let gidx = self.state.get_group_idx(group)?;
+                    let stat = view(&self.state.groups[gidx])?;
+                    match format {
+                        StatFormat::Text => println!("{}", stat),
+                        StatFormat::Json => println!("{}", serde_json::to_string_pretty(&stat)?),
+                        StatFormat::Porcelain => print!("{}", stat.porcelain()),
+                        StatFormat::Env => print!("{}", stat_env(&stat)),
+                    }
                 }
             }
-            SubCommand::Pay { amount, group, from, to } =>
+            SubCommand::Pay { amount, group, from, to, yes } =>
                 {
-                    let gidx = self.state.get_group_idx(group.clone())?;
-                    let group = self.state.get_group_mut(group)?;
+                    let gidx = self.state.get_group_idx(group)?;
+                    let group = &mut self.state.groups[gidx];
+                    let amount = group.in_currency(amount)?;
+                    if !Self::confirm_large(group, amount, yes)? {
+                        println!("Operation Cancelled");
+                        return Ok(());
+                    }
                     group.log_pay_transaction(
-                        (amount * group.currency.subdivision()) as Money,
+                        amount,
                         from,
                         to,
                     )?;
-                    self.state.current_group = Some(gidx);
+                    group.set_last_author(self.author.clone());
+                    self.switch_to(gidx);
                 }
             SubCommand::Split {
                 amount,
                 group,
-                from,
-                to,
+                mut from,
+                mut to,
+                from_file,
+                to_file,
                 name,
                 balance_rest,
+                category,
+                by_ratio,
+                strict,
+                refund,
+                each,
+                except,
+                yes,
             } => {
-                let gidx = self.state.get_group_idx(group.clone())?;
-                let group = self.state.get_group_mut(group)?;
-                group.split((amount * 100.) as i64, from, to, name,
-                            balance_rest.unwrap_or(false))?;
-                self.state.current_group = Some(gidx);
+                let except: Vec<String> = except.iter().map(|name| normalize_name(name)).collect();
+                let gidx = self.state.get_group_idx(group)?;
+                let (amount, currency) = (self.state.groups[gidx].in_currency(amount)?, self.state.groups[gidx].currency);
+                if let Some(path) = from_file {
+                    from.extend(Target::read_file(&path, amount, currency)?);
+                }
+                if let Some(path) = to_file {
+                    to.extend(Target::read_file(&path, amount, currency)?);
+                }
+                // a refund is logged as the mirror of a split, those getting the money back as its payers
+                if refund {
+                    if to.is_empty() || !from.is_empty() {
+                        return Err(anyhow!(SplitterError::InvalidTargetFormat))
+                            .context("Name who got the money back with --to, a refund has no --from");
+                    }
+                    from = std::mem::take(&mut to);
+                }
+                let group = &mut self.state.groups[gidx];
+                let amount = if each {
+                    let (total, per_head) = Self::per_head(group, amount, to, &except)?;
+                    to = per_head;
+                    total
+                } else {
+                    amount
+                };
+                if from.is_empty() {
+                    if !prompt::interactive() {
+                        return Err(anyhow!(SplitterError::InvalidTargetFormat))
+                            .context("Name who paid with --from");
+                    }
+                    let ask_participants = to.is_empty() && by_ratio.is_none() && !each;
+                    let picked = Self::pick_participants(group, amount, &name, ask_participants)?;
+                    let Some((payers, participants)) = picked else {
+                        println!("Operation Cancelled");
+                        return Ok(());
+                    };
+                    from = payers;
+                    to = participants;
+                    let (change, _, _) = group.compute_split(amount, from.clone(), to.clone(), &except,
+                                                             balance_rest, by_ratio.as_deref())?;
+                    print!("{}", change_table(&change, group.currency));
+                    if !prompt::confirm() {
+                        println!("Operation Cancelled");
+                        return Ok(());
+                    }
+                }
+                if let Some(ratio) = &by_ratio {
+                    let missing = group.missing_from_ratio(ratio)?;
+                    if !missing.is_empty() {
+                        let missing = missing.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ");
+                        if strict {
+                            return Err(anyhow!(SplitterError::LogicError))
+                                .context(format!("{} missing from ratio {}", missing, ratio));
+                        }
+                        eprintln!("Warning: {} missing from ratio {}, they take no share", missing, ratio);
+                    }
+                }
+                if !Self::confirm_large(group, amount, yes)? {
+                    println!("Operation Cancelled");
+                    return Ok(());
+                }
+                let options = SplitOptions { balance_rest, category: category.clone(), ratio: by_ratio, except };
+                group.split(if refund { -amount } else { amount }, from, to, name, options)?;
+                group.set_last_author(self.author.clone());
+                for status in group.budget_status(Local::now(), Some(category.as_deref())) {
+                    if let Some(warning) = status.warning(&group.name) {
+                        eprintln!("{}", warning);
+                    }
+                }
+                self.switch_to(gidx);
             }
-            SubCommand::Balance { group } => {
-                let gidx = self.state.get_group_idx(Some(group.clone()))?;
-                self.balance(Some(group))?;
+            SubCommand::Balance { group, yes, json, porcelain, in_currency, rate, algorithm, banker } => {
+                let gidx = self.state.get_group_idx(Some(group))?;
+                let shown_in = match in_currency {
+                    Some(currency) => {
+                        let group = &self.state.groups[gidx];
+                        Some((currency, group.rate_to(currency, rate)?))
+                    }
+                    None => None,
+                };
+                let settlement = self.state.groups[gidx].settle(algorithm, banker.as_deref())?;
+                if self.balance(gidx, settlement, yes, OutputMode::from_flags(json, porcelain), shown_in)? {
+                    self.switch_to(gidx);
+                }
+            }
+            SubCommand::Top { group, count, category, member, json } => {
+                let group = self.state.get_group(group)?;
+                let top = group.top(count, category.as_deref(), member.as_deref());
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&top)?);
+                } else if top.is_empty() {
+                    println!("No matching expenses in group {}", group.name);
+                } else {
+                    print!("{}", top_table(&top, group.currency).max_name_width(self.max_name_width));
+                }
+            }
+            SubCommand::Chart { group, by } => {
+                let group = self.state.get_group(group)?;
+                let spending = spending_by(group.log(), by);
+                if spending.is_empty() {
+                    println!("No expenses in group {} to chart yet", group.name);
+                } else {
+                    println!("Spending of group {} by {}", group.name, format!("{:?}", by).to_lowercase());
+                    print!("{}", spending_chart(&spending, group.currency, terminal_width()));
+                }
+            }
+            SubCommand::Groups { all, json, porcelain } => {
+                let overview = self.state.overview(all);
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&overview)?);
+                } else if porcelain {
+                    print!("{}", overview.porcelain());
+                } else if overview.is_empty() {
+                    println!("No groups yet, create one with `splitter create <name> -a <member>`");
+                } else {
+                    print!("{}", overview_table(&overview).max_name_width(self.max_name_width));
+                }
+            }
+            SubCommand::Use { group: Some(group) } => {
+                let gidx = self.state.get_group_idx(Some(group))?;
                 self.state.current_group = Some(gidx);
+                println!("Using group {}", self.state.groups[gidx].name);
             }
+            SubCommand::Use { group: None } => {
+                let overview = self.state.overview(false);
+                if overview.is_empty() {
+                    println!("No groups yet, create one with `create <name> -a <member>`");
+                } else {
+                    print!("{}", overview_table(&overview).max_name_width(self.max_name_width));
+                }
+            }
+            SubCommand::Members { group, json } => {
+                let group = self.state.get_group(group)?;
+                let members = group.member_overview();
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&members)?);
+                } else if members.is_empty() {
+                    println!("Group {} has no members", group.name);
+                } else {
+                    print!("{}", members_table(&members, group.currency).max_name_width(self.max_name_width));
+                }
+            }
+            SubCommand::ExportLedger { group, member, account_prefix, expense_account, funds_account, out } => {
+                let group = self.state.get_group(Some(group))?;
+                let accounts = LedgerAccounts {
+                    receivable: account_prefix,
+                    expense: expense_account,
+                    funds: funds_account,
+                };
+                let journal = ledger(group, member.as_str(), &accounts, Local::now().date_naive())?;
+                write_or_print(out, journal.as_str())?;
+            }
+            SubCommand::Export { group, output } => self.state.export_group(group, &output)?,
+            SubCommand::Import { file, rename } => {
+                let name = self.state.import_group(&file, rename, self.case_sensitive_names)?;
+                self.switch_to(self.state.groups.len() - 1);
+                println!("Imported group {} from {:?}", name, file);
+            }
+            SubCommand::ExportYnab { group, member, out } => {
+                let group = self.state.get_group(Some(group))?;
+                let csv = ynab(group, member.as_str(), Local::now().date_naive())?;
+                write_or_print(out, csv.as_str())?;
+            }
+            SubCommand::Merge { other } => {
+                let theirs = SplitterState::new(other)?;
+                print!("{}", self.state.merge(theirs)?);
+            }
+            SubCommand::Checkpoint => {
+                let records = self.journal.records().len();
+                if records == 0 {
+                    println!("The journal is empty, nothing to fold into the database");
+                } else {
+                    println!("Folding {} journal record(s) into the database", records);
+                }
+                self.checkpoint = true;
+            }
+            SubCommand::Verify { group, enable } => {
+                let group = self.state.get_group_mut(Some(group))?;
+                if enable {
+                    if group.enable_integrity() {
+                        println!("Integrity mode enabled for group {}", group.name);
+                    } else {
+                        println!("Group {} already is in integrity mode", group.name);
+                    }
+                }
+                println!("{}", group.verify()?);
+            }
+            SubCommand::GroupConfig { group, confirm_above, no_confirm_above, algorithm, banker, no_banker } => {
+                let group = self.state.get_group_mut(group)?;
+                if confirm_above.is_some() || no_confirm_above {
+                    group.confirm_above = confirm_above.map(|amount| group.in_currency(amount)).transpose()?;
+                }
+                if algorithm.is_some() {
+                    group.algorithm = algorithm;
+                }
+                if let Some(banker) = &banker {
+                    if !group.member_names().contains(&banker) {
+                        return Err(anyhow!(SplitterError::MemberNotFound))
+                            .context(format!("{} is not a member of {}", banker, group.name));
+                    }
+                }
+                if banker.is_some() || no_banker {
+                    group.banker = banker;
+                }
+                match group.confirm_above {
+                    Some(threshold) => println!("confirm_above: {}", group.currency.format(threshold)),
+                    None => println!("confirm_above: none"),
+                }
+                println!("algorithm: {}", group.algorithm.unwrap_or_default().name());
+                println!("banker: {}", group.banker.as_deref().unwrap_or("none"));
+            }
+            SubCommand::ExchangeRate { currency, rate, group } => {
+                let group = self.state.get_group_mut(group)?;
+                if currency == group.currency {
+                    return Err(anyhow!(SplitterError::LogicError))
+                        .context(format!("{:?} already is the currency of group {}", currency, group.name));
+                }
+                if let Some(rate) = rate {
+                    group.rates.insert(currency, rate);
+                }
+                match group.rates.get(&currency) {
+                    Some(rate) => println!("1 {:?} = {} {:?} in group {}", group.currency, rate, currency, group.name),
+                    None => println!("No exchange rate to {:?} set for group {}", currency, group.name),
+                }
+            }
+            SubCommand::CloseMonth { group, month, settle } => {
+                let gidx = self.state.get_group_idx(Some(group))?;
+                let group = &mut self.state.groups[gidx];
+                let month = month.unwrap_or_else(|| Month::of(Local::now()).previous());
+                let report = group.close_month(month, settle)?;
+                println!("{}", report);
+                if settle {
+                    group.log_settlement(&report.transfers, Algorithm::Greedy, self.author.clone())?;
+                    println!("Recorded the transfers as payments");
+                }
+                println!("Closed {} of group {}", month, group.name);
+                self.switch_to(gidx);
+            }
+            SubCommand::Report { group, month } => {
+                let gidx = self.state.get_group_idx(group)?;
+                println!("{}", self.state.groups[gidx].report(month));
+            }
+            SubCommand::Carry { source, name, members_from, yes } => {
+                if self.state.carry(source, name.clone(), members_from, yes, self.case_sensitive_names)? {
+                    println!("Carried the balances over into group {}", name);
+                }
+            }
+            SubCommand::Budget { action } => match action {
+                BudgetAction::Set { group, amount, per, category } => {
+                    let group = self.state.get_group_mut(Some(group))?;
+                    group.set_budget(Budget { amount: group.in_currency(amount)?, per, category });
+                    println!("Budget of group {} set", group.name);
+                }
+                BudgetAction::Remove { group, category } => {
+                    let group = self.state.get_group_mut(Some(group))?;
+                    group.remove_budget(category.as_deref())?;
+                    println!("Budget of group {} removed", group.name);
+                }
+                BudgetAction::Status { group } => {
+                    let group = self.state.get_group(group)?;
+                    let status = group.budget_status(Local::now(), None);
+                    if status.is_empty() {
+                        println!("Group {} has no budgets", group.name);
+                    }
+                    for status in status {
+                        println!("{}", status);
+                    }
+                }
+            },
+            SubCommand::Ratio { action } => match action {
+                RatioAction::Set { group, name, shares } => {
+                    let group = self.state.get_group_mut(Some(group))?;
+                    group.set_ratio(&name, &shares)?;
+                    println!("Ratio {} of group {} set", name, group.name);
+                }
+                RatioAction::Show { group } => {
+                    let group = self.state.get_group(Some(group))?;
+                    if group.ratios.is_empty() {
+                        println!("Group {} has no ratios", group.name);
+                    }
+                    for (name, ratio) in &group.ratios {
+                        let shares: Vec<String> = ratio.iter().map(|(member, weight)| format!("{}={}", member, weight)).collect();
+                        println!("{}: {}", name, shares.join(" "));
+                    }
+                }
+                RatioAction::Remove { group, name } => {
+                    let group = self.state.get_group_mut(Some(group))?;
+                    group.ratio(&name)?;
+                    group.ratios.remove(&name);
+                    println!("Ratio {} of group {} removed", name, group.name);
+                }
+            },
+            // answered in main.rs, which must not fail or save for completion requests
+            SubCommand::Complete { .. } => {}
+            SubCommand::Info { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("info cannot be chained with other commands"),
+            SubCommand::Inspect { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("inspect cannot be chained with other commands"),
+            SubCommand::Compute { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("compute cannot be chained with other commands"),
+            SubCommand::Doctor => return Err(anyhow!(SplitterError::LogicError))
+                .context("doctor cannot be chained with other commands"),
+            SubCommand::Completions { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("completions cannot be chained with other commands"),
+            SubCommand::Watch { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("watch cannot be chained with other commands"),
+            // it writes both copies at once, which a batch that may still be dropped must not do
+            SubCommand::Sync { .. } => return Err(anyhow!(SplitterError::LogicError))
+                .context("sync cannot be chained with other commands or run in the interactive mode"),
         };
         Ok(())
     }
 
-    fn compress(input: String) -> Vec<u8> {
-        let mut compressed = Vec::new();
-        let mut com_rdr =
-            CompressorReader::new(input.as_bytes(), 4096, 6, 22);
-        com_rdr.read_to_end(&mut compressed).unwrap();
-        compressed
+    /// What `splitter watch` shows of a group: its stat and the latest entry of its log, if any
+    pub(crate) fn watch_view(&self, group: Option<String>) -> Result<String> {
+        let group = &self.state.groups[self.state.get_group_idx(group)?];
+        Ok(match group.log().len().checked_sub(1) {
+            Some(index) => format!("{}\nLatest: {}", group.stat(), group.log()[index].to_string(index, group.currency)),
+            None => group.stat(),
+        })
+    }
+
+    /// What `splitter doctor` finds in the loaded database: its format, the journal, its size
+    /// and whether each group is consistent
+    #[cfg(feature = "cli")]
+    pub(crate) fn doctor(&self) -> Vec<Check> {
+        let found = format!("{}, version {}, revision {}", self.format.describe(), self.state.version, self.state.revision);
+        let mut checks = vec![if self.state.version == Self::CURRENT_VERSION {
+            Check::ok("format", found)
+        } else {
+            Check::warn("format", found, format!("this splitter writes version {}", Self::CURRENT_VERSION))
+        }];
+        let records = self.journal.records().len();
+        checks.push(match self.journal.torn() {
+            0 if self.journal.full() => Check::warn("journal", format!("{} records", records),
+                                                    "the next save folds them into the database, or `splitter checkpoint`"),
+            0 => Check::ok("journal", format!("{} records", records)),
+            torn => Check::warn("journal", format!("{} records, the last {} bytes were not completely written", records, torn),
+                                "they are ignored and overwritten by the next save"),
+        });
+        let entries: usize = self.state.groups.iter().map(|g| g.log().len()).sum();
+        let groups = self.state.groups.len();
+        checks.push(Check::ok("contents", format!("{} {}, {} {}", groups, if groups == 1 { "group" } else { "groups" },
+                                                  entries, if entries == 1 { "entry" } else { "entries" })));
+        for group in &self.state.groups {
+            let unnormalized = group.unnormalized_members();
+            if !unnormalized.is_empty() {
+                checks.push(Check::warn(format!("names of {}", group.name), unnormalized.join(", "),
+                                        format!("rename them with `splitter rename-member -g {} <old> <new>`", group.name)));
+            }
+            let problems = group.problems();
+            checks.push(if problems.is_empty() {
+                Check::ok(format!("group {}", group.name), "consistent")
+            } else {
+                Check::fail(format!("group {}", group.name), problems.join(", "),
+                            format!("restore a backup or look for the damaged entries with `splitter list {}`", group.name))
+            });
+        }
+        checks
+    }
+
+    /// the prompt of the interactive mode, showing the default group, e.g. `splitter(trip|4 members|Σ0)> `
+    pub(crate) fn prompt(&self) -> String {
+        match self.state.status() {
+            Some(status) => format!("splitter({})> ", status),
+            None => "splitter> ".to_string(),
+        }
+    }
+
+    /// the group called `name` or starting with it, like on the command line
+    pub(crate) fn group(&self, name: &str) -> Result<&Group> {
+        self.state.get_group(Some(name.to_string()))
+    }
+
+    pub(crate) fn group_mut(&mut self, name: &str) -> Result<&mut Group> {
+        self.state.get_group_mut(Some(name.to_string()))
+    }
+
+    /// Adds group `name` like `create` without options and makes it the default
+    pub(crate) fn create_group(&mut self, name: String, members: Vec<String>) -> Result<()> {
+        self.state.check_new_name(&name, self.case_sensitive_names)?;
+        self.state.groups.push(Group::new(name, members, None)?);
+        self.state.current_group = Some(self.state.groups.len() - 1);
+        Ok(())
+    }
+
+    /// the groups with debts open for at least `days` days at `now`, see `nag::stale`
+    pub(crate) fn stale_groups(&self, now: DateTime<Local>, days: u32, threshold: Money) -> Vec<Stale> {
+        nag::stale(&self.state.groups, now, days, threshold)
+    }
+
+    /// what `splitter info` reports about the loaded database
+    pub(crate) fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            path: self.db_path.clone(),
+            format: self.format,
+            version: self.state.version.clone(),
+            revision: self.state.revision,
+        }
+    }
+
+    /// candidates for the shell completion helpers, one name per entry
+    pub(crate) fn complete(&self, what: &CompleteTarget) -> Vec<String> {
+        match what {
+            CompleteTarget::Groups =>
+                self.state.groups.iter().map(|g| g.name.clone()).collect(),
+            CompleteTarget::Members { group } =>
+                self.state.get_group(group.clone())
+                    .map(|g| g.member_names().into_iter().cloned().collect())
+                    .unwrap_or_default(),
+            CompleteTarget::Script { .. } => vec![],
+        }
+    }
+
+    /// Writes the state with a new revision unless it is unchanged since loading or the last save,
+    /// so read-only commands leave the file alone. In journal mode new log entries are appended
+    /// to the journal until it is full or `checkpoint` asks to write the database.
+    pub(crate) fn save(&mut self) -> Result<()> {
+        if self.saved.hash == content_hash(&serde_yaml::to_string(&self.state)?) {
+            return if self.checkpoint && !self.journal.is_empty() { self.write() } else { Ok(()) };
+        }
+        self.state.revision += 1;
+        if self.journal_mode && !self.checkpoint && self.format == StorageFormat::Compressed && !self.journal.full() {
+            if let Some(record) = self.journal_record()? {
+                return self.append(record);
+            }
+        }
+        self.write()
+    }
+
+    /// The journal record for what changed since the last save, if that is only new log entries
+    /// of existing groups
+    fn journal_record(&self) -> Result<Option<JournalRecord>> {
+        if self.state.groups.len() != self.saved.logs.len() {
+            return Ok(None);
+        }
+        let mut before = self.state.clone();
+        before.revision = self.saved.revision;
+        before.current_group = self.saved.current_group;
+        let mut entries = vec![];
+        for (group, &saved) in before.groups.iter_mut().zip(&self.saved.logs) {
+            if group.log().len() < saved {
+                return Ok(None);
+            }
+            entries.extend(group.split_off_log(saved).into_iter()
+                .map(|entry| JournaledEntry { group: group.name.clone(), entry }));
+        }
+        if content_hash(&serde_yaml::to_string(&before)?) != self.saved.hash {
+            return Ok(None);
+        }
+        Ok(Some(JournalRecord { revision: self.state.revision, current_group: self.state.current_group, entries }))
     }
 
-    fn decompress(input: &[u8]) -> String {
-        let mut decompressor = Decompressor::new(input, 4096);
-        let mut dec_data = String::new();
-        decompressor.read_to_string(&mut dec_data).unwrap();
-        dec_data
+    /// Appends `record` to the journal, leaving the database file alone
+    fn append(&mut self, record: JournalRecord) -> Result<()> {
+        let raw = serde_yaml::to_string(&self.state)?;
+        if let Some(conflict) = self.conflict(|| self.format.encode(raw.clone(), self.compression_level))? {
+            return Err(conflict);
+        }
+        self.journal.append(record)?;
+        self.saved = Saved::of(&self.state, &raw);
+        self.write_summaries();
+        Ok(())
     }
 
-    pub(crate) fn save(&self) -> Result<()> {
+    /// Writes the state as it is, without bumping the revision, and clears the journal.
+    /// If another program changed the file since it was loaded, the state goes to `<db>.conflict`
+    /// instead unless `force_save` is set.
+    fn write(&mut self) -> Result<()> {
         let raw = serde_yaml::to_string(&self.state)?;
-        let result = Self::compress(raw);
+        let saved = Saved::of(&self.state, &raw);
+        let result = self.format.encode(raw, self.compression_level);
+        if let Some(conflict) = self.conflict(|| result.clone())? {
+            return Err(conflict);
+        }
         let mut file = std::fs::File::create(self.db_path.as_path())?;
         file.write_all(result.as_slice())?;
+        // the records are part of the database now, a crash before clearing the journal
+        // leaves records that loading skips for their revision
+        file.sync_data()?;
+        self.journal.clear()?;
+        self.stamp = Some(FileStamp::new(result.as_slice(), None));
+        self.saved = saved;
+        self.write_summaries();
+        Ok(())
+    }
+
+    /// Saves like `save`, overwriting the database even if another program changed it since it was
+    /// loaded, as with `--force-save`. The `.conflict` file of a refused save is removed.
+    pub(crate) fn overwrite(&mut self) -> Result<()> {
+        let force_save = std::mem::replace(&mut self.force_save, true);
+        let result = self.save();
+        self.force_save = force_save;
+        result?;
+        let conflict = conflict_path(&self.db_path);
+        if conflict.exists() {
+            std::fs::remove_file(&conflict).context(format!("Could not remove {:?}", conflict))?;
+        }
+        Ok(())
+    }
+
+    /// whether another program changed the database or its journal since it was loaded
+    pub(crate) fn changed_on_disk(&self) -> Result<bool> {
+        let current = FileStamp::read(self.db_path.as_path())?;
+        Ok(!FileStamp::same_content(&self.stamp, &current) || self.journal.changed_on_disk())
+    }
+
+    /// If another program changed the database or its journal since it was loaded (and `force_save`
+    /// is not set), writes the `encoded` state to `<db>.conflict` and returns the error to report
+    fn conflict(&self, encoded: impl FnOnce() -> Vec<u8>) -> Result<Option<anyhow::Error>> {
+        if self.force_save || !self.changed_on_disk()? {
+            return Ok(None);
+        }
+        let current = FileStamp::read(self.db_path.as_path())?;
+        let conflict = conflict_path(&self.db_path);
+        std::fs::write(&conflict, encoded())
+            .context(format!("Could not write {:?}", conflict))?;
+        let modified = current.and_then(|stamp| stamp.modified)
+            .map(|time| format!(" at {}", DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S")))
+            .unwrap_or_default();
+        Ok(Some(anyhow!(
+            "{:?} was changed by another program{} since it was loaded. Your changes were written to {:?} \
+            instead, use --force-save to overwrite the database anyway", self.db_path, modified, conflict)))
+    }
+
+    /// Brings the local database and a copy at `remote` to the same state. Whichever side changed
+    /// since the last sync overwrites the other one, if both changed they are merged.
+    /// Pending changes are saved first, so syncing always writes immediately.
+    pub(crate) fn sync(&mut self, remote: PathBuf) -> Result<()> {
+        self.save()?;
+        let (local, base) = (self.state.revision, self.state.synced_revision);
+        if !remote.exists() {
+            return self.push(remote.as_path());
+        }
+        let remote_state = SplitterState::new(remote.clone())?;
+        let remote_revision = remote_state.revision;
+        if remote_revision < base {
+            return Err(anyhow!("{:?} is at revision {}, older than the last sync at revision {}. \
+                Was it replaced by an old copy? Nothing was changed", remote, remote_revision, base));
+        }
+        match (local > base, remote_revision > base) {
+            (false, false) => println!("Already up to date at revision {}", local),
+            (true, false) => self.push(remote.as_path())?,
+            (false, true) => self.pull(remote_state)?,
+            (true, true) => {
+                let report = self.state.merge(remote_state).context(format!(
+                    "Both the local database (revision {}) and {:?} (revision {}) changed since the last sync \
+                    at revision {} and could not be merged", local, remote, remote_revision, base))?;
+                print!("Merged both copies:\n{}", report);
+                self.state.revision = local.max(remote_revision) + 1;
+                self.push(remote.as_path())?;
+            }
+        }
         Ok(())
     }
+
+    fn log_count(state: &SplitterState) -> usize {
+        state.groups.iter().map(|g| g.log().len()).sum()
+    }
+
+    fn push(&mut self, remote: &Path) -> Result<()> {
+        self.state.synced_revision = self.state.revision;
+        let raw = serde_yaml::to_string(&self.state)?;
+        std::fs::write(remote, StorageFormat::for_path(remote).encode(raw, self.compression_level))
+            .context(format!("Could not write {:?}", remote))?;
+        self.write()?;
+        println!("Pushed revision {} ({} log entries) to {:?}",
+                 self.state.revision, Self::log_count(&self.state), remote);
+        Ok(())
+    }
+
+    fn pull(&mut self, mut remote_state: SplitterState) -> Result<()> {
+        let new_entries = Self::log_count(&remote_state) as i64 - Self::log_count(&self.state) as i64;
+        remote_state.synced_revision = remote_state.revision;
+        remote_state.verbose = self.state.verbose;
+        self.state = remote_state;
+        self.write()?;
+        println!("Pulled revision {} ({:+} log entries)", self.state.revision, new_entries);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod splitter_tests {
+    use sha2::{Digest, Sha256};
+    use super::*;
+
+    fn create(name: &str) -> SubCommand {
+        SubCommand::Create { name: name.to_string(), members: vec!["Alice".to_string(), "Bob".to_string()], integrity: false,
+                             opening_balances: None, balance_with: None }
+    }
+
+    #[test]
+    fn test_external_modification() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.db");
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        splitter.run(create("flat")).unwrap();
+        splitter.save().unwrap();
+
+        // saving twice from the same session is fine
+        splitter.run(create("trip")).unwrap();
+        splitter.save().unwrap();
+
+        // another program rewrites the file in the meantime
+        let mut other = Splitter::new(db.clone()).unwrap();
+        splitter.run(create("club")).unwrap();
+        other.run(create("band")).unwrap();
+        other.save().unwrap();
+        let theirs = std::fs::read(&db).unwrap();
+        let err = splitter.save().unwrap_err();
+        assert!(err.to_string().contains("was changed by another program"), "{err}");
+        assert_eq!(std::fs::read(&db).unwrap(), theirs);
+        let conflict = Splitter::new(dir.path().join("flat.db.conflict")).unwrap();
+        assert!(conflict.state.get_group(Some("club".to_string())).is_ok());
+
+        // only touching the file is no change
+        let mut toucher = Splitter::new(db.clone()).unwrap();
+        std::fs::write(&db, &theirs).unwrap();
+        toucher.run(create("choir")).unwrap();
+        toucher.save().unwrap();
+
+        // a file created after loading counts as a change, too
+        let new_db = dir.path().join("new.db");
+        let mut late = Splitter::new(new_db.clone()).unwrap();
+        std::fs::write(&new_db, &theirs).unwrap();
+        late.run(create("flat")).unwrap();
+        assert!(late.save().is_err());
+
+        let mut splitter = splitter.force_save(true);
+        splitter.save().unwrap();
+        let saved = Splitter::new(db).unwrap();
+        assert!(saved.state.get_group(Some("club".to_string())).is_ok());
+        assert!(saved.state.get_group(Some("band".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_names_normalized_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("names.yaml");
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        splitter.run(SubCommand::Create { name: "trip".to_string(), members: vec!["bob".to_string(), "carol".to_string(),
+                     "dave".to_string()], integrity: false, opening_balances: None, balance_with: None }).unwrap();
+        splitter.save().unwrap();
+        // names as older versions stored them, one of them taken once normalized
+        let stored = std::fs::read_to_string(&db).unwrap().replace("bob", "bob\u{a0}").replace("dave", "'carol '");
+        std::fs::write(&db, stored).unwrap();
+
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        assert_eq!(splitter.take_renamed(), vec!["renamed member \"bob\\u{a0}\" of group trip to \"bob\""]);
+        assert!(splitter.take_renamed().is_empty(), "reported once");
+        #[cfg(feature = "cli")]
+        {
+            let names = splitter.doctor().into_iter().find(|check| check.name == "names of trip").unwrap();
+            assert_eq!(names.found, "\"carol \" is \"carol\" once normalized, but carol already is a member of trip");
+        }
+        splitter.save().unwrap();
+
+        let mut splitter = Splitter::new(db).unwrap();
+        assert!(splitter.take_renamed().is_empty(), "the rename was saved");
+        assert_eq!(splitter.state.groups[0].member_names(), vec!["bob", "carol", "carol "]);
+    }
+
+    #[test]
+    fn test_per_head() {
+        let names = ["alice", "bob", "carol", "dave"].map(String::from).to_vec();
+        let group = Group::new("trip".to_string(), names, None).unwrap();
+        let targets = |raw: &[&str]| raw.iter().map(|target| target.to_string()).collect::<Vec<_>>();
+        assert_eq!(Splitter::per_head(&group, Money(15_50), vec![], &[]).unwrap(), (Money(62_00), vec![]));
+        assert_eq!(Splitter::per_head(&group, Money(15_50), vec![], &["dave".to_string()]).unwrap(), (Money(46_50), vec![]));
+        let (total, to) = Splitter::per_head(&group, Money(10_00), targets(&["alice,bob", "carol:10%"]), &[]).unwrap();
+        assert_eq!(total, Money(30_00));
+        assert_eq!(to, vec!["alice:13.50", "bob:13.50", "carol:10%"]);
+        let (total, to) = Splitter::per_head(&group, Money(1_00), targets(&["alice:rest", "bob:0.40"]), &[]).unwrap();
+        assert_eq!((total, to), (Money(2_00), vec!["alice:rest".to_string(), "bob:0.40".to_string()]));
+
+        let (total, to) = Splitter::per_head(&group, Money(10_00), targets(&["alice", "bob:2x", "carol:5"]), &[]).unwrap();
+        assert_eq!((total, to), (Money(40_00), vec!["alice:11.67".to_string(), "bob:23.33".to_string(), "carol:5.00".to_string()]));
+
+        let err = Splitter::per_head(&group, Money(5_00), targets(&["alice:6"]), &[]).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(SplitterError::LogicError).as_ref());
+        assert!(Splitter::per_head(&group, Money::MAX, vec![], &[]).is_err());
+    }
+
+    fn pay(splitter: &mut Splitter, amount: Money) {
+        splitter.state.groups[0].log_pay_transaction(amount, "Alice".to_string(), "Bob".to_string()).unwrap();
+        splitter.save().unwrap();
+    }
+
+    #[test]
+    fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.db");
+        let journal = journal::path_for(&db);
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        splitter.run(create("flat")).unwrap();
+        splitter.save().unwrap();
+        let written = std::fs::read(&db).unwrap();
+        assert!(!journal.exists());
+
+        pay(&mut splitter, Money(5_00));
+        pay(&mut splitter, Money(2_00));
+        assert_eq!(std::fs::read(&db).unwrap(), written);
+        assert_eq!(std::fs::read_to_string(&journal).unwrap().lines().count(), 2);
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        assert_eq!(splitter.state.revision, 3);
+        assert_eq!(splitter.state.groups[0].log().len(), 2);
+        assert_eq!(splitter.state.groups[0].member_overview()[0].balance, Money(7_00));
+
+        // anything but new log entries writes the whole database
+        splitter.state.groups[0].add(vec!["Carol".to_string()]).unwrap();
+        splitter.save().unwrap();
+        assert_ne!(std::fs::read(&db).unwrap(), written);
+        assert!(!journal.exists());
+
+        pay(&mut splitter, Money(1_00));
+        assert!(journal.exists());
+        splitter.run(SubCommand::Checkpoint).unwrap();
+        splitter.save().unwrap();
+        assert!(!journal.exists());
+        let loaded = Splitter::new(db.clone()).unwrap();
+        assert_eq!(loaded.state.revision, 5);
+        assert_eq!(loaded.saved.hash, splitter.saved.hash);
+
+        // without journal mode the database is written every time
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        pay(&mut splitter, Money(1_00));
+        assert!(!journal.exists());
+    }
+
+    #[test]
+    fn test_journal_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.db");
+        let journal = journal::path_for(&db);
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        splitter.run(create("flat")).unwrap();
+        splitter.save().unwrap();
+        pay(&mut splitter, Money(5_00));
+        let records = std::fs::read(&journal).unwrap();
+
+        // a crash after the checkpoint wrote the database, before it removed the journal
+        splitter.run(SubCommand::Checkpoint).unwrap();
+        splitter.save().unwrap();
+        std::fs::write(&journal, &records).unwrap();
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        assert_eq!((splitter.state.revision, splitter.state.groups[0].log().len()), (2, 1));
+        pay(&mut splitter, Money(2_00));
+        let loaded = Splitter::new(db.clone()).unwrap();
+        assert_eq!((loaded.state.revision, loaded.state.groups[0].log().len()), (3, 2));
+        let records = std::fs::read(&journal).unwrap();
+
+        // a crash in the middle of appending a record
+        std::fs::write(&journal, [records.as_slice(), b"0123abcd {\"revision\":4,\"curr"].concat()).unwrap();
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        assert_eq!((splitter.state.revision, splitter.state.groups[0].log().len()), (3, 2));
+        pay(&mut splitter, Money(1_00));
+        let loaded = Splitter::new(db.clone()).unwrap();
+        assert_eq!((loaded.state.revision, loaded.state.groups[0].log().len()), (4, 3));
+        assert_eq!(loaded.state.groups[0].member_overview()[0].balance, Money(8_00));
+
+        // records that do not continue the database are refused
+        let skipping = std::fs::read_to_string(&journal).unwrap().lines().last().unwrap()
+            .split_once(' ').unwrap().1.replace("\"revision\":4", "\"revision\":9");
+        std::fs::write(&journal, format!("{:x} {}\n", Sha256::digest(skipping.as_bytes()), skipping)).unwrap();
+        let err = Splitter::new(db.clone()).err().unwrap();
+        assert!(format!("{:#}", err).contains("continues with revision 9, but the database is at revision 2"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_journal_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.db");
+        let mut splitter = Splitter::new(db.clone()).unwrap().journal(true);
+        splitter.run(create("flat")).unwrap();
+        splitter.save().unwrap();
+
+        let mut other = Splitter::new(db.clone()).unwrap().journal(true);
+        pay(&mut splitter, Money(5_00));
+        other.state.groups[0].log_pay_transaction(Money(1_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        let err = other.save().unwrap_err();
+        assert!(err.to_string().contains("was changed by another program"), "{err}");
+        let loaded = Splitter::new(db.clone()).unwrap();
+        assert_eq!(loaded.state.groups[0].member_overview()[0].balance, Money(5_00));
+    }
+
+    #[test]
+    fn test_unchanged_database_is_not_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.db");
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        splitter.run(create("flat")).unwrap();
+        splitter.state.groups[0].split(Money(30_00), vec!["Alice".to_string()], vec![], "fuel".to_string(),
+                                       SplitOptions::default()).unwrap();
+        splitter.save().unwrap();
+        let written = std::fs::read(&db).unwrap();
+        // long ago, so that any write would show in the modification time
+        let long_ago = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options().write(true).open(&db).unwrap().set_modified(long_ago).unwrap();
+
+        let stat = SubCommand::Stat { group: None, all: false, porcelain: true, format: StatFormat::Text, threshold: None,
+                                      show_all: false, in_currency: None, rate: None, period: Period::All,
+                                      as_of: None, member: None };
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        let revision = splitter.state.revision;
+        splitter.run(stat).unwrap();
+        splitter.save().unwrap();
+        assert_eq!(splitter.state.revision, revision);
+        assert_eq!(std::fs::read(&db).unwrap(), written);
+        assert_eq!(std::fs::metadata(&db).unwrap().modified().unwrap(), long_ago);
+
+        // a change is written
+        splitter.state.groups[0].log_pay_transaction(Money(5_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        splitter.save().unwrap();
+        assert_eq!(splitter.state.revision, revision + 1);
+        assert_ne!(std::fs::metadata(&db).unwrap().modified().unwrap(), long_ago);
+    }
 }
@@ -1,9 +1,196 @@
 use std::fmt::{Display, Formatter};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Rem, Sub, SubAssign};
+use std::str::FromStr;
+use std::sync::OnceLock;
 use serde::{Deserialize, Serialize};
 
+/// An amount in minor units of the group currency, e.g. cents. Stored as the plain integer, so
+/// databases are the same as when amounts were bare `i64`. Only multiplied and divided by counts,
+/// money times money has no meaning; `.0` is the number of minor units where a plain integer is needed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct Money(pub(crate) i64);
+
+impl Money {
+    pub(crate) const ZERO: Money = Money(0);
+    pub(crate) const MIN: Money = Money(i64::MIN);
+    pub(crate) const MAX: Money = Money(i64::MAX);
+
+    /// Parses a signed amount in major units like `-12.50` or `12,5`, at most 2 decimals.
+    /// `noun` names what is parsed in the error, e.g. `balance`.
+    pub(crate) fn from_major_str(input: &str, noun: &str) -> Result<Self, String> {
+        let trimmed = input.trim();
+        let (sign, magnitude) = match trimmed.strip_prefix('-') {
+            Some(magnitude) => (-1, magnitude),
+            None => (1, trimmed),
+        };
+        let amount = eval_decimal(magnitude).ok()
+            .and_then(|value| value.to_minor(GIVEN_DECIMALS))
+            .filter(|amount| *amount >= 0)
+            .ok_or_else(|| format!("{noun} must be a number like -12.50 with at most 2 decimals (got '{input}')"))?;
+        let amount = i64::try_from(amount).map_err(|_| out_of_range(input))?;
+        Ok(Money(sign * amount))
+    }
+
+    /// renders the amount with the symbol of `currency`, e.g. `12.50€`
+    pub(crate) fn to_major_string(self, currency: Currency) -> String {
+        format!("{}{currency}", self.to_decimal_string(currency))
+    }
+
+    /// renders the amount in major units of `currency` without a symbol, e.g. `-12.50`, or `1250` for yen
+    pub(crate) fn to_decimal_string(self, currency: Currency) -> String {
+        self.with_decimals(currency.decimals())
+    }
+
+    /// renders minor units of a currency with `decimals` in major units, see `to_decimal_string`
+    fn with_decimals(self, decimals: u32) -> String {
+        let subdivision = 10u64.pow(decimals);
+        let sign = if self.is_negative() { "-" } else { "" };
+        let (major, minor) = (self.unsigned_abs() / subdivision, self.unsigned_abs() % subdivision);
+        match decimals {
+            0 => format!("{sign}{major}"),
+            _ => format!("{sign}{major}.{minor:0width$}", width = decimals as usize),
+        }
+    }
+
+    /// the amount in minor units of `to` per major unit instead of `from`, rounded half away from zero
+    pub(crate) fn rescale(self, from: i64, to: i64) -> Money {
+        convert_minor(self, &Rate { mantissa: 1, scale: 0 }, from as i128, to as i128)
+    }
+
+    pub(crate) fn abs(self) -> Self {
+        Money(self.0.abs())
+    }
+
+    pub(crate) fn unsigned_abs(self) -> u64 {
+        self.0.unsigned_abs()
+    }
+
+    /// -1, 0 or 1, a count to multiply money with
+    pub(crate) fn signum(self) -> i64 {
+        self.0.signum()
+    }
+
+    pub(crate) fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub(crate) fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    pub(crate) fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub(crate) fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub(crate) fn checked_mul(self, count: i64) -> Option<Self> {
+        self.0.checked_mul(count).map(Money)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Self) -> Self {
+        Money(self.0 + other.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Self) -> Self {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Self {
+        Money(-self.0)
+    }
+}
+
+impl Neg for &Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+impl AddAssign<&Money> for Money {
+    fn add_assign(&mut self, other: &Money) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl Mul<i64> for Money {
+    type Output = Money;
+
+    fn mul(self, count: i64) -> Self {
+        Money(self.0 * count)
+    }
+}
+
+impl Mul<Money> for i64 {
+    type Output = Money;
+
+    fn mul(self, amount: Money) -> Money {
+        amount * self
+    }
+}
+
+impl Div<i64> for Money {
+    type Output = Money;
+
+    fn div(self, count: i64) -> Self {
+        Money(self.0 / count)
+    }
+}
+
+/// what is left of an amount divided by a count
+impl Rem<i64> for Money {
+    type Output = Money;
+
+    fn rem(self, count: i64) -> Self {
+        Money(self.0 % count)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item=Money>>(iter: I) -> Self {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item=&'a Money>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
 #[non_exhaustive]
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Currency {
     EUR,
     USD,
@@ -12,12 +199,368 @@ pub enum Currency {
 }
 
 impl Currency {
-    pub(crate) fn subdivision(&self) -> f32 {
+    /// all supported currencies
+    #[cfg(feature = "cli")]
+    const ALL: [Currency; 4] = [Self::EUR, Self::USD, Self::JPY, Self::GBP];
+
+    /// the ISO codes of all supported currencies, e.g. `EUR`
+    #[cfg(feature = "cli")]
+    pub(crate) fn codes() -> Vec<String> {
+        Self::ALL.iter().map(|currency| format!("{currency:?}")).collect()
+    }
+
+    /// how many decimals an amount has in major units, none for the yen
+    pub(crate) fn decimals(&self) -> u32 {
         match self {
             Self::EUR |
             Self::USD |
-            Self::GBP |
-            Self::JPY => 100.,
+            Self::GBP => 2,
+            Self::JPY => 0,
+        }
+    }
+
+    /// minor units per major unit, 1 for the yen
+    pub(crate) fn subdivision(&self) -> i64 {
+        10i64.pow(self.decimals())
+    }
+
+    /// Converts an amount given on the command line, read with two decimals by `parse_amount`,
+    /// into minor units of this currency. Fails for decimals the currency does not have.
+    pub(crate) fn amount(&self, given: Money) -> Result<Money, String> {
+        let factor = 10i64.pow(GIVEN_DECIMALS - self.decimals());
+        if !(given % factor).is_zero() {
+            return Err(format!("amounts in {:?} can have {} (got {})", self, self.allowed_decimals(), plain(given)));
+        }
+        Ok(given / factor)
+    }
+
+    /// how many decimals amounts may have, for error messages
+    pub(crate) fn allowed_decimals(&self) -> String {
+        match self.decimals() {
+            0 => "no decimals".to_string(),
+            decimals => format!("at most {decimals} decimals"),
+        }
+    }
+
+    /// renders an amount given in minor units, e.g. `1250` as `12.50€`
+    pub(crate) fn format(&self, amount: Money) -> String {
+        amount.to_major_string(*self)
+    }
+}
+
+/// decimals of amounts given on the command line, see `parse_amount` and `Currency::amount`
+pub(crate) const GIVEN_DECIMALS: u32 = 2;
+
+/// default ceiling for amounts, in minor units. Larger ones are most likely typos like a misplaced
+/// exponent, and sums of many expenses stay far away from overflowing
+pub(crate) const MAX_AMOUNT: Money = Money(1_000_000_000_00);
+
+/// the ceiling of this run, set by `install_ceiling`
+static CEILING: OnceLock<Option<Money>> = OnceLock::new();
+
+/// Sets the ceiling for amounts, `None` for none at all with `--allow-huge`. Only the first call counts.
+#[cfg(feature = "cli")]
+pub(crate) fn install_ceiling(ceiling: Option<Money>) {
+    let _ = CEILING.set(ceiling);
+}
+
+/// the largest amount accepted, `MAX_AMOUNT` unless another one was installed
+pub(crate) fn ceiling() -> Option<Money> {
+    CEILING.get().copied().unwrap_or(Some(MAX_AMOUNT))
+}
+
+/// renders an amount as given on the command line, in hundredths, e.g. `1250` as `12.50`
+pub(crate) fn plain(amount: Money) -> String {
+    amount.with_decimals(GIVEN_DECIMALS)
+}
+
+/// `amount` if it is not above the ceiling
+pub(crate) fn check_ceiling(amount: Money) -> Result<Money, String> {
+    match ceiling() {
+        Some(ceiling) if amount.unsigned_abs() > ceiling.unsigned_abs() => Err(format!(
+            "{} is above the ceiling of {} for amounts, pass --allow-huge if it really is that much",
+            plain(amount), plain(ceiling))),
+        _ => Ok(amount),
+    }
+}
+
+/// the error of an amount not fitting into `Money` at all
+fn out_of_range(input: &str) -> String {
+    format!("amount does not fit into 64 bits, the largest possible one is {} (got '{input}')", plain(Money::MAX))
+}
+
+/// An exact decimal number, `mantissa / 10^scale`, as computed by amount expressions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// the error of an overflowing expression, turned into a "too large" message by the callers
+const OVERFLOW: &str = "overflow";
+
+impl Decimal {
+    fn rescale(self, scale: u32) -> Result<i128, String> {
+        10i128.checked_pow(scale - self.scale)
+            .and_then(|factor| self.mantissa.checked_mul(factor))
+            .ok_or(OVERFLOW.to_string())
+    }
+
+    fn add(self, other: Self) -> Result<Self, String> {
+        let scale = self.scale.max(other.scale);
+        let mantissa = self.rescale(scale)?.checked_add(other.rescale(scale)?).ok_or(OVERFLOW)?;
+        Ok(Self { mantissa, scale })
+    }
+
+    fn mul(self, other: Self) -> Result<Self, String> {
+        let mantissa = self.mantissa.checked_mul(other.mantissa).ok_or(OVERFLOW)?;
+        Ok(Self { mantissa, scale: self.scale + other.scale }.normalized())
+    }
+
+    fn neg(self) -> Self {
+        Self { mantissa: -self.mantissa, scale: self.scale }
+    }
+
+    /// drops trailing zero decimals so products do not grow the scale needlessly
+    fn normalized(mut self) -> Self {
+        while self.scale > 0 && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    /// the value in minor units of a currency with `decimals`, `None` if it has more decimals
+    pub(crate) fn to_minor(self, decimals: u32) -> Option<i128> {
+        let value = self.normalized();
+        if value.scale > decimals {
+            None
+        } else {
+            value.mantissa.checked_mul(10i128.pow(decimals - value.scale))
+        }
+    }
+
+    /// this value as percentage of `total`, rounded towards zero
+    pub(crate) fn percent_of(self, total: Money) -> Option<Money> {
+        let product = self.mantissa.checked_mul(total.0 as i128)?;
+        i64::try_from(product / 10i128.checked_pow(self.scale)? / 100).ok().map(Money)
+    }
+}
+
+/// Recursive descent evaluator for amount expressions like `3*4.20+2*2,50`:
+/// `+`, `-`, `*`, parentheses and decimal numbers with `.` or `,` as decimal separator.
+/// Division is not supported since the rounding of e.g. `10/3` would be a guess.
+struct Expression<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Expression<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while self.input.get(self.position).is_some_and(|c| c.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        self.input.get(self.position).copied()
+    }
+
+    fn unexpected(&self) -> String {
+        match self.input.get(self.position) {
+            Some(b'/') => "division is not supported, the rounding would be ambiguous".to_string(),
+            Some(_) => format!("unexpected '{}' at position {}",
+                               String::from_utf8_lossy(&self.input[self.position..]).chars().next().unwrap(),
+                               self.position + 1),
+            None => "unexpected end of the expression".to_string(),
+        }
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Decimal, String> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(b'+') => {
+                    self.position += 1;
+                    value = value.add(self.term()?)?;
+                }
+                Some(b'-') => {
+                    self.position += 1;
+                    value = value.add(self.term()?.neg())?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// term := factor ('*' factor)*
+    fn term(&mut self) -> Result<Decimal, String> {
+        let mut value = self.factor()?;
+        while self.peek() == Some(b'*') {
+            self.position += 1;
+            value = value.mul(self.factor()?)?;
+        }
+        Ok(value)
+    }
+
+    /// factor := '-' factor | '(' expression ')' | number
+    fn factor(&mut self) -> Result<Decimal, String> {
+        match self.peek() {
+            Some(b'-') => {
+                self.position += 1;
+                Ok(self.factor()?.neg())
+            }
+            Some(b'(') => {
+                self.position += 1;
+                let value = self.expression()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.unexpected());
+                }
+                self.position += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == b'.' || c == b',' => self.number(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// number := digits [('.' | ',') digits], either part may be empty but not both
+    fn number(&mut self) -> Result<Decimal, String> {
+        let start = self.position;
+        let (mut mantissa, mut scale, mut digits, mut separator) = (0i128, 0u32, 0, false);
+        while let Some(&c) = self.input.get(self.position) {
+            match c {
+                b'0'..=b'9' => {
+                    mantissa = mantissa.checked_mul(10)
+                        .and_then(|m| m.checked_add((c - b'0') as i128))
+                        .ok_or(OVERFLOW)?;
+                    digits += 1;
+                    scale += separator as u32;
+                }
+                b'.' | b',' if !separator => separator = true,
+                _ => break,
+            }
+            self.position += 1;
+        }
+        if digits == 0 {
+            self.position = start;
+            return Err(self.unexpected());
+        }
+        Ok(Decimal { mantissa, scale })
+    }
+}
+
+/// Evaluates an amount expression exactly, see `Expression`
+pub(crate) fn eval_decimal(input: &str) -> Result<Decimal, String> {
+    let mut expression = Expression { input: input.as_bytes(), position: 0 };
+    let value = expression.expression()?;
+    if expression.peek().is_some() {
+        return Err(expression.unexpected());
+    }
+    Ok(value)
+}
+
+/// Parses an amount like `12.50`, `12,5` or an expression like `3*4.20+2.50` into hundredths
+/// without going through floats, see `Currency::amount` for currencies with another subdivision.
+/// Used as clap value parser, hence the plain string error. The ceiling is checked once the options are known, see `SubCommand::amounts`.
+pub(crate) fn parse_amount(input: &str) -> Result<Money, String> {
+    const EXPECTED: &str = "amount must be a positive number like 12.50";
+    let not_a_number = || format!("{EXPECTED} (got '{input}')");
+    let value = eval_decimal(input).map_err(|error| match error.as_str() {
+        OVERFLOW => out_of_range(input),
+        _ => format!("{EXPECTED} or an expression like 3*4.20+2.50 (got '{input}'): {error}"),
+    })?;
+    let amount = value.to_minor(GIVEN_DECIMALS)
+        .ok_or_else(|| format!("amount can have at most 2 decimals (got '{input}')"))?;
+    if amount <= 0 {
+        Err(not_a_number())
+    } else {
+        i64::try_from(amount).map(Money).map_err(|_| out_of_range(input))
+    }
+}
+
+/// Parses a balance like `-12.50` into minor units. Unlike an amount it may be zero or negative.
+pub(crate) fn parse_balance(input: &str) -> Result<Money, String> {
+    let amount = Money::from_major_str(input, "balance")?;
+    check_ceiling(amount)
+}
+
+/// Parses a currency code like `USD`, used as clap value parser
+#[cfg(feature = "cli")]
+pub(crate) fn parse_currency(input: &str) -> Result<Currency, String> {
+    Currency::ALL.into_iter().find(|currency| format!("{currency:?}").eq_ignore_ascii_case(input.trim()))
+        .ok_or_else(|| format!("unknown currency '{input}', valid currencies: {}", Currency::codes().join(", ")))
+}
+
+/// most decimals an exchange rate may have
+const MAX_RATE_DECIMALS: u32 = 9;
+
+/// An exchange rate as exact decimal, `mantissa / 10^scale` units of the target currency
+/// per unit of the source currency. Stored as its decimal string, e.g. `"1.0832"`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Rate {
+    mantissa: u64,
+    scale: u32,
+}
+
+impl Rate {
+    /// Converts an amount in minor units of `from` into minor units of `to`, rounding half away
+    /// from zero. Integer math only, so the subdivisions of both currencies are taken into account exactly.
+    pub(crate) fn convert(&self, amount: Money, from: Currency, to: Currency) -> Money {
+        convert_minor(amount, self, from.subdivision() as i128, to.subdivision() as i128)
+    }
+}
+
+fn convert_minor(amount: Money, rate: &Rate, from_subdivision: i128, to_subdivision: i128) -> Money {
+    let numerator = amount.0 as i128 * rate.mantissa as i128 * to_subdivision;
+    let denominator = 10i128.pow(rate.scale) * from_subdivision;
+    let rounded = (numerator.abs() + denominator / 2) / denominator * numerator.signum();
+    // amounts are limited far below i64::MAX and rates to u64, so this cannot overflow i128
+    i64::try_from(rounded).map(Money).unwrap_or(if rounded < 0 { Money::MIN } else { Money::MAX })
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("exchange rate must be a positive decimal number like 1.08 (got '{input}')");
+        let normalized = input.trim().replace(',', ".");
+        let (major, minor) = normalized.split_once('.').unwrap_or((normalized.as_str(), ""));
+        if major.is_empty() && minor.is_empty()
+            || !major.chars().chain(minor.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if minor.len() as u32 > MAX_RATE_DECIMALS {
+            return Err(format!("exchange rate can have at most {MAX_RATE_DECIMALS} decimals (got '{input}')"));
+        }
+        let mantissa: u64 = format!("{major}{minor}").parse().map_err(|_| invalid())?;
+        if mantissa == 0 {
+            return Err(invalid());
+        }
+        Ok(Rate { mantissa, scale: minor.len() as u32 })
+    }
+}
+
+impl TryFrom<String> for Rate {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Rate> for String {
+    fn from(rate: Rate) -> Self {
+        rate.to_string()
+    }
+}
+
+impl Display for Rate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let divisor = 10u64.pow(self.scale);
+        if self.scale == 0 {
+            write!(f, "{}", self.mantissa)
+        } else {
+            write!(f, "{}.{:0width$}", self.mantissa / divisor, self.mantissa % divisor, width = self.scale as usize)
         }
     }
 }
@@ -45,3 +588,144 @@ impl From<&str> for Currency {
         }
     }
 }
+
+#[cfg(test)]
+mod money_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_balance() {
+        assert_eq!(parse_balance("-12.50"), Ok(Money(-12_50)));
+        assert_eq!(parse_balance(" 7,5 "), Ok(Money(7_50)));
+        assert_eq!(parse_balance("0"), Ok(Money::ZERO));
+        assert!(parse_balance("x").unwrap_err().starts_with("balance must be a number like -12.50"));
+        assert!(parse_balance("-").is_err());
+        assert!(parse_balance("1.234").is_err());
+        assert!(parse_balance("--3").is_err());
+    }
+
+    #[test]
+    fn test_money() {
+        assert_eq!(Money::from_major_str("-12.50", "amount"), Ok(Money(-12_50)));
+        assert_eq!(Money::from_major_str(" 3,5 ", "amount"), Ok(Money(3_50)));
+        assert_eq!(Money::from_major_str("12.345", "amount").unwrap_err(),
+                   "amount must be a number like -12.50 with at most 2 decimals (got '12.345')");
+        assert!(Money::from_major_str("92233720368547758.08", "amount").unwrap_err().contains("does not fit into 64 bits"));
+        assert_eq!(Money(-12_05).to_major_string(Currency::EUR), "-12.05€");
+        assert_eq!(Money(7).to_major_string(Currency::USD), Currency::USD.format(Money(7)));
+
+        assert_eq!(Money(10_00) - Money(2_50) * 3, Money(2_50));
+        assert_eq!((Money(10_00) / 3, Money(10_00) % 3), (Money(3_33), Money(1)));
+        assert_eq!([Money(1_00), Money(-25)].iter().sum::<Money>(), Money(75));
+        assert!(Money(-1) < Money::ZERO && Money::ZERO < Money(1));
+        assert_eq!(Money::MAX.checked_add(Money(1)), None);
+        assert_eq!(Money::MAX.checked_add(Money(-1)), Some(Money(i64::MAX - 1)));
+        assert_eq!(Money(15_50).checked_mul(3), Some(Money(46_50)));
+        assert_eq!(Money::MAX.checked_mul(2), None);
+
+        // stored as the bare number of cents, like before amounts had a type
+        assert_eq!(serde_yaml::to_string(&Money(-14_00)).unwrap(), "-1400\n");
+        assert_eq!(serde_json::from_str::<Money>("1250").unwrap(), Money(12_50));
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("12.50"), Ok(Money(12_50)));
+        assert_eq!(parse_amount("12,5"), Ok(Money(12_50)));
+        assert_eq!(parse_amount("7"), Ok(Money(7_00)));
+        assert_eq!(parse_amount(".5"), Ok(Money(50)));
+        assert_eq!(parse_amount("0.01"), Ok(Money(1)));
+        assert_eq!(parse_amount("abc").unwrap_err(),
+                   "amount must be a positive number like 12.50 or an expression like 3*4.20+2.50 (got 'abc'): \
+                   unexpected 'a' at position 1");
+        assert!(parse_amount("-5").unwrap_err().contains("positive number"));
+        assert!(parse_amount("0").unwrap_err().contains("positive number"));
+        assert!(parse_amount("1.2.3").unwrap_err().contains("positive number"));
+        assert!(parse_amount("").unwrap_err().contains("positive number"));
+    }
+
+    #[test]
+    fn test_rate() {
+        let rate: Rate = "1.0832".parse().unwrap();
+        assert_eq!(rate.to_string(), "1.0832");
+        assert_eq!("0,5".parse::<Rate>().unwrap().to_string(), "0.5");
+        assert_eq!("2".parse::<Rate>().unwrap(), Rate { mantissa: 2, scale: 0 });
+        assert!("0".parse::<Rate>().is_err());
+        assert!("-1".parse::<Rate>().is_err());
+        assert!("1.0000000001".parse::<Rate>().unwrap_err().contains("at most 9 decimals"));
+        assert_eq!(serde_yaml::from_str::<Rate>("'1.0832'").unwrap(), rate);
+
+        assert_eq!(rate.convert(Money(100_00), Currency::EUR, Currency::USD), Money(10832));
+        assert_eq!(rate.convert(Money(-100_00), Currency::EUR, Currency::USD), Money(-10832));
+        // rounds half away from zero
+        let half: Rate = "0.5".parse().unwrap();
+        assert_eq!(half.convert(Money(1), Currency::EUR, Currency::USD), Money(1));
+        assert_eq!(half.convert(Money(-1), Currency::EUR, Currency::USD), Money(-1));
+        assert_eq!(half.convert(Money(2), Currency::EUR, Currency::USD), Money(1));
+        assert_eq!("1".parse::<Rate>().unwrap().convert(Money(12_34), Currency::EUR, Currency::GBP), Money(12_34));
+    }
+
+    #[test]
+    fn test_convert_subdivisions() {
+        // 12.34 EUR at 161.5 yen per euro, yen having no minor unit
+        let rate: Rate = "161.5".parse().unwrap();
+        assert_eq!(rate.convert(Money(12_34), Currency::EUR, Currency::JPY), Money(1993));
+        // and back: 1993 yen at 0.00619 euro per yen
+        let back: Rate = "0.00619".parse().unwrap();
+        assert_eq!(back.convert(Money(1993), Currency::JPY, Currency::EUR), Money(12_34));
+        assert_eq!(Money(1993).rescale(1, 100), Money(1993_00));
+        assert_eq!(Money(12_50).rescale(100, 1), Money(13));
+    }
+
+    #[test]
+    fn test_yen() {
+        assert_eq!(Money(-1050).to_major_string(Currency::JPY), "-1050¥");
+        assert_eq!(Money(5).to_decimal_string(Currency::EUR), "0.05");
+        assert_eq!(Currency::JPY.amount(parse_amount("1050").unwrap()), Ok(Money(1050)));
+        assert_eq!(Currency::EUR.amount(parse_amount("10.50").unwrap()), Ok(Money(10_50)));
+        assert_eq!(Currency::JPY.amount(parse_amount("10.50").unwrap()).unwrap_err(),
+                   "amounts in JPY can have no decimals (got 10.50)");
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        assert_eq!(parse_amount("3*4.20+2*2.50"), Ok(Money(17_60)));
+        assert_eq!(parse_amount("3*4.20"), Ok(Money(12_60)));
+        assert_eq!(parse_amount(" (1 + 2,5) * 2 "), Ok(Money(7_00)));
+        assert_eq!(parse_amount("10-2.5-(3-1)"), Ok(Money(5_50)));
+        assert_eq!(parse_amount("-2+5"), Ok(Money(3_00)));
+        assert_eq!(parse_amount("0.1*0.1*100"), Ok(Money(1_00)));
+        assert_eq!(parse_amount("1.005*2"), Ok(Money(2_01)));
+        assert_eq!(parse_amount("1.5*0.15").unwrap_err(), "amount can have at most 2 decimals (got '1.5*0.15')");
+        assert!(parse_amount("10/3").unwrap_err().contains("division is not supported"));
+        assert!(parse_amount("2*(3+4").unwrap_err().contains("unexpected end"));
+        assert!(parse_amount("2**3").unwrap_err().contains("unexpected '*' at position 3"));
+        assert!(parse_amount("5-5").unwrap_err().contains("positive number"));
+        assert!(parse_amount("99999999999*99999999999*99999999999").unwrap_err().contains("does not fit into 64 bits"));
+        assert_eq!(eval_decimal("12.5").unwrap().percent_of(Money(30_00)), Some(Money(3_75)));
+    }
+
+    #[test]
+    fn test_parse_amount_limits() {
+        assert_eq!(parse_amount("1.234").unwrap_err(), "amount can have at most 2 decimals (got '1.234')");
+        assert_eq!(parse_amount("92233720368547758.07"), Ok(Money::MAX));
+        assert_eq!(parse_amount("92233720368547758.08").unwrap_err(),
+                   "amount does not fit into 64 bits, the largest possible one is 92233720368547758.07 \
+                    (got '92233720368547758.08')");
+        assert!(parse_amount("99999999999999999999999").unwrap_err().contains("does not fit into 64 bits"));
+    }
+
+    #[test]
+    fn test_ceiling() {
+        assert_eq!(check_ceiling(MAX_AMOUNT), Ok(MAX_AMOUNT));
+        assert_eq!(check_ceiling(-MAX_AMOUNT), Ok(-MAX_AMOUNT));
+        assert_eq!(check_ceiling(MAX_AMOUNT + Money(1)).unwrap_err(),
+                   "1000000000.01 is above the ceiling of 1000000000.00 for amounts, \
+                    pass --allow-huge if it really is that much");
+        assert!(check_ceiling(Money::MIN).is_err());
+        assert_eq!(parse_balance("-1000000000"), Ok(-MAX_AMOUNT));
+        assert!(parse_balance("-1000000000.01").unwrap_err().contains("above the ceiling"));
+        assert!(parse_balance("-92233720368547758.08").unwrap_err().contains("does not fit into 64 bits"));
+        assert_eq!(plain(Money(-5)), "-0.05");
+    }
+}
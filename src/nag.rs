@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Local};
+use crate::group::Group;
+use crate::logging::LogEntry;
+use crate::logic::Money;
+use crate::money::{Currency, GIVEN_DECIMALS};
+
+/// A group whose debts have been open for a while, printed as a notice when splitter starts,
+/// see the `nag_days` setting
+#[derive(Debug, PartialEq)]
+pub(crate) struct Stale {
+    pub(crate) group: String,
+    pub(crate) currency: Currency,
+    /// what the members with a positive balance are owed in total
+    pub(crate) unsettled: Money,
+    pub(crate) days: i64,
+}
+
+impl Display for Stale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "group '{}' has {} unsettled for {} days — run `splitter balance {}`",
+               self.group, self.currency.format(self.unsettled), self.days, self.group)
+    }
+}
+
+/// Since when the debts left by `log` are open: the time of the first entry after the balances were
+/// last all zero. `None` if they are all zero at the end or that entry has no time.
+pub(crate) fn unsettled_since(log: &[LogEntry]) -> Option<DateTime<Local>> {
+    let mut balances: HashMap<&String, Money> = HashMap::new();
+    let mut since = None;
+    for entry in log {
+        if balances.values().all(|balance| balance.is_zero()) {
+            since = entry.time;
+        }
+        for (member, change) in entry.change() {
+            *balances.entry(member).or_insert(Money::ZERO) += change;
+        }
+    }
+    since.filter(|_| balances.values().any(|balance| !balance.is_zero()))
+}
+
+/// The groups that are not archived, owe at least `threshold` in total and have not been settled
+/// for at least `days` days at `now`. The threshold is read like an amount on the command line and
+/// rounded to the minor units of each group, see `Currency::amount`.
+pub(crate) fn stale(groups: &[Group], now: DateTime<Local>, days: u32, threshold: Money) -> Vec<Stale> {
+    groups.iter()
+        .filter(|group| !group.archived)
+        .filter_map(|group| {
+            let since = unsettled_since(group.log())?;
+            let unsettled = group.member_overview().iter()
+                .map(|member| member.balance)
+                .filter(|balance| balance.is_positive())
+                .sum();
+            let stale = Stale { group: group.name.clone(), currency: group.currency, unsettled,
+                                days: (now - since).num_days() };
+            let threshold = threshold.rescale(10i64.pow(GIVEN_DECIMALS), group.currency.subdivision());
+            (stale.days >= days as i64 && unsettled >= threshold).then_some(stale)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod nag_tests {
+    use chrono::Duration;
+    use crate::logging::LoggedCommand;
+    use super::*;
+
+    fn pay(amount: Money, from: &str, to: &str, time: DateTime<Local>) -> LogEntry {
+        let command = LoggedCommand::Pay { amount, from: from.to_string(), to: to.to_string(), group: "flat".to_string(),
+                                           settled_by: None };
+        let change = HashMap::from([(from.to_string(), amount), (to.to_string(), -amount)]);
+        let mut entry = LogEntry::new(command, change);
+        entry.time = Some(time);
+        entry
+    }
+
+    #[test]
+    fn test_unsettled_since() {
+        let now = Local::now();
+        let ago = |days: i64| now - Duration::days(days);
+        let mut log = vec![];
+        assert_eq!(unsettled_since(&log), None);
+        log.push(pay(Money(10_00), "alice", "bob", ago(40)));
+        log.push(pay(Money(20_00), "alice", "bob", ago(30)));
+        assert_eq!(unsettled_since(&log), Some(ago(40)));
+        // settling up in between restarts the clock
+        log.push(pay(Money(30_00), "bob", "alice", ago(20)));
+        assert_eq!(unsettled_since(&log), None);
+        log.push(pay(Money(10_00), "alice", "bob", ago(5)));
+        assert_eq!(unsettled_since(&log), Some(ago(5)));
+        log[3].time = None;
+        assert_eq!(unsettled_since(&log), None);
+    }
+
+    #[test]
+    fn test_stale() {
+        let group = |name: &str| Group::new(name.to_string(), vec!["alice".to_string(), "bob".to_string()], None).unwrap();
+        let mut flat = group("flat");
+        flat.log_pay_transaction(Money(50_00), "alice".to_string(), "bob".to_string()).unwrap();
+        flat.log_pay_transaction(Money(20_00), "bob".to_string(), "alice".to_string()).unwrap();
+        let mut settled = group("settled");
+        settled.log_pay_transaction(Money(50_00), "alice".to_string(), "bob".to_string()).unwrap();
+        settled.log_pay_transaction(Money(50_00), "bob".to_string(), "alice".to_string()).unwrap();
+        let mut archived = flat.clone();
+        archived.name = "archived".to_string();
+        archived.archived = true;
+        let groups = vec![flat, settled, archived, group("empty")];
+
+        // the entries were recorded just now, so look at them from 92 days later
+        let later = Local::now() + Duration::days(92);
+        let found = stale(&groups, later, 90, Money::ZERO);
+        assert_eq!(found, vec![Stale { group: "flat".to_string(), currency: Currency::EUR, unsettled: Money(30_00), days: 92 }]);
+        assert_eq!(found[0].to_string(), "group 'flat' has 30.00€ unsettled for 92 days — run `splitter balance flat`");
+        assert!(stale(&groups, later, 93, Money::ZERO).is_empty());
+        assert!(stale(&groups, later, 90, Money(30_01)).is_empty());
+        assert!(stale(&groups, Local::now(), 90, Money::ZERO).is_empty());
+    }
+}
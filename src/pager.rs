@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use crate::render::terminal_height;
+
+/// used when `$PAGER` is not set. -F quits right away if everything fits on one screen,
+/// -R keeps colors, -X leaves the text on the screen after quitting
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// The pager command from the value of `$PAGER`, split into program and arguments.
+/// An empty `$PAGER` switches paging off.
+fn pager_command(pager: Option<String>) -> Option<Vec<String>> {
+    let pager = pager.unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let command: Vec<String> = pager.split_whitespace().map(String::from).collect();
+    if command.is_empty() { None } else { Some(command) }
+}
+
+/// whether `text` does not fit into a terminal `height` lines high
+fn exceeds(text: &str, height: usize) -> bool {
+    text.lines().count() >= height
+}
+
+/// Pipes `text` through the pager and waits for it to quit
+fn run_pager(command: &[String], text: &str) -> std::io::Result<()> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    // the user quitting the pager early closes the pipe, which is no error
+    let _ = child.stdin.take().expect("stdin is piped").write_all(text.as_bytes());
+    child.wait()?;
+    Ok(())
+}
+
+/// Prints human readable output. When `enabled`, stdout is a terminal and the text does not fit
+/// on the screen it goes through `$PAGER`, or plainly printed if that cannot be started.
+/// Otherwise it is written straight to stdout, without rendering it into one String first.
+pub(crate) fn page(text: &impl Display, enabled: bool) {
+    if enabled && std::io::stdout().is_terminal() {
+        let text = text.to_string();
+        if exceeds(&text, terminal_height()) {
+            if let Some(command) = pager_command(std::env::var("PAGER").ok()) {
+                if run_pager(&command, &text).is_ok() {
+                    return;
+                }
+            }
+        }
+        print!("{}", text);
+        return;
+    }
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    // a closed stdout, e.g. piped into head, is no error
+    let _ = write!(out, "{}", text).and_then(|_| out.flush());
+}
+
+#[cfg(test)]
+mod pager_tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(pager_command(None).unwrap(), vec!["less", "-FRX"]);
+        assert_eq!(pager_command(Some("most -s".to_string())).unwrap(), vec!["most", "-s"]);
+        assert_eq!(pager_command(Some(" ".to_string())), None);
+    }
+
+    #[test]
+    fn test_exceeds() {
+        // the prompt below the output needs a line as well
+        assert!(!exceeds("a\nb\n", 3));
+        assert!(exceeds("a\nb\nc\n", 3));
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// A calendar month like `2024-06`, the unit `close-month` works with.
+/// Stored as that string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Month {
+    year: i32,
+    month: u32,
+}
+
+impl Month {
+    /// the month `time` falls into
+    pub(crate) fn of(time: DateTime<Local>) -> Self {
+        Month { year: time.year(), month: time.month() }
+    }
+    pub(crate) fn previous(self) -> Self {
+        match self.month {
+            1 => Month { year: self.year - 1, month: 12 },
+            month => Month { year: self.year, month: month - 1 },
+        }
+    }
+    pub(crate) fn next(self) -> Self {
+        match self.month {
+            12 => Month { year: self.year + 1, month: 1 },
+            month => Month { year: self.year, month: month + 1 },
+        }
+    }
+    /// local midnight of the first day
+    pub(crate) fn start(self) -> DateTime<Local> {
+        midnight(NaiveDate::from_ymd_opt(self.year, self.month, 1).unwrap())
+    }
+    /// the start of the next month, the first moment not belonging to this one
+    pub(crate) fn end(self) -> DateTime<Local> {
+        self.next().start()
+    }
+}
+
+/// local midnight at the start of `date`
+fn midnight(date: NaiveDate) -> DateTime<Local> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    // the earlier one if the clocks are turned back at midnight
+    Local.from_local_datetime(&midnight).earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&midnight))
+}
+
+/// the first moment after `date`, local midnight of the next day
+pub(crate) fn day_end(date: NaiveDate) -> DateTime<Local> {
+    midnight(date.succ_opt().unwrap_or(NaiveDate::MAX))
+}
+
+impl FromStr for Month {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("month must look like 2024-06 (got '{input}')");
+        let (year, month) = input.trim().split_once('-').ok_or_else(invalid)?;
+        let year: i32 = year.parse().map_err(|_| invalid())?;
+        let month: u32 = month.parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) || !(1..=9999).contains(&year) {
+            return Err(invalid());
+        }
+        Ok(Month { year, month })
+    }
+}
+
+impl TryFrom<String> for Month {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Month> for String {
+    fn from(month: Month) -> Self {
+        month.to_string()
+    }
+}
+
+impl Display for Month {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+/// A month closed with `close-month`. Entries recorded after its end belong to the open period.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ClosedMonth {
+    pub(crate) month: Month,
+    /// when it was closed
+    pub(crate) time: DateTime<Local>,
+    /// whether the suggested transfers were recorded as payments
+    pub(crate) settled: bool,
+}
+
+#[cfg(test)]
+mod period_tests {
+    use super::*;
+
+    #[test]
+    fn test_month() {
+        let june: Month = "2024-06".parse().unwrap();
+        assert_eq!(june.to_string(), "2024-06");
+        assert_eq!(june.next().to_string(), "2024-07");
+        assert_eq!("2024-12".parse::<Month>().unwrap().next().to_string(), "2025-01");
+        assert_eq!("2024-01".parse::<Month>().unwrap().previous().to_string(), "2023-12");
+        assert_eq!(Month::of(june.start()), june);
+        assert_eq!(june.end(), june.next().start());
+        assert!(june < june.next());
+        for invalid in ["2024-13", "2024-00", "june", "2024", "2024-6x"] {
+            assert!(invalid.parse::<Month>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use crate::logging::{LogEntry, LoggedCommand};
+use crate::logic::{Target, Transaction};
+use crate::report::{GroupListing, GroupOverview, GroupStat, ListedEntry};
+
+/// Stable output for scripts, selected with `--porcelain`: one record per line, fields separated
+/// by tabs, amounts in minor units, no header. The field order of each record is documented on its
+/// implementation and in the README and must not change; new fields may only be appended.
+pub(crate) trait Porcelain {
+    fn porcelain(&self) -> String;
+}
+
+/// A field that cannot contain the separators: `\`, tab and newline are escaped as `\\`, `\t` and `\n`
+fn field(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+/// optional fields are `-` if missing
+fn optional(value: Option<&str>) -> String {
+    value.map_or("-".to_string(), field)
+}
+
+/// times in UTC as RFC 3339, independent of the local timezone
+fn time(time: Option<DateTime<Local>>) -> String {
+    time.map_or("-".to_string(), |time| time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn flag(value: bool) -> &'static str {
+    if value { "1" } else { "0" }
+}
+
+fn record(fields: &[String]) -> String {
+    format!("{}\n", fields.join("\t"))
+}
+
+/// `stat`: group, currency, member, balance
+impl Porcelain for GroupStat {
+    fn porcelain(&self) -> String {
+        self.members.iter()
+            .map(|(member, balance)| record(&[
+                field(&self.name), format!("{:?}", self.currency), field(member), balance.0.to_string()]))
+            .collect()
+    }
+}
+
+/// comma-separated member names, `-` if there are none
+fn members(targets: &[Target]) -> String {
+    match targets {
+        [] => "-".to_string(),
+        targets => targets.iter().map(|t| field(&t.member)).collect::<Vec<_>>().join(","),
+    }
+}
+
+/// the members credited by a buy-in of `newcomer`, comma-separated in alphabetical order
+fn credited(entry: &LogEntry, newcomer: &str) -> String {
+    let mut credited: Vec<String> = entry.change().iter()
+        .filter(|(member, change)| member.as_str() != newcomer && change.is_positive())
+        .map(|(member, _)| field(member))
+        .collect();
+    credited.sort();
+    if credited.is_empty() { "-".to_string() } else { credited.join(",") }
+}
+
+/// `list`: group, index, time, author, kind (`split`, `refund`, `pay`, `opening`, `write-off`, `buy-in` or `reset`),
+/// amount, name, category, payers, recipients. Payers and recipients are comma-separated member names, the payers
+/// of a refund are the members who got the money back. For an opening
+/// balance the name is the group it was carried over from and the member is the payer. A buy-in is paid
+/// by the members credited to the new member. A reset lists the members left as recipients.
+impl Porcelain for GroupListing<'_> {
+    fn porcelain(&self) -> String {
+        self.entries.iter()
+            .map(|ListedEntry { index, entry }| {
+                let (kind, name, category, from, to) = match &entry.command {
+                    LoggedCommand::Split { name, amount, from, to, category, .. } =>
+                        (if amount.is_negative() { "refund" } else { "split" }, field(name), optional(category.as_deref()), members(from), members(to)),
+                    LoggedCommand::Pay { from, to, .. } =>
+                        ("pay", "-".to_string(), "-".to_string(), field(from), field(to)),
+                    LoggedCommand::Opening { member, source, .. } =>
+                        ("opening", field(source), "-".to_string(), field(member), "-".to_string()),
+                    LoggedCommand::WriteOff { member, to, .. } =>
+                        ("write-off", "-".to_string(), "-".to_string(), field(member),
+                         to.iter().map(|t| field(t)).collect::<Vec<_>>().join(",")),
+                    LoggedCommand::BuyIn { member, .. } =>
+                        ("buy-in", "-".to_string(), "-".to_string(), credited(entry, member), field(member)),
+                    LoggedCommand::Correction { members, .. } =>
+                        ("correction", "-".to_string(), "-".to_string(), "-".to_string(),
+                         members.iter().map(|m| field(m)).collect::<Vec<_>>().join(",")),
+                    LoggedCommand::Reset { members, .. } =>
+                        ("reset", "-".to_string(), "-".to_string(), "-".to_string(),
+                         members.iter().map(|m| field(m)).collect::<Vec<_>>().join(",")),
+                };
+                record(&[field(self.group), index.to_string(), time(entry.time),
+                    optional(entry.author.as_deref()), kind.to_string(), entry.command.amount().0.to_string(),
+                    name, category, from, to])
+            })
+            .collect()
+    }
+}
+
+/// `groups`: name, currency, members, entries, net zero, last activity, archived, current.
+/// Flags are `1` or `0`.
+impl Porcelain for [GroupOverview] {
+    fn porcelain(&self) -> String {
+        self.iter()
+            .map(|group| record(&[field(&group.name), format!("{:?}", group.currency), group.members.to_string(),
+                group.entries.to_string(), flag(group.net_zero).to_string(), time(group.last_activity),
+                flag(group.archived).to_string(), flag(group.current).to_string()]))
+            .collect()
+    }
+}
+
+/// `balance`: from, to, amount of each suggested transaction
+impl Porcelain for [Transaction] {
+    fn porcelain(&self) -> String {
+        self.iter()
+            .map(|t| record(&[field(&t.from), field(&t.to), t.amount.0.to_string()]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod porcelain_tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(field("dinner\tout\nback\\slash"), "dinner\\tout\\nback\\\\slash");
+        assert_eq!(optional(None), "-");
+        assert_eq!(record(&["a".to_string(), "b c".to_string()]), "a\tb c\n");
+    }
+}
@@ -0,0 +1,209 @@
+use std::io;
+use std::io::{BufRead, IsTerminal, Write};
+use std::thread::sleep;
+use std::time::Duration;
+use crate::render::terminal_height;
+
+/// how often an unrecognized answer is asked again before giving up with "no"
+const MAX_ATTEMPTS: usize = 3;
+
+/// Interprets a single answer, `None` if it is neither an unambiguous yes nor no
+fn parse_answer(answer: &str) -> Option<bool> {
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" | "j" | "ja" => Some(true),
+        "n" | "no" | "nein" => Some(false),
+        _ => None,
+    }
+}
+
+/// Asks for confirmation on `output` and reads answers from `input` until one is recognized.
+/// Gives up after `MAX_ATTEMPTS` unrecognized answers or at the end of input and returns false.
+pub(crate) fn confirm_with(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+    for _ in 0..MAX_ATTEMPTS {
+        write!(output, "Confirm? [y/n]: ")?;
+        output.flush()?;
+        let mut buffer = String::new();
+        if input.read_line(&mut buffer)? == 0 {
+            writeln!(output)?;
+            return Ok(false);
+        }
+        match parse_answer(&buffer) {
+            Some(answer) => return Ok(answer),
+            None => writeln!(output, "Please answer yes or no (got '{}')", buffer.trim())?,
+        }
+    }
+    writeln!(output, "No clear answer, assuming no")?;
+    Ok(false)
+}
+
+/// Interactive confirmation on stdin/stdout. A yes is followed by a short pause
+/// so an accidental confirmation can still be aborted with Ctrl-C.
+pub(crate) fn confirm() -> bool {
+    let confirmed = confirm_with(&mut io::stdin().lock(), &mut io::stdout())
+        .expect("stdin Input Error");
+    if confirmed {
+        sleep(Duration::from_secs(2));
+    }
+    confirmed
+}
+
+/// whether a user answers the prompts, stdin and stdout both being a terminal
+pub(crate) fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Reads a selection like `1 3`, `1,3` or `2-4` of the numbers 1 to `count`.
+/// Returns the zero-based indices in ascending order.
+fn parse_selection(answer: &str, count: usize) -> Result<Vec<usize>, String> {
+    let number = |part: &str| match part.trim().parse::<usize>() {
+        Ok(number) if (1..=count).contains(&number) => Ok(number - 1),
+        _ => Err(format!("Please pick numbers from 1 to {} (got '{}')", count, part.trim())),
+    };
+    let mut picked = vec![];
+    for part in answer.split([' ', ',']).filter(|part| !part.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => picked.extend(number(first)?..=number(last)?),
+            None => picked.push(number(part)?),
+        }
+    }
+    picked.sort();
+    picked.dedup();
+    Ok(picked)
+}
+
+/// Lists `names` numbered, `page_size` at a time, and asks `question` until a valid selection is given,
+/// see `parse_selection`. An empty answer is only accepted with `allow_empty`. Returns the indices
+/// picked, `None` if the input ends or after `MAX_ATTEMPTS` invalid answers.
+pub(crate) fn pick_with(input: &mut impl BufRead, output: &mut impl Write, question: &str, names: &[&String],
+                        page_size: usize, allow_empty: bool) -> io::Result<Option<Vec<usize>>> {
+    let read = |input: &mut dyn BufRead| -> io::Result<Option<String>> {
+        let mut buffer = String::new();
+        Ok(if input.read_line(&mut buffer)? == 0 { None } else { Some(buffer.trim().to_string()) })
+    };
+    let page_size = page_size.max(1);
+    let mut answer = None;
+    for (page, chunk) in names.chunks(page_size).enumerate() {
+        for (offset, name) in chunk.iter().enumerate() {
+            writeln!(output, "{:>3}) {}", page * page_size + offset + 1, name)?;
+        }
+        if (page + 1) * page_size < names.len() {
+            write!(output, "-- Enter for more, or pick now: ")?;
+            output.flush()?;
+            match read(input)? {
+                None => return Ok(None),
+                Some(early) if !early.is_empty() => {
+                    answer = Some(early);
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let answer = match answer.take() {
+            Some(answer) => answer,
+            None => {
+                write!(output, "{} ", question)?;
+                output.flush()?;
+                match read(input)? {
+                    Some(answer) => answer,
+                    None => return Ok(None),
+                }
+            }
+        };
+        match parse_selection(&answer, names.len()) {
+            Ok(picked) if !picked.is_empty() || allow_empty => return Ok(Some(picked)),
+            Ok(_) => writeln!(output, "Please pick at least one")?,
+            Err(error) => writeln!(output, "{}", error)?,
+        }
+    }
+    writeln!(output, "No clear answer, giving up")?;
+    Ok(None)
+}
+
+/// `pick_with` on stdin/stdout, a page being the height of the terminal
+pub(crate) fn pick(question: &str, names: &[&String], allow_empty: bool) -> Option<Vec<usize>> {
+    pick_with(&mut io::stdin().lock(), &mut io::stdout(), question, names,
+              terminal_height().saturating_sub(2), allow_empty)
+        .expect("stdin Input Error")
+}
+
+#[cfg(test)]
+mod prompt_tests {
+    use super::*;
+
+    fn answer(input: &str) -> (bool, String) {
+        let mut output = vec![];
+        let confirmed = confirm_with(&mut input.as_bytes(), &mut output).unwrap();
+        (confirmed, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_confirm_answers() {
+        for yes in ["y\n", "Yes\n", "J\n", "ja\n", "  YES  \n"] {
+            assert!(answer(yes).0, "{yes:?}");
+        }
+        for no in ["n\n", "No\n", "NEIN\n", ""] {
+            assert!(!answer(no).0, "{no:?}");
+        }
+    }
+
+    #[test]
+    fn test_confirm_reprompts() {
+        let (confirmed, output) = answer("yed\ny\n");
+        assert!(confirmed);
+        assert_eq!(output.matches("Confirm?").count(), 2);
+        assert!(output.contains("got 'yed'"));
+
+        let (confirmed, output) = answer("yed\nmaybe\nsure\ny\n");
+        assert!(!confirmed);
+        assert_eq!(output.matches("Confirm?").count(), 3);
+        assert!(output.contains("assuming no"));
+    }
+
+    fn pick(input: &str, names: &[&str], page_size: usize, allow_empty: bool) -> (Option<Vec<usize>>, String) {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let names: Vec<&String> = names.iter().collect();
+        let mut output = vec![];
+        let picked = pick_with(&mut input.as_bytes(), &mut output, "Who paid?", &names, page_size, allow_empty).unwrap();
+        (picked, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("3 1,1", 4), Ok(vec![0, 2]));
+        assert_eq!(parse_selection(" 2-4 ", 4), Ok(vec![1, 2, 3]));
+        assert_eq!(parse_selection("", 4), Ok(vec![]));
+        assert!(parse_selection("5", 4).is_err());
+        assert!(parse_selection("0", 4).is_err());
+        assert!(parse_selection("alice", 4).is_err());
+    }
+
+    #[test]
+    fn test_pick() {
+        let (picked, output) = pick("2\n", &["alice", "bob"], 10, false);
+        assert_eq!(picked, Some(vec![1]));
+        assert_eq!(output, "  1) alice\n  2) bob\nWho paid? ");
+
+        let (picked, output) = pick("\n7\n1 3\n", &["alice", "bob", "carol"], 10, false);
+        assert_eq!(picked, Some(vec![0, 2]));
+        assert!(output.contains("Please pick at least one"));
+        assert!(output.contains("Please pick numbers from 1 to 3 (got '7')"));
+        assert_eq!(pick("\n", &["alice"], 10, true).0, Some(vec![]));
+        assert_eq!(pick("", &["alice"], 10, true).0, None);
+    }
+
+    #[test]
+    fn test_pick_pages() {
+        let names = ["a", "b", "c", "d", "e"];
+        // Enter shows the next page, the question comes after the last one
+        let (picked, output) = pick("\n\n5\n", &names, 2, false);
+        assert_eq!(picked, Some(vec![4]));
+        assert_eq!(output.matches("-- Enter for more").count(), 2);
+        assert!(output.contains("  5) e\nWho paid? "));
+        // an answer on an earlier page ends the listing
+        let (picked, output) = pick("1-2\n", &names, 2, false);
+        assert_eq!(picked, Some(vec![0, 1]));
+        assert!(!output.contains("3) c") && !output.contains("Who paid?"));
+    }
+}
@@ -0,0 +1,447 @@
+//! Databases kept where splitter cannot open them as a file, given as `--database https://...` or
+//! `--database 'cmd:<command>'`. They are fetched into a local copy, which is loaded and saved like
+//! any database and uploaded again after a run that changed it.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use chrono::Local;
+use sha2::{Digest, Sha256};
+use crate::error::*;
+
+/// prefix of a database read by running a command
+const COMMAND_PREFIX: &str = "cmd:";
+
+/// environment variable holding the `Authorization` header sent to a database given as URL
+#[cfg(feature = "http")]
+const AUTHORIZATION_VAR: &str = "SPLITTER_HTTP_AUTHORIZATION";
+
+/// whether `database` names a remote database rather than a file
+pub(crate) fn is_remote(database: &str) -> bool {
+    database.starts_with(COMMAND_PREFIX) || database.starts_with("http://") || database.starts_with("https://")
+}
+
+/// the version tag of content whose storage has none of its own
+fn content_tag(bytes: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+/// outcome of `Remote::push`
+#[derive(Debug, PartialEq)]
+pub(crate) enum Pushed {
+    Done,
+    /// someone else changed the database since it was fetched, nothing was written
+    Conflict,
+}
+
+/// A database kept somewhere else
+pub(crate) trait Remote {
+    /// the database with a tag identifying its version, `None` if it does not exist yet
+    fn fetch(&self) -> Result<Option<(Vec<u8>, String)>>;
+    /// Replaces the database by `bytes` unless it changed since it was fetched as version `tag`,
+    /// `None` meaning it did not exist then
+    fn push(&self, bytes: &[u8], tag: Option<&str>) -> Result<Pushed>;
+}
+
+/// The remote database `database` as given on the command line or in the config file. A `cmd:`
+/// database is written back by piping it into `push_command`.
+pub(crate) fn open(database: &str, push_command: Option<String>) -> Result<Box<dyn Remote>> {
+    if let Some(fetch) = database.strip_prefix(COMMAND_PREFIX) {
+        return Ok(Box::new(CommandRemote { fetch: fetch.trim().to_string(), push: push_command }));
+    }
+    #[cfg(feature = "http")]
+    {
+        Ok(Box::new(HttpRemote {
+            url: database.to_string(),
+            authorization: std::env::var(AUTHORIZATION_VAR).ok(),
+        }))
+    }
+    #[cfg(not(feature = "http"))]
+    Err(anyhow!("This splitter was built without the `http` feature and cannot open {}, \
+        use `cmd:curl -sf {}` with a --push-command instead", database, database))
+}
+
+/// A database read from the output of a shell command and written by piping it into another one
+struct CommandRemote {
+    fetch: String,
+    push: Option<String>,
+}
+
+/// runs `command` in the shell with `input` on stdin, returning its output
+fn shell(command: &str, input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut child = Command::new("sh").arg("-c").arg(command)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("Could not run `{}`", command))?;
+    if let Some(input) = input {
+        child.stdin.take().expect("stdin is piped").write_all(input)
+            .context(format!("Could not write to `{}`", command))?;
+    }
+    let output = child.wait_with_output().context(format!("Could not run `{}`", command))?;
+    if !output.status.success() {
+        return Err(anyhow!("`{}` failed ({}): {}", command, output.status,
+                           String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(output.stdout)
+}
+
+impl Remote for CommandRemote {
+    /// empty output means there is no database yet
+    fn fetch(&self) -> Result<Option<(Vec<u8>, String)>> {
+        let bytes = shell(&self.fetch, None)?;
+        Ok((!bytes.is_empty()).then(|| {
+            let tag = content_tag(&bytes);
+            (bytes, tag)
+        }))
+    }
+
+    fn push(&self, bytes: &[u8], tag: Option<&str>) -> Result<Pushed> {
+        let Some(push) = &self.push else {
+            return Err(anyhow!("The database is read with `{}`, give the command writing it back with \
+                --push-command or push_command in the config file", self.fetch));
+        };
+        if self.fetch()?.as_ref().map(|(_, current)| current.as_str()) != tag {
+            return Ok(Pushed::Conflict);
+        }
+        shell(push, Some(bytes))?;
+        Ok(Pushed::Done)
+    }
+}
+
+/// A database at a URL, read with GET and written with PUT, e.g. on a WebDAV share. Lost updates
+/// are prevented by the ETag of the server, or by comparing the content if it sends none.
+#[cfg(feature = "http")]
+struct HttpRemote {
+    url: String,
+    authorization: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl HttpRemote {
+    fn request(&self, method: &str) -> ureq::Request {
+        let request = ureq::request(method, &self.url);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Remote for HttpRemote {
+    fn fetch(&self) -> Result<Option<(Vec<u8>, String)>> {
+        let response = match self.request("GET").call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(e).context(format!("Could not download {}", self.url)),
+        };
+        let etag = response.header("ETag").map(String::from);
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(&mut response.into_reader(), &mut bytes)
+            .context(format!("Could not download {}", self.url))?;
+        let tag = etag.unwrap_or_else(|| content_tag(&bytes));
+        Ok(Some((bytes, tag)))
+    }
+
+    fn push(&self, bytes: &[u8], tag: Option<&str>) -> Result<Pushed> {
+        let request = match tag {
+            None => self.request("PUT").set("If-None-Match", "*"),
+            Some(tag) if tag.starts_with("sha256:") => {
+                if self.fetch()?.as_ref().map(|(_, current)| current.as_str()) != Some(tag) {
+                    return Ok(Pushed::Conflict);
+                }
+                self.request("PUT")
+            }
+            Some(etag) => self.request("PUT").set("If-Match", etag),
+        };
+        match request.send_bytes(bytes) {
+            Ok(_) => Ok(Pushed::Done),
+            Err(ureq::Error::Status(412, _)) => Ok(Pushed::Conflict),
+            Err(e) => Err(e).context(format!("Could not upload to {}", self.url)),
+        }
+    }
+}
+
+/// Where the local copy of the remote database `name` is kept in `dir`, see `Mirror::new`
+pub(crate) fn local_copy(name: &str, dir: &Path) -> PathBuf {
+    let file_name = name.split_whitespace()
+        .filter_map(|word| word.trim_matches(['\'', '"']).split(['?', '#']).next()?.rsplit('/').next())
+        .rfind(|file| file.contains('.') && file.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c)))
+        .unwrap_or("remote.db");
+    let hash = format!("{:x}", Sha256::digest(name.as_bytes()));
+    dir.join(format!("{}-{}", &hash[..12], file_name))
+}
+
+/// A remote database together with its local copy
+pub(crate) struct Mirror {
+    remote: Box<dyn Remote>,
+    /// the database as given, for messages
+    name: String,
+    local: PathBuf,
+    /// the version of the remote database the local copy was fetched as
+    tag: Option<String>,
+}
+
+impl Mirror {
+    /// The mirror of `remote`, given as `name`, with the local copy in `dir`. The copy is named
+    /// after a hash of `name` and the last file name with an extension in it, so its format is
+    /// recognized like for a file.
+    pub(crate) fn new(name: &str, remote: Box<dyn Remote>, dir: &Path) -> Self {
+        Self {
+            remote,
+            name: name.to_string(),
+            local: local_copy(name, dir),
+            tag: None,
+        }
+    }
+
+    /// the local copy, loaded and saved instead of the remote database
+    pub(crate) fn local(&self) -> &Path {
+        &self.local
+    }
+
+    /// where the hash of the local copy as last fetched or uploaded is kept
+    fn synced(&self) -> PathBuf {
+        let mut path = self.local.as_os_str().to_owned();
+        path.push(".synced");
+        PathBuf::from(path)
+    }
+
+    /// whether the local copy holds changes the remote database does not have
+    fn changed(&self) -> Option<Vec<u8>> {
+        let bytes = std::fs::read(&self.local).ok()?;
+        let synced = std::fs::read_to_string(self.synced()).unwrap_or_default();
+        (synced != content_tag(&bytes)).then_some(bytes)
+    }
+
+    /// Moves the local copy out of the way of the next fetch, returning where it went. The new
+    /// name keeps the extension, so it can be opened and merged like the database.
+    fn set_aside(&self) -> Result<PathBuf> {
+        let stem = self.local.file_stem().unwrap_or_default().to_string_lossy();
+        let time = Local::now().format("%Y%m%d-%H%M%S");
+        let extension = self.local.extension().map(|extension| format!(".{}", extension.to_string_lossy()));
+        let aside = (1..).map(|n| match n {
+            1 => format!("{}.unsaved-{}{}", stem, time, extension.as_deref().unwrap_or_default()),
+            n => format!("{}.unsaved-{}-{}{}", stem, time, n, extension.as_deref().unwrap_or_default()),
+        })
+            .map(|name| self.local.with_file_name(name))
+            .find(|aside| !aside.exists())
+            .expect("some name is free");
+        std::fs::rename(&self.local, &aside).context(format!("Could not move {:?} to {:?}", self.local, aside))?;
+        Ok(aside)
+    }
+
+    /// Fetches the remote database into the local copy. A local copy holding changes that were
+    /// never uploaded, e.g. after a crash, is set aside first.
+    pub(crate) fn fetch(&mut self) -> Result<()> {
+        if let Some(dir) = self.local.parent() {
+            std::fs::create_dir_all(dir).context(format!("Could not create {:?} for the local copy", dir))?;
+        }
+        if self.changed().is_some() {
+            let aside = self.set_aside()?;
+            eprintln!("Warning: {:?} held changes that were never uploaded to {}, they were moved to {:?}. \
+                `splitter -d '{}' merge {:?}` brings them in", self.local, self.name, aside, self.name, aside);
+        }
+        let fetched = self.remote.fetch().with_context(|| match self.local.exists() {
+            true => format!("Could not fetch {}, the copy of the last run is {:?}", self.name, self.local),
+            false => format!("Could not fetch {}", self.name),
+        })?;
+        match fetched {
+            Some((bytes, tag)) => {
+                std::fs::write(&self.local, &bytes).context(format!("Could not write {:?}", self.local))?;
+                std::fs::write(self.synced(), content_tag(&bytes))?;
+                self.tag = Some(tag);
+            }
+            None => {
+                for path in [self.local.clone(), self.synced()] {
+                    if path.exists() {
+                        std::fs::remove_file(&path).context(format!("Could not remove {:?}", path))?;
+                    }
+                }
+                self.tag = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads the local copy if it changed since the fetch, returning whether it did. If that
+    /// fails, the copy is set aside with its changes and the error says how to bring them in.
+    pub(crate) fn push(&mut self) -> Result<bool> {
+        let Some(bytes) = self.changed() else { return Ok(false) };
+        let problem = match self.remote.push(&bytes, self.tag.as_deref()) {
+            Ok(Pushed::Done) => {
+                std::fs::write(self.synced(), content_tag(&bytes))?;
+                return Ok(true);
+            }
+            Ok(Pushed::Conflict) => anyhow!("{} was changed by someone else since it was fetched", self.name),
+            Err(e) => e.context(format!("Could not upload to {}", self.name)),
+        };
+        let aside = self.set_aside()?;
+        Err(problem.context(format!("Your changes are kept in {:?}, `splitter -d '{}' merge {:?}` brings them in",
+                                    aside, self.name, aside)))
+    }
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+
+    /// content and version of the database, `None` while it does not exist
+    type Stored = Option<(Vec<u8>, u32)>;
+
+    /// a database in memory, versioned by a counter like an ETag
+    #[derive(Clone, Default)]
+    struct Mock {
+        stored: Rc<RefCell<Stored>>,
+        broken: Rc<RefCell<bool>>,
+    }
+
+    impl Mock {
+        fn set(&self, bytes: &[u8]) {
+            let version = self.stored.borrow().as_ref().map_or(1, |(_, version)| version + 1);
+            *self.stored.borrow_mut() = Some((bytes.to_vec(), version));
+        }
+        fn get(&self) -> Option<Vec<u8>> {
+            self.stored.borrow().as_ref().map(|(bytes, _)| bytes.clone())
+        }
+    }
+
+    impl Remote for Mock {
+        fn fetch(&self) -> Result<Option<(Vec<u8>, String)>> {
+            if *self.broken.borrow() {
+                return Err(anyhow!("connection refused"));
+            }
+            Ok(self.stored.borrow().clone().map(|(bytes, version)| (bytes, version.to_string())))
+        }
+        fn push(&self, bytes: &[u8], tag: Option<&str>) -> Result<Pushed> {
+            if *self.broken.borrow() {
+                return Err(anyhow!("connection refused"));
+            }
+            if self.stored.borrow().as_ref().map(|(_, version)| version.to_string()).as_deref() != tag {
+                return Ok(Pushed::Conflict);
+            }
+            self.set(bytes);
+            Ok(Pushed::Done)
+        }
+    }
+
+    fn mirror(mock: &Mock, dir: &Path) -> Mirror {
+        Mirror::new("https://dav.example.org/club/trip.db", Box::new(mock.clone()), dir)
+    }
+
+    #[test]
+    fn test_local_name() {
+        let dir = Path::new("/cache");
+        let local = |name: &str| Mirror::new(name, Box::new(Mock::default()), dir).local().file_name().unwrap()
+            .to_string_lossy().split_once('-').unwrap().1.to_string();
+        assert_eq!(local("https://dav.example.org/club/trip.db?version=2"), "trip.db");
+        assert_eq!(local("cmd:ssh host cat 'books/flat.yaml'"), "flat.yaml");
+        assert_eq!(local("cmd:cat /srv/trip.yml 2>/dev/null || true"), "trip.yml");
+        assert_eq!(local("cmd:ssh host cat"), "remote.db");
+        assert_eq!(local("cmd:curl -sf https://example.org/a%20b.db"), "remote.db");
+        assert!(is_remote("cmd:cat x") && is_remote("https://x") && !is_remote("./https.db"));
+    }
+
+    #[test]
+    fn test_fetch_and_push() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = Mock::default();
+        let mut mirror = mirror(&mock, dir.path());
+
+        // a new database is created by the first push
+        mirror.fetch().unwrap();
+        assert!(!mirror.local().exists());
+        assert!(!mirror.push().unwrap(), "nothing was saved");
+        std::fs::write(mirror.local(), b"first").unwrap();
+        assert!(mirror.push().unwrap());
+        assert_eq!(mock.get().unwrap(), b"first");
+
+        let mut mirror = self::mirror(&mock, dir.path());
+        mirror.fetch().unwrap();
+        assert_eq!(std::fs::read(mirror.local()).unwrap(), b"first");
+        assert!(!mirror.push().unwrap(), "unchanged");
+        std::fs::write(mirror.local(), b"second").unwrap();
+        assert!(mirror.push().unwrap());
+        assert_eq!(mock.get().unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = Mock::default();
+        mock.set(b"base");
+        let mut mirror = mirror(&mock, dir.path());
+        mirror.fetch().unwrap();
+        std::fs::write(mirror.local(), b"ours").unwrap();
+        mock.set(b"theirs");
+
+        let error = format!("{:#}", mirror.push().unwrap_err());
+        assert!(error.starts_with("Your changes are kept in"), "{}", error);
+        assert!(error.contains("was changed by someone else since it was fetched"), "{}", error);
+        assert_eq!(mock.get().unwrap(), b"theirs");
+        let aside: Vec<PathBuf> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains(".unsaved-"))
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert_eq!(std::fs::read(&aside[0]).unwrap(), b"ours");
+        assert_eq!(aside[0].extension().unwrap(), "db");
+        assert!(!mirror.local().exists());
+    }
+
+    #[test]
+    fn test_failures_keep_a_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = Mock::default();
+        mock.set(b"base");
+        let mut mirror = mirror(&mock, dir.path());
+        mirror.fetch().unwrap();
+
+        // fetching fails: the copy of the last run is named
+        *mock.broken.borrow_mut() = true;
+        let error = format!("{:#}", self::mirror(&mock, dir.path()).fetch().unwrap_err());
+        assert!(error.contains("the copy of the last run is"), "{}", error);
+        assert!(error.ends_with("connection refused"), "{}", error);
+        assert_eq!(std::fs::read(mirror.local()).unwrap(), b"base");
+
+        // uploading fails: the changes are set aside
+        std::fs::write(mirror.local(), b"ours").unwrap();
+        let error = format!("{:#}", mirror.push().unwrap_err());
+        assert!(error.contains("Could not upload to https://dav.example.org/club/trip.db"), "{}", error);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2, "the copy set aside and the hash");
+
+        // changes left behind, e.g. by a crash before uploading, are set aside by the next fetch
+        *mock.broken.borrow_mut() = false;
+        let mut mirror = self::mirror(&mock, dir.path());
+        mirror.fetch().unwrap();
+        std::fs::write(mirror.local(), b"crashed").unwrap();
+        mirror.fetch().unwrap();
+        assert_eq!(std::fs::read(mirror.local()).unwrap(), b"base");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_command_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("trip.db");
+        let remote = |push: Option<&str>| open(&format!("cmd:cat {:?} 2>/dev/null || true", file),
+                                               push.map(|push| format!("{} {:?}", push, file))).unwrap();
+
+        assert!(remote(None).fetch().unwrap().is_none());
+        assert!(remote(None).push(b"x", None).is_err(), "no push command");
+        assert_eq!(remote(Some("cat >")).push(b"first", None).unwrap(), Pushed::Done);
+        let (bytes, tag) = remote(None).fetch().unwrap().unwrap();
+        assert_eq!(bytes, b"first");
+        assert_eq!(remote(Some("cat >")).push(b"second", None).unwrap(), Pushed::Conflict);
+        assert_eq!(remote(Some("cat >")).push(b"second", Some(&tag)).unwrap(), Pushed::Done);
+        assert_eq!(remote(Some("cat >")).push(b"third", Some(&tag)).unwrap(), Pushed::Conflict);
+        assert_eq!(std::fs::read(&file).unwrap(), b"second");
+
+        let error = format!("{:#}", open("cmd:echo oops >&2; exit 3", None).unwrap().fetch().unwrap_err());
+        assert!(error.contains("failed") && error.ends_with("oops"), "{}", error);
+    }
+}
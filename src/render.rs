@@ -0,0 +1,368 @@
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+#[cfg(any(feature = "cli", test))]
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, TimeDelta};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// How a command prints its result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OutputMode {
+    Human,
+    Json,
+    /// see `porcelain.rs`
+    Porcelain,
+}
+
+impl OutputMode {
+    pub(crate) fn from_flags(json: bool, porcelain: bool) -> Self {
+        match (json, porcelain) {
+            (true, _) => Self::Json,
+            (_, true) => Self::Porcelain,
+            _ => Self::Human,
+        }
+    }
+}
+
+/// columns `text` takes up in a terminal, wide CJK characters counting twice and combining marks not at all
+pub(crate) fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// `text` shortened to at most `max` columns, ending in `…` if anything was cut.
+/// Cuts only between graphemes, so accents stay with their letters.
+pub(crate) fn truncate(text: &str, max: usize) -> String {
+    if display_width(text) <= max {
+        return text.to_string();
+    }
+    let mut width = 0;
+    let mut truncated: String = text.graphemes(true)
+        .take_while(|grapheme| {
+            width += display_width(grapheme);
+            width < max
+        })
+        .collect();
+    if max > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// `text` padded with spaces to `width` columns
+fn pad(text: &str, width: usize, right: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(display_width(text)));
+    if right { padding + text } else { format!("{}{}", text, padding) }
+}
+
+/// Plain text table with a header row, columns padded to their widest cell
+pub(crate) struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    right_aligned: Vec<bool>,
+    name_columns: Vec<bool>,
+    max_name_width: Option<usize>,
+}
+
+impl Table {
+    pub(crate) fn new(header: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+            right_aligned: vec![false; header.len()],
+            name_columns: vec![false; header.len()],
+            max_name_width: None,
+        }
+    }
+
+    /// mark a column as holding names, which `max_name_width` shortens
+    pub(crate) fn name_column(mut self, column: usize) -> Self {
+        self.name_columns[column] = true;
+        self
+    }
+
+    /// truncate the names in name columns to this many columns
+    pub(crate) fn max_name_width(mut self, width: Option<usize>) -> Self {
+        self.max_name_width = width;
+        self
+    }
+
+    /// right-align a column, used for amounts
+    pub(crate) fn align_right(mut self, column: usize) -> Self {
+        self.right_aligned[column] = true;
+        self
+    }
+
+    pub(crate) fn row(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.header.len());
+        self.rows.push(row);
+    }
+
+    /// the rows with the names truncated
+    fn cells(&self) -> Vec<Vec<String>> {
+        self.rows.iter()
+            .map(|row| row.iter().zip(&self.name_columns)
+                .map(|(cell, name)| match (name, self.max_name_width) {
+                    (true, Some(max)) => truncate(cell, max),
+                    _ => cell.clone(),
+                })
+                .collect())
+            .collect()
+    }
+
+    fn widths(&self, rows: &[Vec<String>]) -> Vec<usize> {
+        rows.iter().chain(std::iter::once(&self.header))
+            .fold(vec![0; self.header.len()], |mut widths, row| {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(display_width(cell));
+                }
+                widths
+            })
+    }
+
+    fn write_row(&self, f: &mut Formatter<'_>, row: &[String], widths: &[usize]) -> std::fmt::Result {
+        let cells: Vec<String> = row.iter().zip(widths).zip(&self.right_aligned)
+            .map(|((cell, width), right)| pad(cell, *width, *right))
+            .collect();
+        writeln!(f, "{}", cells.join("  ").trim_end())
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rows = self.cells();
+        let widths = self.widths(&rows);
+        self.write_row(f, &self.header, &widths)?;
+        let total = widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1);
+        writeln!(f, "{}", "-".repeat(total))?;
+        for row in &rows {
+            self.write_row(f, row, &widths)?;
+        }
+        Ok(())
+    }
+}
+
+/// narrowest the bars of a `BarChart` get, however long the labels
+const MIN_BAR_WIDTH: usize = 10;
+
+/// Horizontal bar chart of non-negative values, bars scaled to the largest one
+pub(crate) struct BarChart {
+    /// label, value and the value as shown
+    rows: Vec<(String, i64, String)>,
+}
+
+impl BarChart {
+    pub(crate) fn new() -> Self {
+        Self { rows: vec![] }
+    }
+
+    pub(crate) fn row(&mut self, label: String, value: i64, shown: String) {
+        self.rows.push((label, value, shown));
+    }
+
+    /// the chart fitted into `width` columns, as far as the labels allow
+    pub(crate) fn render(&self, width: usize) -> String {
+        let label_width = self.rows.iter().map(|(label, _, _)| display_width(label)).max().unwrap_or(0);
+        let shown_width = self.rows.iter().map(|(_, _, shown)| display_width(shown)).max().unwrap_or(0);
+        let bar_width = width.saturating_sub(label_width + shown_width + 4).max(MIN_BAR_WIDTH);
+        let max = self.rows.iter().map(|(_, value, _)| *value).max().unwrap_or(0).max(1);
+        self.rows.iter()
+            .map(|(label, value, shown)| {
+                let bar = (value.max(&0).unsigned_abs() as u128 * bar_width as u128 / max as u128) as usize;
+                format!("{}  {:<bar_width$}  {}\n", pad(label, label_width, false), "#".repeat(bar),
+                        pad(shown, shown_width, true))
+            })
+            .collect()
+    }
+}
+
+/// columns of the terminal from `$COLUMNS`, 80 if unknown
+pub(crate) fn terminal_width() -> usize {
+    std::env::var("COLUMNS").ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(80)
+}
+
+/// lines of the terminal from `$LINES` or asked from the terminal, 24 if unknown
+pub(crate) fn terminal_height() -> usize {
+    std::env::var("LINES").ok()
+        .and_then(|lines| lines.parse().ok())
+        .or_else(asked_height)
+        .filter(|lines| *lines > 0)
+        .unwrap_or(24)
+}
+
+#[cfg(feature = "cli")]
+fn asked_height() -> Option<usize> {
+    terminal_size::terminal_size().map(|(_, height)| height.0 as usize)
+}
+
+/// the library has no terminal of its own to ask
+#[cfg(not(feature = "cli"))]
+fn asked_height() -> Option<usize> {
+    None
+}
+
+/// dates further back than this are shown as dates even with `DateFormat::Relative`
+const RELATIVE_DAYS: i64 = 30;
+
+/// How human readable output shows dates, from `--date-format` or the config file.
+/// Machine readable output always uses ISO 8601.
+// only `--date-format` and the config file give one
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DateFormat {
+    /// a strftime pattern like `%d.%m.%Y`
+    Pattern(String),
+    /// like `3 days ago`, within the last 30 days
+    Relative,
+}
+
+static DATE_FORMAT: OnceLock<DateFormat> = OnceLock::new();
+
+impl DateFormat {
+    /// `relative` or a strftime pattern, used as clap value parser
+    #[cfg(any(feature = "cli", test))]
+    pub(crate) fn parse(input: &str) -> Result<Self, String> {
+        if input == "relative" {
+            Ok(Self::Relative)
+        } else if input.is_empty() || StrftimeItems::new(input).any(|item| item == Item::Error) {
+            Err(format!("date format must be 'relative' or a strftime pattern like %d.%m.%Y (got '{input}')"))
+        } else {
+            Ok(Self::Pattern(input.to_string()))
+        }
+    }
+
+    /// makes this the format of every date shown, can only be done once
+    #[cfg(feature = "cli")]
+    pub(crate) fn install(self) {
+        let _ = DATE_FORMAT.set(self);
+    }
+
+    /// `time` seen at `now`. `default` is the pattern of the place the date is shown in, used
+    /// by relative dates beyond 30 days or in the future.
+    fn render(&self, time: DateTime<Local>, now: DateTime<Local>, default: &str) -> String {
+        let pattern = match self {
+            Self::Pattern(pattern) => pattern.as_str(),
+            Self::Relative => {
+                let ago = now - time;
+                let count = |count: i64, unit: &str| format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" });
+                if ago < TimeDelta::zero() || ago > TimeDelta::days(RELATIVE_DAYS) {
+                    default
+                } else if ago < TimeDelta::minutes(1) {
+                    return "just now".to_string();
+                } else if ago < TimeDelta::hours(1) {
+                    return count(ago.num_minutes(), "minute");
+                } else if ago < TimeDelta::days(1) {
+                    return count(ago.num_hours(), "hour");
+                } else {
+                    return count(ago.num_days(), "day");
+                }
+            }
+        };
+        time.format(pattern).to_string()
+    }
+}
+
+/// `time` as human readable output shows it, `default` being the strftime pattern used
+/// where no date format is set
+pub(crate) fn date(time: DateTime<Local>, default: &str) -> String {
+    match DATE_FORMAT.get() {
+        Some(format) => format.render(time, Local::now(), default),
+        None => time.format(default).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::*;
+
+    #[test]
+    fn test_date_format() {
+        use chrono::TimeZone;
+        let now = Local.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap();
+        let time = Local.with_ymd_and_hms(2024, 6, 12, 9, 5, 0).unwrap();
+        let pattern = |pattern: &str| DateFormat::parse(pattern).unwrap();
+        assert_eq!(pattern("%Y-%m-%dT%H:%M").render(time, now, "%Y-%m-%d"), "2024-06-12T09:05");
+        assert_eq!(pattern("%d.%m.%Y").render(time, now, "%Y-%m-%d"), "12.06.2024");
+        assert!(DateFormat::parse("%Q").is_err());
+        assert!(DateFormat::parse("").is_err());
+
+        let relative = |ago: TimeDelta| DateFormat::Relative.render(now - ago, now, "%Y-%m-%d");
+        assert_eq!(relative(TimeDelta::seconds(59)), "just now");
+        assert_eq!(relative(TimeDelta::minutes(1)), "1 minute ago");
+        assert_eq!(relative(TimeDelta::minutes(59)), "59 minutes ago");
+        assert_eq!(relative(TimeDelta::hours(23)), "23 hours ago");
+        assert_eq!(relative(TimeDelta::days(1)), "1 day ago");
+        assert_eq!(relative(TimeDelta::days(30)), "30 days ago");
+        // older dates and those in the future are shown as dates
+        assert_eq!(relative(TimeDelta::days(30) + TimeDelta::seconds(1)), "2024-05-31");
+        assert_eq!(relative(-TimeDelta::hours(1)), "2024-06-30");
+    }
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new(&["Name", "Amount"]).align_right(1);
+        table.row(vec!["dinner".to_string(), "45.00€".to_string()]);
+        table.row(vec!["ice".to_string(), "4.50€".to_string()]);
+        assert_eq!(table.to_string(),
+                   "Name    Amount\n\
+                    --------------\n\
+                    dinner  45.00€\n\
+                    ice      4.50€\n");
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let mut chart = BarChart::new();
+        chart.row("alice".to_string(), 30_00, "30.00€".to_string());
+        chart.row("bob".to_string(), 12_00, "12.00€".to_string());
+        chart.row("carol".to_string(), 0, "0.00€".to_string());
+        assert_eq!(chart.render(35), "alice  ####################  30.00€\n\
+                                      bob    ########              12.00€\n\
+                                      carol                         0.00€\n");
+        // long labels still leave room for the bars
+        assert!(chart.render(0).starts_with("alice  ##########  30.00€\n"));
+        assert_eq!(BarChart::new().render(80), "");
+    }
+
+    #[test]
+    fn test_table_unicode() {
+        // the CJK characters take two columns each, the combining diaeresis none
+        let mut table = Table::new(&["Name", "Amount"]).align_right(1);
+        table.row(vec!["山田".to_string(), "45.00€".to_string()]);
+        table.row(vec!["Zoe\u{308}".to_string(), "4.50€".to_string()]);
+        table.row(vec!["🍕 pizza".to_string(), "12.00€".to_string()]);
+        assert_eq!(table.to_string(),
+                   "Name      Amount\n\
+                    ----------------\n\
+                    山田      45.00€\n\
+                    Zoe\u{308}        4.50€\n\
+                    🍕 pizza  12.00€\n");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("alice", 5), "alice");
+        assert_eq!(truncate("alexander", 5), "alex…");
+        assert_eq!(truncate("山田太郎", 5), "山田…");
+        assert_eq!(truncate("山田太郎", 4), "山…");
+        // the accent is cut off together with its letter
+        assert_eq!(truncate("Zoe\u{308}lla", 4), "Zoe\u{308}…");
+        assert_eq!(truncate("Zoe\u{308}lla", 3), "Zo…");
+        assert_eq!(truncate("🍕🍕🍕", 4), "🍕…");
+    }
+
+    #[test]
+    fn test_table_max_name_width() {
+        let mut table = Table::new(&["Name", "Balance"]).align_right(1).name_column(0).max_name_width(Some(6));
+        table.row(vec!["bartholomew".to_string(), "-3.00€".to_string()]);
+        table.row(vec!["山田太郎".to_string(), "3.00€".to_string()]);
+        assert_eq!(table.to_string(),
+                   "Name    Balance\n\
+                    ---------------\n\
+                    barth…   -3.00€\n\
+                    山田…     3.00€\n");
+    }
+}
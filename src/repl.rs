@@ -0,0 +1,201 @@
+use std::io::{BufRead, Write};
+use clap::{CommandFactory, Parser};
+use crate::config::{Cli, SubCommand};
+use crate::error::*;
+use crate::logic::Splitter;
+use crate::money::check_ceiling;
+use crate::prompt;
+
+/// Splits an input line into arguments like a shell does: at whitespace, except within single or
+/// double quotes, and a backslash escapes the next character
+fn words(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err("the line ends with a lone backslash".to_string()),
+            },
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("the quote {} is not closed", q));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// commands of the interactive mode besides the subcommands, with what they do
+const SESSION_COMMANDS: [(&str, &str); 4] = [
+    ("help", "list the commands, `help <command>` shows the options of one"),
+    ("save", "write the changes so far to the database, asking first if another program changed it"),
+    ("exit", "save and end the session, as does Ctrl-D"),
+    ("quit", "the same as exit"),
+];
+
+/// what `help` prints: the commands of the session, then every subcommand with its description
+fn help() -> String {
+    let cli = Cli::command();
+    let subcommands: Vec<(String, String)> = cli.get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| (command.get_name().to_string(), command.get_about().map(|about| about.to_string()).unwrap_or_default()))
+        .collect();
+    let width = subcommands.iter().map(|(name, _)| name.len())
+        .chain(SESSION_COMMANDS.iter().map(|(name, _)| name.len()))
+        .max()
+        .unwrap_or(0);
+    let mut help = String::from("Session commands:\n");
+    for (name, about) in SESSION_COMMANDS {
+        help.push_str(&format!("  {:<width$}  {}\n", name, about));
+    }
+    help.push_str("\nCommands, written like on the command line without the leading `splitter`:\n");
+    for (name, about) in subcommands {
+        help.push_str(format!("  {:<width$}  {}", name, about).trim_end());
+        help.push('\n');
+    }
+    help
+}
+
+/// Saves the session. If another program changed the database since it was loaded, asks on `input`
+/// whether to overwrite it like `--force-save`, otherwise the changes stay in the `.conflict` file.
+fn save(logic: &mut Splitter, input: &mut impl BufRead) -> Result<()> {
+    let Err(e) = logic.save() else { return Ok(()) };
+    if !logic.changed_on_disk()? {
+        return Err(e);
+    }
+    println!("Another program changed the database since it was loaded. Overwrite it with this session?");
+    if !prompt::confirm_with(input, &mut std::io::stdout())? {
+        return Err(e);
+    }
+    logic.overwrite()
+}
+
+/// Parses and runs one line of the interactive mode. Changes stay in memory until `save` or the end
+/// of the session. Returns false when the line ends the session.
+fn execute(logic: &mut Splitter, line: &str, input: &mut impl BufRead) -> Result<bool> {
+    let mut args = words(line).map_err(|problem| anyhow!(problem))?;
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => return Ok(true),
+        ["exit" | "quit"] => return Ok(false),
+        ["save"] => {
+            save(logic, input)?;
+            println!("Saved");
+            return Ok(true);
+        }
+        ["help"] => {
+            print!("{}", help());
+            return Ok(true);
+        }
+        ["help", _] => args = vec![args[1].clone(), "--help".to_string()],
+        _ => {}
+    }
+    let command = match Cli::try_parse_from(std::iter::once("splitter".to_string()).chain(args)) {
+        Ok(Cli { command: Some(command), .. }) => command,
+        Ok(_) => return Ok(true),
+        Err(e) => {
+            // help and version end up here as well
+            let _ = e.print();
+            return Ok(true);
+        }
+    };
+    for amount in SubCommand::amounts(&command) {
+        check_ceiling(amount).map_err(|problem| anyhow!(problem))?;
+    }
+    logic.try_run(command)?;
+    logic.take_failure()?;
+    Ok(true)
+}
+
+/// The interactive mode: reads commands from `input` after a prompt naming the default group and
+/// runs them on the state kept in memory, until `exit`, `quit` or the end of input, which save it.
+/// A failing command changes nothing, it is reported and the session goes on.
+pub(crate) fn run(logic: &mut Splitter, input: &mut impl BufRead) -> Result<()> {
+    println!("splitter {}, `help` lists the commands", env!("CARGO_PKG_VERSION"));
+    loop {
+        print!("{}", logic.prompt());
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            println!();
+            break;
+        }
+        match execute(logic, &line, input) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+    }
+    save(logic, input)
+}
+
+#[cfg(test)]
+mod repl_tests {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("  split 10 -n 'Pizza Hut'  ").unwrap(), strings(&["split", "10", "-n", "Pizza Hut"]));
+        assert_eq!(words(r#"create "" -a Al\ Bo "it's""#).unwrap(), strings(&["create", "", "-a", "Al Bo", "it's"]));
+        assert_eq!(words("say 'a\\b'").unwrap(), strings(&["say", "a\\b"]));
+        assert!(words("").unwrap().is_empty());
+        assert_eq!(words("split 'open").unwrap_err(), "the quote ' is not closed");
+        assert!(words("split \\").is_err());
+    }
+
+    #[test]
+    fn test_save_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.yaml");
+        let conflict = dir.path().join("flat.yaml.conflict");
+        let mut logic = Splitter::new(db.clone()).unwrap();
+        let mut no_input = std::io::empty();
+        execute(&mut logic, "create flat -a alice -a bob", &mut no_input).unwrap();
+        save(&mut logic, &mut no_input).unwrap();
+
+        // another program changes the file while the session goes on
+        let changed = |session: &mut Splitter| {
+            let mut other = Splitter::new(db.clone()).unwrap();
+            execute(&mut other, "create club -a carl", &mut std::io::empty()).unwrap();
+            other.save().unwrap();
+            execute(session, "pay 5 -f bob -t alice", &mut std::io::empty()).unwrap();
+        };
+        changed(&mut logic);
+        let theirs = std::fs::read(&db).unwrap();
+        let err = save(&mut logic, &mut "n\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("was changed by another program"), "{err}");
+        assert_eq!(std::fs::read(&db).unwrap(), theirs);
+        assert!(conflict.is_file());
+
+        save(&mut logic, &mut "y\n".as_bytes()).unwrap();
+        assert!(!conflict.exists());
+        let saved = std::fs::read_to_string(&db).unwrap();
+        assert!(saved.contains("alice") && !saved.contains("club"), "{saved}");
+        // the stamp of the overwritten database catches the next change of another program
+        changed(&mut logic);
+        assert!(save(&mut logic, &mut std::io::empty()).is_err());
+    }
+
+    #[test]
+    fn test_help() {
+        let help = help();
+        assert!(help.starts_with("Session commands:\n  help "), "{}", help);
+        assert!(help.lines().any(|line| line.split_whitespace().next() == Some("split")), "{}", help);
+        assert!(help.contains("  use "));
+        assert!(!help.contains("__complete"));
+    }
+}
@@ -0,0 +1,956 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Local, NaiveDate};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::group::Totals;
+use crate::logging::{LogEntry, LoggedCommand};
+use crate::logic::{Money, Transaction, TransactionChange};
+use crate::money::{Currency, Rate};
+use crate::period::Month;
+use crate::render::{date, BarChart, Table};
+use crate::storage::by_name;
+
+/// Aggregate figures over a group's log, computed in a single pass
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct LogSummary {
+    pub(crate) entries: usize,
+    pub(crate) first: Option<DateTime<Local>>,
+    pub(crate) last: Option<DateTime<Local>>,
+    /// total amount of money moved by splits and payments
+    pub(crate) volume: Money,
+    /// number of splits, payments between members are not expenses
+    pub(crate) expenses: usize,
+    pub(crate) expense_volume: Money,
+    pub(crate) largest_expense: Option<Expense>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Expense {
+    pub(crate) name: String,
+    pub(crate) amount: Money,
+    pub(crate) time: Option<DateTime<Local>>,
+}
+
+impl LogSummary {
+    pub(crate) fn of(log: &[LogEntry]) -> Self {
+        log.iter().fold(Self::default(), |accu, entry| accu.add(entry))
+    }
+
+    fn add(self, entry: &LogEntry) -> Self {
+        let (expenses, expense_volume, largest_expense) = match &entry.command {
+            LoggedCommand::Split { name, amount, .. } => (
+                self.expenses + 1,
+                self.expense_volume + *amount,
+                match self.largest_expense {
+                    // refunds lower the volume but are no expense
+                    largest if amount.is_negative() => largest,
+                    Some(largest) if largest.amount >= *amount => Some(largest),
+                    _ => Some(Expense { name: name.clone(), amount: *amount, time: entry.time }),
+                }
+            ),
+            LoggedCommand::Pay { .. } | LoggedCommand::Opening { .. } | LoggedCommand::WriteOff { .. }
+            | LoggedCommand::BuyIn { .. } | LoggedCommand::Reset { .. } | LoggedCommand::Correction { .. } =>
+                (self.expenses, self.expense_volume, self.largest_expense),
+        };
+        Self {
+            entries: self.entries + 1,
+            first: match (self.first, entry.time) {
+                (Some(first), Some(time)) => Some(first.min(time)),
+                (first, time) => first.or(time),
+            },
+            last: match (self.last, entry.time) {
+                (Some(last), Some(time)) => Some(last.max(time)),
+                (last, time) => last.or(time),
+            },
+            // carried over, written off, bought in and corrected balances did not move any money
+            volume: self.volume + match entry.command {
+                LoggedCommand::Opening { .. } | LoggedCommand::WriteOff { .. } | LoggedCommand::BuyIn { .. }
+                | LoggedCommand::Correction { .. } => Money::ZERO,
+                _ => entry.command.amount(),
+            },
+            expenses,
+            expense_volume,
+            largest_expense,
+        }
+    }
+
+    /// mean amount of a split, if there were any
+    pub(crate) fn average_expense(&self) -> Option<Money> {
+        if self.expenses == 0 {
+            None
+        } else {
+            Some(self.expense_volume / self.expenses as i64)
+        }
+    }
+
+    pub(crate) fn to_string(&self, curr: Currency) -> String {
+        if self.entries == 0 {
+            return "No log entries".to_string();
+        }
+        let range = match (self.first, self.last) {
+            (Some(first), Some(last)) =>
+                format!(" from {} to {}", date(first, "%Y-%m-%d"), date(last, "%Y-%m-%d")),
+            _ => String::new(),
+        };
+        format!("{} log entries{}, {} moved in total", self.entries, range, curr.format(self.volume))
+    }
+}
+
+/// everything `stat` shows about a group
+#[derive(Debug, Serialize)]
+pub(crate) struct GroupStat {
+    pub(crate) name: String,
+    pub(crate) currency: Currency,
+    /// members and their balances, sorted by name
+    pub(crate) members: Vec<(String, Money)>,
+    /// members whose balance is closer to zero than `threshold`, shown as a single line
+    pub(crate) below_threshold: Vec<(String, Money)>,
+    pub(crate) threshold: Money,
+    pub(crate) summary: LogSummary,
+    /// set if the amounts were converted from the group currency
+    pub(crate) converted: Option<Conversion>,
+    /// set if the balances are those of a past date, see `stat --as-of`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) as_of: Option<AsOf>,
+    /// what the members paid and consumed over the whole log, sorted by name; empty for a past
+    /// date or a period
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) totals: Vec<(String, Totals)>,
+}
+
+/// the date historical balances were replayed up to
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct AsOf {
+    pub(crate) date: NaiveDate,
+    /// entries without a date, written by older versions, which are counted in
+    pub(crate) undated: usize,
+}
+
+/// how amounts shown in another currency than the group's were converted
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct Conversion {
+    pub(crate) from: Currency,
+    pub(crate) rate: Rate,
+}
+
+impl Display for Conversion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(approximate, converted from {:?} at a rate of {})", self.from, self.rate)
+    }
+}
+
+/// how many balances are listed before a group is deleted or reset, unless `--full` is given
+pub(crate) const BRIEF_BALANCES: usize = 5;
+
+impl GroupStat {
+    /// moves members whose absolute balance is below `threshold` out of `members`
+    pub(crate) fn hide_below(mut self, threshold: Money) -> Self {
+        let (below, members) = self.members.into_iter()
+            .partition(|(_, balance)| balance.abs() < threshold);
+        self.members = members;
+        self.below_threshold = below;
+        self.threshold = threshold;
+        self
+    }
+
+    /// What a group holds, short enough to keep a confirmation after it on screen: the number of
+    /// members, the summary of the log and the `count` balances furthest from zero.
+    pub(crate) fn brief(&self, count: usize) -> String {
+        let mut largest: Vec<&(String, Money)> = self.members.iter().collect();
+        largest.sort_by_key(|(_, balance)| Reverse(balance.unsigned_abs()));
+        let mut brief = format!("Group {} ({}), {} member{}\n{}", self.name, self.currency, self.members.len(),
+                                if self.members.len() == 1 { "" } else { "s" }, self.summary.to_string(self.currency));
+        brief += if self.members.len() > count { "\nLargest balances:" } else { "\nBalances:" };
+        for (name, balance) in largest.iter().take(count) {
+            brief += &format!("\n{}: {}", name, self.currency.format(*balance));
+        }
+        if self.members.len() > count {
+            let more = self.members.len() - count;
+            brief += &format!("\n… and {} more, --full lists every member", more);
+        }
+        brief
+    }
+
+    /// keeps only `member` and their balance
+    pub(crate) fn only(mut self, member: &str) -> Option<Self> {
+        self.members.retain(|(name, _)| name == member);
+        self.totals.retain(|(name, _)| name == member);
+        self.below_threshold.clear();
+        if self.members.is_empty() { None } else { Some(self) }
+    }
+
+    /// shows every amount in `to`, the balances of the group stay in its own currency
+    pub(crate) fn convert(mut self, to: Currency, rate: Rate) -> Self {
+        let from = self.currency;
+        let convert = |amount: Money| rate.convert(amount, from, to);
+        for (_, balance) in self.members.iter_mut().chain(self.below_threshold.iter_mut()) {
+            *balance = convert(*balance);
+        }
+        for (_, totals) in self.totals.iter_mut() {
+            totals.paid = convert(totals.paid);
+            totals.consumed = convert(totals.consumed);
+        }
+        self.threshold = convert(self.threshold);
+        self.summary.volume = convert(self.summary.volume);
+        self.summary.expense_volume = convert(self.summary.expense_volume);
+        if let Some(largest) = &mut self.summary.largest_expense {
+            largest.amount = convert(largest.amount);
+        }
+        self.currency = to;
+        self.converted = Some(Conversion { from, rate });
+        self
+    }
+}
+
+impl Display for GroupStat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Group Statistics for group {} ({}):", self.name, self.currency)?;
+        if let Some(as_of) = &self.as_of {
+            writeln!(f, "Historical balances as of {}, later entries are left out", as_of.date)?;
+            if as_of.undated > 0 {
+                writeln!(f, "{} {} without a date counted in", as_of.undated,
+                         if as_of.undated == 1 { "entry" } else { "entries" })?;
+            }
+        }
+        if let Some(conversion) = &self.converted {
+            writeln!(f, "All amounts in {:?} {}", self.currency, conversion)?;
+        }
+        writeln!(f, "Members:")?;
+        for (name, balance) in &self.members {
+            write!(f, "\n{}: {}", name, self.currency.format(*balance))?;
+        }
+        if !self.below_threshold.is_empty() {
+            let count = self.below_threshold.len();
+            write!(f, "\n{} member{} within ±{}", count, if count == 1 { "" } else { "s" },
+                   self.currency.format(self.threshold))?;
+        }
+        write!(f, "\n\n{}", self.summary.to_string(self.currency))?;
+        if let (Some(average), Some(largest)) =
+            (self.summary.average_expense(), &self.summary.largest_expense) {
+            let date = largest.time
+                .map(|time| format!(" ({})", date(time, "%Y-%m-%d")))
+                .unwrap_or_default();
+            write!(f, "\n{} expenses, {} on average, the largest was {} {}{}",
+                   self.summary.expenses, self.currency.format(average),
+                   largest.name, self.currency.format(largest.amount), date)?;
+        }
+        Ok(())
+    }
+}
+
+/// one member's balance in every group they belong to, see `stat --member` without a group
+#[derive(Debug, Serialize)]
+pub(crate) struct MemberAcross {
+    pub(crate) member: String,
+    /// in the order of the groups
+    pub(crate) groups: Vec<MemberBalance>,
+    /// the balances added up per currency
+    pub(crate) totals: BTreeMap<Currency, Money>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct MemberBalance {
+    pub(crate) group: String,
+    pub(crate) currency: Currency,
+    pub(crate) balance: Money,
+}
+
+impl MemberAcross {
+    /// from the stats of the groups, each narrowed down to `member` by `GroupStat::only`
+    pub(crate) fn new(member: String, stats: &[GroupStat]) -> Self {
+        let groups: Vec<MemberBalance> = stats.iter()
+            .flat_map(|stat| stat.members.iter().map(|(_, balance)| MemberBalance {
+                group: stat.name.clone(),
+                currency: stat.currency,
+                balance: *balance,
+            }))
+            .collect();
+        let mut totals = BTreeMap::new();
+        for group in &groups {
+            *totals.entry(group.currency).or_default() += group.balance;
+        }
+        Self { member, groups, totals }
+    }
+}
+
+impl Display for MemberAcross {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Balances of {} in {} group{}:", self.member, self.groups.len(),
+                 if self.groups.len() == 1 { "" } else { "s" })?;
+        let mut table = Table::new(&["Group", "Balance"]).align_right(1).name_column(0);
+        for group in &self.groups {
+            table.row(vec![group.group.clone(), group.currency.format(group.balance)]);
+        }
+        write!(f, "{}", table)?;
+        let totals: Vec<String> = self.totals.iter().map(|(currency, total)| currency.format(*total)).collect();
+        write!(f, "Total: {}", totals.join(", "))
+    }
+}
+
+/// what a member paid for and consumed in the splits of a period
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct MemberPeriod {
+    pub(crate) name: String,
+    pub(crate) paid: Money,
+    pub(crate) consumed: Money,
+}
+
+/// everything `report` and `close-month` show about a period of a group
+#[derive(Debug)]
+pub(crate) struct PeriodReport {
+    pub(crate) group: String,
+    pub(crate) currency: Currency,
+    /// e.g. `2024-06` or `the open period since 2024-07-01`
+    pub(crate) period: String,
+    pub(crate) expenses: usize,
+    pub(crate) spend: Money,
+    /// sorted by name
+    pub(crate) members: Vec<MemberPeriod>,
+    /// transfers settling what the entries of the period changed
+    pub(crate) transfers: Vec<Transaction>,
+}
+
+impl Display for PeriodReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Report for group {}, {}", self.group, self.period)?;
+        writeln!(f, "{} expense{}, {} spent in total", self.expenses, if self.expenses == 1 { "" } else { "s" },
+                 self.currency.format(self.spend))?;
+        for member in &self.members {
+            writeln!(f, "{}: paid {}, consumed {}", member.name, self.currency.format(member.paid),
+                     self.currency.format(member.consumed))?;
+        }
+        if self.transfers.is_empty() {
+            write!(f, "Nothing to settle")
+        } else {
+            write!(f, "Suggested transfers:\n{}", Transaction::table(&self.transfers, self.currency))
+        }
+    }
+}
+
+/// how `spending_by` groups the splits of a log
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+pub(crate) enum SpendingBy {
+    /// what each member paid
+    Payer,
+    Category,
+    Month,
+}
+
+/// spending under one label of `spending_by`
+#[derive(Debug, PartialEq)]
+pub(crate) struct Spending {
+    pub(crate) label: String,
+    pub(crate) amount: Money,
+    /// number of splits counted
+    pub(crate) count: usize,
+}
+
+/// The splits of `log` added up per payer, category or month. Payers and categories come largest
+/// first, months in order. Splits without a category or time are listed as `(none)` and `unknown`,
+/// the latter before all months.
+pub(crate) fn spending_by(log: &[LogEntry], by: SpendingBy) -> Vec<Spending> {
+    // months sort by their key, the others by amount
+    let mut totals: BTreeMap<(Option<Month>, String), (Money, usize)> = BTreeMap::new();
+    let mut add = |key: (Option<Month>, String), amount: Money| {
+        let total = totals.entry(key).or_default();
+        total.0 += amount;
+        total.1 += 1;
+    };
+    for entry in log {
+        let LoggedCommand::Split { amount, category, .. } = &entry.command else {
+            continue;
+        };
+        match by {
+            SpendingBy::Payer => {
+                let shares = entry.shares();
+                for (member, change) in entry.change() {
+                    let share = shares.iter().find(|(name, _)| name == member).map_or(Money::ZERO, |(_, share)| *share);
+                    if (share + *change).is_positive() {
+                        add((None, member.clone()), share + *change);
+                    }
+                }
+            }
+            SpendingBy::Category => add((None, category.clone().unwrap_or("(none)".to_string())), *amount),
+            SpendingBy::Month => match entry.time {
+                Some(time) => add((Some(Month::of(time)), Month::of(time).to_string()), *amount),
+                // written by older versions, so before all months
+                None => add((None, "unknown".to_string()), *amount),
+            },
+        }
+    }
+    let mut spending: Vec<Spending> = totals.into_iter()
+        .map(|((_, label), (amount, count))| Spending { label, amount, count })
+        .collect();
+    if by != SpendingBy::Month {
+        spending.sort_by(|a, b| b.amount.cmp(&a.amount).then(a.label.cmp(&b.label)));
+    }
+    spending
+}
+
+/// `chart`: a bar per label of `spending`, with the number of splits and the share of the total
+pub(crate) fn spending_chart(spending: &[Spending], currency: Currency, width: usize) -> String {
+    let total: Money = spending.iter().map(|s| s.amount).sum();
+    let mut chart = BarChart::new();
+    for s in spending {
+        let percent = (s.amount.0 as i128 * 100 + total.0 as i128 / 2) / (total.0 as i128).max(1);
+        chart.row(format!("{} ({} split{}, {}%)", s.label, s.count, if s.count == 1 { "" } else { "s" }, percent),
+                  s.amount.0, currency.format(s.amount));
+    }
+    chart.render(width)
+}
+
+/// everything `list` shows about a group
+#[derive(Serialize)]
+pub(crate) struct GroupListing<'a> {
+    pub(crate) group: &'a str,
+    pub(crate) currency: Currency,
+    pub(crate) members: usize,
+    pub(crate) entries: Vec<ListedEntry<'a>>,
+    pub(crate) totals: LogSummary,
+}
+
+/// a log entry together with its index in the group's log, as used by `undo`
+#[derive(Serialize)]
+pub(crate) struct ListedEntry<'a> {
+    pub(crate) index: usize,
+    #[serde(flatten)]
+    pub(crate) entry: &'a LogEntry,
+}
+
+impl Display for GroupListing<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Log Listing for Group {} ({}, {} members)", self.group, self.currency, self.members)?;
+        if self.entries.is_empty() {
+            writeln!(f, "(no entries)")?;
+        }
+        for ListedEntry { index, entry } in &self.entries {
+            entry.write(f, *index, self.currency)?;
+            writeln!(f)?;
+        }
+        write!(f, "{}", self.totals.to_string(self.currency))
+    }
+}
+
+/// one row of `groups`
+#[derive(Debug, Serialize)]
+pub(crate) struct GroupOverview {
+    pub(crate) name: String,
+    pub(crate) currency: Currency,
+    pub(crate) members: usize,
+    pub(crate) entries: usize,
+    /// whether all balances add up to zero, as they always should
+    pub(crate) net_zero: bool,
+    pub(crate) last_activity: Option<DateTime<Local>>,
+    pub(crate) archived: bool,
+    /// the group used when no group is given
+    pub(crate) current: bool,
+}
+
+/// Sorts by last activity, most recent first. Groups without any dated entry come last.
+pub(crate) fn sort_overview(overview: &mut [GroupOverview]) {
+    overview.sort_by(|a, b| match (a.last_activity, b.last_activity) {
+        (Some(a_last), Some(b_last)) => b_last.cmp(&a_last),
+        (a_last, b_last) => b_last.is_some().cmp(&a_last.is_some()),
+    }.then_with(|| a.name.cmp(&b.name)));
+}
+
+/// how a batch command went per group: `None` for success, else what went wrong
+pub(crate) fn batch_table(outcomes: &[(String, Option<String>)]) -> Table {
+    let mut table = Table::new(&["Group", "Result"]).name_column(0);
+    for (group, problem) in outcomes {
+        let result = match problem {
+            None => "ok".to_string(),
+            Some(problem) => format!("failed: {}", problem.split_whitespace().collect::<Vec<_>>().join(" ")),
+        };
+        table.row(vec![group.clone(), result]);
+    }
+    table
+}
+
+pub(crate) fn overview_table(overview: &[GroupOverview]) -> Table {
+    let mut table = Table::new(&["", "Name", "Currency", "Members", "Entries", "Net zero", "Last activity"])
+        .align_right(3)
+        .align_right(4)
+        .name_column(1);
+    for group in overview {
+        let name = if group.archived { format!("{} (archived)", group.name) } else { group.name.clone() };
+        table.row(vec![
+            if group.current { "→" } else { "" }.to_string(),
+            name,
+            group.currency.to_string(),
+            group.members.to_string(),
+            group.entries.to_string(),
+            if group.net_zero { "✓" } else { "✗" }.to_string(),
+            group.last_activity.map(|time| date(time, "%Y-%m-%d")).unwrap_or_default(),
+        ]);
+    }
+    table
+}
+
+/// one row of `members`
+#[derive(Debug, Serialize)]
+pub(crate) struct MemberOverview {
+    pub(crate) name: String,
+    pub(crate) balance: Money,
+}
+
+/// how a split would change each balance, sorted by name
+pub(crate) fn change_table(change: &TransactionChange, currency: Currency) -> Table {
+    let mut table = Table::new(&["Name", "Change"]).align_right(1).name_column(0);
+    for (member, amount) in by_name(change) {
+        table.row(vec![member.clone(), format!("{}{}", if amount.is_positive() { "+" } else { "" }, currency.format(*amount))]);
+    }
+    table
+}
+
+/// what `compute` prints: what everyone would pay, consume and how their balance would change
+#[derive(Debug, Serialize)]
+pub(crate) struct SplitPreview {
+    pub(crate) amount: Money,
+    pub(crate) currency: Currency,
+    /// sorted by name
+    pub(crate) members: Vec<PreviewRow>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct PreviewRow {
+    pub(crate) name: String,
+    pub(crate) paid: Money,
+    pub(crate) share: Money,
+    pub(crate) change: Money,
+}
+
+pub(crate) fn preview_table(preview: &SplitPreview) -> Table {
+    let mut table = Table::new(&["Name", "Paid", "Share", "Change"])
+        .align_right(1).align_right(2).align_right(3).name_column(0);
+    for row in &preview.members {
+        table.row(vec![row.name.clone(), preview.currency.format(row.paid), preview.currency.format(row.share),
+                       preview.currency.format(row.change)]);
+    }
+    table
+}
+
+pub(crate) fn members_table(members: &[MemberOverview], currency: Currency) -> Table {
+    let mut table = Table::new(&["Name", "Balance"]).align_right(1).name_column(0);
+    for member in members {
+        table.row(vec![member.name.clone(), currency.format(member.balance)]);
+    }
+    table
+}
+
+/// what `merge` and `sync` took over from the other copy
+#[derive(Debug, Default)]
+pub(crate) struct MergeReport {
+    /// groups only the other copy had
+    pub(crate) new_groups: Vec<String>,
+    /// per group present in both: entries new to us, entries new to them
+    pub(crate) merged: Vec<(String, usize, usize)>,
+}
+
+impl Display for MergeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for group in &self.new_groups {
+            writeln!(f, "new group {}", group)?;
+        }
+        for (group, pulled, pushed) in &self.merged {
+            writeln!(f, "group {}: {} new entries from the other copy, {} only here", group, pulled, pushed)?;
+        }
+        Ok(())
+    }
+}
+
+/// one row of `top`
+#[derive(Debug, Serialize)]
+pub(crate) struct TopEntry {
+    /// position in the group's log, as used by `undo`
+    pub(crate) index: usize,
+    pub(crate) time: Option<DateTime<Local>>,
+    pub(crate) name: String,
+    pub(crate) category: Option<String>,
+    pub(crate) amount: Money,
+    pub(crate) payers: Vec<String>,
+    pub(crate) shares: Vec<(String, Money)>,
+}
+
+/// The `count` largest splits in the log, largest first and more recent first among equal amounts.
+/// With `member`, only splits the member paid for or took part in are considered and only their
+/// share is listed. Refunds are left out.
+pub(crate) fn top_expenses(log: &[LogEntry], count: usize,
+                           category: Option<&str>, member: Option<&str>) -> Vec<TopEntry> {
+    let mut top: Vec<TopEntry> = log.iter().enumerate()
+        .filter_map(|(index, entry)| match &entry.command {
+            LoggedCommand::Split { amount, .. } if amount.is_negative() => None,
+            LoggedCommand::Split { name, amount, from, category: entry_category, .. } => {
+                if category.is_some() && category != entry_category.as_deref() {
+                    return None;
+                }
+                let payers: Vec<String> = from.iter().map(|t| t.member.clone()).collect();
+                let mut shares = entry.shares();
+                if let Some(member) = member {
+                    shares.retain(|(name, _)| name == member);
+                    if shares.is_empty() && !payers.iter().any(|payer| payer == member) {
+                        return None;
+                    }
+                }
+                Some(TopEntry {
+                    index,
+                    time: entry.time,
+                    name: name.clone(),
+                    category: entry_category.clone(),
+                    amount: *amount,
+                    payers,
+                    shares,
+                })
+            }
+            LoggedCommand::Pay { .. } | LoggedCommand::Opening { .. } | LoggedCommand::WriteOff { .. }
+            | LoggedCommand::BuyIn { .. } | LoggedCommand::Reset { .. } | LoggedCommand::Correction { .. } => None,
+        })
+        .collect();
+    top.sort_by(|a, b| b.amount.cmp(&a.amount)
+        .then(b.time.cmp(&a.time))
+        .then(b.index.cmp(&a.index)));
+    top.truncate(count);
+    top
+}
+
+pub(crate) fn top_table(top: &[TopEntry], currency: Currency) -> Table {
+    let mut table = Table::new(&["#", "Date", "Name", "Amount", "Paid by", "Shares"])
+        .align_right(0)
+        .align_right(3)
+        .name_column(2);
+    for entry in top {
+        let name = match &entry.category {
+            Some(category) => format!("{} [{}]", entry.name, category),
+            None => entry.name.clone(),
+        };
+        table.row(vec![
+            entry.index.to_string(),
+            entry.time.map(|time| date(time, "%Y-%m-%d")).unwrap_or_default(),
+            name,
+            currency.format(entry.amount),
+            entry.payers.join(", "),
+            entry.shares.iter()
+                .map(|(member, share)| format!("{} {}", member, currency.format(*share)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod report_tests {
+    use chrono::TimeZone;
+    use crate::group::{Group, SplitOptions};
+    use crate::logging::LoggedCommand;
+    use super::*;
+
+    fn pay(amount: Money, day: u32) -> LogEntry {
+        let mut entry = LogEntry::new(
+            LoggedCommand::Pay {
+                amount,
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                group: "testgroup".to_string(),
+                settled_by: None,
+            },
+            Default::default());
+        entry.time = Some(Local.with_ymd_and_hms(2024, 6, day, 12, 0, 0).unwrap());
+        entry
+    }
+
+    #[test]
+    fn test_log_summary() {
+        assert_eq!(LogSummary::of(&[]), LogSummary::default());
+        assert_eq!(LogSummary::of(&[]).to_string(Currency::EUR), "No log entries");
+
+        let log = vec![pay(Money(10_00), 12), pay(Money(2_50), 3), pay(Money(1_00), 20)];
+        let summary = LogSummary::of(&log);
+        assert_eq!(summary.entries, 3);
+        assert_eq!(summary.volume, Money(13_50));
+        assert_eq!(summary.first, log[1].time);
+        assert_eq!(summary.last, log[2].time);
+        assert_eq!(summary.to_string(Currency::EUR),
+                   "3 log entries from 2024-06-03 to 2024-06-20, 13.50€ moved in total");
+        assert_eq!(summary.expenses, 0);
+        assert_eq!(summary.average_expense(), None);
+    }
+
+    #[test]
+    fn test_expense_summary() {
+        let split = |name: &str, amount: Money, day: u32| {
+            let mut entry = LogEntry::new(
+                LoggedCommand::Split {
+                    name: name.to_string(),
+                    amount,
+                    from: vec![],
+                    to: vec![],
+                    group: "testgroup".to_string(),
+                    balance_rest: false,
+                    category: None,
+                    ratio: None,
+                    included: vec![],
+                    excluded: vec![],
+                },
+                Default::default());
+            entry.time = Some(Local.with_ymd_and_hms(2024, 6, day, 12, 0, 0).unwrap());
+            entry
+        };
+        let log = vec![split("rewe", Money(20_00), 1), pay(Money(50_00), 2), split("dinner", Money(45_00), 3),
+                       split("bakery", Money(4_00), 4)];
+        let summary = LogSummary::of(&log);
+        assert_eq!(summary.entries, 4);
+        assert_eq!(summary.volume, Money(119_00));
+        assert_eq!(summary.expenses, 3);
+        assert_eq!(summary.expense_volume, Money(69_00));
+        assert_eq!(summary.average_expense(), Some(Money(23_00)));
+        assert_eq!(summary.largest_expense,
+                   Some(Expense { name: "dinner".to_string(), amount: Money(45_00), time: log[2].time }));
+
+        let stat = GroupStat {
+            name: "testgroup".to_string(),
+            currency: Currency::EUR,
+            members: vec![("Alice".to_string(), Money(-10_00)), ("Bob".to_string(), Money(10_00))],
+            below_threshold: vec![],
+            threshold: Money::ZERO,
+            summary,
+            converted: None,
+            as_of: None,
+            totals: vec![],
+        };
+        let rendered = stat.to_string();
+        assert!(rendered.contains("Alice: -10.00€\nBob: 10.00€"));
+        assert!(rendered.contains("4 log entries from 2024-06-01 to 2024-06-04, 119.00€ moved in total"));
+        assert!(rendered.ends_with("3 expenses, 23.00€ on average, the largest was dinner 45.00€ (2024-06-03)"));
+    }
+
+    #[test]
+    fn test_stat_threshold() {
+        let stat = GroupStat {
+            name: "testgroup".to_string(),
+            currency: Currency::EUR,
+            members: vec![("Alice".to_string(), Money(-2)), ("Bob".to_string(), Money(5)), ("Carol".to_string(), Money(-3)),
+                          ("Dave".to_string(), Money::ZERO)],
+            below_threshold: vec![],
+            threshold: Money::ZERO,
+            summary: LogSummary::default(),
+            converted: None,
+            as_of: None,
+            totals: vec![],
+        };
+        let stat = stat.hide_below(Money(5));
+        assert_eq!(stat.members, vec![("Bob".to_string(), Money(5))]);
+        assert_eq!(stat.below_threshold.len(), 3);
+        assert!(stat.to_string().contains("Members:\n\nBob: 0.05€\n3 members within ±0.05€\n\n"));
+        let json = serde_json::to_value(&stat).unwrap();
+        assert_eq!(json["below_threshold"][0], serde_json::json!(["Alice", -2]));
+    }
+
+    #[test]
+    fn test_stat_convert() {
+        let stat = GroupStat {
+            name: "testgroup".to_string(),
+            currency: Currency::EUR,
+            members: vec![("Alice".to_string(), Money(-10_00)), ("Bob".to_string(), Money(10_00))],
+            below_threshold: vec![],
+            threshold: Money::ZERO,
+            summary: LogSummary { entries: 1, volume: Money(10_00), expenses: 1, expense_volume: Money(10_00),
+                largest_expense: Some(Expense { name: "dinner".to_string(), amount: Money(10_00), time: None }),
+                ..Default::default() },
+            converted: None,
+            as_of: None,
+            totals: vec![],
+        };
+        let stat = stat.convert(Currency::USD, "1.0832".parse().unwrap());
+        assert_eq!(stat.members, vec![("Alice".to_string(), Money(-10_83)), ("Bob".to_string(), Money(10_83))]);
+        let rendered = stat.to_string();
+        assert!(rendered.starts_with("Group Statistics for group testgroup ($):\n\
+                                      All amounts in USD (approximate, converted from EUR at a rate of 1.0832)\n\
+                                      Members:\n\nAlice: -10.83$"), "{rendered}");
+        assert!(rendered.ends_with("the largest was dinner 10.83$"));
+    }
+
+    #[test]
+    fn test_brief() {
+        let names: Vec<String> = (0..40).map(|i| format!("member{:02}", i)).collect();
+        let mut group = Group::new("big".to_string(), names.clone(), None).unwrap();
+        group.log_pay_transaction(Money(7_00), "member03".to_string(), "member31".to_string()).unwrap();
+        group.split(Money(80_00), vec!["member12".to_string()], vec![], "rent".to_string(),
+                    SplitOptions::default()).unwrap();
+        let brief = group.stat_data().brief(BRIEF_BALANCES);
+        assert!(brief.starts_with("Group big (€), 40 members\n2 log entries"), "{brief}");
+        assert!(brief.contains("87.00€ moved in total\nLargest balances:\nmember12: 78.00€\nmember31: -9.00€\n\
+                                member03: 5.00€\nmember00: -2.00€\nmember01: -2.00€\n… and 35 more"), "{brief}");
+        assert_eq!(names.iter().filter(|name| brief.contains(name.as_str())).count(), BRIEF_BALANCES);
+
+        let small = Group::new("flat".to_string(), vec!["Alice".to_string()], None).unwrap();
+        assert_eq!(small.stat_data().brief(BRIEF_BALANCES), "Group flat (€), 1 member\nNo log entries\nBalances:\nAlice: 0.00€");
+    }
+
+    #[test]
+    fn test_member_across() {
+        let group = |name: &str, currency| {
+            let mut group = Group::new(name.to_string(), vec!["Alice".to_string(), "Bob".to_string()],
+                                       Some(currency)).unwrap();
+            group.log_pay_transaction(Money(4_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+            group.stat_view(Money::ZERO, None, None, false, None).unwrap()
+        };
+        let stats: Vec<GroupStat> = [group("flat", Currency::EUR), group("trip", Currency::USD),
+                                     group("club", Currency::EUR)].into_iter()
+            .filter_map(|stat| stat.only("Bob"))
+            .collect();
+        let across = MemberAcross::new("Bob".to_string(), &stats);
+        assert_eq!(across.groups[1], MemberBalance { group: "trip".to_string(), currency: Currency::USD, balance: Money(-4_00) });
+        assert_eq!(across.totals, BTreeMap::from([(Currency::EUR, Money(-8_00)), (Currency::USD, Money(-4_00))]));
+        assert_eq!(across.to_string(), "Balances of Bob in 3 groups:\n\
+                                        Group  Balance\n\
+                                        --------------\n\
+                                        flat    -4.00€\n\
+                                        trip    -4.00$\n\
+                                        club    -4.00€\n\
+                                        Total: -8.00€, -4.00$");
+        let json = serde_json::to_value(&across).unwrap();
+        assert_eq!(json["totals"], serde_json::json!({"EUR": -800, "USD": -400}));
+    }
+
+    #[test]
+    fn test_group_listing() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        assert_eq!(group.listing(None).to_string(), "Log Listing for Group testgroup (€, 2 members)\n\
+                                  (no entries)\n\
+                                  No log entries");
+        group.log_pay_transaction(Money(12_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        let listing = group.listing(None).to_string();
+        assert!(listing.starts_with("Log Listing for Group testgroup (€, 2 members)\n[0] "));
+        assert!(listing.contains("pay: in group testgroup Alice → Bob 12.00€\n"));
+        assert!(listing.ends_with("12.00€ moved in total"));
+
+        assert!(listing.contains("by (unknown): pay"));
+
+        let json = serde_json::to_value(group.listing(None)).unwrap();
+        assert_eq!(json["group"], "testgroup");
+        assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(json["entries"][0]["index"], 0);
+        assert_eq!(json["totals"]["volume"], 12_00);
+
+        group.set_last_author(Some("Alice".to_string()));
+        group.log_pay_transaction(Money(3_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.set_last_author(Some("Bob".to_string()));
+        let listing = group.listing(Some("Bob")).to_string();
+        assert!(listing.contains("[1] ") && listing.contains("by Bob: pay"), "{listing}");
+        assert!(!listing.contains("[0] "));
+        let json = serde_json::to_value(group.listing(Some("Alice"))).unwrap();
+        assert_eq!(json["entries"][0]["author"], "Alice");
+    }
+
+    #[test]
+    fn test_long_listing() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Alice".to_string(), "Bob".to_string()], None).unwrap();
+        for i in 0..5_000 {
+            group.log_pay_transaction(Money(1_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+            group.split(Money(3_00 + i), vec!["Bob".to_string()], vec![], format!("lunch {}", i), SplitOptions::default())
+                .unwrap();
+        }
+        let listing = group.listing(None).to_string();
+        assert!(listing.contains("[9999] ") && listing.contains("`lunch 4999 52.99€"));
+    }
+
+    #[test]
+    fn test_top_expenses() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Alice".to_string(), "Bob".to_string(), "Charly".to_string()],
+                                   None).unwrap();
+        group.split(Money(30_00), vec!["Alice".to_string()], vec![], "dinner".to_string(),
+                    SplitOptions { category: Some("food".to_string()), ..SplitOptions::default() }).unwrap();
+        group.split(Money(45_50), vec!["Bob".to_string(), "Charly".to_string()], vec![],
+                    "hotel".to_string(), SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(100_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.split(Money(30_00), vec!["Charly:10".to_string(), "Bob".to_string()], vec!["Alice:3".to_string()],
+                    "lunch".to_string(),
+                    SplitOptions { category: Some("food".to_string()), ..SplitOptions::default() }).unwrap();
+
+        let top = group.top(5, None, None);
+        assert_eq!(top.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+                   vec!["hotel", "lunch", "dinner"], "ties are sorted by recency");
+        assert_eq!(top[0].index, 1);
+        assert_eq!(top[0].payers, vec!["Bob".to_string(), "Charly".to_string()]);
+        assert_eq!(top[0].shares.iter().map(|(_, share)| share).sum::<Money>(), Money(45_50));
+        assert_eq!(top[1].shares, vec![("Alice".to_string(), Money(3_00)), ("Bob".to_string(), Money(13_50)),
+                                       ("Charly".to_string(), Money(13_50))]);
+
+        assert_eq!(group.top(1, None, None).len(), 1);
+        let food = group.top(5, Some("food"), Some("Alice"));
+        assert_eq!(food.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!["lunch", "dinner"]);
+        assert_eq!(food[0].shares, vec![("Alice".to_string(), Money(3_00))]);
+        assert!(group.top(5, Some("travel"), None).is_empty());
+        assert!(group.top(5, None, Some("Django")).is_empty());
+
+        let table = top_table(&food, Currency::EUR).to_string();
+        assert!(table.contains("lunch [food]"));
+        assert!(table.contains("Alice 3.00€"));
+    }
+
+    #[test]
+    fn test_spending_by() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Alice".to_string(), "Bob".to_string(), "Charly".to_string()],
+                                   None).unwrap();
+        assert!(spending_by(group.log(), SpendingBy::Payer).is_empty());
+        group.split(Money(30_00), vec!["Alice".to_string()], vec![], "dinner".to_string(),
+                    SplitOptions { category: Some("food".to_string()), ..SplitOptions::default() }).unwrap();
+        group.split(Money(45_50), vec!["Bob".to_string(), "Charly".to_string()], vec![],
+                    "hotel".to_string(), SplitOptions::default()).unwrap();
+        group.log_pay_transaction(Money(100_00), "Bob".to_string(), "Alice".to_string()).unwrap();
+        group.split(Money(30_00), vec!["Charly:10".to_string(), "Bob".to_string()], vec!["Alice:3".to_string()],
+                    "lunch".to_string(),
+                    SplitOptions { category: Some("food".to_string()), ..SplitOptions::default() }).unwrap();
+        let spending = |log: &[LogEntry], by| spending_by(log, by).into_iter()
+            .map(|s| (s.label, s.amount, s.count))
+            .collect::<Vec<_>>();
+
+        assert_eq!(spending(group.log(), SpendingBy::Payer), vec![
+            ("Bob".to_string(), Money(42_75), 2), ("Charly".to_string(), Money(32_75), 2), ("Alice".to_string(), Money(30_00), 1)]);
+        assert_eq!(spending(group.log(), SpendingBy::Category), vec![
+            ("food".to_string(), Money(60_00), 2), ("(none)".to_string(), Money(45_50), 1)]);
+
+        let mut log = group.log().to_vec();
+        log[0].time = Some(Local.with_ymd_and_hms(2024, 7, 3, 12, 0, 0).unwrap());
+        log[1].time = None;
+        log[3].time = Some(Local.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap());
+        assert_eq!(spending(&log, SpendingBy::Month), vec![
+            ("unknown".to_string(), Money(45_50), 1), ("2024-06".to_string(), Money(30_00), 1), ("2024-07".to_string(), Money(30_00), 1)]);
+
+        let chart = spending_chart(&spending_by(group.log(), SpendingBy::Category), Currency::EUR, 50);
+        assert_eq!(chart, "food (2 splits, 57%)   ###################  60.00€\n\
+                           (none) (1 split, 43%)  ##############       45.50€\n");
+    }
+
+    #[test]
+    fn test_members_table() {
+        let mut group = Group::new("testgroup".to_owned(),
+                                   vec!["Charly".to_string(), "Alice".to_string(), "Bob".to_string()],
+                                   None).unwrap();
+        group.log_pay_transaction(Money(12_00), "Alice".to_string(), "Bob".to_string()).unwrap();
+        let members = group.member_overview();
+        assert_eq!(members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), vec!["Alice", "Bob", "Charly"]);
+        assert_eq!(members_table(&members, Currency::EUR).to_string(),
+                   "Name    Balance\n\
+                    ---------------\n\
+                    Alice    12.00€\n\
+                    Bob     -12.00€\n\
+                    Charly    0.00€\n");
+        let change = TransactionChange::from([("bob".to_string(), Money(-22_50)), ("alice".to_string(), Money(22_50))]);
+        assert_eq!(change_table(&change, Currency::EUR).to_string(),
+                   "Name    Change\n\
+                    --------------\n\
+                    alice  +22.50€\n\
+                    bob    -22.50€\n");
+    }
+}
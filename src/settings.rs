@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use crate::error::*;
+use crate::logic::Money;
+use crate::money::{parse_amount, plain};
+
+/// Optional per-user (or, in portable mode, per-directory) settings file.
+/// Every field is optional, a missing file is equivalent to an empty one.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Settings {
+    /// database used when `--database` is not given
+    pub(crate) database: Option<PathBuf>,
+    /// command writing back a database given as `cmd:`, unless `--push-command` is given
+    pub(crate) push_command: Option<String>,
+    /// file receiving a one-line summary of every new log entry, unless `--summary-file` is given
+    pub(crate) summary_file: Option<PathBuf>,
+    /// your own name, recorded as author of the entries you add unless `--author` is given
+    pub(crate) me: Option<String>,
+    /// file recording every invocation, unless `--audit-log` is given
+    pub(crate) audit_log: Option<PathBuf>,
+    /// brotli quality for compressed databases, unless `--compression-level` is given
+    pub(crate) compression_level: Option<u32>,
+    /// append new log entries to a journal instead of rewriting a compressed database, like `--journal`
+    pub(crate) journal: bool,
+    /// how human readable output shows dates, unless `--date-format` is given
+    pub(crate) date_format: Option<String>,
+    /// let a new group be called like an existing one except for case, by default `Trip` and `trip` collide
+    pub(crate) case_sensitive_group_names: bool,
+    /// print a notice on startup for every group with debts older than this many days, unless `--no-nag` is given
+    pub(crate) nag_days: Option<u32>,
+    /// largest amount accepted, like `5000`, instead of 1000000000.00; `--allow-huge` lifts it
+    #[serde(deserialize_with = "amount", serialize_with = "write_amount")]
+    pub(crate) amount_ceiling: Option<Money>,
+    /// the least unsettled amount of a group, like `50` or `12.50`, that `nag_days` notices
+    #[serde(deserialize_with = "amount", serialize_with = "write_amount")]
+    pub(crate) nag_threshold: Option<Money>,
+}
+
+/// reads an amount written as number or string, see `parse_amount`
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Money>, D::Error> {
+    let raw = match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+        None | Some(serde_yaml::Value::Null) => return Ok(None),
+        Some(serde_yaml::Value::Number(number)) => number.to_string(),
+        Some(serde_yaml::Value::String(string)) => string,
+        Some(other) => return Err(D::Error::custom(format!("expected an amount like 12.50, got {:?}", other))),
+    };
+    parse_amount(&raw).map(Some).map_err(D::Error::custom)
+}
+
+/// writes an amount the way `amount` reads it
+fn write_amount<S: Serializer>(amount: &Option<Money>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match amount {
+        Some(amount) => serializer.serialize_str(&plain(*amount)),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Settings {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let raw = std::fs::read_to_string(path)
+            .context(format!("Could not read config file {:?}", path))?;
+        serde_yaml::from_str(raw.as_str())
+            .context(format!("Could not parse config file {:?}", path))
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::error::*;
+use crate::logic::{Money, Transaction};
+use crate::storage::by_name;
+
+/// groups with more members owing or owed than this are settled greedily by `min-transfers`,
+/// the search for the fewest transfers takes exponential time
+const MAX_EXACT: usize = 12;
+
+/// How `balance` works out who pays whom
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Algorithm {
+    /// fast and usually close to the fewest transfers, pays exact matches first
+    #[default]
+    Greedy,
+    /// the fewest transfers possible, slow for large groups: above 12 members with a balance it
+    /// falls back to greedy
+    MinTransfers,
+    /// everyone settles with one banker member only, one transfer per member but often more than needed
+    Pairwise,
+}
+
+impl Algorithm {
+    /// the name as given on the command line
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Greedy => "greedy",
+            Self::MinTransfers => "min-transfers",
+            Self::Pairwise => "pairwise",
+        }
+    }
+}
+
+/// The transfers bringing every balance in `members` to zero with `algorithm`. `pairwise` settles
+/// with `banker`, by default the member owed the most.
+pub(crate) fn settle(members: &HashMap<String, Money>, algorithm: Algorithm, banker: Option<&str>)
+                     -> Result<Vec<Transaction>> {
+    match algorithm {
+        Algorithm::Greedy => Ok(greedy(members)),
+        Algorithm::MinTransfers => Ok(min_transfers(members)),
+        Algorithm::Pairwise => pairwise(members, banker),
+    }
+}
+
+/// Pays the debtors off against the creditors, smallest first, after matching up the ones owing
+/// exactly what someone else is owed
+pub(crate) fn greedy(members: &HashMap<String, Money>) -> Vec<Transaction> {
+    struct Member {
+        name: String,
+        balance: Money,
+    }
+    let mut creditors: Vec<Member> =
+        by_name(members).into_iter().filter(|&(_, balance)| balance.is_positive())
+            .map(|(name, balance)| Member { name: name.clone(), balance: *balance })
+            .collect();
+    let mut debtors: Vec<Member> =
+        by_name(members).into_iter().filter(|&(_, balance)| balance.is_negative())
+            .map(|(name, balance)| Member { name: name.clone(), balance: *balance })
+            .collect();
+    creditors.sort_by(|el1, el2| el1.balance.partial_cmp(&el2.balance).unwrap());
+    debtors.sort_by(
+        |el1, el2| el1.balance.abs().partial_cmp(&el2.balance.abs())
+            .unwrap());
+    let mut transactions = vec![];
+    // find matching c and d & match them up
+    for d in debtors.iter_mut() {
+        for c in creditors.iter_mut() {
+            if -d.balance < c.balance {
+                break; // break the loop
+            }
+            if d.balance == -c.balance {
+                transactions.push(Transaction::new(&d.name, &c.name, c.balance));
+                d.balance = Money::ZERO;
+                c.balance = Money::ZERO;
+            }
+        }
+    }
+
+    let mut c_idx = 0;
+    // non-matching loop
+    for d in debtors.iter_mut() {
+        if d.balance.is_zero() {
+            continue;
+        }
+        while creditors.get(c_idx).unwrap().balance.is_zero() {
+            c_idx += 1;
+        }
+        let mut c = creditors.get_mut(c_idx).unwrap();
+        if c.balance == -d.balance {
+            transactions.push(Transaction::new(&d.name, &c.name, c.balance));
+            d.balance = Money::ZERO;
+            c.balance = Money::ZERO;
+            c_idx += 1;
+            continue;
+        }
+        while c.balance < -d.balance {
+            d.balance += c.balance;
+            transactions.push(Transaction::new(&d.name, &c.name, c.balance));
+            c.balance = Money::ZERO;
+            c_idx += 1;
+            c = creditors.get_mut(c_idx).unwrap();
+        }
+        // the last creditor may be owed exactly the rest
+        if c.balance >= -d.balance {
+            c.balance += d.balance;
+            transactions.push(Transaction::new(&d.name, &c.name, d.balance));
+            d.balance = Money::ZERO;
+        }
+    }
+    transactions
+}
+
+/// Splits the members with a balance into as many groups adding up to zero as possible, each
+/// settled greedily among itself. A group of n members needs at most n - 1 transfers, so more groups
+/// mean fewer transfers.
+fn min_transfers(members: &HashMap<String, Money>) -> Vec<Transaction> {
+    let open: Vec<(&String, &Money)> = by_name(members).into_iter().filter(|(_, balance)| !balance.is_zero()).collect();
+    if open.len() > MAX_EXACT {
+        return greedy(members);
+    }
+    let count = 1usize << open.len();
+    let sums: Vec<Money> = (0..count)
+        .map(|mask| open.iter().enumerate().filter(|(idx, _)| mask & (1 << idx) != 0).map(|(_, (_, b))| **b).sum())
+        .collect();
+    // for every mask adding up to zero the most zero sum groups it splits into, and the first of them
+    let mut most = vec![0usize; count];
+    let mut first = vec![0usize; count];
+    for mask in (1..count).filter(|mask| sums[*mask].is_zero()) {
+        let lowest = mask & mask.wrapping_neg();
+        let mut sub = mask;
+        while sub != 0 {
+            if sub & lowest != 0 && sums[sub].is_zero() && 1 + most[mask ^ sub] > most[mask] {
+                most[mask] = 1 + most[mask ^ sub];
+                first[mask] = sub;
+            }
+            sub = (sub - 1) & mask;
+        }
+    }
+    let mut transfers = vec![];
+    let mut mask = count - 1;
+    while mask != 0 {
+        let group: HashMap<String, Money> = open.iter().enumerate()
+            .filter(|(idx, _)| first[mask] & (1 << idx) != 0)
+            .map(|(_, (name, balance))| ((*name).clone(), **balance))
+            .collect();
+        transfers.extend(greedy(&group));
+        mask ^= first[mask];
+    }
+    transfers
+}
+
+/// Everyone owing pays `banker`, who pays everyone owed. Without a banker it is the member owed the most.
+fn pairwise(members: &HashMap<String, Money>, banker: Option<&str>) -> Result<Vec<Transaction>> {
+    let members = by_name(members);
+    let banker = match banker {
+        Some(banker) if members.iter().any(|(name, _)| *name == banker) => banker,
+        Some(banker) => return Err(anyhow!(SplitterError::MemberNotFound))
+            .context(format!("The banker {} is no member", banker)),
+        None => match members.iter().max_by(|(a_name, a), (b_name, b)| a.cmp(b).then(b_name.cmp(a_name))) {
+            Some((name, _)) => name.as_str(),
+            None => return Ok(vec![]),
+        },
+    };
+    Ok(members.into_iter()
+        .filter(|(name, balance)| *name != banker && !balance.is_zero())
+        .map(|(name, balance)| if balance.is_negative() {
+            Transaction::new(name, banker, *balance)
+        } else {
+            Transaction::new(banker, name, *balance)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod settle_tests {
+    use super::*;
+
+    /// owing and owed so that greedy misses the two groups adding up to zero
+    fn fixture() -> HashMap<String, Money> {
+        [("A", 5_00), ("B", 5_00), ("C", -3_00), ("D", -3_00), ("E", -2_00), ("F", -2_00)].iter()
+            .map(|(name, balance)| (name.to_string(), Money(*balance)))
+            .collect()
+    }
+
+    /// whether `transfers` bring every balance of `members` to zero
+    fn settles(members: &HashMap<String, Money>, transfers: &[Transaction]) -> bool {
+        let mut left = members.clone();
+        for transfer in transfers {
+            *left.get_mut(&transfer.from).unwrap() += transfer.amount;
+            *left.get_mut(&transfer.to).unwrap() -= transfer.amount;
+        }
+        left.values().all(|balance| balance.is_zero())
+    }
+
+    #[test]
+    fn test_transfer_counts() {
+        let members = fixture();
+        let count = |algorithm, banker| {
+            let transfers = settle(&members, algorithm, banker).unwrap();
+            assert!(settles(&members, &transfers), "{:?} does not settle: {:?}", algorithm, transfers);
+            transfers.len()
+        };
+        assert_eq!(count(Algorithm::Greedy, None), 5);
+        assert_eq!(count(Algorithm::MinTransfers, None), 4);
+        assert_eq!(count(Algorithm::Pairwise, None), 5);
+        assert_eq!(count(Algorithm::Pairwise, Some("C")), 5);
+    }
+
+    #[test]
+    fn test_min_transfers() {
+        let transfers = min_transfers(&fixture());
+        assert_eq!(transfers, vec![
+            Transaction::new("F", "A", Money(2_00)), Transaction::new("D", "A", Money(3_00)),
+            Transaction::new("E", "B", Money(2_00)), Transaction::new("C", "B", Money(3_00))]);
+        assert!(min_transfers(&HashMap::new()).is_empty());
+
+        // too many for the exact search
+        let many: HashMap<String, Money> = (0..14).map(|idx| (format!("m{:02}", idx), Money(if idx % 2 == 0 { 1_00 } else { -1_00 })))
+            .collect();
+        assert!(settles(&many, &min_transfers(&many)));
+    }
+
+    #[test]
+    fn test_pairwise() {
+        let transfers = pairwise(&fixture(), None).unwrap();
+        // the banker is the one owed the most, the first in alphabetical order on a tie
+        assert!(transfers.iter().all(|transfer| transfer.from == "A" || transfer.to == "A"));
+        assert_eq!(transfers[0], Transaction::new("A", "B", Money(5_00)));
+        assert!(pairwise(&fixture(), Some("Z")).is_err());
+        assert!(pairwise(&HashMap::new(), None).unwrap().is_empty());
+    }
+}
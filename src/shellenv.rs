@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use crate::report::GroupStat;
+
+/// Turns a name into the rest of a shell variable name: uppercase ASCII letters, digits and `_`,
+/// every other character becomes `_`
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Variable suffixes for `names`, in the same order. Names that sanitize to the same suffix are
+/// told apart by appending `_2`, `_3`, … in alphabetical order of the original names,
+/// so the first name keeps the plain suffix no matter in which order the names are given.
+pub(crate) fn variable_names(names: &[&str]) -> Vec<String> {
+    let mut sorted: Vec<(usize, &str)> = names.iter().copied().enumerate().collect();
+    sorted.sort_by_key(|(_, name)| *name);
+    // plain suffixes are claimed first so a disambiguated name never takes one of them
+    let mut taken: HashSet<String> = names.iter().map(|name| sanitize(name)).collect();
+    let mut claimed = HashSet::new();
+    let mut result = vec![String::new(); names.len()];
+    for (index, name) in sorted {
+        let plain = sanitize(name);
+        result[index] = if claimed.insert(plain.clone()) {
+            plain
+        } else {
+            let unique = (2..).map(|n| format!("{}_{}", plain, n))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap();
+            taken.insert(unique.clone());
+            unique
+        };
+    }
+    result
+}
+
+/// quotes a value for POSIX shells
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `stat --format env`: `KEY=value` lines meant for `eval`, balances in minor units
+pub(crate) fn stat_env(stat: &GroupStat) -> String {
+    let mut env = format!("GROUP_NAME={}\nGROUP_CURRENCY={:?}\nGROUP_TOTAL_ENTRIES={}\n",
+                          quote(&stat.name), stat.currency, stat.summary.entries);
+    let names: Vec<&str> = stat.members.iter().map(|(name, _)| name.as_str()).collect();
+    for (variable, (_, balance)) in variable_names(&names).iter().zip(&stat.members) {
+        env += &format!("BALANCE_{}={}\n", variable, balance.0);
+    }
+    env
+}
+
+#[cfg(test)]
+mod shellenv_tests {
+    use crate::money::{Currency, Money};
+    use crate::report::LogSummary;
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("alice"), "ALICE");
+        assert_eq!(sanitize("Anna-Lena(2)"), "ANNA_LENA_2_");
+        assert_eq!(sanitize("jürgen"), "J_RGEN");
+    }
+
+    #[test]
+    fn test_collisions() {
+        assert_eq!(variable_names(&["bob", "alice"]), vec!["BOB", "ALICE"]);
+        assert_eq!(variable_names(&["anna_b", "anna-b", "Anna-B"]), vec!["ANNA_B_3", "ANNA_B_2", "ANNA_B"]);
+        // independent of the order the names come in
+        assert_eq!(variable_names(&["Anna-B", "anna_b", "anna-b"]), vec!["ANNA_B", "ANNA_B_3", "ANNA_B_2"]);
+        // a disambiguated name does not take the plain name of another member
+        assert_eq!(variable_names(&["a-2", "a_2", "a-", "a_"]), vec!["A_2", "A_2_2", "A_", "A__2"]);
+        assert_eq!(variable_names(&["a", "A", "a_2"]), vec!["A_3", "A", "A_2"]);
+    }
+
+    #[test]
+    fn test_stat_env() {
+        let stat = GroupStat {
+            name: "it's a trip".to_string(),
+            currency: Currency::EUR,
+            members: vec![("alice".to_string(), Money(-12_34)), ("bob".to_string(), Money(12_34))],
+            below_threshold: vec![],
+            threshold: Money::ZERO,
+            converted: None,
+            as_of: None,
+            totals: vec![],
+            summary: LogSummary { entries: 42, ..Default::default() },
+        };
+        assert_eq!(stat_env(&stat), "GROUP_NAME='it'\\''s a trip'\n\
+                                     GROUP_CURRENCY=EUR\n\
+                                     GROUP_TOTAL_ENTRIES=42\n\
+                                     BALANCE_ALICE=-1234\n\
+                                     BALANCE_BOB=1234\n");
+    }
+}
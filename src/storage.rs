@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use brotli::{CompressorReader, Decompressor};
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use crate::error::*;
+
+/// files larger than this are refused, they are most likely no splitter database
+pub(crate) const MAX_DATABASE_SIZE: u64 = 256 * 1024 * 1024;
+/// brotli quality used for databases of at least `SMALL_PAYLOAD` bytes of yaml
+const DEFAULT_LEVEL: u32 = 6;
+/// below this the database is compressed with `SMALL_LEVEL`, the saved bytes are not worth the time
+const SMALL_PAYLOAD: usize = 64 * 1024;
+const SMALL_LEVEL: u32 = 4;
+/// highest quality brotli supports
+pub(crate) const MAX_LEVEL: u32 = 11;
+
+/// How the database is kept on disk
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StorageFormat {
+    /// brotli compressed yaml, the default
+    Compressed,
+    /// plain yaml for databases ending in `.yaml` or `.yml`, meant to be kept in version control
+    Plain,
+}
+
+impl StorageFormat {
+    #[cfg(feature = "cli")]
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Self::Compressed => "brotli compressed yaml",
+            Self::Plain => "plain yaml",
+        }
+    }
+
+    pub(crate) fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Self::Plain,
+            _ => Self::Compressed,
+        }
+    }
+
+    /// the yaml stored in `input`, decoded while it is read
+    pub(crate) fn decoder<'a>(&self, input: impl Read + 'a) -> Box<dyn Read + 'a> {
+        match self {
+            Self::Compressed => Box::new(Decompressor::new(input, 4096)),
+            Self::Plain => Box::new(input),
+        }
+    }
+
+    /// `level` is the brotli quality, chosen by the size of `yaml` if not given
+    pub(crate) fn encode(&self, yaml: String, level: Option<u32>) -> Vec<u8> {
+        match self {
+            Self::Compressed => {
+                let level = level.unwrap_or(if yaml.len() < SMALL_PAYLOAD { SMALL_LEVEL } else { DEFAULT_LEVEL });
+                compress(yaml, level)
+            }
+            Self::Plain => yaml.into_bytes(),
+        }
+    }
+}
+
+/// What the database file looked like when it was loaded, to notice other programs changing it
+#[derive(Debug)]
+pub(crate) struct FileStamp {
+    len: u64,
+    pub(crate) modified: Option<SystemTime>,
+    hash: [u8; 32],
+}
+
+impl FileStamp {
+    pub(crate) fn new(raw: &[u8], modified: Option<SystemTime>) -> Self {
+        Self {
+            len: raw.len() as u64,
+            modified,
+            hash: Sha256::digest(raw).into(),
+        }
+    }
+
+    /// the stamp of the file as it is now, `None` if it does not exist
+    pub(crate) fn read(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw = std::fs::read(path).context(format!("Could not read {:?}", path))?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(Some(Self::new(raw.as_slice(), modified)))
+    }
+
+    /// Whether the content is the same. Only touching the file does not count as a change.
+    pub(crate) fn same_content(this: &Option<Self>, other: &Option<Self>) -> bool {
+        match (this, other) {
+            (Some(this), Some(other)) => this.len == other.len && this.hash == other.hash,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// where a save that ran into a concurrent change puts the state instead of the database at `db`
+pub(crate) fn conflict_path(db: &Path) -> PathBuf {
+    let mut path = db.as_os_str().to_owned();
+    path.push(".conflict");
+    PathBuf::from(path)
+}
+
+/// hash of the serialized state, to tell whether it changed without keeping a copy
+pub(crate) fn content_hash(yaml: &str) -> [u8; 32] {
+    Sha256::digest(yaml.as_bytes()).into()
+}
+
+fn compress(input: String, level: u32) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut com_rdr =
+        CompressorReader::new(input.as_bytes(), 4096, level.min(MAX_LEVEL), 22);
+    com_rdr.read_to_end(&mut compressed).unwrap();
+    compressed
+}
+
+/// Passes what is read through, hashing it on the way for the `FileStamp`
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.len += read as u64;
+        Ok(read)
+    }
+}
+
+/// Streams the database at `path` through its decoder into `parse`, so neither the file nor the
+/// decoded text is held in memory besides what `parse` keeps. Returns the result with the stamp of the file.
+pub(crate) fn read_database<T>(path: &Path, parse: impl FnOnce(&mut dyn Read) -> Result<T>) -> Result<(T, FileStamp)> {
+    let meta = std::fs::metadata(path).context(format!("Could not read {:?}", path))?;
+    if meta.len() > MAX_DATABASE_SIZE {
+        return Err(anyhow!(SplitterError::LogicError)).context(format!(
+            "{:?} is {} MiB, more than the {} MiB a splitter database can have. Is it the right file?",
+            path, meta.len() / (1024 * 1024), MAX_DATABASE_SIZE / (1024 * 1024)));
+    }
+    let file = std::fs::File::open(path).context(format!("Could not read {:?}", path))?;
+    let mut hashing = HashingReader { inner: BufReader::new(file), hasher: Sha256::new(), len: 0 };
+    let parsed = parse(&mut StorageFormat::for_path(path).decoder(&mut hashing))?;
+    // whatever follows the end of the brotli stream is part of the file all the same
+    std::io::copy(&mut hashing, &mut std::io::sink()).context(format!("Could not read {:?}", path))?;
+    let stamp = FileStamp { len: hashing.len, modified: meta.modified().ok(), hash: hashing.hasher.finalize().into() };
+    Ok((parsed, stamp))
+}
+
+/// The entries of a map keyed by member name in alphabetical order. Everything shown to the user
+/// or written to disk iterates member and change maps through this, so the same command gives the
+/// same output every time instead of following the random order of the `HashMap`.
+pub(crate) fn by_name<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(name, _)| *name);
+    entries
+}
+
+/// `serialize_with` helper writing a map with its keys sorted, so that saving the same
+/// state twice gives the same file and a small change gives a small diff
+pub(crate) fn sorted<S: Serializer, V: Serialize>(map: &HashMap<String, V>, serializer: S)
+                                                 -> std::result::Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+
+    fn decode(format: StorageFormat, raw: &[u8]) -> String {
+        let mut yaml = String::new();
+        format.decoder(raw).read_to_string(&mut yaml).unwrap();
+        yaml
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let yaml = "version: 0.1.0\ngroups: []\n".to_string();
+        for format in [StorageFormat::Compressed, StorageFormat::Plain] {
+            assert_eq!(decode(format, &format.encode(yaml.clone(), None)), yaml);
+            assert_eq!(decode(format, &format.encode(yaml.clone(), Some(0))), yaml);
+        }
+        assert_eq!(StorageFormat::Plain.encode(yaml.clone(), Some(11)), yaml.as_bytes());
+        assert_eq!(StorageFormat::for_path(Path::new("flat.yaml")), StorageFormat::Plain);
+        assert_eq!(StorageFormat::for_path(Path::new("flat.yml")), StorageFormat::Plain);
+        assert_eq!(StorageFormat::for_path(Path::new("default.db")), StorageFormat::Compressed);
+    }
+
+    #[test]
+    fn test_compression_level() {
+        let yaml: String = (0..2_000).map(|i| format!("- name: entry {}\n  amount: {}\n", i, i * 7)).collect();
+        let fast = StorageFormat::Compressed.encode(yaml.clone(), Some(0));
+        let best = StorageFormat::Compressed.encode(yaml.clone(), Some(MAX_LEVEL));
+        assert!(best.len() < fast.len());
+        assert_eq!(decode(StorageFormat::Compressed, &best), yaml);
+        assert_eq!(content_hash(&yaml), content_hash(&yaml.clone()));
+        assert_ne!(content_hash(&yaml), content_hash("groups: []\n"));
+    }
+
+    #[test]
+    fn test_read_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml: String = (0..50_000).map(|i| format!("- entry {}\n", i)).collect();
+        for name in ["large.db", "large.yaml"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, StorageFormat::for_path(&path).encode(yaml.clone(), None)).unwrap();
+            let (read, stamp) = read_database(&path, |input| {
+                let mut read = String::new();
+                input.read_to_string(&mut read)?;
+                Ok(read)
+            }).unwrap();
+            assert_eq!(read, yaml);
+            assert!(FileStamp::same_content(&Some(stamp), &FileStamp::read(&path).unwrap()));
+        }
+
+        let path = dir.path().join("huge.db");
+        std::fs::File::create(&path).unwrap().set_len(MAX_DATABASE_SIZE + 1).unwrap();
+        let err = read_database(&path, |_| Ok(())).unwrap_err();
+        assert!(format!("{:#}", err).contains("is 256 MiB, more than the 256 MiB a splitter database can have"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_sorted() {
+        #[derive(Serialize)]
+        struct Wrapper {
+            #[serde(serialize_with = "sorted")]
+            map: HashMap<String, i64>,
+        }
+        let map: HashMap<String, i64> = (0..20).map(|i| (format!("m{:02}", 19 - i), i)).collect();
+        let yaml = serde_yaml::to_string(&Wrapper { map }).unwrap();
+        let keys: Vec<&str> = yaml.lines().skip(1).map(|line| line.trim().split(':').next().unwrap()).collect();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(keys, expected);
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+use chrono::Local;
+use crate::error::*;
+use crate::journal;
+use crate::logic::Splitter;
+use crate::render::date;
+use crate::storage::FileStamp;
+
+/// how often `splitter watch` looks at the database
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// how long to wait before loading a database again that could not be loaded, it may have been
+/// read halfway through a save
+const RETRY_AFTER: Duration = Duration::from_millis(100);
+
+/// the database and its journal, as far as telling whether they changed goes
+type Stamps = (Option<FileStamp>, Option<FileStamp>);
+
+/// `splitter watch`: shows a group again every time its database or journal changes
+pub(crate) struct Watch {
+    db: PathBuf,
+    group: Option<String>,
+    /// the files as they were when last shown, `None` before the first time
+    shown: Option<Stamps>,
+}
+
+impl Watch {
+    pub(crate) fn new(db: PathBuf, group: Option<String>) -> Self {
+        Self { db, group, shown: None }
+    }
+
+    fn stamps(&self) -> Result<Stamps> {
+        Ok((FileStamp::read(&self.db)?, FileStamp::read(&journal::path_for(&self.db))?))
+    }
+
+    fn view(&self) -> Result<String> {
+        Splitter::new(self.db.clone())?.watch_view(self.group.clone())
+    }
+
+    /// The group under a timestamped header if the files changed since it was last shown. A
+    /// database that cannot be loaded is tried once more, a failure is only reported once per change.
+    pub(crate) fn poll(&mut self) -> Result<Option<String>> {
+        let mut stamps = self.stamps()?;
+        if let Some((db, journal)) = &self.shown {
+            if FileStamp::same_content(db, &stamps.0) && FileStamp::same_content(journal, &stamps.1) {
+                return Ok(None);
+            }
+        }
+        let view = self.view().or_else(|_| {
+            sleep(RETRY_AFTER);
+            stamps = self.stamps()?;
+            self.view()
+        });
+        self.shown = Some(stamps);
+        Ok(Some(format!("── {} ──\n{}", date(Local::now(), "%Y-%m-%d %H:%M:%S"), view?)))
+    }
+
+    /// Polls until interrupted. Nothing is written to the database, Ctrl-C ending the process
+    /// leaves it as it was.
+    pub(crate) fn run(&mut self, out: &mut impl Write) -> Result<()> {
+        loop {
+            match self.poll() {
+                Ok(Some(view)) => {
+                    writeln!(out, "{}\n", view)?;
+                    out.flush()?;
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Error: {:#}", e),
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use std::time::Instant;
+    use crate::config::SubCommand;
+    use crate::money::Money;
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../tests/golden/money-format.yaml");
+
+    #[test]
+    fn test_poll() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("splitter.yaml");
+        std::fs::write(&db, FIXTURE).unwrap();
+        let mut watch = Watch::new(db.clone(), Some("trip".to_string()));
+        let first = watch.poll().unwrap().unwrap();
+        assert!(first.starts_with("── "), "{}", first);
+        assert!(first.contains("\nLatest: [4] "), "{}", first);
+        assert!(watch.poll().unwrap().is_none());
+        // touching the file is no change
+        std::fs::write(&db, FIXTURE).unwrap();
+        assert!(watch.poll().unwrap().is_none());
+
+        let mut splitter = Splitter::new(db.clone()).unwrap();
+        splitter.run_all(vec![SubCommand::Pay { amount: Money(7_00), group: Some("trip".to_string()),
+                                                from: "alice".to_string(), to: "bob".to_string(), yes: true }]).unwrap();
+        splitter.save().unwrap();
+        let changed = Instant::now();
+        let view = loop {
+            if let Some(view) = watch.poll().unwrap() {
+                break view;
+            }
+            assert!(changed.elapsed() < POLL_INTERVAL, "the change was not picked up");
+            sleep(POLL_INTERVAL / 10);
+        };
+        assert!(view.contains("\nLatest: [5] "), "{}", view);
+        assert!(view.ends_with("alice → bob 7.00€"), "{}", view);
+
+        // a broken database is reported once, the next complete save is shown again
+        std::fs::write(&db, &FIXTURE[..FIXTURE.len() / 2]).unwrap();
+        assert!(watch.poll().is_err());
+        assert!(watch.poll().unwrap().is_none());
+        std::fs::write(&db, FIXTURE).unwrap();
+        assert!(watch.poll().unwrap().unwrap().contains("\nLatest: [4] "));
+    }
+
+    #[test]
+    fn test_poll_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("splitter.yaml");
+        std::fs::write(&db, &FIXTURE[..FIXTURE.len() / 2]).unwrap();
+        let complete = db.clone();
+        let writer = std::thread::spawn(move || {
+            sleep(RETRY_AFTER / 4);
+            std::fs::write(complete, FIXTURE).unwrap();
+        });
+        let mut watch = Watch::new(db, Some("trip".to_string()));
+        let view = watch.poll().unwrap().unwrap();
+        writer.join().unwrap();
+        assert!(view.contains("\nLatest: [4] "), "{}", view);
+        assert!(watch.poll().unwrap().is_none());
+    }
+}
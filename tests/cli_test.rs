@@ -1,3 +1,6 @@
+// the first tests are kept as they were written, before clippy checked the tests
+#![allow(dead_code, clippy::needless_borrows_for_generic_args, clippy::unused_io_amount)]
+
 #[cfg(test)]
 mod integration {
     use std::fs;
@@ -138,12 +141,1318 @@ mod integration {
         }
     }
 
+    /// runs the built binary in `cwd` with no HOME set, returning its output
+    fn run_homeless(cwd: &std::path::Path, exe: &std::path::Path, args: &[&str]) -> std::process::Output {
+        Command::new(exe)
+            .args(args)
+            .current_dir(cwd)
+            .env_remove("HOME")
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_portable() {
+        // --portable keeps everything in the current working directory
+        {
+            let cwd = tempfile::tempdir().unwrap();
+            let exe = PathBuf::from(env!("CARGO_BIN_EXE_splitter"));
+            let out = run_homeless(cwd.path(), &exe,
+                                   &["--portable", "create", "club", "-a", "alice", "-a", "bob"]);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            assert!(cwd.path().join("splitter.db").is_file());
+            let out = run_homeless(cwd.path(), &exe, &["--portable", "--verbose", "stat", "club"]);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            assert!(stdout.contains("Portable mode"));
+            assert!(stdout.contains("alice") && stdout.contains("bob"));
+        }
+        // a marker file next to the executable has the same effect
+        {
+            let bin_dir = tempfile::tempdir().unwrap();
+            let cwd = tempfile::tempdir().unwrap();
+            let exe = bin_dir.path().join("splitter");
+            fs::copy(env!("CARGO_BIN_EXE_splitter"), &exe).unwrap();
+            fs::write(bin_dir.path().join("portable"), "").unwrap();
+            let out = run_homeless(cwd.path(), &exe, &["create", "club", "-a", "alice"]);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            assert!(cwd.path().join("splitter.db").is_file());
+            let out = run_homeless(cwd.path(), &exe, &["stat", "club"]);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        }
+    }
+
+    /// runs the built binary against `db`, returning its output
+    fn run(db: &std::path::Path, args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .arg("-d")
+            .arg(db)
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("complete.db");
+        assert!(run(&db, &["create", "trip", "-a", "zoe", "-a", "bob"]).status.success());
+        assert!(run(&db, &["create", "flat", "-a", "amy"]).status.success());
+        let before = fs::read(&db).unwrap();
+
+        let out = run(&db, &["__complete", "groups"]);
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "trip\nflat\n");
+        let out = run(&db, &["__complete", "members", "trip"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "bob\nzoe\n");
+        // the current group is the last one created
+        let out = run(&db, &["__complete", "members"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "amy\n");
+        let out = run(&db, &["__complete", "members", "nonexistent"]);
+        assert!(out.status.success());
+        assert!(out.stdout.is_empty());
+        assert_eq!(before, fs::read(&db).unwrap(), "completion must not modify the database");
+
+        // broken or missing databases give no suggestions, but no error either
+        let broken = dir.path().join("broken.db");
+        fs::write(&broken, "not a database").unwrap();
+        let out = run(&broken, &["__complete", "groups"]);
+        assert!(out.status.success());
+        assert!(out.stdout.is_empty() && out.stderr.is_empty());
+        let missing = dir.path().join("missing.db");
+        let out = run(&missing, &["__complete", "groups"]);
+        assert!(out.status.success());
+        assert!(!missing.exists());
+
+        let out = run(&db, &["__complete", "script", "bash"]);
+        let script = String::from_utf8_lossy(&out.stdout);
+        assert!(script.contains("__complete members") && script.contains("delete-group"));
+    }
+
+    #[test]
+    fn test_completions() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("completions.db");
+        let out = run(&db, &["completions", "bash"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let script = String::from_utf8_lossy(&out.stdout);
+        for word in ["split", "pay", "delete-group", "completions", "--from", "--to", "--balance-rest"] {
+            assert!(script.contains(word), "{} missing from the bash completion", word);
+        }
+        for shell in ["zsh", "fish"] {
+            let out = run(&db, &["completions", shell]);
+            assert!(out.status.success() && out.stdout.len() > 1000, "{}", shell);
+        }
+        assert!(!db.exists(), "completions must not touch the database");
+        assert!(!run(&db, &["completions", "elvish"]).status.success());
+    }
+
+    #[test]
+    fn test_and_then() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("chain.db");
+        // all steps succeed and are saved together
+        let out = run(&db, &["create", "trip", "-a", "alice", "-a", "bob",
+            "--and-then", "split", "30", "-n", "dinner", "-f", "alice",
+            "--and-then", "stat"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(String::from_utf8_lossy(&out.stdout).contains("bob: -15.00€"));
+        let before = fs::read(&db).unwrap();
+
+        // a failing step discards the earlier ones and names the step
+        let out = run(&db, &["split", "10", "-n", "beer", "-f", "alice",
+            "--and-then", "pay", "5", "-f", "nobody", "-t", "alice"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Step 2 of 2 failed"));
+        assert_eq!(before, fs::read(&db).unwrap());
+
+        // parse errors in later steps are reported before anything runs
+        let out = run(&db, &["split", "10", "-n", "beer", "-f", "alice", "--and-then", "pya", "5"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Step 2"));
+        assert_eq!(before, fs::read(&db).unwrap());
+    }
+
+    #[test]
+    fn test_summary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("summary.db");
+        let summary = dir.path().join("summary.txt");
+        let summary_arg = summary.to_str().unwrap();
+        let out = run(&db, &["--summary-file", summary_arg, "create", "trip", "-a", "alice", "-a", "bob",
+            "--and-then", "split", "30", "-n", "dinner", "-f", "alice"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        // read-only commands and failed ones add nothing
+        assert!(run(&db, &["--summary-file", summary_arg, "stat", "trip"]).status.success());
+        assert!(!run(&db, &["--summary-file", summary_arg, "pay", "5", "-f", "bob", "-t", "nobody"]).status.success());
+        let out = run(&db, &["--summary-file", summary_arg, "pay", "5", "-f", "bob", "-t", "alice"]);
+        assert!(out.status.success());
+
+        let written = fs::read_to_string(&summary).unwrap();
+        let lines: Vec<_> = written.lines().collect();
+        assert_eq!(lines.len(), 2, "{written}");
+        assert!(lines[0].starts_with("[0] ") && lines[0].contains("split: in group trip `dinner 30.00€"));
+        assert!(lines[1].starts_with("[1] ") && lines[1].ends_with("pay: in group trip bob → alice 5.00€"));
+
+        // an unwritable summary file only warns
+        let out = run(&db, &["--summary-file", dir.path().join("missing/summary.txt").to_str().unwrap(),
+            "pay", "5", "-f", "bob", "-t", "alice"]);
+        assert!(out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Warning: could not write summary"));
+    }
+
+    #[test]
+    fn test_plain_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flat.yaml");
+        let out = run(&db, &["create", "flat", "-a", "dora", "-a", "carl", "-a", "bert", "-a", "anna",
+            "--and-then", "split", "40", "-n", "rewe", "-f", "anna"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let before = fs::read_to_string(&db).unwrap();
+        assert!(before.contains("  members:\n    anna: 3000\n    bert: -1000\n    carl: -1000\n    dora: -1000\n"),
+                "{before}");
+
+        // read-only commands do not touch the file
+        let modified = fs::metadata(&db).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(run(&db, &["stat", "flat"]).status.success());
+        assert_eq!(fs::metadata(&db).unwrap().modified().unwrap(), modified);
+
+        // a new split only adds its log entry and changes the balances
+        assert!(run(&db, &["split", "8", "-n", "bakery", "-f", "bert"]).status.success());
+        let after = fs::read_to_string(&db).unwrap();
+        let mut added: Vec<_> = after.lines().collect();
+        let removed: Vec<_> = before.lines()
+            .filter(|line| match added.iter().position(|l| l == line) {
+                Some(idx) => {
+                    added.remove(idx);
+                    false
+                }
+                None => true,
+            })
+            .collect();
+        let (totals, removed): (Vec<_>, Vec<_>) = removed.into_iter().partition(|line| line.starts_with("      "));
+        assert_eq!(removed, vec!["    anna: 3000", "    bert: -1000", "    carl: -1000", "    dora: -1000",
+                                 "revision: 1"]);
+        assert_eq!(added.len(), 4 + 18 + totals.len() + 1,
+                   "the new balances, log entry, running totals and revision:\n{added:#?}");
+        assert!(added.iter().any(|line| line.contains("bakery")));
+    }
+
+    #[test]
+    fn test_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let (alice, bob) = (dir.path().join("alice.db"), dir.path().join("bob.db"));
+        let remote = dir.path().join("shared.db");
+        let remote_arg = remote.to_str().unwrap();
+        let sync = |db: &std::path::Path| {
+            let out = run(db, &["sync", remote_arg]);
+            (out.status.success(), String::from_utf8_lossy(&out.stdout).to_string() +
+                String::from_utf8_lossy(&out.stderr).as_ref())
+        };
+        assert!(run(&alice, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+
+        // only the local side changed: push
+        let (ok, output) = sync(&alice);
+        assert!(ok && output.contains("Pushed revision 1"), "{output}");
+        // only the remote side changed: pull
+        let (ok, output) = sync(&bob);
+        assert!(ok && output.contains("Pulled revision 1 (+0 log entries)"), "{output}");
+        let (ok, output) = sync(&bob);
+        assert!(ok && output.contains("Already up to date"), "{output}");
+
+        assert!(run(&alice, &["split", "20", "-n", "rewe", "-f", "alice"]).status.success());
+        assert!(sync(&alice).0);
+        let (ok, output) = sync(&bob);
+        assert!(ok && output.contains("(+1 log entries)"), "{output}");
+        assert!(String::from_utf8_lossy(&run(&bob, &["stat", "flat"]).stdout).contains("bob: -10.00€"));
+
+        // both sides changed: the logs are merged
+        assert!(run(&alice, &["split", "4", "-n", "bakery", "-f", "alice"]).status.success());
+        assert!(sync(&alice).0);
+        assert!(run(&bob, &["pay", "10", "-f", "bob", "-t", "alice"]).status.success());
+        let (ok, output) = sync(&bob);
+        assert!(ok && output.contains("group flat: 1 new entries from the other copy, 1 only here"), "{output}");
+        let (ok, output) = sync(&alice);
+        assert!(ok && output.contains("Pulled"), "{output}");
+        for db in [&alice, &bob] {
+            let stat = String::from_utf8_lossy(&run(db, &["stat", "flat"]).stdout).to_string();
+            assert!(stat.contains("alice: 2.00€") && stat.contains("bob: -2.00€"), "{stat}");
+        }
+
+        // a chain may still be dropped, so it cannot sync
+        let before = fs::read(&remote).unwrap();
+        let out = run(&alice, &["split", "11", "-n", "again", "-f", "alice", "--and-then", "sync", remote_arg]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("sync cannot be chained"));
+        assert_eq!(fs::read(&remote).unwrap(), before);
+        assert!(!String::from_utf8_lossy(&run(&alice, &["list", "flat"]).stdout).contains("again"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ours, theirs) = (dir.path().join("ours.db"), dir.path().join("theirs.db"));
+        assert!(run(&ours, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        fs::copy(&ours, &theirs).unwrap();
+        assert!(run(&ours, &["split", "20", "-n", "rewe", "-f", "alice"]).status.success());
+        assert!(run(&theirs, &["split", "8", "-n", "bakery", "-f", "bob",
+            "--and-then", "create", "trip", "-a", "carol"]).status.success());
+        let out = run(&ours, &["merge", theirs.to_str().unwrap()]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("new group trip") && stdout.contains("1 new entries from the other copy"), "{stdout}");
+        let stat = String::from_utf8_lossy(&run(&ours, &["stat", "flat"]).stdout).to_string();
+        assert!(stat.contains("alice: 6.00€") && stat.contains("bob: -6.00€"), "{stat}");
+    }
+
+    #[test]
+    fn test_porcelain() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("porcelain.yaml");
+        fs::copy("tests/golden/porcelain.yaml", &db).unwrap();
+        let stdout = |args: &[&str]| {
+            let out = run(&db, args);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8(out.stdout).unwrap()
+        };
+        assert_eq!(stdout(&["groups", "--porcelain"]), include_str!("golden/groups.txt"));
+        assert_eq!(stdout(&["stat", "trip", "--porcelain"]), include_str!("golden/stat.txt"));
+        assert_eq!(stdout(&["list", "trip", "--porcelain"]), include_str!("golden/list.txt"));
+        assert_eq!(stdout(&["balance", "trip", "--porcelain"]), include_str!("golden/balance.txt"));
+        // only suggested, like --json
+        assert_eq!(stdout(&["stat", "trip", "--porcelain"]), include_str!("golden/stat.txt"));
+        assert!(!run(&db, &["list", "--porcelain", "--json"]).status.success());
+
+        let stat = stdout(&["stat", "trip", "--threshold", "5"]);
+        assert!(stat.contains("carol: -14.00€\n1 member within ±5.00€"), "{stat}");
+        assert!(stdout(&["stat", "trip", "--threshold", "5", "--show-all"]).contains("bob: 3.00€"));
+        let json: serde_json::Value = serde_json::from_str(&stdout(&["stat", "trip", "--threshold", "5", "--format", "json"])).unwrap();
+        assert_eq!(json["below_threshold"], serde_json::json!([["bob", 300]]));
+
+        let env = stdout(&["stat", "trip", "--format", "env"]);
+        assert!(env.starts_with("GROUP_NAME='trip'\nGROUP_CURRENCY=EUR\nGROUP_TOTAL_ENTRIES=3\n"), "{env}");
+        assert!(env.ends_with("BALANCE_ALICE=1100\nBALANCE_BOB=300\nBALANCE_CAROL=-1400\n"), "{env}");
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("ties.db");
+        assert!(run(&db, &["create", "flat", "-a", "dora", "-a", "bob", "-a", "carl", "-a", "al"]).status.success());
+        assert!(run(&db, &["split", "40", "-n", "food", "-f", "bob", "-f", "dora",
+                           "-t", "bob:10", "-t", "dora:10", "-t", "al:10", "-t", "carl:10"]).status.success());
+        // equal balances are matched up in name order, not in the order of the member map
+        for _ in 0..5 {
+            let out = run(&db, &["balance", "flat", "--porcelain"]);
+            assert_eq!(String::from_utf8_lossy(&out.stdout), include_str!("golden/balance-ties.txt"));
+        }
+        for _ in 0..5 {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .env("RUST_LIB_BACKTRACE", "0").arg("-d").arg(&db)
+                .args(["add", "-g", "flat", "carl", "bob", "x y", "al"]).output().unwrap();
+            assert!(!out.status.success());
+            assert_eq!(String::from_utf8_lossy(&out.stderr), include_str!("golden/add-duplicates.txt"));
+        }
+    }
+
+    #[test]
+    fn test_human_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("human.yaml");
+        fs::copy("tests/golden/porcelain.yaml", &db).unwrap();
+        let stdout = |args: &[&str]| {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .env("TZ", "UTC").arg("-d").arg(&db).args(args).output().unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8(out.stdout).unwrap()
+        };
+        assert_eq!(stdout(&["list", "trip"]), include_str!("golden/list-human.txt"));
+        assert_eq!(stdout(&["stat", "trip"]), include_str!("golden/stat-human.txt"));
+    }
+
+    #[test]
+    fn test_doctor() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("doctor.db");
+        let home = dir.path().join("home");
+        let doctor = |db: &std::path::Path| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home).arg("-d").arg(db).arg("doctor").output().unwrap();
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "food", "-f", "alice"]).status.success());
+        let out = doctor(&db);
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(out.status.success(), "{}", stdout);
+        assert!(stdout.contains("ok    config") && stdout.contains("is readable and writable")
+                && stdout.contains("1 group, 1 entry") && stdout.contains("ok    group flat  consistent"), "{}", stdout);
+        assert!(!home.exists());
+
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        fs::write(home.join(".config/splitter/config.yaml"), "me: [alice\n").unwrap();
+        let broken = dir.path().join("broken.db");
+        fs::write(&broken, b"this is no database").unwrap();
+        let out = doctor(&broken);
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(!out.status.success());
+        assert!(stdout.contains("fail  config") && stdout.contains("fail  load"), "{}", stdout);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("2 checks failed"));
+    }
+
+    #[test]
+    fn test_nag() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("nag.db");
+        let home = dir.path().join("home");
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        let config = |content: &str| fs::write(home.join(".config/splitter/config.yaml"), content).unwrap();
+        let stderr = |args: &[&str]| {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .env("HOME", &home).arg("-d").arg(&db).args(args).output().unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stderr).to_string()
+        };
+        stderr(&["create", "flat", "-a", "alice", "-a", "bob"]);
+        stderr(&["split", "30", "-n", "food", "-f", "alice"]);
+
+        // without nag_days nothing is checked
+        assert_eq!(stderr(&["stat", "flat"]), "");
+        config("nag_days: 0\n");
+        let notice = "group 'flat' has 15.00€ unsettled for 0 days — run `splitter balance flat`\n";
+        assert_eq!(stderr(&["stat", "flat"]), notice);
+        assert_eq!(stderr(&["--no-nag", "stat", "flat"]), "");
+        for machine in [&["stat", "flat", "--porcelain"][..], &["list", "flat", "--json"], &["stat", "flat", "--format", "env"],
+                        &["balance", "flat", "--json"], &["stat", "flat", "--and-then", "groups", "--porcelain"]] {
+            assert_eq!(stderr(machine), "", "{:?}", machine);
+        }
+        config("nag_days: 0\nnag_threshold: 15\n");
+        assert_eq!(stderr(&["stat", "flat"]), notice);
+        config("nag_days: 0\nnag_threshold: '15.01'\n");
+        assert_eq!(stderr(&["stat", "flat"]), "");
+    }
+
+    #[test]
+    fn test_stat_as_of() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("as-of.db");
+        let stdout = |args: &[&str]| {
+            let out = run(&db, args);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        stdout(&["create", "flat", "-a", "alice", "-a", "bob"]);
+        stdout(&["split", "30", "-n", "food", "-f", "alice"]);
+        stdout(&["pay", "5", "-f", "bob", "-t", "alice"]);
+
+        let now = stdout(&["stat", "flat", "--as-of", "2999-12-31"]);
+        let regular = stdout(&["stat", "flat"]);
+        assert!(now.contains("Historical balances as of 2999-12-31"), "{}", now);
+        assert_eq!(now.lines().filter(|line| !line.starts_with("Historical")).collect::<Vec<_>>(),
+                   regular.lines().collect::<Vec<_>>());
+        assert_eq!(stdout(&["stat", "flat", "--as-of", "2999-12-31", "--porcelain"]), stdout(&["stat", "flat", "--porcelain"]));
+
+        // before anything was recorded
+        assert_eq!(stdout(&["stat", "flat", "--as-of", "2000-01-01", "--porcelain"]), "flat\tEUR\talice\t0\nflat\tEUR\tbob\t0\n");
+        assert_eq!(stdout(&["stat", "flat", "--as-of", "2999-12-31", "--member", "bob", "--porcelain"]), "flat\tEUR\tbob\t-1000\n");
+        assert!(!run(&db, &["stat", "flat", "--member", "carl"]).status.success());
+        assert!(!run(&db, &["stat", "flat", "--as-of", "2024-02-30"]).status.success());
+    }
+
+    #[test]
+    fn test_stat_member_across_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("across.db");
+        let stdout = |args: &[&str]| {
+            let out = run(&db, args);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        stdout(&["create", "flat", "-a", "alice", "-a", "bob"]);
+        stdout(&["split", "30", "-n", "food", "-f", "alice"]);
+        stdout(&["create", "club", "-a", "carl"]);
+        stdout(&["create", "trip", "-a", "alice", "-a", "carl"]);
+        stdout(&["pay", "5", "-f", "carl", "-t", "alice", "-g", "trip"]);
+
+        // club does not know alice and is skipped
+        assert_eq!(stdout(&["stat", "--member", "alice"]), "Balances of alice in 2 groups:\n\
+                                                            Group  Balance\n\
+                                                            --------------\n\
+                                                            flat    15.00€\n\
+                                                            trip    -5.00€\n\
+                                                            Total: 10.00€\n");
+        assert_eq!(stdout(&["stat", "--member", "alice", "--porcelain"]), "flat\tEUR\talice\t1500\ntrip\tEUR\talice\t-500\n");
+        let json: serde_json::Value = serde_json::from_str(&stdout(&["stat", "--member", "alice", "--format", "json"])).unwrap();
+        assert_eq!(json["totals"]["EUR"], 1000);
+        // naming a group still shows just that one
+        assert_eq!(stdout(&["stat", "trip", "--member", "alice", "--porcelain"]), "trip\tEUR\talice\t-500\n");
+
+        let out = run(&db, &["stat", "--member", "zoe"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("zoe is not a member of any group"));
+    }
+
+    #[test]
+    fn test_opening_balances() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("opening.db");
+        let csv = dir.path().join("balances.csv");
+        fs::write(&csv, "member,balance\nalice,30\nbob,-20.50\n").unwrap();
+        let csv = csv.to_str().unwrap();
+
+        let out = run(&db, &["create", "flat", "--opening-balances", csv]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("add up to 9.50€ instead of zero"));
+        assert!(!db.exists());
+
+        let out = run(&db, &["create", "flat", "-a", "carl", "--opening-balances", csv, "--balance-with", "carl"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&db, &["stat", "flat", "--porcelain"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "flat\tEUR\talice\t3000\nflat\tEUR\tbob\t-2050\nflat\tEUR\tcarl\t-950\n");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat"]).stdout).to_string();
+        assert_eq!(list.matches("balances.csv").count(), 3, "{}", list);
+        assert!(!run(&db, &["undo", "flat"]).status.success());
+    }
+
+    #[test]
+    fn test_confirm_above() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("confirm.db");
+        let stdout = |args: &[&str]| {
+            let out = run(&db, args);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        stdout(&["create", "flat", "-a", "alice", "-a", "bob"]);
+        assert!(stdout(&["group-config", "flat"]).starts_with("confirm_above: none\n"));
+        assert!(stdout(&["group-config", "flat", "--confirm-above", "100"]).starts_with("confirm_above: 100.00€\n"));
+
+        // just below and at the threshold nothing is asked
+        stdout(&["split", "100", "-n", "rent", "-f", "alice"]);
+        stdout(&["pay", "100", "-f", "bob", "-t", "alice"]);
+        // just above fails without a terminal to ask on
+        for args in [&["split", "100.01", "-n", "typo", "-f", "alice"][..], &["pay", "100.01", "-f", "bob", "-t", "alice"]] {
+            let out = run(&db, args);
+            assert!(!out.status.success());
+            assert!(String::from_utf8_lossy(&out.stderr)
+                .contains("100.01€ is above the confirmation threshold of 100.00€ of group flat, pass --yes to record it"));
+        }
+        stdout(&["split", "1200", "-n", "tv", "-f", "alice", "--yes"]);
+        stdout(&["pay", "1200", "-f", "bob", "-t", "alice", "-y"]);
+        assert_eq!(stdout(&["list", "flat"]).lines().filter(|line| line.contains("split") || line.contains("pay")).count(), 4);
+
+        assert!(stdout(&["group-config", "flat", "--no-confirm-above"]).starts_with("confirm_above: none\n"));
+        stdout(&["pay", "5000", "-f", "bob", "-t", "alice"]);
+    }
+
+    #[test]
+    fn test_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("info.db");
+        let info = || {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .env("HOME", dir.path().join("home")).arg("-d").arg(&db).args(["info", "--json"]).output().unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()
+        };
+        let json = info();
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["database"].is_null());
+        assert!(!db.exists());
+        assert_eq!(json["currencies"], serde_json::json!(["EUR", "USD", "JPY", "GBP"]));
+        let subcommands = json["subcommands"].as_array().unwrap();
+        assert!(["split", "pay", "export-ledger", "info"].iter().all(|name| subcommands.contains(&serde_json::json!(name))));
+
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        let json = info();
+        assert_eq!(json["database"]["format"], "compressed");
+        assert_eq!(json["database"]["version"], json["storage_version"]);
+        assert_eq!(json["database"]["revision"], 1);
+    }
+
+    #[test]
+    fn test_group_name_collision() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("names.db");
+        let home = dir.path().join("home");
+        let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home).arg("-d").arg(&db).args(args).output().unwrap();
+        assert!(run(&["create", "Trip", "-a", "alice"]).status.success());
+        let out = run(&["create", "trip", "-a", "alice"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Group 'Trip' already exists"));
+
+        assert!(run(&["carry", "Trip", "trip-2", "--yes"]).status.success());
+        let out = run(&["create", "TRIP", "-a", "alice"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("An archived group of this name exists: 'Trip'"));
+        let out = run(&["carry", "trip-2", "trip", "--yes"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("An archived group of this name exists: 'Trip'"));
+
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        fs::write(home.join(".config/splitter/config.yaml"), "case_sensitive_group_names: true\n").unwrap();
+        assert!(run(&["create", "trip", "-a", "alice"]).status.success());
+        assert!(!run(&["create", "Trip", "-a", "alice"]).status.success());
+    }
+
+    #[test]
+    fn test_amount_ceiling() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("huge.db");
+        let home = dir.path().join("home");
+        let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home).arg("-d").arg(&db).args(args).output().unwrap();
+        let stderr = |out: std::process::Output| String::from_utf8_lossy(&out.stderr).to_string();
+        assert!(run(&["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&["split", "1000000000", "-n", "castle", "-f", "alice"]).status.success());
+        let out = run(&["split", "1000000000.01", "-n", "castle", "-f", "alice"]);
+        assert!(stderr(out).contains("1000000000.01 is above the ceiling of 1000000000.00 for amounts, pass --allow-huge"));
+        let out = run(&["pay", "1e12", "-f", "alice", "-t", "bob"]);
+        assert!(!out.status.success());
+        assert!(run(&["--allow-huge", "split", "1000000000.01", "-n", "castle", "-f", "alice"]).status.success());
+        let out = run(&["--allow-huge", "pay", "92233720368547758.08", "-f", "alice", "-t", "bob"]);
+        assert!(stderr(out).contains("amount does not fit into 64 bits"));
+
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        fs::write(home.join(".config/splitter/config.yaml"), "amount_ceiling: 100\n").unwrap();
+        assert!(run(&["split", "100", "-n", "dinner", "-f", "alice"]).status.success());
+        assert!(stderr(run(&["split", "100.01", "-n", "dinner", "-f", "alice"])).contains("ceiling of 100.00"));
+        let out = run(&["compute", "100", "--members", "2", "-f", "p1:150"]);
+        assert!(stderr(out).contains("Invalid target 'p1:150' at byte 3: 150.00 is above the ceiling of 100.00"));
+        assert!(run(&["--allow-huge", "compute", "500", "--members", "2"]).status.success());
+    }
+
+    #[test]
+    fn test_compute() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("compute.db");
+        let out = run(&db, &["compute", "30", "--members", "alice,bob,carl", "-f", "alice"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.lines().any(|line| line.split_whitespace().eq(["alice", "30.00€", "10.00€", "20.00€"])), "{}", stdout);
+
+        let out = run(&db, &["compute", "137.40", "--members", "5", "--to", "bob:10%", "--json"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        let members = json["members"].as_array().unwrap();
+        assert_eq!(members.len(), 5);
+        assert_eq!(members[0]["name"], "bob");
+        assert_eq!(members[0]["share"], 1374);
+        assert_eq!(members.iter().map(|row| row["share"].as_i64().unwrap()).sum::<i64>(), 13740);
+        assert!(!db.exists());
+
+        assert!(!run(&db, &["compute", "10", "--members", "1", "-f", "a", "-t", "b"]).status.success());
+
+        // yen have no minor unit
+        let out = run(&db, &["compute", "3000", "--members", "alice,bob,carl", "-f", "alice", "--currency", "JPY"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.lines().any(|line| line.split_whitespace().eq(["alice", "3000¥", "1000¥", "2000¥"])), "{}", stdout);
+        let out = run(&db, &["compute", "30.50", "--members", "2", "--currency", "JPY"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("amounts in JPY can have no decimals (got 30.50)"));
+    }
+
+    /// runs the interactive mode on `db` with `input` typed in
+    fn session(db: &std::path::Path, input: &str) -> std::process::Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .arg("-d")
+            .arg(db)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn test_interactive() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("repl.db");
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["create", "trip", "-a", "carl", "-a", "dora"]).status.success());
+        let out = session(&db, "use fl\nsplit 30 -f alice -n 'pizza night'\nsplit 10 -f nobody -n x\nquit\nmembers\n");
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.lines().nth(1).unwrap().starts_with("splitter(trip|2 members|Σ0)> Using group flat"), "{}", stdout);
+        assert!(stdout.contains("splitter(flat|2 members|Σ15.00€)> "), "{}", stdout);
+        // a failing command does not end the session, quit does
+        assert!(String::from_utf8_lossy(&out.stderr).contains("nobody"));
+        assert!(!stdout.contains("alice  "), "{}", stdout);
+
+        // the group picked stays the default
+        let out = run(&db, &["members"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("15.00€"));
+        let out = run(&db, &["use"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("→  flat"));
+        assert!(!run(&db, &["use", "beach"]).status.success());
+
+        // parse errors and help do not end the session, the end of input saves like quit
+        let out = session(&db, "spilt 10\nhelp\nhelp pay\npay 5 -f bob -t alice\nsave\npay 5 -f bob -t alice\n");
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("unrecognized subcommand 'spilt'"));
+        assert!(stdout.contains("Session commands:") && stdout.contains("Usage: splitter pay"), "{}", stdout);
+        assert!(stdout.contains("Saved\n"));
+        let out = run(&db, &["members"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("5.00€"), "{}", String::from_utf8_lossy(&out.stdout));
+    }
+
+    #[test]
+    fn test_fix_residual() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("residual.yaml");
+        let yaml = fs::read_to_string("tests/golden/porcelain.yaml").unwrap();
+        fs::write(&db, yaml.replacen("alice: 1100", "alice: 1101", 1)).unwrap();
+
+        let out = run(&db, &["members", "trip"]);
+        assert!(out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("the balances of group trip add up to 0.01€ instead of zero"), "{}", stderr);
+        // only warned about, never corrected on its own
+        assert_eq!(fs::read_to_string(&db).unwrap().matches("alice: 1101").count(), 1);
+
+        assert!(!run(&db, &["fix-residual", "trip"]).status.success());
+        let out = run(&db, &["fix-residual", "trip", "--assign", "carol"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&db, &["members", "trip"]);
+        assert!(out.stderr.is_empty(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(String::from_utf8_lossy(&out.stdout).contains("-14.01€"));
+        assert!(!run(&db, &["fix-residual", "trip", "--spread"]).status.success());
+    }
+
+    #[test]
+    fn test_default_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("default.db");
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["create", "trip", "-a", "carl", "-a", "dora"]).status.success());
+        let current = |db: &std::path::Path| {
+            let out = run(db, &["groups", "--porcelain"]);
+            String::from_utf8_lossy(&out.stdout).lines()
+                .find(|line| line.ends_with("\t1"))
+                .map(|line| line.split('\t').next().unwrap().to_string())
+        };
+        assert_eq!(current(&db).as_deref(), Some("trip"));
+
+        // looking at another group keeps the default
+        assert!(run(&db, &["stat", "flat"]).status.success());
+        let out = run(&db, &["stat"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("carl"), "{}", String::from_utf8_lossy(&out.stdout));
+        assert!(run(&db, &["list", "flat"]).status.success());
+        assert_eq!(current(&db).as_deref(), Some("trip"));
+
+        // changing it switches, unless asked not to
+        assert!(run(&db, &["--no-switch", "pay", "5", "-g", "flat", "-f", "alice", "-t", "bob"]).status.success());
+        assert_eq!(current(&db).as_deref(), Some("trip"));
+        assert!(run(&db, &["pay", "5", "-g", "flat", "-f", "bob", "-t", "alice"]).status.success());
+        assert_eq!(current(&db).as_deref(), Some("flat"));
+        assert!(run(&db, &["use", "trip"]).status.success());
+        assert_eq!(current(&db).as_deref(), Some("trip"));
+
+        // deleting another group keeps the default, deleting the default leaves none selected
+        assert!(run(&db, &["create", "beach", "-a", "erin"]).status.success());
+        assert!(run(&db, &["use", "trip"]).status.success());
+        assert!(run(&db, &["delete-group", "flat", "-y"]).status.success());
+        assert_eq!(current(&db).as_deref(), Some("trip"));
+        assert!(run(&db, &["delete-group", "trip", "-y"]).status.success());
+        // a single group needs no selection, several do
+        assert_eq!(current(&db).as_deref(), Some("beach"));
+        assert!(String::from_utf8_lossy(&run(&db, &["stat"]).stdout).contains("erin"));
+        assert!(run(&db, &["--no-switch", "create", "camp", "-a", "gina"]).status.success());
+        assert_eq!(current(&db), None);
+        let out = run(&db, &["stat"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("No default group is selected"),
+                "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(run(&db, &["use", "camp"]).status.success());
+        assert!(String::from_utf8_lossy(&run(&db, &["stat"]).stdout).contains("gina"));
+    }
+
+    #[test]
+    fn test_rename_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("rename.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "fuel", "-f", "alice"]).status.success());
+        assert!(run(&db, &["create", "flat", "-a", "carl"]).status.success());
+        assert!(run(&db, &["use", "trip"]).status.success());
+
+        let out = run(&db, &["rename-group", "Flat", "x"]);
+        assert!(!out.status.success());
+        let out = run(&db, &["rename-group", "trip", "FLAT"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("already exists"), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&db, &["rename-group", "trip", "summer-2024"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "Renamed group trip to summer-2024\n");
+
+        let out = run(&db, &["list"]);
+        let list = String::from_utf8_lossy(&out.stdout);
+        assert!(list.contains("in group summer-2024") && !list.contains("trip"), "{}", list);
+        let out = run(&db, &["groups", "--porcelain"]);
+        assert!(String::from_utf8_lossy(&out.stdout).lines().any(|line| line.starts_with("summer-2024\t") && line.ends_with("\t1")));
+    }
+
+    #[test]
+    fn test_rename_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("rename_member.db");
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bbo"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "rent", "-f", "bbo"]).status.success());
+        assert!(!run(&db, &["rename-member", "bob", "bob2"]).status.success());
+        let out = run(&db, &["rename-member", "bbo", "bob"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "Renamed bbo to bob in group flat\n");
+
+        // undoing the split from before the rename takes the money back from bob
+        assert!(run(&db, &["undo"]).status.success());
+        let out = run(&db, &["members", "--json"]);
+        let members = String::from_utf8_lossy(&out.stdout);
+        assert!(members.contains("\"bob\"") && !members.contains("bbo"), "{}", members);
+        assert!(!members.contains("1500") && !members.contains("15.00"), "{}", members);
+        assert!(run(&db, &["pay", "1", "-f", "bob", "-t", "alice"]).status.success());
+    }
+
+    #[test]
+    fn test_database_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let stderr = |out: &std::process::Output| String::from_utf8_lossy(&out.stderr).to_string();
+
+        let db = dir.path().join("missing/nested/trip.db");
+        let out = run(&db, &["create", "trip", "-a", "alice"]);
+        assert!(!out.status.success());
+        assert!(stderr(&out).contains("does not exist, create it or pass --create-dirs"), "{}", stderr(&out));
+        assert!(!dir.path().join("missing").exists());
+        assert!(run(&db, &["--create-dirs", "create", "trip", "-a", "alice"]).status.success());
+        assert!(db.is_file());
+
+        let out = run(dir.path(), &["groups"]);
+        assert!(stderr(&out).contains("is a directory, point --database to a file in it"), "{}", stderr(&out));
+
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "").unwrap();
+        let out = run(&file.join("trip.db"), &["--create-dirs", "groups"]);
+        assert!(!out.status.success());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "");
+
+        // as root the directory stays writable, then there is nothing to check
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let locked = dir.path().join("locked");
+            fs::create_dir(&locked).unwrap();
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+            if fs::write(locked.join("probe"), "").is_err() {
+                let db = locked.join("trip.db");
+                let out = run(&db, &["create", "trip", "-a", "alice"]);
+                assert!(stderr(&out).contains("is not writable, changes could not be saved"), "{}", stderr(&out));
+                assert!(run(&db, &["groups"]).status.success(), "looking needs no write access");
+                assert_eq!(fs::read_dir(&locked).unwrap().count(), 0);
+            }
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remote_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let shared = dir.path().join("shared.db");
+        let database = format!("cmd:cat '{}' 2>/dev/null || true", shared.display());
+        let push = format!("cat > '{}'", shared.display());
+        let splitter = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home)
+            .arg("-d").arg(&database)
+            .args(args)
+            .output()
+            .unwrap();
+
+        // without a way to write it back the changes are kept next to the local copy
+        let out = splitter(&["create", "trip", "-a", "alice", "-a", "bob"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("--push-command") && stderr.contains("merge"), "{}", stderr);
+        assert!(!shared.exists());
+
+        assert!(splitter(&["--push-command", &push, "create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(splitter(&["--push-command", &push, "split", "30", "-f", "alice", "-n", "fuel"]).status.success());
+        assert!(shared.is_file());
+        let out = run(&shared, &["list"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("fuel"));
+
+        // the local copies are named after the remote database and reads need no push command
+        let mut copies = fs::read_dir(home.join(".config/splitter/remote")).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().split_once('-').unwrap().1.to_string())
+            .collect::<Vec<_>>();
+        copies.sort();
+        assert_eq!(copies.len(), 3, "{:?}", copies);
+        assert_eq!(copies[0], "shared.db");
+        assert!(copies[2].starts_with("shared.unsaved-") && copies[2].ends_with(".db"), "{:?}", copies);
+        let out = splitter(&["members", "trip"]);
+        assert!(out.status.success());
+        assert!(String::from_utf8_lossy(&out.stdout).contains("bob"));
+
+        // completion reads only the local copy, without fetching or creating anything
+        assert!(run(&shared, &["create", "flat", "-a", "carol"]).status.success());
+        let out = splitter(&["__complete", "groups"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "trip\n");
+        let elsewhere = dir.path().join("elsewhere");
+        let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &elsewhere)
+            .args(["-d", &database, "__complete", "groups"])
+            .output()
+            .unwrap();
+        assert!(out.status.success() && out.stdout.is_empty());
+        assert!(!elsewhere.exists());
+    }
+
+    #[test]
+    fn test_inspect() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("trip.yaml");
+        let inspect = |file: &std::path::Path| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .arg("inspect").arg(file)
+            .output()
+            .unwrap();
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["verify", "trip", "--enable"]).status.success());
+        assert!(run(&db, &["split", "30", "-f", "alice", "-n", "fuel"]).status.success());
+        let out = inspect(&db);
+        assert!(out.status.success());
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("format: plain yaml\n"), "{}", stdout);
+        assert!(stdout.contains("group trip: 1 entries, hash chain intact"), "{}", stdout);
+
+        let yaml = fs::read_to_string(&db).unwrap();
+        fs::write(&db, yaml.replacen("amount: 3000", "amount: 300", 1)).unwrap();
+        let out = inspect(&db);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stdout).contains("hash chain broken at entry [0]"));
+        assert!(String::from_utf8_lossy(&out.stderr).contains("1 hash chain is broken"));
+
+        let other = dir.path().join("notes.txt");
+        fs::write(&other, "shopping list\n").unwrap();
+        let out = Command::new(env!("CARGO_BIN_EXE_splitter")).args(["inspect", "--json"]).arg(&other).output().unwrap();
+        assert!(!out.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(json["kind"], "unknown");
+        assert!(json["head"].as_str().unwrap().contains("|shopping list.|"), "{}", json);
+    }
+
+    #[test]
+    fn test_feed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("feed.db");
+        for group in ["flat", "trip", "club"] {
+            assert!(run(&db, &["create", group, "-a", "alice", "-a", "bob"]).status.success());
+        }
+        for (group, name) in [("trip", "fuel"), ("flat", "rent"), ("club", "balls"), ("trip", "hotel"), ("flat", "power")] {
+            assert!(run(&db, &["split", "10", "-g", group, "-f", "alice", "-n", name]).status.success());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let out = run(&db, &["feed", "--limit", "4"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let order: Vec<&str> = stdout.lines().filter_map(|line| line.split_once(": [")).map(|(group, _)| group).collect();
+        assert_eq!(order, vec!["flat", "trip", "club", "flat"], "{}", stdout);
+        assert!(stdout.lines().next().unwrap().starts_with("flat: [1] "), "{}", stdout);
+
+        let out = run(&db, &["feed", "--json"]);
+        let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        let names: Vec<&str> = json.as_array().unwrap().iter()
+            .map(|item| item["entry"]["command"]["Split"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["power", "hotel", "balls", "rent", "fuel"]);
+        assert_eq!(json[1]["group"], "trip");
+    }
+
+    #[test]
+    fn test_export_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("trip.db");
+        let other = dir.path().join("other.db");
+        let export = dir.path().join("trip.json");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-f", "alice", "-n", "fuel"]).status.success());
+        let out = run(&db, &["export", "trip", export.to_str().unwrap()]);
+        assert!(out.status.success());
+        let out = run(&db, &["export", "trip", "-"]);
+        assert_eq!(fs::read(&export).unwrap(), out.stdout);
+        let out = run(&db, &["export", "nope", "-"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Group 'nope' not found"));
+
+        assert!(run(&other, &["import", export.to_str().unwrap()]).status.success());
+        let out = run(&other, &["import", export.to_str().unwrap()]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("--rename"));
+        assert!(run(&other, &["import", export.to_str().unwrap(), "--rename", "copy"]).status.success());
+        let stat = String::from_utf8_lossy(&run(&other, &["stat", "copy"]).stdout).to_string();
+        assert!(stat.contains("15.00"), "{}", stat);
+        let list = String::from_utf8_lossy(&run(&other, &["list", "copy"]).stdout).to_string();
+        assert!(list.contains("split: in group copy `fuel 30.00€"), "{}", list);
+    }
+
+    #[test]
+    fn test_global_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("json.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-f", "alice", "-n", "fuel"]).status.success());
+        let json = |args: &[&str]| {
+            let out = run(&db, &[&["--json"], args].concat());
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()
+        };
+        assert_eq!(json(&["balance", "trip"])[0]["amount_minor"], 15_00);
+        assert_eq!(json(&["stat", "trip"])["name"], "trip");
+        assert_eq!(json(&["list", "trip", "--porcelain"])["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(json(&["groups"])[0]["name"], "trip");
+        // without --yes nothing is asked and nothing applied
+        assert_eq!(json(&["balance", "trip"]).as_array().unwrap().len(), 1);
+
+        let out = run(&db, &["--json", "pay", "5", "-f", "bob", "-t", "alice"]);
+        assert!(!out.status.success());
+        assert!(out.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("other commands print no JSON"));
+    }
+
+    #[test]
+    fn test_batch_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("batch.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "egon"]).status.success());
+        assert!(run(&db, &["create", "club", "-a", "bob"]).status.success());
+        let members = |group: &str| String::from_utf8_lossy(&run(&db, &["members", group]).stdout).to_string();
+
+        // egon is in flat already, the other groups get him anyway
+        let out = run(&db, &["add", "--member", "egon", "--groups", "trip,flat,club,beach"]);
+        assert!(!out.status.success());
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let results: Vec<Vec<&str>> = stdout.lines().skip(2).map(|line| line.split_whitespace().take(2).collect()).collect();
+        assert_eq!(results, vec![vec!["trip", "ok"], vec!["flat", "failed:"], vec!["club", "ok"], vec!["beach", "failed:"]]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("add failed in 2 of 4 groups"));
+        assert!(members("trip").contains("egon") && members("club").contains("egon"));
+
+        // with --strict a failure changes nothing
+        let out = run(&db, &["add", "--member", "fred", "--groups", "trip,beach", "--strict"]);
+        assert!(!out.status.success());
+        assert!(!run(&db, &["add", "--member", "fred", "--all", "--strict", "--member", "egon"]).status.success());
+        assert!(!members("trip").contains("fred"));
+
+        assert!(run(&db, &["pay", "5", "-g", "club", "-f", "bob", "-t", "egon"]).status.success());
+        let out = run(&db, &["remove", "egon", "--all"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stdout).lines()
+            .any(|line| line.split_whitespace().take(2).eq(["club", "failed:"])));
+        assert!(!members("trip").contains("egon") && !members("flat").contains("egon"));
+        assert!(members("club").contains("egon"));
+    }
+
+    #[test]
+    fn test_balance_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("settle.db");
+        let ok = |args: &[&str]| {
+            let out = run(&db, args);
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        ok(&["create", "six", "-a", "A", "-a", "B", "-a", "C", "-a", "D", "-a", "E", "-a", "F"]);
+        // A and B are owed 5 each, by C and E and by D and F
+        for (amount, from, to) in [("3", "A", "C"), ("2", "A", "E"), ("3", "B", "D"), ("2", "B", "F")] {
+            ok(&["pay", amount, "-f", from, "-t", to]);
+        }
+        let transfers = |extra: &[&str]| ok(&[&["balance", "six", "--porcelain"], extra].concat()).lines().count();
+        assert_eq!(transfers(&[]), 5);
+        assert_eq!(transfers(&["--algorithm", "greedy"]), 5);
+        assert_eq!(transfers(&["--algorithm", "min-transfers"]), 4);
+        assert_eq!(transfers(&["--algorithm", "pairwise"]), 5);
+        let pairwise = ok(&["balance", "six", "--porcelain", "--algorithm", "pairwise", "--banker", "C"]);
+        assert!(pairwise.lines().all(|line| line.split('\t').take(2).any(|member| member == "C")), "{}", pairwise);
+        assert!(!run(&db, &["balance", "six", "--algorithm", "pairwise", "--banker", "Z"]).status.success());
+
+        // the group default is used unless --algorithm is given
+        assert!(ok(&["group-config", "six", "--algorithm", "min-transfers"]).contains("algorithm: min-transfers"));
+        assert_eq!(transfers(&[]), 4);
+        assert_eq!(transfers(&["--algorithm", "greedy"]), 5);
+        assert!(!run(&db, &["group-config", "six", "--banker", "Z"]).status.success());
+
+        // the payments recorded name the algorithm
+        ok(&["balance", "six", "--porcelain", "--yes"]);
+        let list = ok(&["list", "six"]);
+        assert_eq!(list.matches("settling min-transfers").count(), 4, "{}", list);
+        assert!(ok(&["stat", "six", "--porcelain"]).lines().all(|line| line.ends_with("\t0")));
+        assert_eq!(transfers(&[]), 0);
+    }
+
+    #[test]
+    fn test_shorthands() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("short.db");
+        let home = dir.path().join("home");
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home).arg("-d").arg(&db).args(args).output().unwrap();
+
+        // without groups there is no default group to enter into
+        let out = run(&["--author", "alice", "s", "10", "x"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("pick one with `splitter use <group>`"));
+
+        assert!(run(&["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        let out = run(&["s", "23.40", "dinner"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("`splitter whoami`"));
+        assert!(String::from_utf8_lossy(&run(&["whoami"]).stdout).starts_with("Nobody yet"));
+
+        fs::write(home.join(".config/splitter/config.yaml"), "me: alice\n").unwrap();
+        assert_eq!(String::from_utf8_lossy(&run(&["whoami"]).stdout), "alice\n");
+        let out = run(&["s", "23.40", "pizza", "night"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&["p", "5", "bob"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(!run(&["p", "5", "carl"]).status.success());
+
+        let list = String::from_utf8_lossy(&run(&["list", "flat", "--porcelain"]).stdout).to_string();
+        let rows: Vec<Vec<&str>> = list.lines().map(|line| line.split('\t').collect()).collect();
+        assert_eq!(rows.len(), 2, "{}", list);
+        assert_eq!((rows[0][3], rows[0][4], rows[0][5], rows[0][6], rows[0][8]), ("alice", "split", "2340", "pizza night", "alice"));
+        assert_eq!((rows[1][4], rows[1][5], rows[1][8], rows[1][9]), ("pay", "500", "alice", "bob"));
+        let members = String::from_utf8_lossy(&run(&["members"]).stdout).to_string();
+        assert!(members.contains("16.70€") && members.contains("-16.70€"), "{}", members);
+    }
+
+    #[test]
+    fn test_date_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("dates.yaml");
+        fs::copy("tests/golden/porcelain.yaml", &db).unwrap();
+        let home = dir.path().join("home");
+        let stdout = |args: &[&str]| {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .env("TZ", "UTC").env("HOME", &home).arg("-d").arg(&db).args(args).output().unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8(out.stdout).unwrap()
+        };
+        let list = stdout(&["--date-format", "%d.%m.%Y", "list", "trip"]);
+        assert!(list.contains("[0] 01.06.2024 by alice") && list.contains("from 01.06.2024 to 04.06.2024"), "{list}");
+        // entries older than 30 days keep the usual format
+        let list = stdout(&["--date-format", "relative", "list", "trip"]);
+        assert!(list.contains("[0] 2024-06-01 12:00 by alice"), "{list}");
+        assert!(stdout(&["--date-format", "relative", "pay", "1", "-f", "bob", "-t", "alice", "-g", "trip",
+                         "--and-then", "list", "trip"]).contains("[3] just now by"));
+        // machine readable output ignores it
+        let porcelain = stdout(&["--date-format", "%d.%m.%Y", "list", "trip", "--porcelain"]);
+        assert!(porcelain.contains("2024-06-01T12:00:00Z"), "{porcelain}");
+
+        fs::create_dir_all(home.join(".config/splitter")).unwrap();
+        fs::write(home.join(".config/splitter/config.yaml"), "date_format: '%Y/%m/%d'\n").unwrap();
+        assert!(stdout(&["top", "trip"]).contains("2024/06/01"));
+        assert!(stdout(&["--date-format", "%d.%m.%Y", "top", "trip"]).contains("01.06.2024"));
+        fs::write(home.join(".config/splitter/config.yaml"), "date_format: ''\n").unwrap();
+        let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+            .env("HOME", &home).arg("-d").arg(&db).args(["list", "trip"]).output().unwrap();
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Invalid date_format in the config file"));
+        assert!(!run(&db, &["--date-format", "%Q", "list", "trip"]).status.success());
+    }
+
+    #[test]
+    fn test_exchange_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("rate.db");
+        assert!(run(&db, &["create", "trip", "-a", "anna", "-a", "bert"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "dinner", "-f", "anna"]).status.success());
+        let out = run(&db, &["stat", "trip", "--in", "usd"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("No exchange rate from EUR to USD"));
+
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip", "--in", "USD", "--rate", "2"]).stdout).to_string();
+        assert!(stat.contains("anna: 30.00$") && stat.contains("approximate"), "{stat}");
+        assert!(run(&db, &["exchange-rate", "USD", "1.1", "-g", "trip"]).status.success());
+        let out = run(&db, &["balance", "trip", "--in", "USD", "--json"]);
+        let suggestions: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(suggestions[0]["amount_minor"], 16_50);
+        // the balances themselves stay in euros
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip"]).stdout).to_string();
+        assert!(stat.contains("anna: 15.00€"), "{stat}");
+    }
+
+    #[test]
+    fn test_target_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("files.db");
+        let shares = dir.path().join("shares.txt");
+        fs::write(&shares, "# who paid\nanna:20\nbert:rest\n").unwrap();
+        assert!(run(&db, &["create", "trip", "-a", "anna", "-a", "bert", "-a", "carl"]).status.success());
+        let out = run(&db, &["split", "60", "-n", "hut", "--from-file", shares.to_str().unwrap()]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip"]).stdout).to_string();
+        assert!(stat.contains("anna: 0.00€") && stat.contains("bert: 20.00€"), "{stat}");
+
+        fs::write(&shares, "carl:5\ncarl:\n").unwrap();
+        let out = run(&db, &["split", "60", "-n", "hut", "-f", "anna", "--to-file", shares.to_str().unwrap()]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("shares.txt:2"));
+    }
+
+    #[test]
+    fn test_ratio() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("ratio.db");
+        assert!(run(&db, &["create", "flat", "-a", "anna", "-a", "bert", "-a", "cleo"]).status.success());
+        assert!(run(&db, &["ratio", "set", "flat", "income", "anna=3", "bert=2"]).status.success());
+        let out = run(&db, &["split", "900", "-n", "rent", "--by-ratio", "income", "-f", "anna", "--strict"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("cleo missing from ratio income"));
+        let out = run(&db, &["split", "900", "-n", "rent", "--by-ratio", "income", "-f", "anna"]);
+        assert!(out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Warning: cleo missing from ratio income"));
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "flat"]).stdout).to_string();
+        assert!(stat.contains("anna: 360.00€") && stat.contains("bert: -360.00€") && stat.contains("cleo: 0.00€"),
+                "{stat}");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat"]).stdout).to_string();
+        assert!(list.contains("split by ratio income"), "{list}");
+        let show = String::from_utf8_lossy(&run(&db, &["ratio", "show", "flat"]).stdout).to_string();
+        assert_eq!(show, "income: anna=3 bert=2\n");
+    }
+
+    #[test]
+    fn test_close_month() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("months.yaml");
+        fs::copy("tests/golden/porcelain.yaml", &db).unwrap();
+        let out = run(&db, &["close-month", "trip", "--month", "2024-06", "--settle"]);
+        let report = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(report.contains("2 expenses, 42.00€ spent in total") && report.contains("alice: paid 30.00€, consumed 14.00€")
+                    && report.contains("carol  alice  11.00€"), "{report}");
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip", "--porcelain"]).stdout).to_string();
+        assert!(stat.lines().all(|line| line.ends_with("\t0")), "{stat}");
+        // the open period only holds the settlement
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip", "--period", "current"]).stdout).to_string();
+        assert!(stat.contains("carol: 14.00€") && stat.contains("2 log entries"), "{stat}");
+        let report = String::from_utf8_lossy(&run(&db, &["report", "trip"]).stdout).to_string();
+        assert!(report.contains("the open period since 2024-07-01") && report.contains("0 expenses"), "{report}");
+
+        let out = run(&db, &["close-month", "trip", "--month", "2024-06"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("already closed"));
+    }
+
+    #[test]
+    fn test_chart() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("chart.yaml");
+        fs::copy("tests/golden/porcelain.yaml", &db).unwrap();
+        let chart = |by: &str| {
+            let mut command = Command::new(env!("CARGO_BIN_EXE_splitter"));
+            let out = command.env("COLUMNS", "60").args(["-d", db.to_str().unwrap(), "chart", "trip", "--by", by])
+                .output().unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8(out.stdout).unwrap()
+        };
+        let payers = chart("payer");
+        assert!(payers.starts_with("Spending of group trip by payer\n"), "{payers}");
+        assert!(payers.lines().all(|line| line.chars().count() <= 60), "{payers}");
+        assert!(payers.contains("alice (1 split, 71%)"), "{payers}");
+        assert!(chart("month").contains("2024-06 (2 splits, 100%)"));
+        assert!(chart("category").contains("(none) (1 split, 29%)"));
+        let out = run(&db, &["chart", "flat"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "No expenses in group flat to chart yet\n");
+    }
+
+    #[test]
+    fn test_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("budget.db");
+        assert!(run(&db, &["create", "flat", "-a", "anna", "-a", "bert"]).status.success());
+        assert!(run(&db, &["budget", "set", "flat", "500", "--per", "month"]).status.success());
+        assert!(run(&db, &["budget", "set", "flat", "200", "-c", "food"]).status.success());
+        let out = run(&db, &["split", "150", "-n", "rewe", "-f", "anna", "-c", "food"]);
+        assert!(out.status.success());
+        assert!(!String::from_utf8_lossy(&out.stderr).contains("Warning"));
+        let out = run(&db, &["split", "60", "-n", "market", "-f", "bert", "-c", "food"]);
+        assert!(out.status.success(), "budgets never block a split");
+        let stderr = String::from_utf8_lossy(&out.stderr).to_string();
+        assert!(stderr.contains("budget for food this month of group flat exceeded: 210.00€ of 200.00€ spent (105%)"),
+                "{stderr}");
+        let status = String::from_utf8_lossy(&run(&db, &["budget", "status", "flat"]).stdout).to_string();
+        assert!(status.contains("[########------------]  42% 210.00€ of 500.00€, budget this month"), "{status}");
+        assert!(status.contains("[####################] 105% 210.00€ of 200.00€, budget for food this month"), "{status}");
+    }
+
+    #[test]
+    fn test_carry() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("carry.db");
+        assert!(run(&db, &["create", "flat-2024", "-a", "anna", "-a", "bert", "-a", "cleo"]).status.success());
+        assert!(run(&db, &["split", "90", "-n", "rent", "-f", "anna"]).status.success());
+        assert!(run(&db, &["pay", "10", "-f", "bert", "-t", "anna"]).status.success());
+        let closing = String::from_utf8_lossy(&run(&db, &["stat", "flat-2024", "--porcelain"]).stdout).to_string();
+        let out = run(&db, &["carry", "flat-2024", "flat-2025", "--yes"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        // the new group is the default now
+        let opening = String::from_utf8_lossy(&run(&db, &["stat", "--porcelain"]).stdout).to_string();
+        assert_eq!(opening, closing.replace("flat-2024", "flat-2025"));
+        let groups = String::from_utf8_lossy(&run(&db, &["groups", "--all", "--porcelain"]).stdout).to_string();
+        assert!(groups.lines().any(|line| line.starts_with("flat-2024\t") && line.ends_with("\t1\t0")), "{groups}");
+        // the old log stays untouched
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat-2024"]).stdout).to_string();
+        assert!(list.contains("rent") && !list.contains("opening"), "{list}");
+        let out = run(&db, &["undo", "flat-2025"]);
+        assert!(!out.status.success());
+        assert!(run(&db, &["carry", "flat-2024", "flat-2025", "--yes"]).status.code() != Some(0));
+    }
+
+    #[test]
+    fn test_author() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("author.db");
+        assert!(run(&db, &["create", "flat", "-a", "anna", "-a", "bert"]).status.success());
+        assert!(run(&db, &["--author", "anna", "split", "30", "-n", "rewe", "-f", "anna"]).status.success());
+        assert!(run(&db, &["split", "8", "-n", "bakery", "-f", "bert"]).status.success());
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat"]).stdout).to_string();
+        assert!(list.contains("by anna: split") && list.contains("by (unknown): split"), "{list}");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat", "--author", "anna"]).stdout).to_string();
+        assert!(list.contains("rewe") && !list.contains("bakery"), "{list}");
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("club.yaml");
+        assert!(run(&db, &["create", "club", "-a", "alice", "-a", "bob", "--integrity"]).status.success());
+        assert!(run(&db, &["split", "20", "-n", "rent", "-f", "alice"]).status.success());
+        assert!(run(&db, &["pay", "10", "-f", "bob", "-t", "alice"]).status.success());
+        let out = run(&db, &["verify", "club"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("intact (2 entries)"));
+
+        let tampered = fs::read_to_string(&db).unwrap().replace("amount: 2000", "amount: 200");
+        fs::write(&db, tampered).unwrap();
+        let out = run(&db, &["verify", "club"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("broken at entry [0]"), "{stderr}");
+    }
+
     #[test]
     fn test_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("split.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+
         // split on non-existing group, should fail
+        let out = run(&db, &["split", "10", "-n", "beer", "-f", "alice", "-g", "nope"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("valid groups: trip"), "{stderr}");
+        assert!(stderr.contains("default group: 'trip'"), "{stderr}");
 
         // split from non-existing member, should fail
+        let out = run(&db, &["split", "10", "-n", "beer", "-f", "carol"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("carol not within group trip"), "{stderr}");
+        assert!(stderr.contains("valid members: alice, bob"), "{stderr}");
+
         // split to non-existing member, should fail
+        let out = run(&db, &["split", "10", "-n", "beer", "-f", "alice", "-t", "dave:5"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("valid members: alice, bob"));
         // split from an existing member
         // split to existing member
         // split from and to existing members
@@ -154,6 +1463,157 @@ mod integration {
         // split amount with wildcard on existing group with existing members. should work.
     }
 
+    #[test]
+    fn test_refund() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("refund.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol"]).status.success());
+        assert!(run(&db, &["split", "150", "-n", "deposit", "-f", "alice"]).status.success());
+
+        let out = run(&db, &["split", "--refund", "90", "-n", "deposit back", "-f", "alice"]);
+        assert!(!out.status.success());
+        let out = run(&db, &["split", "--refund", "90", "-n", "deposit back"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Name who got the money back with --to"));
+
+        let out = run(&db, &["split", "--refund", "90", "-n", "deposit back", "--to", "alice"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!((after["alice"], after["bob"], after["carol"]), (40_00, -20_00, -20_00));
+        let out = run(&db, &["list", "trip"]);
+        let listed = String::from_utf8_lossy(&out.stdout);
+        assert!(listed.contains("refund: in group trip `deposit back 90.00€ returned to"), "{listed}");
+        // the refund is undone like a split
+        assert!(run(&db, &["undo", "--force"]).status.success());
+        assert_eq!(balances(&db)["alice"], 10000);
+    }
+
+    #[test]
+    fn test_quote() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("quote.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "charly"]).status.success());
+        assert!(run(&db, &["ratio", "set", "trip", "income", "alice=3", "bob=2", "charly=1"]).status.success());
+        let before = fs::read(&db).unwrap();
+
+        let out = run(&db, &["quote", "trip", "180", "--among", "alice,bob", "--by-ratio", "income", "--json"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let quote: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        let shares: Vec<i64> = quote["members"].as_array().unwrap().iter().map(|m| m["share"].as_i64().unwrap()).collect();
+        assert_eq!(shares, vec![10800, 7200, 0]);
+        let out = run(&db, &["--no-pager", "quote", "trip", "10"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("charly  0.00€  3.33€  -3.33€"), "{stdout}");
+        assert!(!run(&db, &["quote", "trip", "10", "--among", "dave"]).status.success());
+        assert_eq!(fs::read(&db).unwrap(), before);
+    }
+
+    #[test]
+    fn test_split_weighted_shares() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("shares.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol"]).status.success());
+        // bob came as a couple, carol only had a coffee
+        let out = run(&db, &["split", "100", "-f", "alice", "-t", "bob:2x", "-t", "carol:4", "-n", "hotel"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!((after["alice"], after["bob"], after["carol"]), (6800, -6400, -400));
+        let listed = String::from_utf8_lossy(&run(&db, &["list", "trip"]).stdout).to_string();
+        assert!(listed.contains("bob: 2x (=64.00€)"), "{listed}");
+        assert!(!run(&db, &["split", "10", "-f", "alice:2x", "-n", "hotel"]).status.success());
+    }
+
+    #[test]
+    fn test_pasted_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("pasted.db");
+        let out = run(&db, &["create", "trip", "-a", "alice", "-a", "carol", "-a", "carol\u{a0}"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("given more than once: carol (given as \"carol\\u{a0}\")"), "{stderr}");
+
+        assert!(run(&db, &["create", "trip", "-a", "alice ", "-a", "bob\u{a0}", "-a", "carol"]).status.success());
+        let out = run(&db, &["split", "30", "-f", "\u{a0}alice", "-t", "bob :12", "-t", "carol\u{a0}:3", "-n", "dinner"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&db, &["pay", "5", "-f", "bob ", "-t", "alice\u{a0}"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!(after.len(), 3, "{after:?}");
+        assert_eq!((after["alice"], after["bob"], after["carol"]), (1000, -700, -300));
+    }
+
+    #[test]
+    fn test_stored_names_migrated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("stored.yaml");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["pay", "5", "-f", "alice", "-t", "bob"]).status.success());
+        // as written by a version that took names as given
+        let stored = fs::read_to_string(&db).unwrap().replace("bob", "bob\u{a0}");
+        fs::write(&db, &stored).unwrap();
+
+        let out = run(&db, &["__complete", "members", "trip"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "alice\nbob\n");
+        assert!(out.stderr.is_empty(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(fs::read_to_string(&db).unwrap(), stored, "completion must not modify the database");
+
+        let out = run(&db, &["stat", "trip"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&out.stderr),
+                   "Warning: renamed member \"bob\\u{a0}\" of group trip to \"bob\"\n");
+        assert!(!fs::read_to_string(&db).unwrap().contains('\u{a0}'));
+        let out = run(&db, &["stat", "trip"]);
+        assert!(out.status.success());
+        assert!(out.stderr.is_empty(), "the second load is silent: {}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(balances(&db)["bob"], -500);
+    }
+
+    #[test]
+    fn test_split_each() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("each.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol"]).status.success());
+        let out = run(&db, &["split", "--each", "15.50", "-f", "alice", "-n", "dinner"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!((after["alice"], after["bob"], after["carol"]), (31_00, -15_50, -15_50));
+        let listed = String::from_utf8_lossy(&run(&db, &["list", "trip"]).stdout).to_string();
+        assert!(listed.contains("dinner 46.50€"), "{listed}");
+
+        // only those named with --to take part, an amount of their own comes out of the total
+        let out = run(&db, &["split", "--each", "10", "-f", "carol", "-t", "alice", "-t", "bob:12", "-n", "drinks"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!((after["alice"], after["bob"], after["carol"]), (23_00, -27_50, 4_50));
+
+        let out = run(&db, &["split", "--each", "10", "-f", "carol", "-t", "alice", "-t", "bob:25", "-n", "drinks"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("add up to 25.00€, more than 20.00€ for 2 members at 10.00€ each"), "{stderr}");
+        assert_eq!(balances(&db)["carol"], 4_50);
+    }
+
+    #[test]
+    fn test_split_except() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("except.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol", "-a", "dave"]).status.success());
+        // carol and dave were not there
+        let out = run(&db, &["split", "40", "-f", "alice", "--except", "carol", "--except", "dave", "-n", "dinner"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let after = balances(&db);
+        assert_eq!((after["alice"], after["bob"], after["carol"], after["dave"]), (2000, -2000, 0, 0));
+        let listed = String::from_utf8_lossy(&run(&db, &["list", "trip"]).stdout).to_string();
+        assert!(listed.contains(", without carol, dave"), "{listed}");
+
+        let out = run(&db, &["split", "10", "-f", "alice", "-t", "carol:5", "--except", "carol", "-n", "taxi"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("carol is left out with --except, but named with --from or --to"), "{stderr}");
+        assert_eq!(balances(&db), after);
+    }
+
     #[test]
     fn test_balance() {
         // balance on non-existing group
@@ -163,6 +1623,356 @@ mod integration {
         // balance on non-0-group and abort, should not change anything
     }
 
+    fn balances(db: &std::path::Path) -> std::collections::HashMap<String, i64> {
+        let out = run(db, &["members", "trip", "--json"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let members: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        members.as_array().unwrap().iter()
+            .map(|m| (m["name"].as_str().unwrap().to_string(), m["balance"].as_i64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_balance_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("balance.db");
+        let out = run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol",
+            "--and-then", "split", "90", "-n", "hotel", "-f", "alice",
+            "--and-then", "split", "30", "-n", "dinner", "-f", "bob"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let mut expected = balances(&db);
+
+        // --json alone only suggests
+        let out = run(&db, &["balance", "trip", "--json"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let suggestions: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(balances(&db), expected);
+        for t in suggestions.as_array().unwrap() {
+            let amount = t["amount_minor"].as_i64().unwrap();
+            assert_eq!(t["currency"], "EUR");
+            assert!(t["amount"].as_str().unwrap().ends_with('€'));
+            *expected.get_mut(t["from"].as_str().unwrap()).unwrap() += amount;
+            *expected.get_mut(t["to"].as_str().unwrap()).unwrap() -= amount;
+        }
+        assert!(expected.values().all(|balance| *balance == 0), "{expected:?}");
+
+        // together with --yes they are applied
+        let out = run(&db, &["balance", "trip", "--json", "--yes"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(balances(&db), expected);
+    }
+
+    #[test]
+    fn test_max_name_width() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("names.db");
+        assert!(run(&db, &["create", "trip", "-a", "bartholomew", "-a", "bob"]).status.success());
+        let out = run(&db, &["--max-name-width", "6", "members", "trip"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&out.stdout),
+                   "Name    Balance\n\
+                    ---------------\n\
+                    barth…    0.00€\n\
+                    bob       0.00€\n");
+        // without it the names are shown in full
+        let out = run(&db, &["members", "trip"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("bartholomew  "));
+        assert!(!run(&db, &["--max-name-width", "1", "members", "trip"]).status.success());
+    }
+
+    #[test]
+    fn test_pager() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("pager.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "10", "-n", "dinner", "-f", "alice"]).status.success());
+        // a pager swallowing everything, used if the output went through it
+        let list = |args: &[&str]| {
+            let out = Command::new(env!("CARGO_BIN_EXE_splitter"))
+                .arg("-d").arg(&db)
+                .args(args)
+                .env("PAGER", "true")
+                .env("LINES", "2")
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).to_string()
+        };
+        // stdout is no terminal here, so nothing is paged
+        let plain = list(&["list", "trip"]);
+        assert!(plain.contains("Log Listing for Group trip"));
+        assert!(plain.contains("dinner"));
+        assert_eq!(list(&["--no-pager", "list", "trip"]), plain);
+        assert!(list(&["list", "trip", "--json"]).starts_with('{'));
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("audit.db");
+        let audit = dir.path().join("audit.log");
+        let audit_arg = audit.to_str().unwrap();
+        assert!(run(&db, &["--audit-log", audit_arg, "create", "trip", "-a", "alice"]).status.success());
+        assert!(run(&db, &["--audit-log", audit_arg, "stat", "trip"]).status.success());
+        assert!(!run(&db, &["--audit-log", audit_arg, "stat", "nonexistent"]).status.success());
+        // without the option nothing is recorded
+        assert!(run(&db, &["stat", "trip"]).status.success());
+
+        let written = fs::read_to_string(&audit).unwrap();
+        let lines: Vec<Vec<&str>> = written.lines().map(|line| line.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0][1], db.to_str().unwrap());
+        assert!(lines[1][2].ends_with(r#""stat","trip"]"#), "{}", lines[1][2]);
+        assert_eq!(lines[1][3], "ok 0");
+        assert_eq!(lines[2][3], "error 1");
+        assert!(lines[2][4].ends_with("ms"));
+    }
+
+    #[test]
+    fn test_audit_log_without_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("audit.db");
+        let audit = dir.path().join("audit.log");
+        // the arguments and outcome of the line the invocation appended
+        let audited = |args: &[&str]| {
+            let before = fs::read_to_string(&audit).unwrap_or_default().lines().count();
+            let out = run(&db, &[&["--audit-log", audit.to_str().unwrap()], args].concat());
+            let written = fs::read_to_string(&audit).unwrap();
+            assert_eq!(written.lines().count(), before + 1, "{:?} was not recorded", args);
+            let line: Vec<String> = written.lines().last().unwrap().split('\t').map(String::from).collect();
+            (out, line[2].clone(), line[3].clone())
+        };
+        let (out, args, outcome) = audited(&["doctor"]);
+        assert!(args.ends_with(r#""doctor"]"#), "{args}");
+        assert_eq!(outcome, if out.status.success() { "ok 0" } else { "error 1" });
+        let (out, _, outcome) = audited(&["info"]);
+        assert!(out.status.success());
+        assert_eq!(outcome, "ok 0");
+        let (_, args, outcome) = audited(&["compute", "30", "--members", "3", "-f", "member1"]);
+        assert!(args.ends_with(r#""compute","30","--members","3","-f","member1"]"#), "{args}");
+        assert_eq!(outcome, "ok 0");
+        let (out, _, outcome) = audited(&["inspect", db.to_str().unwrap()]);
+        assert!(!out.status.success(), "there is no database to inspect yet");
+        assert_eq!(outcome, "error 1");
+        let (out, _, outcome) = audited(&["completions", "bash"]);
+        assert!(out.status.success());
+        assert_eq!(outcome, "ok 0");
+        // --json refused before anything runs is a failed invocation all the same
+        let (out, _, outcome) = audited(&["--json"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("--json needs a command"));
+        assert_eq!(outcome, "error 1");
+        let (out, _, outcome) = audited(&["--json", "create", "trip", "-a", "alice"]);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("--json works with list"));
+        assert_eq!(outcome, "error 1");
+    }
+
+    #[test]
+    fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("journal.db");
+        let journal = dir.path().join("journal.db.journal");
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        let written = fs::read(&db).unwrap();
+        assert!(run(&db, &["--journal", "pay", "5", "-f", "alice", "-t", "bob"]).status.success());
+        assert!(run(&db, &["--journal", "split", "12", "-n", "pizza", "-f", "bob"]).status.success());
+        assert_eq!(fs::read(&db).unwrap(), written);
+        assert_eq!(fs::read_to_string(&journal).unwrap().lines().count(), 2);
+
+        // loading replays the journal, with or without --journal
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "flat", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "flat\tEUR\talice\t-100\nflat\tEUR\tbob\t100\n");
+
+        let out = run(&db, &["checkpoint"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "Folding 2 journal record(s) into the database\n");
+        assert!(!journal.exists());
+        assert_ne!(fs::read(&db).unwrap(), written);
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "flat", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "flat\tEUR\talice\t-100\nflat\tEUR\tbob\t100\n");
+    }
+
+    #[test]
+    fn test_delete_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("delete.db");
+        for name in ["old1", "old2", "old3", "flat"] {
+            assert!(run(&db, &["create", name, "-a", "alice"]).status.success());
+        }
+        let before = fs::read(&db).unwrap();
+        let out = run(&db, &["delete-group", "old1", "missing", "-y"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Groups not found: missing"));
+        assert_eq!(before, fs::read(&db).unwrap());
+
+        let out = run(&db, &["delete-group", "old1", "old2", "old3", "-y"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let out = run(&db, &["__complete", "groups"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "flat\n");
+
+        // --all without --yes asks, and no answer keeps everything
+        let out = run(&db, &["delete-group", "--all"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("delete all 1 group forever"));
+        assert_eq!(String::from_utf8_lossy(&run(&db, &["__complete", "groups"]).stdout), "flat\n");
+        assert!(!run(&db, &["delete-group", "--all", "flat"]).status.success());
+        assert!(run(&db, &["delete-group", "--all", "-y"]).status.success());
+        assert_eq!(String::from_utf8_lossy(&run(&db, &["__complete", "groups"]).stdout), "");
+    }
+
+    #[test]
+    fn test_boolean_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("flags.db");
+        assert!(run(&db, &["create", "foo", "-a", "alice"]).status.success());
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob", "-a", "carl"]).status.success());
+        let out = run(&db, &["split", "20", "-n", "fuel", "-f", "alice", "-t", "bob:2", "--balance-rest"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&run(&db, &["stat", "--all", "--porcelain"]).stdout),
+                   "foo\tEUR\talice\t0\nflat\tEUR\talice\t1400\nflat\tEUR\tbob\t-800\nflat\tEUR\tcarl\t-600\n");
+        assert!(run(&db, &["list", "--all"]).status.success());
+        assert!(run(&db, &["remove", "-g", "flat", "carl", "-f"]).status.success());
+        assert!(run(&db, &["delete-group", "foo", "-y"]).status.success());
+        assert_eq!(String::from_utf8_lossy(&run(&db, &["__complete", "groups"]).stdout), "flat\n");
+        // a value after the flag is taken for a group name now
+        assert!(!run(&db, &["delete-group", "flat", "-y", "true"]).status.success());
+    }
+
+    #[test]
+    fn test_delete_big_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("big.db");
+        let names: Vec<String> = (1..=40).map(|i| format!("m{:02}", i)).collect();
+        let mut create = vec!["create", "big"];
+        for name in &names {
+            create.extend(["-a", name.as_str()]);
+        }
+        assert!(run(&db, &create).status.success());
+        assert!(run(&db, &["split", "400", "-n", "rent", "-f", "m07"]).status.success());
+
+        // the question stays on screen: counts and the largest balances only
+        let out = run(&db, &["delete-group", "big"]);
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(stdout.contains("Group big (€), 40 members\n1 log entries"), "{}", stdout);
+        assert!(stdout.contains("400.00€ moved in total") && stdout.contains("m07: 390.00€"), "{}", stdout);
+        assert!(stdout.contains("… and 35 more, --full lists every member"), "{}", stdout);
+        assert!(!stdout.contains("m40"), "{}", stdout);
+        assert!(stdout.contains("Operation Cancelled"));
+
+        let out = run(&db, &["delete-group", "big", "--full"]);
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(names.iter().all(|name| stdout.contains(name.as_str())), "{}", stdout);
+        let out = run(&db, &["reset", "big", "--keep-members"]);
+        assert!(!String::from_utf8_lossy(&out.stdout).contains("m40"));
+    }
+
+    #[test]
+    fn test_reset() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("reset.db");
+        assert!(run(&db, &["create", "flat", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "food", "-f", "alice"]).status.success());
+        assert!(run(&db, &["pay", "5", "-f", "bob", "-t", "alice"]).status.success());
+
+        // without --yes and without an answer nothing changes
+        let before = fs::read(&db).unwrap();
+        let out = run(&db, &["reset", "flat", "--keep-members"]);
+        let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(stdout.contains("clear the history of group 'flat' forever") && stdout.contains("Operation Cancelled"),
+                "{}", stdout);
+        assert_eq!(before, fs::read(&db).unwrap());
+        assert!(!run(&db, &["reset", "flat"]).status.success());
+
+        let out = run(&db, &["reset", "flat", "--keep-members", "--yes"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "flat", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "flat\tEUR\talice\t0\nflat\tEUR\tbob\t0\n");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat", "--porcelain"]).stdout).to_string();
+        assert_eq!(list.lines().count(), 1, "{}", list);
+        assert!(list.contains("\treset\t0\t-\t-\t-\talice,bob"), "{}", list);
+        assert!(!run(&db, &["undo", "flat"]).status.success());
+
+        let out = run(&db, &["reset", "flat", "-a", "carol", "-a", "dave", "-y"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "flat", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "flat\tEUR\tcarol\t0\nflat\tEUR\tdave\t0\n");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "flat"]).stdout).to_string();
+        assert!(list.contains("reset: in group flat 1 entry cleared, members carol, dave"), "{}", list);
+    }
+
+    #[test]
+    fn test_buy_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("buy_in.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["add", "-g", "trip", "carol", "--buy-in-share"]).status.success());
+        assert!(run(&db, &["split", "60", "-n", "hotel", "-f", "alice"]).status.success());
+        let out = run(&db, &["add", "-g", "trip", "egon", "--buy-in-share"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "trip\tEUR\talice\t5500\ntrip\tEUR\tbob\t-2000\n\
+                          trip\tEUR\tcarol\t-2000\ntrip\tEUR\tegon\t-1500\n");
+        let list = String::from_utf8_lossy(&run(&db, &["list", "trip", "--porcelain"]).stdout).to_string();
+        assert!(list.lines().last().unwrap().contains("\tbuy-in\t1500\t-\t-\talice\tegon"), "{}", list);
+        assert!(!run(&db, &["add", "-g", "trip", "fred", "--buy-in", "5", "--buy-in-share"]).status.success());
+    }
+
+    #[test]
+    fn test_rebalance_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("rebalance.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob", "-a", "carol"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "dinner", "-f", "alice"]).status.success());
+        assert!(run(&db, &["add", "-g", "trip", "egon"]).status.success());
+        assert!(!run(&db, &["rebalance-entry", "trip", "0"]).status.success(), "--include is required");
+        let out = run(&db, &["rebalance-entry", "trip", "0", "--include", "egon"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&out.stdout),
+                   "Entry [0] of group trip now includes egon, balances changed by:\n\
+                    alice: +2.50€\nbob: +2.50€\ncarol: +2.50€\negon: -7.50€\n");
+        let stat = String::from_utf8_lossy(&run(&db, &["stat", "trip", "--porcelain"]).stdout).to_string();
+        assert_eq!(stat, "trip\tEUR\talice\t2250\ntrip\tEUR\tbob\t-750\n\
+                          trip\tEUR\tcarol\t-750\ntrip\tEUR\tegon\t-750\n");
+        assert!(!run(&db, &["rebalance-entry", "trip", "0", "--include", "egon"]).status.success());
+    }
+
+    #[test]
+    fn test_undo_dependents() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("undo.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        assert!(run(&db, &["split", "30", "-n", "dinner", "-f", "alice"]).status.success());
+        assert!(run(&db, &["pay", "15", "-f", "bob", "-t", "alice"]).status.success());
+        assert!(run(&db, &["split", "10", "-n", "ice", "-f", "bob"]).status.success());
+        let before = fs::read(&db).unwrap();
+
+        // nobody confirms, so nothing changes
+        let out = run(&db, &["undo", "trip", "0"]);
+        assert!(out.status.success());
+        let warning = String::from_utf8_lossy(&out.stderr).to_string();
+        assert!(warning.contains("later history builds on entry [0]"), "{}", warning);
+        assert!(warning.contains("- 1 later entry involves the same members: [2]"));
+        assert!(warning.contains("- a payment was recorded since: [1]"));
+        assert_eq!(before, fs::read(&db).unwrap());
+
+        // the latest entry is undone without asking
+        let out = run(&db, &["undo", "trip"]);
+        assert!(String::from_utf8_lossy(&out.stderr).is_empty());
+        assert!(String::from_utf8_lossy(&out.stdout).contains("Success"));
+        let out = run(&db, &["undo", "trip", "0", "--force"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("Success"));
+        let list = String::from_utf8_lossy(&run(&db, &["list", "trip", "--porcelain"]).stdout).to_string();
+        assert_eq!(list.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_split_without_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("picker.db");
+        assert!(run(&db, &["create", "trip", "-a", "alice", "-a", "bob"]).status.success());
+        // without a terminal there is nobody to ask
+        let out = run(&db, &["split", "45", "-n", "dinner"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Name who paid with --from"));
+    }
+
     #[test]
     fn test_add() {
         // add to non-existent group